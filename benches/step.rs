@@ -0,0 +1,108 @@
+// Times stepping the canned worlds forwards, run with `cargo bench`
+use criterion::{criterion_group, criterion_main, Criterion};
+use rusty_sandbox::{particle::ParticleVariant, sim::*, Simulation};
+
+// The size of every canned world, a screen's worth of particles
+static WIDTH: usize = 640;
+static HEIGHT: usize = 360;
+
+// How many frames each world is stepped before it's timed, so the first frame's wake-up isn't what's measured
+static WARM_UP_FRAMES: usize = 10;
+
+// A world packed solid with Sand, which soon comes to rest
+fn full_screen_of_sand(_: usize, _: usize) -> Option<ParticleVariant> {
+    Some(ParticleVariant::Sand)
+}
+
+// A pool of Water half filling a Brick tank
+fn water_pool(x: usize, y: usize) -> Option<ParticleVariant> {
+    match (x, y) {
+        (0 | 639, _) | (_, 359) => Some(ParticleVariant::Brick),
+        (_, 180..) => Some(ParticleVariant::Water),
+        _ => None
+    }
+}
+
+// A column of each of a handful of materials (some burning, melting or dissolving) on a Stone floor
+fn mixed_scene(x: usize, y: usize) -> Option<ParticleVariant> {
+    match (x / 80, y) {
+        (_, 340..) => Some(ParticleVariant::Stone),
+        (0, 200..) => Some(ParticleVariant::Sand),
+        (1, 100..) => Some(ParticleVariant::Water),
+        (2, 250..) => Some(ParticleVariant::Oil),
+        (3, 300..) => Some(ParticleVariant::Wax),
+        (3, 280..) => Some(ParticleVariant::Fire),
+        (4, 150..) => Some(ParticleVariant::Gravel),
+        (5, 320..) => Some(ParticleVariant::Lava),
+        (6, 0..=40) => Some(ParticleVariant::Steam),
+        (7, 200..) if x.is_multiple_of(2) => Some(ParticleVariant::Salt),
+        (7, 200..) => Some(ParticleVariant::Water),
+        _ => None
+    }
+}
+
+// A light rain of Sand and Water over an empty world, where nearly every cell the simulation visits is empty space
+fn sparse_rain(x: usize, y: usize) -> Option<ParticleVariant> {
+    match (x % 16, y % 12) {
+        (0, 0) => Some(ParticleVariant::Sand),
+        (8, 6) => Some(ParticleVariant::Water),
+        _ => None
+    }
+}
+
+// What a canned world holds in each cell (if anything)
+type Scene = fn(usize, usize) -> Option<ParticleVariant>;
+
+static SCENES: [(&str, Scene); 4] = [
+    ("full screen of sand", full_screen_of_sand),
+    ("water pool", water_pool),
+    ("mixed scene", mixed_scene),
+    ("sparse rain", sparse_rain)
+];
+
+// Create a simulation holding the scene
+fn create(scene: Scene) -> Simulation {
+    let mut simulation = Simulation::new(WIDTH, HEIGHT);
+    let (mut world, ..) = simulation.parts();
+    for x in 0..WIDTH {
+        for y in 0..HEIGHT {
+            if let Some(variant) = scene(x, y) {
+                world.spawn(x, y, variant);
+            }
+        }
+    }
+    simulation
+}
+
+// The whole simulation (with rigid bodies and chunk sleeping), as the sandbox steps it
+fn bench_simulation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Simulation::step");
+    group.sample_size(20);
+    for (name, scene) in SCENES {
+        let mut simulation = create(scene);
+        for _ in 0..WARM_UP_FRAMES {
+            simulation.step();
+        }
+        group.bench_function(name, |b| b.iter(|| simulation.step()));
+    }
+    group.finish();
+}
+
+// Just the world and it's fields, with every chunk awake (see `step_world`)
+fn bench_step_world(c: &mut Criterion) {
+    let mut group = c.benchmark_group("step_world");
+    group.sample_size(20);
+    for (name, scene) in SCENES {
+        let mut simulation = create(scene);
+        let (mut world, mut pressure, mut wind, _) = simulation.parts();
+        let (mut params, mut rng) = (SimParams::default(), Rng::new(0));
+        group.bench_function(name, |b| b.iter(|| {
+            step_world(&mut world, &mut pressure, &mut wind, &params, &mut rng);
+            params.frame += 1;
+        }));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_simulation, bench_step_world);
+criterion_main!(benches);
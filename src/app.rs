@@ -0,0 +1,1920 @@
+use macroquad::prelude::*;
+
+use crate::brush::{BrushOptions, BrushShape, MAX_BRUSH_RADIUS, SPRAY_DENSITIES};
+use crate::chunk::ChunkStore;
+use crate::cli::Options;
+use crate::config::{parse_settings, save_settings, Settings, CONFIG_FILE};
+use crate::edit::History;
+use crate::input::{flood_region, line_cells, paint_cell, shape_cells, Budget, BudgetOverflow, Shape, MAX_FILL_CELLS};
+use crate::keys::{get_pressed_key, key_name, Action, KeyBindings, ACTIONS};
+use crate::net::{join, Host, Server, Session};
+use crate::material::{install_materials, parse_materials, Materials, MATERIALS_FILE};
+use crate::particle::{variants, MatterState, ParticleVariant};
+use crate::plugin::{PluginBrush, Plugins};
+use crate::region::Region;
+use crate::render;
+use crate::replay::{fingerprint_rules, parse_replay, save_replay, Input, Playback, Replay};
+use crate::png::{decode_png, encode_png, is_png, ApngEncoder};
+use crate::save::{autosave_file, decode_world_code, encode_world_code, import_image, is_binary_world, is_world_code, is_stamp_name, parse_palette, parse_world, parse_world_binary, save_world_binary, slot_file, stamp_file, Palette, WorldFile, AUTOSAVE_FILES, MAX_WORLD_CODE_LENGTH, PALETTE_FILE, RUNNING_MARKER, SAVE_SLOTS, STAMPS_FOLDER, STAMP_EXTENSION};
+use crate::script::{load_scripts, scan_scripts, SCRIPTS_FOLDER};
+use crate::storage;
+use crate::sim::{tick, update_light, BodyShape, GpuBackend, OffscreenMode, SimulationState, BODY_BALL_RADIUS, BODY_BOX_SIZE};
+use crate::world::{resize_grid, Grid, World};
+use crate::Simulation;
+
+// NOTE: enable DEBUG and recompile for runtime stats / tracking / debugging helpers
+static DEBUG: bool = false;
+
+// Font size for the '{ParticleVariant} Selected' screen
+static SELECTED_FONT_SIZE: f32 = 150.0;
+
+// How many frames the simulation steps through per second, regardless of how quickly the screen is being rendered
+static TICK_RATE: f32 = 60.0;
+
+// The most frames the simulation will step through to catch up within a single render, after a slow render (or a hitch)
+// ... at normal speed (sped up, it's allowed as many times more)
+static MAX_TICKS_PER_RENDER: u32 = 4;
+
+// The slowest and fastest the simulation can be run at, as a multiple of `TICK_RATE`
+static MIN_SPEED: f32 = 0.25;
+static MAX_SPEED: f32 = 8.0;
+
+// The width (in pixels) of the speed slider's track, see `draw_speed_slider`
+static SPEED_SLIDER_WIDTH: f32 = 200.0;
+
+// The (smoothed) render time, in seconds, above which the simulation's quality is lowered a level, and below which it's raised again
+// ... and how many renders it waits between changes, giving each level time to take effect before judging it (see `Quality`)
+static QUALITY_DEGRADE_FRAME_TIME: f32 = 1.0 / 30.0;
+static QUALITY_RESTORE_FRAME_TIME: f32 = 1.0 / 50.0;
+static QUALITY_COOLDOWN: u32 = 60;
+
+// The render rates (in frames per second) the user can cap rendering to, or no cap at all
+static FPS_CAPS: [Option<u32>; 4] = [None, Some(30), Some(60), Some(144)];
+
+// The render rate (in frames per second) low-power mode drops to while the window is minimised, or while the sandbox sits idle
+static LOW_POWER_FPS: u32 = 10;
+
+// How much the global wind strength changes per keypress
+static WIND_STEP: f32 = 0.1;
+
+// The size of the canned world benchmarked when `--benchmark` isn't given a world to `--load`
+static BENCHMARK_SIZE: (usize, usize) = (640, 360);
+
+// How many frames a server simulates between streaming the world's changes to it's viewers (see `Server::stream`)
+static SERVER_STREAM_INTERVAL: u64 = 2;
+
+// How often (in seconds) a server writes the world out to `--save`, so a crash (or a restart) loses little
+static SERVER_SAVE_INTERVAL: u64 = 60;
+
+// How often (in seconds) the scripts folder is checked for new, edited or removed scripts
+static SCRIPTS_RELOAD_INTERVAL: f64 = 1.0;
+
+// The most zoomed in the camera can get
+static MAX_ZOOM: u8 = 5;
+
+// How many actions are listed in each column of the controls screen
+static CONTROLS_ROWS: usize = 16;
+
+// The folder (within the working directory) screenshots and recordings are saved into
+static CAPTURES_FOLDER: &str = "captures";
+
+// How many frames a second recordings capture
+static RECORDING_FPS: f64 = 20.0;
+
+// What the save menu does with the slot picked from it
+#[derive(Clone, Copy, PartialEq)]
+enum SlotMenu {
+    Save,
+    Load
+}
+
+// A button on the touch toolbar, standing in for a key: an action (as if it's key was pressed), undoing or redoing an edit (as
+// ... Ctrl+Z and Ctrl+Y do), or opening the save menu (as Ctrl+S and Ctrl+O do)
+#[derive(Clone, Copy, PartialEq)]
+enum TouchButton {
+    Action(Action),
+    Undo,
+    Redo,
+    Menu(SlotMenu)
+}
+
+// What the left mouse button does to the world (the selection tool and pasting aside)
+#[derive(Clone, Copy, PartialEq)]
+enum Tool {
+    // Paint with the brush, see `Input::Paint`
+    Brush,
+    // Flood-fill the region clicked, see `Input::Fill`
+    Fill,
+    // Drag out a straight line, see `Input::Line`
+    Line,
+    // Drag out a rectangle or an ellipse (only it's outline, or filled), see `Input::Shape`
+    Rectangle,
+    FilledRectangle,
+    Ellipse,
+    FilledEllipse
+}
+
+impl Tool {
+    // Return the tool after this one
+    fn next(&self) -> Tool {
+        match self {
+            Tool::Brush           => Tool::Fill,
+            Tool::Fill            => Tool::Line,
+            Tool::Line            => Tool::Rectangle,
+            Tool::Rectangle       => Tool::FilledRectangle,
+            Tool::FilledRectangle => Tool::Ellipse,
+            Tool::Ellipse         => Tool::FilledEllipse,
+            Tool::FilledEllipse   => Tool::Brush
+        }
+    }
+
+    // Return the shape the tool drags out (and whether it's filled), if it drags one out
+    fn get_shape(&self) -> Option<(Shape, bool)> {
+        match self {
+            Tool::Rectangle       => Some((Shape::Rectangle, false)),
+            Tool::FilledRectangle => Some((Shape::Rectangle, true)),
+            Tool::Ellipse         => Some((Shape::Ellipse, false)),
+            Tool::FilledEllipse   => Some((Shape::Ellipse, true)),
+            Tool::Brush | Tool::Fill | Tool::Line => None
+        }
+    }
+
+    // Return true if the tool is used by dragging from one cell to another
+    fn is_dragged(&self) -> bool {
+        *self == Tool::Line || self.get_shape().is_some()
+    }
+}
+
+impl std::fmt::Display for Tool {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Tool::Brush           => write!(f, "Brush"),
+            Tool::Fill            => write!(f, "Fill"),
+            Tool::Line            => write!(f, "Line"),
+            Tool::Rectangle       => write!(f, "Rectangle"),
+            Tool::FilledRectangle => write!(f, "Filled rectangle"),
+            Tool::Ellipse         => write!(f, "Ellipse"),
+            Tool::FilledEllipse   => write!(f, "Filled ellipse")
+        }
+    }
+}
+
+// The touch toolbar's buttons (from the top), shown down the bottom-right once the sandbox has been touched
+static TOUCH_BUTTONS: [(&str, TouchButton); 11] = [
+    ("Pause",   TouchButton::Action(Action::Pause)),
+    ("Step",    TouchButton::Action(Action::Step)),
+    ("Undo",    TouchButton::Undo),
+    ("Redo",    TouchButton::Redo),
+    ("Brush +", TouchButton::Action(Action::BrushBigger)),
+    ("Brush -", TouchButton::Action(Action::BrushSmaller)),
+    ("Zoom +",  TouchButton::Action(Action::ZoomIn)),
+    ("Zoom -",  TouchButton::Action(Action::ZoomOut)),
+    ("Gravity", TouchButton::Action(Action::Gravity)),
+    ("Save",    TouchButton::Menu(SlotMenu::Save)),
+    ("Load",    TouchButton::Menu(SlotMenu::Load))
+];
+
+// The material palette's group headings, in the order it lists them (see `palette_group`)
+static PALETTE_GROUPS: [&str; 4] = ["Powders", "Liquids", "Solids", "Gases & Energy"];
+
+// The height (in pixels) of each of the material palette's rows, the size of the colour swatch beside each material's name, and
+// ... the space left for the group headings to the left of them
+static PALETTE_ROW_HEIGHT: f32 = 22.0;
+static PALETTE_SWATCH_SIZE: f32 = 14.0;
+static PALETTE_HEADING_WIDTH: f32 = 130.0;
+
+// The window's configuration: sized as it was last closed, fullscreen if launched with `--fullscreen`, and with vsync on unless
+// ... it's switched off in the config file, or launched with `--no-vsync`
+// ... the swap interval can only be picked as the window opens, so (unlike the FPS cap) it can't be changed while running
+pub fn window_conf() -> Conf {
+    let options = Options::from_env();
+    // Problems with the config file are reported once the sandbox runs, see `load_settings`
+    let settings = storage::read(CONFIG_FILE).map_or_else(|_| Settings::default(), |bytes| parse_settings(&String::from_utf8_lossy(&bytes)).0);
+    Conf {
+        window_title: "Rusty Sandbox".to_owned(),
+        window_width: settings.window_size.0 as i32,
+        window_height: settings.window_size.1 as i32,
+        fullscreen: options.is_fullscreen,
+        platform: miniquad::conf::Platform { swap_interval: Some((settings.is_vsync && !options.is_vsync_disabled) as i32), ..Default::default() },
+        ..Default::default()
+    }
+}
+
+// Return which of the material palette's groups (see `PALETTE_GROUPS`) a material is listed under, going by it's state of matter
+fn palette_group(variant: ParticleVariant) -> usize {
+    match variant.get_state() {
+        MatterState::Powder => 0,
+        MatterState::Liquid => 1,
+        MatterState::Solid => 2,
+        MatterState::Gas | MatterState::Energy => 3
+    }
+}
+
+// Sort materials into the material palette's groups, keeping the order they were given in within each group
+fn group_palette(materials: &[ParticleVariant]) -> Vec<Vec<ParticleVariant>> {
+    let mut groups = vec![Vec::new(); PALETTE_GROUPS.len()];
+    for &variant in materials {
+        groups[palette_group(variant)].push(variant);
+    }
+    groups
+}
+
+// Draw the material palette down from the top-left: each group's heading, beside a colour swatch and name for each of it's materials
+// ... wrapped onto as many rows as the window's width needs, outlining the selected material (and the material Spouts emit, while
+// ... painting Spouts), then return the material clicked this frame (and the mouse button it was clicked with) and where the palette ends
+fn draw_palette(groups: &[Vec<ParticleVariant>], selected: ParticleVariant, emitted: Option<ParticleVariant>) -> (Option<(ParticleVariant, MouseButton)>, f32) {
+    let (mouse_x, mouse_y) = mouse_position();
+    let names: Vec<Vec<String>> = groups.iter().map(|group| group.iter().map(|variant| variant.to_string()).collect()).collect();
+    let cell_width = names.iter().flatten().map(|name| measure_text(name, None, 18, 1.0).width).fold(0.0, f32::max) + PALETTE_SWATCH_SIZE + 20.0;
+    let columns = (((screen_width() - 50.0 - PALETTE_HEADING_WIDTH) / cell_width) as usize).max(1);
+
+    let mut clicked = None;
+    let mut top = 25.0;
+    for ((heading, group), names) in PALETTE_GROUPS.iter().zip(groups).zip(&names) {
+        if group.is_empty() {
+            continue;
+        }
+        draw_text(heading, 25.0, top + 15.0, 20.0, BLUE);
+        for (i, (&variant, name)) in group.iter().zip(names).enumerate() {
+            let x = 25.0 + PALETTE_HEADING_WIDTH + (i % columns) as f32 * cell_width;
+            let y = top + (i / columns) as f32 * PALETTE_ROW_HEIGHT;
+            if variant == selected || Some(variant) == emitted {
+                draw_rectangle_lines(x - 4.0, y - 1.0, cell_width - 6.0, PALETTE_ROW_HEIGHT, 2.0, if variant == selected { WHITE } else { ORANGE });
+            }
+            draw_rectangle(x, y + 3.0, PALETTE_SWATCH_SIZE, PALETTE_SWATCH_SIZE, render::get_variant_colour(variant, false));
+            draw_text(name, x + PALETTE_SWATCH_SIZE + 6.0, y + 15.0, 18.0, WHITE);
+
+            let is_hovered = Rect::new(x - 4.0, y - 1.0, cell_width, PALETTE_ROW_HEIGHT).contains(vec2(mouse_x, mouse_y));
+            for button in [MouseButton::Left, MouseButton::Right] {
+                if is_hovered && is_mouse_button_pressed(button) {
+                    clicked = Some((variant, button));
+                }
+            }
+        }
+        top += group.len().div_ceil(columns) as f32 * PALETTE_ROW_HEIGHT + 4.0;
+    }
+    (clicked, top)
+}
+
+// Draw the speed slider (on a doubling scale, from `MIN_SPEED` to `MAX_SPEED`) with it's left end at (x, y), returning the speed
+// ... picked with the mouse if it's being dragged (from a press on the slider, until the press ends)
+fn draw_speed_slider((x, y): (f32, f32), speed: f32, is_dragging: &mut bool) -> Option<f32> {
+    let (min, max) = (MIN_SPEED.log2(), MAX_SPEED.log2());
+    let knob_x = x + (speed.log2() - min) / (max - min) * SPEED_SLIDER_WIDTH;
+    draw_rectangle(x, y - 2.0, SPEED_SLIDER_WIDTH, 4.0, GRAY);
+    draw_rectangle(knob_x - 4.0, y - 8.0, 8.0, 16.0, if *is_dragging { WHITE } else { BLUE });
+
+    let (mouse_x, mouse_y) = mouse_position();
+    if is_mouse_button_pressed(MouseButton::Left) && Rect::new(x - 8.0, y - 10.0, SPEED_SLIDER_WIDTH + 16.0, 20.0).contains(vec2(mouse_x, mouse_y)) {
+        *is_dragging = true;
+    } else if !is_mouse_button_down(MouseButton::Left) {
+        *is_dragging = false;
+    }
+    is_dragging.then(|| 2f32.powf(min + ((mouse_x - x) / SPEED_SLIDER_WIDTH).clamp(0.0, 1.0) * (max - min)))
+}
+
+// Return a speed as it's shown, without trailing zeroes (ie: "0.25x", "2x")
+fn format_speed(speed: f32) -> String {
+    format!("{:.2}", speed).trim_end_matches('0').trim_end_matches('.').to_owned() + "x"
+}
+
+// Load the user's settings from the config file (if there is one), reporting any problems with it
+fn load_settings() -> Settings {
+    let Ok(bytes) = storage::read(CONFIG_FILE) else { return Settings::default() };
+    let (settings, errors) = parse_settings(&String::from_utf8_lossy(&bytes));
+    for error in errors {
+        eprintln!("{} {}", storage::describe(CONFIG_FILE), error);
+    }
+    settings
+}
+
+// Save the user's settings to the config file
+fn store_settings(settings: &Settings) {
+    if let Err(error) = storage::write(CONFIG_FILE, save_settings(settings).as_bytes()) {
+        eprintln!("{}, the settings weren't saved", error);
+    }
+}
+
+// Load the palette file (if there is one, or the one picked with `--palette`) to import images with, sticking to the default
+// ... palette if it's malformed
+fn load_palette(options: &Options) -> Palette {
+    let path = options.palette.as_deref().unwrap_or(PALETTE_FILE);
+    match std::fs::read_to_string(path) {
+        Ok(text) => parse_palette(&text).unwrap_or_else(|errors| {
+            for error in errors {
+                eprintln!("{} {}", path, error);
+            }
+            eprintln!("Ignoring {}, using the default palette instead", path);
+            Palette::default()
+        }),
+        // Only a missing file which was asked for by name is worth mentioning
+        Err(error) if options.palette.is_some() => {
+            eprintln!("{}: {}, using the default palette instead", path, error);
+            Palette::default()
+        },
+        Err(_) => Palette::default()
+    }
+}
+
+// Read a world from a file: a PNG image (imported through the palette), a binary world file, a world code, or a text world file
+fn read_world(path: &str, options: &Options) -> Result<WorldFile, String> {
+    let bytes = std::fs::read(path).map_err(|error| format!("{}: {}", path, error))?;
+    if is_png(&bytes) {
+        let image = decode_png(&bytes).map_err(|error| format!("{}: {}", path, error))?;
+        Ok(import_image(&image, &load_palette(options)))
+    } else if is_binary_world(&bytes) {
+        parse_world_binary(&bytes).map_err(|error| format!("{} {}", path, error))
+    } else if is_world_code(&String::from_utf8_lossy(&bytes)) {
+        decode_world_code(&String::from_utf8_lossy(&bytes)).map_err(|error| format!("{}: {}", path, error))
+    } else {
+        parse_world(&String::from_utf8_lossy(&bytes)).map_err(|error| format!("{} {}", path, error))
+    }
+}
+
+// Return the fingerprint of the rules the sandbox simulates by: the installed materials, and the scripts the scripts folder holds now
+// ... (see `fingerprint_rules`), which are what a replay played back or a session joined is about to be simulated by
+fn fingerprint_installed_rules() -> u64 {
+    fingerprint_rules(&load_scripts(&scan_scripts()).unwrap_or_default())
+}
+
+// Read a replay file, returning the simulation it starts from alongside it's playback
+// ... refusing one recorded by different materials or scripts, as it would only drift away from the run it recorded
+fn read_replay(path: &str) -> Result<(Simulation, Playback), String> {
+    let text = std::fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+    let replay = parse_replay(&text).map_err(|error| format!("{} {}", path, error))?;
+    if replay.rules != fingerprint_installed_rules() {
+        return Err(format!("{} was recorded alongside different materials or scripts, so it can't be played back exactly", path));
+    }
+    let simulation = replay.start().map_err(|error| format!("{} {}", path, error))?;
+    Ok((simulation, Playback::new(replay)))
+}
+
+// Make an input to the simulation, recording it into the replay being recorded (if any) and sending it to the network session's
+// ... clients (if hosting one), and returning true if any painting was refused for exceeding the particle budget
+// ... a client leaves making it's inputs to the host instead, which sends them back once they're made (see `Session`), while a
+// ... viewer leaves them to the server, seeing them made once the world streams back
+fn perform(input: Input, replay: &mut Option<Replay>, session: &mut Option<Session>, world: &mut World, state: &mut SimulationState, history: &mut History, plugins: &mut Plugins) -> bool {
+    if let Some(Session::Client(client)) = session {
+        client.send(&input);
+        return false;
+    }
+    if let Some(Session::Viewer(viewer)) = session {
+        viewer.send(&input);
+        return false;
+    }
+    let is_exceeded = input.apply(world, state, history, plugins);
+    if let Some(Session::Host(host)) = session {
+        host.record(state.frame(), &input);
+    }
+    if let Some(replay) = replay {
+        replay.record(state.frame(), input);
+    }
+    is_exceeded
+}
+
+// Read the system clipboard's text (if it has any, and the platform lets it be read), through macroquad's miniquad context
+fn get_clipboard() -> Option<String> {
+    // SAFETY: only the clipboard is touched, from the main thread, between frames
+    unsafe { get_internal_gl() }.quad_context.clipboard_get()
+}
+
+// Put text onto the system clipboard (if the platform lets it be written), see `get_clipboard`
+fn set_clipboard(text: &str) {
+    // SAFETY: see `get_clipboard`
+    unsafe { get_internal_gl() }.quad_context.clipboard_set(text)
+}
+
+// Read every stamp in the stamps folder (see `stamp_file`), sorted by name, skipping (and reporting) any which are broken
+fn load_stamps() -> Vec<(String, Region)> {
+    let mut stamps: Vec<(String, Region)> = storage::list(STAMPS_FOLDER).into_iter()
+        .filter_map(|file| Some(file.strip_suffix(STAMP_EXTENSION)?.strip_suffix('.')?.to_owned()))
+        .filter_map(|name| {
+            let file = stamp_file(&name)?;
+            let region = storage::read(&file).and_then(|code| {
+                Region::decode(&String::from_utf8_lossy(&code)).map_err(|error| format!("{}: {}", storage::describe(&file), error))
+            });
+            match region {
+                Ok(region) => Some((name, region)),
+                Err(error) => {
+                    eprintln!("{}, skipping the stamp", error);
+                    None
+                }
+            }
+        }).collect();
+    stamps.sort_by_key(|(name, _)| name.to_lowercase());
+    stamps
+}
+
+// Save a region as a stamp with the given name (replacing any stamp of the same name)
+fn save_stamp(name: &str, region: &Region) -> Result<(), String> {
+    let file = stamp_file(name).ok_or_else(|| format!("'{}' can't name a stamp", name))?;
+    storage::write(&file, region.encode().as_bytes())
+}
+
+// Return every PNG image in the working directory, which the save menu offers to import
+fn scan_images() -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(".") else { return Vec::new() };
+    let mut images: Vec<String> = entries.filter_map(|entry| entry.ok()).map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| name.to_ascii_lowercase().ends_with(".png")).collect();
+    images.sort();
+    images
+}
+
+// Return a pixel of the world's image as it's drawn over the black background: fully opaque, darkened if translucent (ie: Glass)
+fn flatten(pixel: &[u8]) -> [u8; 4] {
+    let alpha = pixel[3] as u16;
+    [(pixel[0] as u16 * alpha / 255) as u8, (pixel[1] as u16 * alpha / 255) as u8, (pixel[2] as u16 * alpha / 255) as u8, 255]
+}
+
+// Return the world's image as it's drawn over the black background (see `flatten`)
+fn capture(image: &Image) -> Image {
+    let bytes = image.bytes.chunks_exact(4).flat_map(flatten).collect();
+    Image { bytes, width: image.width, height: image.height }
+}
+
+// Open where `--pipe` writes a headless run's frames to: stdout for "-", otherwise a file (or a named pipe, ie: made with `mkfifo`)
+fn open_pipe(path: &str) -> Result<Box<dyn std::io::Write>, String> {
+    if path == "-" {
+        return Ok(Box::new(std::io::BufWriter::new(std::io::stdout().lock())));
+    }
+    let file = std::fs::File::create(path).map_err(|error| format!("{}: {}", path, error))?;
+    Ok(Box::new(std::io::BufWriter::new(file)))
+}
+
+// Save a screenshot or recording into the captures folder (named after what it is, and when it was taken), returning it's path
+fn save_capture(kind: &str, bytes: Vec<u8>) -> Result<String, String> {
+    let time = (miniquad::date::now() * 1000.0) as u64;
+    let path = std::path::Path::new(CAPTURES_FOLDER).join(format!("{}-{}.png", kind, time));
+    std::fs::create_dir_all(CAPTURES_FOLDER).and_then(|_| std::fs::write(&path, bytes)).map_err(|error| format!("{}: {}", path.display(), error))?;
+    Ok(path.display().to_string())
+}
+
+// Save a finished recording, returning what became of it (for the UI)
+fn finish_recording(recording: ApngEncoder) -> String {
+    let frames = recording.frame_count();
+    match recording.finish().and_then(|bytes| save_capture("recording", bytes)) {
+        Ok(path) => format!("saved {} frames to {}", frames, path),
+        Err(error) => {
+            eprintln!("{}, the recording wasn't saved", error);
+            "couldn't save the recording".to_owned()
+        }
+    }
+}
+
+// Save the world into a stored binary world file (a save slot or an autosave, see `save_world_binary`)
+fn store_world(world: &World, file: &str) -> Result<(), String> {
+    storage::write(file, &save_world_binary(world))
+}
+
+// Read the world from a stored binary world file
+fn load_stored_world(file: &str) -> Result<WorldFile, String> {
+    let bytes = storage::read(file)?;
+    parse_world_binary(&bytes).map_err(|error| format!("{} {}", storage::describe(file), error))
+}
+
+// Save the world into a save slot
+fn save_to_slot(world: &World, slot: usize) -> Result<(), String> {
+    store_world(world, &slot_file(slot))
+}
+
+// Read the world saved in a save slot
+fn load_from_slot(slot: usize) -> Result<WorldFile, String> {
+    load_stored_world(&slot_file(slot))
+}
+
+// Return the most recently written autosave file (from zero), and how long ago (in seconds) it was written, if there's one
+fn latest_autosave() -> Option<(usize, u64)> {
+    (0..AUTOSAVE_FILES).filter_map(|index| Some((index, storage::age(&autosave_file(index))?))).min_by_key(|&(_, age)| age)
+}
+
+// Return the save menu's label for a save slot: the size of the world saved in it, if there's one
+fn describe_slot(slot: usize) -> String {
+    match load_from_slot(slot) {
+        Ok(file) => format!("Slot {}: {}x{} world", slot + 1, file.size.0, file.size.1),
+        Err(_) => format!("Slot {}: empty", slot + 1)
+    }
+}
+
+// Lay the materials file (if there is one, or the one picked with `--materials`) over the built-in materials, sticking to the
+// ... built-ins if it's malformed, then lay every plugin's materials over those
+fn install_material_files(plugins: &Plugins, options: &Options) {
+    let mut materials = Materials::default();
+    let path = options.materials.as_deref().unwrap_or(MATERIALS_FILE);
+    match std::fs::read_to_string(path) {
+        Ok(text) => match parse_materials(&text) {
+            Ok(definitions) => if let Err(error) = materials.merge(definitions) {
+                eprintln!("{}: {}, using the built-in materials instead", path, error);
+            },
+            Err(errors) => {
+                for error in errors {
+                    eprintln!("{} {}", path, error);
+                }
+                eprintln!("Ignoring {}, using the built-in materials instead", path);
+            }
+        },
+        // Only a missing file which was asked for by name is worth mentioning
+        Err(error) if options.materials.is_some() => eprintln!("{}: {}, using the built-in materials instead", path, error),
+        Err(_) => {}
+    }
+    for problem in plugins.load_materials(&mut materials) {
+        eprintln!("{}", problem);
+    }
+    install_materials(materials);
+}
+
+// Run the sandbox without opening a window (ie: for CI physics tests, benchmarks, or servers), alongside the same materials, scripts
+// ... and plugins as the windowed sandbox: simulating the `--load` world (or, when benchmarking without one, a canned world of falling Sand)
+// ... for the `--headless` or `--benchmark` number of frames, then writing the world out to `--save` (if given)
+// ... a `--replay` is played back instead of loading a world, making it's inputs as their frames come round
+// ... with `--pipe`, every frame is also written out as raw RGBA pixels (row by row, as it's drawn over the black background) as
+// ... it's simulated, making a video at the sandbox's tick rate (however long each frame actually takes to simulate)
+pub fn run_headless(mut plugins: Plugins, options: &Options) -> Result<(), String> {
+    install_material_files(&plugins, options);
+    let seed = options.seed.unwrap_or(0);
+    let mut playback = None;
+    let mut simulation = match (&options.replay, &options.load) {
+        (Some(path), _) => {
+            let (simulation, replay) = read_replay(path)?;
+            playback = Some(replay);
+            simulation
+        },
+        (None, Some(path)) => {
+            let mut simulation = Simulation::with_seed(0, 0, seed);
+            simulation.replace(&read_world(path, options)?);
+            simulation
+        },
+        (None, None) => {
+            let (width, height) = options.size.unwrap_or(BENCHMARK_SIZE);
+            let mut simulation = Simulation::with_seed(width, height, seed);
+            let (mut world, ..) = simulation.parts();
+            let mut budget = Budget::new(&world, BudgetOverflow::Refuse);
+            for (x, y) in (0..width).flat_map(|x| (0..height / 2).map(move |y| (x, y))) {
+                paint_cell(&mut world, &mut budget, (x as isize, y as isize), ParticleVariant::Sand, None);
+            }
+            simulation
+        }
+    };
+    let (.., state) = simulation.parts();
+    match load_scripts(&scan_scripts()) {
+        Ok(scripts) => state.scripts = scripts,
+        Err(problems) => return Err(problems.join("\n"))
+    }
+
+    let ticks = options.headless.or(options.benchmark).unwrap_or(0);
+    let mut pipe = options.pipe.as_deref().map(open_pipe).transpose()?;
+    if let Some(path) = &options.pipe {
+        eprintln!("Piping {} frames of {}x{} RGBA pixels at {} frames per second to {}", ticks, simulation.width(), simulation.height(), TICK_RATE, path);
+    }
+    let mut pixels = vec![[0; 4]; simulation.width() * simulation.height()];
+    let mut history = History::default();
+    let start = std::time::Instant::now();
+    for _ in 0..ticks {
+        if let Some(playback) = &mut playback {
+            let (mut world, _, _, state) = simulation.parts();
+            playback.apply_due(&mut world, state, &mut history, &mut plugins);
+        }
+        simulation.step();
+        let (mut world, _, _, state) = simulation.parts();
+        plugins.tick(&mut world, state);
+        if let Some(pipe) = &mut pipe {
+            render::paint_world(&world, None, &mut pixels);
+            let bytes: Vec<u8> = pixels.iter().flat_map(|pixel| flatten(pixel)).collect();
+            pipe.write_all(&bytes).map_err(|error| format!("{}: {}", options.pipe.as_deref().unwrap_or_default(), error))?;
+        }
+    }
+    if let Some(mut pipe) = pipe {
+        pipe.flush().map_err(|error| format!("{}: {}", options.pipe.as_deref().unwrap_or_default(), error))?;
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+    let summary = format!("Simulated {} frames of a {}x{} world in {:.2}s ({:.1} frames per second)", ticks, simulation.width(), simulation.height(), elapsed, ticks as f64 / elapsed);
+    // ... which can't go to stdout while the frames are being piped there
+    match options.pipe.as_deref() {
+        Some("-") => eprintln!("{}", summary),
+        _ => println!("{}", summary)
+    }
+    match &options.save {
+        Some(path) => std::fs::write(path, simulation.save()).map_err(|error| format!("{}: {}", path, error)),
+        None => Ok(())
+    }
+}
+
+// Serve the `--load` (or an empty `--size`) world at the `--serve` address without a window, simulating it at the sandbox's own
+// ... pace for anyone joining (see `Server`), making their inputs as they come in, and writing the world out to `--save` (if given)
+// ... every so often, until the process is stopped
+pub fn run_server(mut plugins: Plugins, options: &Options) -> Result<(), String> {
+    install_material_files(&plugins, options);
+    let seed = options.seed.unwrap_or(0);
+    let mut simulation = match &options.load {
+        Some(path) => {
+            let mut simulation = Simulation::with_seed(0, 0, seed);
+            simulation.replace(&read_world(path, options)?);
+            simulation
+        },
+        None => {
+            let (width, height) = options.size.unwrap_or(BENCHMARK_SIZE);
+            Simulation::with_seed(width, height, seed)
+        }
+    };
+    let (.., state) = simulation.parts();
+    match load_scripts(&scan_scripts()) {
+        Ok(scripts) => state.scripts = scripts,
+        Err(problems) => return Err(problems.join("\n"))
+    }
+
+    let mut server = Server::new(options.serve.as_deref().unwrap_or_default())?;
+    let address = server.address().map_or_else(|| options.serve.clone().unwrap_or_default(), |address| address.to_string());
+    println!("Serving a {}x{} world at {}", simulation.width(), simulation.height(), address);
+    let mut history = History::default();
+    let tick_length = std::time::Duration::from_secs_f32(1.0 / TICK_RATE);
+    let mut next_tick = std::time::Instant::now();
+    let mut last_save = std::time::Instant::now();
+    loop {
+        let (mut world, _, _, state) = simulation.parts();
+        server.accept(&world);
+        for input in server.receive() {
+            input.apply(&mut world, state, &mut history, &mut plugins);
+        }
+        simulation.step();
+        let (mut world, _, _, state) = simulation.parts();
+        plugins.tick(&mut world, state);
+        if state.frame() % SERVER_STREAM_INTERVAL == 0 {
+            server.stream(&world);
+        }
+
+        if let Some(path) = options.save.as_ref().filter(|_| last_save.elapsed().as_secs() >= SERVER_SAVE_INTERVAL) {
+            if let Err(error) = std::fs::write(path, simulation.save()) {
+                eprintln!("{}: {}", path, error);
+            }
+            last_save = std::time::Instant::now();
+        }
+
+        // Keep to the sandbox's tick rate, without trying to catch up on frames a slow step has already cost (as the windowed
+        // ... sandbox would eventually give up on them too)
+        next_tick += tick_length;
+        match next_tick.checked_duration_since(std::time::Instant::now()) {
+            Some(wait) => std::thread::sleep(wait),
+            None => next_tick = std::time::Instant::now()
+        }
+    }
+}
+
+// Run the sandbox (within a window opened with `window_conf`), extended by the given plugins
+// ... a modded sandbox is a crate with it's own `main` registering it's plugins, then handing them to this
+pub async fn run(mut plugins: Plugins, options: Options) {
+    install_material_files(&plugins, &options);
+
+    // The user's settings, which the sandbox starts with and saves as it closes (rather than quitting straight away), and the
+    // ... settings as they were last saved
+    let mut settings = load_settings();
+    let mut stored_settings = settings.clone();
+    prevent_quit();
+
+    // The simulation, with it's RNG seeded from the clock (unless launched with `--seed`)
+    // ... it's world is loaded from `--load`, or sized by `--size`, or otherwise sized to fit the window as it opens (and whenever it resizes)
+    let seed = options.seed.unwrap_or_else(|| miniquad::date::now() as u64);
+    let mut simulation = Simulation::with_seed(options.size.map_or(0, |size| size.0), options.size.map_or(0, |size| size.1), seed);
+    if let Some(path) = &options.load {
+        match read_world(path, &options) {
+            Ok(file) => simulation.replace(&file),
+            Err(error) => eprintln!("{}, starting with an empty world instead", error)
+        }
+    }
+    // ... or, when playing a replay back (see `Playback`), the replay sets the simulation up instead
+    let mut playback: Option<Playback> = None;
+    if let Some(path) = &options.replay {
+        match read_replay(path) {
+            Ok((replayed, replay)) => {
+                simulation = replayed;
+                playback = Some(replay);
+            },
+            Err(error) => eprintln!("{}, starting with an empty world instead", error)
+        }
+    }
+    // ... or, when joining a network session (or a server) with `--join` (see `Session`), the host sets the simulation up instead
+    let mut session: Option<Session> = None;
+    if let Some(address) = &options.join {
+        match join(address, fingerprint_installed_rules()) {
+            Ok((joined_session, joined)) => {
+                simulation = joined;
+                session = Some(joined_session);
+            },
+            Err(error) => eprintln!("{}, starting with an empty world instead", error)
+        }
+    }
+    let is_fixed_size = simulation.width() > 0;
+
+    // The network session hosted with `--host`, which also needs a fixed-size world (as every peer has to share it), and the
+    // ... outcome of the last session to end
+    if let Some(address) = options.host.as_ref().filter(|_| is_fixed_size) {
+        match Host::new(address) {
+            Ok(host) => session = Some(Session::Host(host)),
+            Err(error) => eprintln!("{}, playing alone instead", error)
+        }
+    }
+    let mut network_status = String::new();
+
+    // The replay being recorded with `--record` (from the world as it starts), saved as the sandbox closes
+    // ... it needs a fixed-size world, as resizing the world to the window isn't recorded
+    let mut replay = options.record.as_ref().filter(|_| is_fixed_size).map(|_| {
+        let (world, ..) = simulation.parts();
+        Replay::new(seed, &world)
+    });
+
+    // Mark the sandbox as running, noticing if the last run never cleared it's own mark (ie: it crashed), in which case the latest
+    // ... autosave is offered back (unless a replay or network session is running, as it's world can't be swapped out)
+    // ... on the web, closing the page never lets the sandbox clear it's mark, so there's no telling a crash apart from a close
+    let is_marking_runs = !cfg!(target_arch = "wasm32");
+    let is_unclean_exit = is_marking_runs && storage::exists(RUNNING_MARKER);
+    if is_marking_runs {
+        if let Err(error) = storage::write(RUNNING_MARKER, b"") {
+            eprintln!("{}, a crash won't be noticed next time", error);
+        }
+    }
+    let mut recovery = latest_autosave().filter(|_| is_unclean_exit && replay.is_none() && playback.is_none() && session.is_none());
+
+    // The autosave file to autosave into next (the one after the latest, so the latest is the last to be overwritten), and when
+    // ... the world was last autosaved
+    let mut autosave_index = latest_autosave().map_or(0, |(index, _)| (index + 1) % AUTOSAVE_FILES);
+    let mut last_autosave = get_time();
+
+    // The 2D world-space light field's cells (see `update_light`), matching the shape of the particle grid
+    let mut light_cells: Vec<f32> = vec![0.0; simulation.width() * simulation.height()];
+
+    // The image the world is rendered into each frame, and the texture it's uploaded to so it can be drawn in one go
+    let mut world_image = Image::empty();
+    let mut world_texture = Texture2D::empty();
+
+    // Whether particles are shaded by the light around them (rather than always drawn fully lit)
+    let mut is_lighting_enabled = settings.is_lighting;
+
+    // The GPU powder backend (created the first time it's switched on), and whether it's in use
+    let mut gpu: Option<GpuBackend> = None;
+    let mut is_gpu_enabled = false;
+
+    // How much rendering time has built up towards simulating the next frame
+    let mut tick_accumulator: f32 = 0.0;
+
+    // The index of the FPS cap in use (see `FPS_CAPS`), and whether low-power mode is enabled
+    let mut fps_cap = FPS_CAPS.iter().position(|&cap| cap == settings.fps_cap).unwrap_or(0);
+    let mut is_low_power = settings.is_low_power;
+
+    // The time the current render started at, and the last position of the mouse (so low-power mode can tell when the user is idle)
+    let mut frame_start = get_time();
+    let mut last_mouse = mouse_position();
+
+    // The smoothed render time (in seconds), and how many more renders until the simulation's quality can next change
+    let mut average_frame_time = 1.0 / TICK_RATE;
+    let mut quality_cooldown = 0;
+
+    // The size (in pixels) of our paint radius, the built-in brush's shape, and the spray density every tool paints at
+    let mut paint_radius: u16 = settings.paint_radius;
+    let mut brush_shape: BrushShape = settings.brush_shape;
+    let mut spray_density: u8 = settings.spray_density;
+
+    // The zoom multiplyer
+    let mut camera_zoom: u8 = 1;
+
+    // The camera offsets (used to 'control' the camera's location on the grid via zoomed X/Y offset)
+    let mut camera_offset_x: i16 = 0;
+    let mut camera_offset_y: i16 = 0;
+
+    // Flag to ensure paint 'smoothing' doesn't activate between clicks (individual paints)
+    let mut is_drawing_secondary = false;
+
+    // Trackers for mouse movements (used in 'smoothing' fast paints)
+    let mut last_x: u16 = 0;
+    let mut last_y: u16 = 0;
+
+    // Flag lock to tell the engine when the user is hitting a GUI button
+    let mut is_clicking_ui = false;
+
+    // Whether the sandbox has been touched (ie: it's running on a phone or tablet), which brings up the touch toolbar, and where the
+    // ... middle of the fingers dragging the camera was last frame, alongside how far (in cells) it's been dragged but not yet moved
+    let mut is_touch_screen = false;
+    let mut last_touch_drag: Option<Vec2> = None;
+    let mut touch_drag_remainder = Vec2::ZERO;
+
+    // The script files (and when each was last modified) as of the last check of the scripts folder, and when that check was
+    let mut script_files = Vec::new();
+    let mut last_script_check = f64::NEG_INFINITY;
+
+    // The plugin brush painting with the left click (if any, otherwise the built-in brush is)
+    let mut plugin_brush: Option<PluginBrush> = None;
+
+    // The tool the left mouse button uses, and the outcome of it's last use (ie: a fill refused for being too large)
+    let mut tool = Tool::Brush;
+    let mut tool_status = String::new();
+
+    // The cell the line or shape being dragged out (with a dragged tool, see `Tool::is_dragged`) starts from
+    let mut drag_start: Option<(isize, isize)> = None;
+
+    // The selection tool: whether it's on (so left-dragging picks a region, rather than painting), and the corners of the region picked
+    let mut is_selecting = false;
+    let mut selection: Option<((usize, usize), (usize, usize))> = None;
+
+    // The region last copied (in case the system clipboard can't be read back), the region being pasted (following the cursor
+    // ... until it's placed) if any, and the outcome of the last copy or paste
+    let mut clipboard: Option<Region> = None;
+    let mut pasting: Option<Region> = None;
+    let mut clipboard_status = String::new();
+
+    // The stamp browser while it's open (alongside every saved stamp, by name), and the name being typed in to save the region
+    // ... last copied as a stamp, while it's being named
+    let mut stamp_menu: Option<Vec<(String, Region)>> = None;
+    let mut stamp_name: Option<String> = None;
+
+    // The current primary particle variant selected by the user
+    let mut selected_variant = settings.material;
+
+    // The variant that newly painted Spouts will emit
+    let mut spout_variant = settings.spout_material;
+
+    // What painting does once the world has reached the particle budget
+    let mut budget_overflow = settings.budget_overflow;
+
+    // The user's edits to the world (ie: paint strokes), which can be undone and redone
+    let mut history = History::default();
+
+    // The chunks of the world stored away on disk while they're far from the camera's view (see `OffscreenMode::Stored`)
+    let mut chunk_store = ChunkStore::new();
+
+    // The keys bound to each action, whether the controls screen (where they're rebound) is open, and the action awaiting a new key
+    let mut bindings = settings.keys.clone();
+    let mut is_controls_open = false;
+    let mut rebinding: Option<Action> = None;
+
+    // The save menu while it's open (alongside it's slots' labels, and the PNG images it offers to import while loading), the
+    // ... world to load at the start of the next frame (and what it was loaded from), and the outcome of the last save or load
+    let mut slot_menu: Option<(SlotMenu, Vec<String>, Vec<String>)> = None;
+    let mut pending_load: Option<(String, Result<WorldFile, String>)> = None;
+    let mut slot_status = String::new();
+
+    // The recording being captured (and when it started), if any, and the outcome of the last screenshot or recording
+    let mut recording: Option<(ApngEncoder, f64)> = None;
+    let mut capture_status = String::new();
+
+    // Whether the simulation is paused (painting still works, so a scene can be built up before it's set loose)
+    let mut is_paused = false;
+
+    // How fast the simulation runs (as a multiple of `TICK_RATE`), and whether it's slider is being dragged
+    let mut speed: f32 = 1.0;
+    let mut is_dragging_speed = false;
+
+    // The variants with a material hotkey (Brick is reserved for right-click), in the order of their hotkeys, added materials last
+    let palette: Vec<ParticleVariant> = variants().filter(|v| *v != ParticleVariant::Brick && *v != ParticleVariant::Body).collect();
+
+    // Every material which can be painted, grouped as the material palette lists them
+    let palette_groups = group_palette(&variants().filter(|v| *v != ParticleVariant::Body).collect::<Vec<_>>());
+
+    // The logic + renderer loop
+    loop {
+        clear_background(BLACK);
+
+        // Load the world picked from the save menu, replacing the current world (before it's resized to the window, like any other)
+        if let Some((source, result)) = pending_load.take() {
+            match result {
+                Ok(file) => {
+                    chunk_store.discard();
+                    simulation.replace(&file);
+                    light_cells = vec![0.0; simulation.width() * simulation.height()];
+                    history = History::default();
+                    slot_status = format!("loaded {}", source);
+                },
+                Err(error) => {
+                    eprintln!("{}", error);
+                    slot_status = format!("couldn't load {}", source);
+                }
+            }
+        }
+
+        // Keep up with the network session: a host lets in anyone joining (restarting the session from the world as it is, so
+        // ... everyone starts from exactly the same place), while a client switches to the host's world whenever it restarts
+        let restarted = match &mut session {
+            Some(Session::Host(host)) => host.accept().then(|| host.restart(&mut simulation, miniquad::date::now() as u64)),
+            Some(Session::Client(client)) => client.receive().transpose(),
+            // ... while a viewer lays in whatever the server has streamed since
+            Some(Session::Viewer(viewer)) => {
+                let (mut world, ..) = simulation.parts();
+                viewer.receive(&mut world).err().map(Err)
+            },
+            None => None
+        };
+        match restarted {
+            Some(Ok(restarted)) => {
+                simulation = restarted;
+                light_cells = vec![0.0; simulation.width() * simulation.height()];
+                history = History::default();
+                // ... the restarted simulation has yet to load the scripts
+                script_files = Vec::new();
+                last_script_check = f64::NEG_INFINITY;
+            },
+            Some(Err(error)) => {
+                eprintln!("{}, leaving the network session", error);
+                network_status = "left the session, playing alone".to_owned();
+                session = None;
+            },
+            None => {}
+        }
+        // ... a client (like a replay playing) keeps to the simulation's settings as the host sends them, rather than setting them itself
+        // ... as does a viewer, which never simulates the world itself at all
+        let is_client = matches!(session, Some(Session::Client(_) | Session::Viewer(_)));
+        let is_following = playback.is_some() || is_client;
+
+        // Resize the world (and it's fields) to match the window, whenever the window actually changes size (and isn't minimised)
+        // ... everything within is kept centred horizontally, and resting on the floor, cropping anything which no longer fits
+        let window_size = (screen_width() as usize, screen_height() as usize);
+        let is_minimised = window_size.0 == 0 || window_size.1 == 0;
+        let world_size = simulation.size();
+        if window_size != world_size && !is_minimised && !is_fixed_size {
+            let offset = ((window_size.0 as isize - world_size.0 as isize) / 2, window_size.1 as isize - world_size.1 as isize);
+            simulation.resize(window_size, offset);
+            light_cells = resize_grid(light_cells, world_size, window_size, offset, || 0.0);
+            // ... the edits were made to cells which have since moved, so they can no longer be undone
+            history = History::default();
+        }
+
+        // Rebuild the world's image and texture whenever the world changes size (nearest filtering keeps each particle's pixel crisp when zoomed)
+        let world_size = simulation.size();
+        if (world_image.width as usize, world_image.height as usize) != world_size {
+            world_image = Image::gen_image_color(world_size.0 as u16, world_size.1 as u16, BLANK);
+            world_texture.delete();
+            world_texture = Texture2D::from_image(&world_image);
+            world_texture.set_filter(FilterMode::Nearest);
+        }
+        let (mut world, mut pressure, mut wind, state) = simulation.parts();
+        let mut light = Grid::new(world_size.1, &mut light_cells);
+
+        // Hot-reload the scripts whenever they're added, edited or removed, keeping the previous scripts running if the new ones are broken
+        // ... except while a replay is recording or playing (or a network session is running), where only the scripts it started
+        // ... with are run (as they're not recorded, or sent)
+        let is_replay_running = replay.is_some() || playback.is_some() || session.is_some();
+        if get_time() - last_script_check >= SCRIPTS_RELOAD_INTERVAL && (!is_replay_running || last_script_check == f64::NEG_INFINITY) {
+            last_script_check = get_time();
+            let files = scan_scripts();
+            if files != script_files {
+                match load_scripts(&files) {
+                    Ok(scripts) => state.scripts = scripts,
+                    Err(problems) => {
+                        for problem in problems {
+                            eprintln!("{}", problem);
+                        }
+                        eprintln!("Keeping the previous scripts until those in {} are fixed", SCRIPTS_FOLDER);
+                    }
+                }
+                script_files = files;
+            }
+        }
+
+        // The actions' keys are ignored while the controls screen is open, so keys can be rebound without setting anything off
+        // ... as they are while a menu (or the offer to restore an autosave) is open, or Ctrl is held (so Ctrl shortcuts don't set off the actions on their letters)
+        let is_ctrl_down = is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl);
+        let is_shift_down = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+        let is_alt_down = is_key_down(KeyCode::LeftAlt) || is_key_down(KeyCode::RightAlt);
+        let is_menu_open = slot_menu.is_some() || stamp_menu.is_some() || recovery.is_some();
+        let is_ignoring_keys = is_controls_open || is_menu_open || is_ctrl_down;
+
+        // Touch: the toolbar's buttons, and dragging with two (or more) fingers to pan the camera
+        let touches = touches();
+        is_touch_screen |= !touches.is_empty();
+        let mut touch_pressed: Option<TouchButton> = None;
+        if is_touch_screen && !is_controls_open && !is_menu_open {
+            for (i, (label, button)) in TOUCH_BUTTONS.iter().enumerate() {
+                let position = vec2(screen_width() - 110.0, screen_height() - 25.0 - (TOUCH_BUTTONS.len() - i) as f32 * 40.0);
+                if macroquad::ui::root_ui().button(position, *label) {
+                    is_clicking_ui = true;
+                    touch_pressed = Some(*button);
+                }
+            }
+        }
+        if touches.len() >= 2 {
+            let centre = touches.iter().fold(Vec2::ZERO, |sum, touch| sum + touch.position) / touches.len() as f32;
+            if let Some(last) = last_touch_drag {
+                touch_drag_remainder += (centre - last) / camera_zoom as f32;
+                camera_offset_x += touch_drag_remainder.x.trunc() as i16;
+                camera_offset_y += touch_drag_remainder.y.trunc() as i16;
+                touch_drag_remainder = touch_drag_remainder.fract();
+            }
+            last_touch_drag = Some(centre);
+            // ... the first finger (which macroquad treats as the mouse) doesn't paint until every finger is lifted
+            is_clicking_ui = true;
+        } else {
+            last_touch_drag = None;
+        }
+        let is_pressed = |action: Action| (!is_ignoring_keys && bindings.is_pressed(action)) || touch_pressed == Some(TouchButton::Action(action));
+        let is_held = |action: Action| !is_ignoring_keys && bindings.is_down(action);
+        // ... and the UI names the first key bound to each action
+        let key = |action: Action| bindings.get_keys(action).first().map_or("unbound".to_owned(), |key| key_name(*key));
+
+        // UI: Top-left, the material palette (hidden behind the controls screen and menus): left click picks the material to paint
+        // ... and, when painting Spouts, right click picks the material they emit
+        if !is_controls_open && !is_menu_open {
+            let is_painting_spouts = selected_variant == ParticleVariant::Spout;
+            let (clicked, bottom) = draw_palette(&palette_groups, selected_variant, is_painting_spouts.then_some(spout_variant));
+            match clicked {
+                Some((variant, MouseButton::Left)) => selected_variant = variant,
+                Some((variant, _)) if is_painting_spouts && variant != ParticleVariant::Spout => spout_variant = variant,
+                _ => ()
+            }
+            is_clicking_ui |= clicked.is_some();
+            let mut bottom = bottom;
+            if is_painting_spouts {
+                draw_text("Right-click a material for Spouts to emit it", 25.0 + PALETTE_HEADING_WIDTH, bottom + 15.0, 18.0, ORANGE);
+                bottom += PALETTE_ROW_HEIGHT;
+            }
+
+            // UI: beneath the palette, the simulation's speed and it's slider (the host alone sets the pace of a network session)
+            if !is_client {
+                let speed_str = format!("Speed: {} ({} and {} to change)", format_speed(speed), key(Action::SlowDown), key(Action::SpeedUp));
+                draw_text(speed_str.as_str(), 25.0, bottom + 20.0, 18.0, BLUE);
+                let slider_x = 25.0 + measure_text(speed_str.as_str(), None, 18, 1.0).width + 15.0;
+                if let Some(picked) = draw_speed_slider((slider_x, bottom + 15.0), speed, &mut is_dragging_speed) {
+                    speed = picked;
+                }
+                is_clicking_ui |= is_dragging_speed;
+            }
+        }
+
+        // UI: Top-Centre
+        let selected_display_str = if selected_variant == ParticleVariant::Spout {
+            format!("{} ({})", selected_variant, spout_variant)
+        } else {
+            format!("{}", selected_variant)
+        };
+        let selected_display_size = measure_text(selected_display_str.as_str(), None, SELECTED_FONT_SIZE as u16, 1.0);
+        draw_text(selected_display_str.as_str(), (screen_width() / 2.0) - (selected_display_size.width / 2.0), 175.0, SELECTED_FONT_SIZE, Color::new(0.0, 0.47, 0.95, 0.275));
+
+        // UI: Bottom-left
+        let spray_str = if spray_density < 100 { format!(", spraying {}%", spray_density) } else { String::new() };
+        draw_text(format!("Paint Size: {}px ({}{})", paint_radius, brush_shape, spray_str).as_str(), 25.0, screen_height() - 50.0, 50.0, BLUE);
+        draw_text(format!("Use {} and {} to increase/decrease size, {} to change shape, {} to spray, or {} to rebind the controls!", key(Action::BrushBigger), key(Action::BrushSmaller), key(Action::BrushShape), key(Action::Spray), key(Action::Controls)).as_str(), 25.0, screen_height() - 25.0, 20.0, BLUE);
+        draw_text(format!("Wind: {:+.1} ({} and {} to blow left/right)", state.global_wind, key(Action::WindLeft), key(Action::WindRight)).as_str(), 25.0, screen_height() - 100.0, 20.0, BLUE);
+        draw_text(format!("Gravity: {} ({} to rotate)", state.gravity, key(Action::Gravity)).as_str(), 25.0, screen_height() - 120.0, 20.0, BLUE);
+        draw_text(format!("Press {} to drop a box, or {} to drop a ball", key(Action::DropBox), key(Action::DropBall)).as_str(), 25.0, screen_height() - 140.0, 20.0, BLUE);
+        draw_text(format!("Lighting: {} ({} to toggle)", if is_lighting_enabled { "On" } else { "Off" }, key(Action::Lighting)).as_str(), 25.0, screen_height() - 160.0, 20.0, BLUE);
+        draw_text(format!("Powders: {} ({} to toggle the GPU)", if is_gpu_enabled && gpu.as_ref().is_some_and(|gpu| gpu.fits(world_size.0, world_size.1)) { "GPU" } else { "CPU" }, key(Action::GpuPowders)).as_str(), 25.0, screen_height() - 180.0, 20.0, BLUE);
+        let stored_str = match chunk_store.stored_count() {
+            0 => String::new(),
+            count => format!(", {} chunks stored", count)
+        };
+        draw_text(format!("Off-screen: {} ({} to change{}), Quality: {}", state.offscreen, key(Action::Offscreen), stored_str, state.quality).as_str(), 25.0, screen_height() - 200.0, 20.0, BLUE);
+
+        // Whether any painting this frame was refused for exceeding the particle budget
+        let mut is_budget_exceeded = false;
+
+        // Network: make the inputs the host's clients sent since last frame, as if the host made them itself
+        let received = match &mut session {
+            Some(Session::Host(host)) => host.receive(),
+            _ => Vec::new()
+        };
+        for input in received {
+            is_budget_exceeded |= perform(input, &mut replay, &mut session, &mut world, state, &mut history, &mut plugins);
+        }
+
+        // The cell under the cursor (which may lie outside of the world), and where a region being pasted is placed (centred on it)
+        let cursor = {
+            let (mouse_x, mouse_y) = mouse_position();
+            ((mouse_x / camera_zoom as f32) as isize - camera_offset_x as isize, (mouse_y / camera_zoom as f32) as isize - camera_offset_y as isize)
+        };
+        let paste_position = |region: &Region| (cursor.0 - region.size().0 as isize / 2, cursor.1 - region.size().1 as isize / 2);
+
+        // How the tools paint this frame
+        let brush_options = BrushOptions { shape: brush_shape, radius: paint_radius as usize, density: spray_density };
+
+        // Where a line or shape being dragged out from a cell ends (holding Shift squares up a shape's box, for squares and circles)
+        // ... and the cells it covers
+        let drag_end = move |from: (isize, isize)| match tool.get_shape() {
+            Some(_) if is_shift_down => {
+                let side = (cursor.0 - from.0).abs().max((cursor.1 - from.1).abs());
+                (from.0 + side * (cursor.0 - from.0).signum(), from.1 + side * (cursor.1 - from.1).signum())
+            },
+            _ => cursor
+        };
+        let drag_cells = move |from: (isize, isize)| match tool.get_shape() {
+            Some((shape, is_filled)) => shape_cells(shape, from, drag_end(from), is_filled),
+            None => line_cells(from, drag_end(from))
+        };
+
+        // Disable the mouse when clicking UI elements (or while the controls screen or a menu is open, or a replay is playing)
+        if !is_clicking_ui && !is_controls_open && !is_menu_open && playback.is_none() {
+            // Control: middle click (or Alt+left click) picks the material under the cursor, like an eyedropper (picking a Spout picks
+            // ... the material it emits too), holding off painting until a left click ends
+            if is_mouse_button_pressed(MouseButton::Middle) || (is_alt_down && is_mouse_button_pressed(MouseButton::Left)) {
+                let cell = usize::try_from(cursor.0).ok().zip(usize::try_from(cursor.1).ok());
+                if let Some(particle) = cell.and_then(|(x, y)| world.get(x, y)).filter(|particle| particle.active && particle.variant != ParticleVariant::Body) {
+                    selected_variant = particle.variant;
+                    if let Some(template) = particle.template.filter(|_| particle.variant == ParticleVariant::Spout) {
+                        spout_variant = template;
+                    }
+                }
+                is_clicking_ui = is_mouse_button_pressed(MouseButton::Left);
+            }
+            // Control: with the selection tool on, left-dragging picks the corners of a region (within the world)
+            else if is_selecting {
+                let corner = (cursor.0.min(world_size.0 as isize - 1).max(0) as usize, cursor.1.min(world_size.1 as isize - 1).max(0) as usize);
+                if is_mouse_button_pressed(MouseButton::Left) {
+                    selection = Some((corner, corner));
+                } else if let Some((_, end)) = selection.as_mut().filter(|_| is_mouse_button_down(MouseButton::Left)) {
+                    *end = corner;
+                }
+            }
+            // Control: while pasting, left click places the region as an edit of it's own (holding off painting until the click ends)
+            else if let Some(region) = pasting.take_if(|_| is_mouse_button_pressed(MouseButton::Left)) {
+                let input = Input::Paste { position: paste_position(&region), region, overflow: budget_overflow };
+                is_budget_exceeded |= perform(input, &mut replay, &mut session, &mut world, state, &mut history, &mut plugins);
+                is_clicking_ui = true;
+            }
+            // Control: with the fill tool, left click flood-fills the region clicked (unless it's too large to)
+            else if tool == Tool::Fill {
+                if is_mouse_button_pressed(MouseButton::Left) {
+                    tool_status = match flood_region(&world, cursor) {
+                        Some(_) => {
+                            let input = Input::Fill { position: cursor, density: brush_options.density, variant: selected_variant, overflow: budget_overflow };
+                            is_budget_exceeded |= perform(input, &mut replay, &mut session, &mut world, state, &mut history, &mut plugins);
+                            String::new()
+                        },
+                        None => format!("too large to fill (over {} cells)", MAX_FILL_CELLS)
+                    };
+                }
+            }
+            // Control: with the line or shape tools, left-dragging draws a straight line (or a shape) from where the drag started
+            // ... once it's let go
+            else if tool.is_dragged() {
+                if is_mouse_button_pressed(MouseButton::Left) {
+                    drag_start = Some(cursor);
+                } else if let Some(from) = drag_start.take_if(|_| is_mouse_button_released(MouseButton::Left)) {
+                    let (to, density, variant, overflow) = (drag_end(from), brush_options.density, selected_variant, budget_overflow);
+                    let input = match tool.get_shape() {
+                        Some((shape, is_filled)) => Input::Shape { shape, from, to, is_filled, density, variant, overflow },
+                        None => Input::Line { from, to, density, variant, overflow }
+                    };
+                    is_budget_exceeded |= perform(input, &mut replay, &mut session, &mut world, state, &mut history, &mut plugins);
+                }
+            }
+            // Control: left click for Sand
+            else if is_mouse_button_down(MouseButton::Left) && pasting.is_none() {
+                let (mouse_x, mouse_y) = mouse_position();
+                let mouse_x = (mouse_x as u16 / camera_zoom as u16) - camera_offset_x as u16;
+                let mouse_y = (mouse_y as u16 / camera_zoom as u16) - camera_offset_y as u16;
+
+                // Fill an X/Y radius from the cursor with Sand particles (Spouts remember which variant they were painted to emit)
+                let template = (selected_variant == ParticleVariant::Spout).then_some(spout_variant);
+                let brush = plugin_brush.and_then(|brush| plugins.brushes().iter().position(|&other| other == brush));
+                let input = Input::Paint { position: (mouse_x as isize, mouse_y as isize), options: brush_options, variant: selected_variant, template, brush, overflow: budget_overflow };
+                is_budget_exceeded |= perform(input, &mut replay, &mut session, &mut world, state, &mut history, &mut plugins);
+            }
+
+            // Control: right click for Brick
+            if is_mouse_button_down(MouseButton::Right) && pasting.is_none() {
+                let (mouse_x, mouse_y) = mouse_position();
+                let mouse_x = (mouse_x as u16 / camera_zoom as u16) - camera_offset_x as u16;
+                let mouse_y = (mouse_y as u16 / camera_zoom as u16) - camera_offset_y as u16;
+                // If the distance is large (e.g: a fast mouse flick) then we need to 'best-guess' the path of the cursor mid-frame
+                // ... so that there's no gaps left between paint intersections, a nice touch for UX!
+                if is_drawing_secondary {
+                    if (last_x, last_y) != (mouse_x, mouse_y) {
+                        let input = Input::Path { from: (last_x as isize, last_y as isize), to: (mouse_x as isize, mouse_y as isize), variant: ParticleVariant::Brick, overflow: budget_overflow };
+                        is_budget_exceeded |= perform(input, &mut replay, &mut session, &mut world, state, &mut history, &mut plugins);
+                        last_x = mouse_x;
+                        last_y = mouse_y;
+                    }
+                } else {
+                    // Reset X/Y tracking when we're not smoothing
+                    last_x = mouse_x;
+                    last_y = mouse_y;
+                    // Switch the secondary draw on after one frame (to avoid the pathing system activating between 'paints')
+                    is_drawing_secondary = true;
+                }
+            }
+        }
+
+        // Control release: Disable the secondary paint smoothing
+        if is_mouse_button_released(MouseButton::Right) {
+            is_drawing_secondary = false;
+        }
+
+        // Control release: a paint stroke ends once it's mouse button is let go, becoming a single edit to undo
+        if (is_mouse_button_released(MouseButton::Left) || is_mouse_button_released(MouseButton::Right)) && playback.is_none() {
+            perform(Input::Finish, &mut replay, &mut session, &mut world, state, &mut history, &mut plugins);
+        }
+
+        // UI: Bottom-left, the particle budget (in red, when painting was just refused for exceeding it)
+        let budget = Budget::new(&world, budget_overflow);
+        let budget_str = format!("Particle budget: {} left, {} when full ({} to change)", budget.room, budget.overflow, key(Action::BudgetOverflow));
+        draw_text(budget_str.as_str(), 25.0, screen_height() - 220.0, 20.0, if is_budget_exceeded { RED } else { BLUE });
+        let fps_cap_str = FPS_CAPS[fps_cap].map_or("Off".to_owned(), |fps| format!("{} FPS", fps));
+        draw_text(format!("FPS cap: {} ({} to change), Low-power: {} ({} to toggle)", fps_cap_str, key(Action::FpsCap), if is_low_power { "On" } else { "Off" }, key(Action::LowPower)).as_str(), 25.0, screen_height() - 240.0, 20.0, BLUE);
+
+        let brush_str = plugin_brush.map_or("Built-in", |brush| plugins.brush_name(brush));
+        let tool_status_str = if tool_status.is_empty() { String::new() } else { format!(", {}", tool_status) };
+        draw_text(format!("Tool: {} ({} to change{}), Brush: {} ({} to change), middle-click (or Alt+click) to pick a material", tool, key(Action::Tool), tool_status_str, brush_str, key(Action::Brush)).as_str(), 25.0, screen_height() - 260.0, 20.0, BLUE);
+        let (undos, redos) = history.counts();
+        draw_text(format!("Edits: {} to undo (Ctrl+Z), {} to redo (Ctrl+Y)", undos, redos).as_str(), 25.0, screen_height() - 280.0, 20.0, BLUE);
+        let simulation_str = if is_paused {
+            format!("Paused at frame {} ({} to resume, {} to step a frame)", state.frame(), key(Action::Pause), key(Action::Step))
+        } else {
+            format!("Running at {} ({} to pause)", format_speed(speed), key(Action::Pause))
+        };
+        draw_text(format!("Simulation: {}", simulation_str).as_str(), 25.0, screen_height() - 300.0, 20.0, BLUE);
+        let slot_status_str = if slot_status.is_empty() { String::new() } else { format!(", {}", slot_status) };
+        draw_text(format!("Worlds: Ctrl+S to save, Ctrl+O to load{}", slot_status_str).as_str(), 25.0, screen_height() - 320.0, 20.0, BLUE);
+        let capture_status_str = match &recording {
+            Some((_, start)) => format!(", recording ({:.0}s left)", settings.recording_seconds as f64 - (get_time() - start)),
+            None if capture_status.is_empty() => String::new(),
+            None => format!(", {}", capture_status)
+        };
+        draw_text(format!("Capture: {} for a screenshot, {} to record{}", key(Action::Screenshot), key(Action::Record), capture_status_str).as_str(), 25.0, screen_height() - 340.0, 20.0, BLUE);
+        let replay_str = match (&replay, &playback) {
+            (Some(_), _) => Some(format!("recording frame {}", state.frame())),
+            (_, Some(playback)) if playback.is_finished(state) => Some(format!("finished playing {} frames", playback.replay.frames)),
+            (_, Some(playback)) => Some(format!("playing frame {} of {}", state.frame(), playback.replay.frames)),
+            (None, None) => None
+        };
+        if let Some(replay_str) = replay_str {
+            draw_text(format!("Replay: {}", replay_str).as_str(), 25.0, screen_height() - 360.0, 20.0, BLUE);
+        }
+        let network_str = match &session {
+            Some(Session::Host(host)) => Some(format!("hosting at {}, {} joined", options.host.as_deref().unwrap_or_default(), host.client_count())),
+            Some(Session::Client(_)) => Some(format!("joined {} (the host sets gravity, wind and pausing)", options.join.as_deref().unwrap_or_default())),
+            Some(Session::Viewer(_)) => Some(format!("viewing {} (simulated by the server)", options.join.as_deref().unwrap_or_default())),
+            None if network_status.is_empty() => None,
+            None => Some(network_status.clone())
+        };
+        if let Some(network_str) = network_str {
+            draw_text(format!("Network: {}", network_str).as_str(), 25.0, screen_height() - 400.0, 20.0, BLUE);
+        }
+        let clipboard_str = match &pasting {
+            Some(_) => format!(", pasting ({} to rotate, {} and {} to flip, Escape to stop)", key(Action::Rotate), key(Action::FlipHorizontal), key(Action::FlipVertical)),
+            None if clipboard_status.is_empty() => String::new(),
+            None => format!(", {}", clipboard_status)
+        };
+        draw_text(format!("Clipboard: {} to select{}, Ctrl+C to copy (Ctrl+Shift+C for the whole world), Ctrl+V to paste{}", key(Action::Select), if is_selecting { " (on)" } else { "" }, clipboard_str).as_str(), 25.0, screen_height() - 380.0, 20.0, BLUE);
+
+        // Control: undo or redo the last edit
+        if ((is_ctrl_down && is_key_pressed(KeyCode::Z)) || touch_pressed == Some(TouchButton::Undo)) && playback.is_none() {
+            perform(Input::Undo, &mut replay, &mut session, &mut world, state, &mut history, &mut plugins);
+        }
+        if ((is_ctrl_down && is_key_pressed(KeyCode::Y)) || touch_pressed == Some(TouchButton::Redo)) && playback.is_none() {
+            perform(Input::Redo, &mut replay, &mut session, &mut world, state, &mut history, &mut plugins);
+        }
+
+        // Control: open the save menu, to save the world into a slot or load a world from one (unless that would break a replay)
+        let touch_menu = match touch_pressed {
+            Some(TouchButton::Menu(menu)) => Some(menu),
+            _ => None
+        };
+        if (is_ctrl_down && !is_controls_open && (is_key_pressed(KeyCode::S) || is_key_pressed(KeyCode::O))) || touch_menu.is_some() {
+            let menu = touch_menu.unwrap_or(if is_key_pressed(KeyCode::S) { SlotMenu::Save } else { SlotMenu::Load });
+            if menu == SlotMenu::Load && is_replay_running {
+                slot_status = "can't load a world while a replay or network session is running".to_owned();
+            } else {
+                let images = if menu == SlotMenu::Load { scan_images() } else { Vec::new() };
+                slot_menu = Some((menu, (0..SAVE_SLOTS).map(describe_slot).collect(), images));
+            }
+        }
+
+        // Control: switch the selection tool on (or off, forgetting the selection)
+        if is_pressed(Action::Select) {
+            is_selecting = !is_selecting;
+            selection = None;
+            pasting = None;
+        }
+
+        // Control: copy the whole world onto the system clipboard as a world code (to share it), if it's small enough to share
+        if is_ctrl_down && is_shift_down && is_key_pressed(KeyCode::C) {
+            if let Err(error) = chunk_store.restore_all(&mut world) {
+                eprintln!("{}, the chunk was lost", error);
+            }
+            let code = encode_world_code(&world);
+            clipboard_status = if code.len() <= MAX_WORLD_CODE_LENGTH {
+                set_clipboard(&code);
+                format!("copied the world as a {} character code", code.len())
+            } else {
+                format!("the world's too big to share as a code ({} characters, over {})", code.len(), MAX_WORLD_CODE_LENGTH)
+            };
+        }
+
+        // Control: copy the selected region into the clipboard, and onto the system clipboard as a region code (to share it)
+        if is_ctrl_down && !is_shift_down && is_key_pressed(KeyCode::C) {
+            if let Some((from, to)) = selection {
+                let region = Region::copy(&world, from, to);
+                set_clipboard(&region.encode());
+                clipboard_status = format!("copied a {}x{} region", region.size().0, region.size().1);
+                clipboard = Some(region);
+            }
+        }
+
+        // Control: start pasting the region code on the system clipboard (if there's one), otherwise the region last copied
+        // ... or load the world code on the system clipboard (if that's what's there) in place of the world, like the save menu
+        let clipboard_text = if is_ctrl_down && is_key_pressed(KeyCode::V) && playback.is_none() { get_clipboard() } else { None };
+        if let Some(code) = clipboard_text.as_deref().filter(|text| is_world_code(text)) {
+            if is_replay_running {
+                clipboard_status = "can't load a world while a replay or network session is running".to_owned();
+            } else {
+                pending_load = Some(("the world code".to_owned(), decode_world_code(code)));
+                clipboard_status = String::new();
+            }
+        } else if is_ctrl_down && is_key_pressed(KeyCode::V) && playback.is_none() {
+            pasting = clipboard_text.and_then(|text| Region::decode(&text).ok()).or_else(|| clipboard.clone());
+            if pasting.is_some() {
+                is_selecting = false;
+            } else {
+                clipboard_status = "there's no region to paste".to_owned();
+            }
+        }
+
+        // Control: turn or flip the region being pasted, or stop pasting it
+        if let Some(region) = &mut pasting {
+            if is_pressed(Action::Rotate) {
+                *region = region.rotate();
+            }
+            if is_pressed(Action::FlipHorizontal) {
+                *region = region.flip_horizontal();
+            }
+            if is_pressed(Action::FlipVertical) {
+                *region = region.flip_vertical();
+            }
+        }
+        if !is_ignoring_keys && is_key_pressed(KeyCode::Escape) {
+            pasting = None;
+        }
+
+        // Control: open the stamp browser, to pick a stamp to place (or save the region last copied as a stamp)
+        if is_pressed(Action::Stamps) && playback.is_none() {
+            stamp_menu = Some(load_stamps());
+            stamp_name = None;
+        }
+
+        // Control: cycle the built-in brush's shape
+        if is_pressed(Action::BrushShape) {
+            brush_shape = brush_shape.next();
+        }
+
+        // Control: cycle through the spray densities, back round to a solid brush
+        if is_pressed(Action::Spray) {
+            let index = SPRAY_DENSITIES.iter().position(|&density| density == spray_density).map_or(0, |index| index + 1);
+            spray_density = SPRAY_DENSITIES[index % SPRAY_DENSITIES.len()];
+        }
+
+        // Control: cycle through the built-in brush, then every plugin brush
+        if is_pressed(Action::Brush) {
+            let brushes = plugins.brushes();
+            plugin_brush = match plugin_brush.and_then(|current| brushes.iter().position(|&brush| brush == current)) {
+                Some(index) => brushes.get(index + 1).copied(),
+                None => brushes.first().copied()
+            };
+        }
+
+        // Control: cycle through the tools
+        if is_pressed(Action::Tool) {
+            tool = tool.next();
+            tool_status.clear();
+            drag_start = None;
+        }
+
+        // Control: cycle the FPS cap
+        if is_pressed(Action::FpsCap) {
+            fps_cap = (fps_cap + 1) % FPS_CAPS.len();
+        }
+
+        // Control: toggle low-power mode
+        if is_pressed(Action::LowPower) {
+            is_low_power = !is_low_power;
+        }
+
+        // Control: switch what painting does once the particle budget is reached
+        if is_pressed(Action::BudgetOverflow) {
+            budget_overflow = budget_overflow.next();
+        }
+
+        // Control: increase paint radius
+        if is_pressed(Action::BrushBigger) && (paint_radius as usize) < MAX_BRUSH_RADIUS {
+            paint_radius += 1;
+        }
+
+        // Control: decrease paint radius
+        if is_pressed(Action::BrushSmaller) && paint_radius > 1 {
+            paint_radius -= 1;
+        }
+
+        // Control: global wind strength and direction (these controls, which change the simulation, are left to a replay playing)
+        if is_pressed(Action::WindLeft) && !is_following {
+            state.global_wind = (state.global_wind - WIND_STEP).max(-1.0);
+        }
+        if is_pressed(Action::WindRight) && !is_following {
+            state.global_wind = (state.global_wind + WIND_STEP).min(1.0);
+        }
+
+        // Control: drop a rigid box or ball at the cursor (if there's room for it)
+        let dropped_shape = if is_pressed(Action::DropBox) {
+            Some(BodyShape::Box(BODY_BOX_SIZE, BODY_BOX_SIZE))
+        } else if is_pressed(Action::DropBall) {
+            Some(BodyShape::Ball(BODY_BALL_RADIUS))
+        } else {
+            None
+        };
+        if let Some(shape) = dropped_shape.filter(|_| playback.is_none()) {
+            let (mouse_x, mouse_y) = mouse_position();
+            let half_size = match shape { BodyShape::Box(width, _) => width / 2, BodyShape::Ball(radius) => radius } as f32;
+            let body_x = (mouse_x / camera_zoom as f32) - camera_offset_x as f32 - half_size;
+            let body_y = (mouse_y / camera_zoom as f32) - camera_offset_y as f32 - half_size;
+            perform(Input::Drop { shape, position: (body_x, body_y) }, &mut replay, &mut session, &mut world, state, &mut history, &mut plugins);
+        }
+
+        // Control: toggle lighting
+        if is_pressed(Action::Lighting) {
+            is_lighting_enabled = !is_lighting_enabled;
+        }
+
+        // Control: toggle the GPU powder backend (staying on the CPU if the GPU can't run it, the world is too big for it, or a
+        // ... replay is running, as the GPU steps powders differently to the CPU)
+        if is_pressed(Action::GpuPowders) && !is_replay_running {
+            if gpu.is_none() {
+                gpu = GpuBackend::new();
+            }
+            let is_fitting = gpu.as_ref().is_some_and(|gpu| gpu.fits(world_size.0, world_size.1));
+            if gpu.is_some() && !is_fitting && !is_gpu_enabled {
+                eprintln!("A {}x{} world is too big for the GPU, keeping the powders on the CPU", world_size.0, world_size.1);
+            }
+            is_gpu_enabled = !is_gpu_enabled && is_fitting;
+        }
+
+        // Control: cycle how the chunks outside of the camera's view are simulated
+        if is_pressed(Action::Offscreen) && !is_following {
+            state.offscreen = state.offscreen.next();
+        }
+
+        // Control: rotate gravity (or switch it off)
+        if is_pressed(Action::Gravity) && !is_following {
+            state.gravity = state.gravity.next();
+        }
+
+        // Control: pause (or resume) the simulation
+        if is_pressed(Action::Pause) && !is_client {
+            is_paused = !is_paused;
+        }
+
+        // Control: halve or double the simulation's speed
+        if is_pressed(Action::SlowDown) && !is_client {
+            speed = (speed / 2.0).max(MIN_SPEED);
+        }
+        if is_pressed(Action::SpeedUp) && !is_client {
+            speed = (speed * 2.0).min(MAX_SPEED);
+        }
+
+        // Control: select a material from the palette by it's hotkey
+        for (n, variant) in palette.iter().enumerate().take(9) {
+            if is_pressed(Action::Material(n as u8)) {
+                selected_variant = *variant;
+            }
+        }
+
+        // Control: rendering scale (zoom), with the mouse wheel or the zoom keys
+        let (_, scroll_y) = mouse_wheel();
+        if scroll_y > 0.0 || is_pressed(Action::ZoomIn) {
+            // Maximum zoom of 5x
+            camera_zoom = (camera_zoom + 1).min(MAX_ZOOM);
+        } else if scroll_y < 0.0 || is_pressed(Action::ZoomOut) {
+            // Minimum zoom of 1x (default)
+            camera_zoom = (camera_zoom - 1).max(1);
+        }
+
+        // Control: the pan keys (WASD and Arrow Keys by default) for camera 'offset' movement
+        if is_held(Action::PanUp)    { camera_offset_y += 1 }
+        if is_held(Action::PanLeft)  { camera_offset_x += 1 }
+        if is_held(Action::PanDown)  { camera_offset_y -= 1 }
+        if is_held(Action::PanRight) { camera_offset_x -= 1 }
+
+        // Track the cells in view of the camera, clamped to the world (a replay playing keeps to the view it was recorded with, and a
+        // ... client to the host's)
+        let view_x = (-(camera_offset_x as f32)).clamp(0.0, world_size.0 as f32);
+        let view_y = (-(camera_offset_y as f32)).clamp(0.0, world_size.1 as f32);
+        let view_end_x = (screen_width() / camera_zoom as f32 - camera_offset_x as f32).clamp(0.0, world_size.0 as f32);
+        let view_end_y = (screen_height() / camera_zoom as f32 - camera_offset_y as f32).clamp(0.0, world_size.1 as f32);
+        let view = ((view_x as usize, view_y as usize), (view_end_x.ceil() as usize, view_end_y.ceil() as usize));
+        if !is_following {
+            state.viewport = Some(view);
+        }
+
+        // Store away the chunks far from the camera's view while off-screen chunks are to be stored, or bring them all back otherwise
+        // ... only a fixed-size world can reach far beyond the window, and a replay or network session needs every cell it's given
+        // ... kept as it is (as storing chunks away isn't an input every peer makes)
+        let is_storing_chunks = state.offscreen == OffscreenMode::Stored && is_fixed_size && !is_replay_running;
+        let stored = if is_storing_chunks { chunk_store.update(&mut world, state, view) } else { chunk_store.restore_all(&mut world) };
+        if let Err(error) = stored {
+            eprintln!("{}, the chunk was lost", error);
+        }
+
+        // Lower the simulation's quality while rendering is struggling to keep up, and restore it once there's headroom again
+        // ... (and again, a replay playing keeps to the quality it was recorded at)
+        average_frame_time += (get_frame_time() - average_frame_time) * 0.1;
+        let quality = if average_frame_time > QUALITY_DEGRADE_FRAME_TIME {
+            state.quality.degrade()
+        } else if average_frame_time < QUALITY_RESTORE_FRAME_TIME {
+            state.quality.restore()
+        } else {
+            state.quality
+        };
+        if quality_cooldown > 0 {
+            quality_cooldown -= 1;
+        } else if quality != state.quality && !is_following {
+            state.quality = quality;
+            quality_cooldown = QUALITY_COOLDOWN;
+        }
+
+        // Step the simulation at a fixed rate, independent of the render rate (so it runs at the same speed on any display)
+        // ... a slow render is caught up on with extra steps, up to a limit, so a hitch can't snowball into ever slower renders
+        let tick_length = state.quality.get_tick_interval() as f32 / TICK_RATE;
+        // ... the speed scales the time passed, so slowed down, a step is only due every few renders (and sped up, several are)
+        let max_ticks = MAX_TICKS_PER_RENDER * speed.max(1.0).ceil() as u32;
+        // ... low-power mode skips simulating altogether while the window is minimised
+        tick_accumulator = (tick_accumulator + get_frame_time() * speed).min(max_ticks as f32 * tick_length);
+        // ... as does pausing (while a client leaves the pace to the host, see below)
+        if (is_low_power && is_minimised) || is_paused || is_client {
+            tick_accumulator = 0.0;
+        }
+        let mut ticks_due = 0;
+        while tick_accumulator >= tick_length {
+            tick_accumulator -= tick_length;
+            ticks_due += 1;
+        }
+        // ... though while paused, the step key simulates a single frame (the simulation's pace is then left entirely to the user)
+        if is_paused && !is_client && is_pressed(Action::Step) {
+            ticks_due = 1;
+        }
+        for _ in 0..ticks_due {
+            // ... a replay playing makes it's inputs as their frames come round (holding still once it's over), while a replay
+            // ... recording (or a network session's host) notes down any change to the simulation's settings
+            if let Some(playback) = &mut playback {
+                playback.apply_due(&mut world, state, &mut history, &mut plugins);
+                if playback.is_finished(state) {
+                    tick_accumulator = 0.0;
+                    break;
+                }
+            }
+            if let Some(replay) = &mut replay {
+                replay.record_state(state);
+            }
+            if let Some(Session::Host(host)) = &mut session {
+                host.record_state(state);
+            }
+            tick(&mut world, &mut pressure, &mut wind, state, gpu.as_mut().filter(|_| is_gpu_enabled));
+            plugins.tick(&mut world, state);
+            if let Some(Session::Host(host)) = &mut session {
+                host.tick();
+            }
+        }
+        // ... a client simulates exactly the frames the host has (making the inputs the host made before each), catching up on any
+        // ... it's behind on within the same limit (allowing for a host running at the fastest speed)
+        if let Some(Session::Client(client)) = &mut session {
+            for _ in 0..MAX_TICKS_PER_RENDER * MAX_SPEED as u32 {
+                client.apply_due(&mut world, state, &mut history, &mut plugins);
+                if client.is_caught_up(state) {
+                    break;
+                }
+                tick(&mut world, &mut pressure, &mut wind, state, None);
+                plugins.tick(&mut world, state);
+            }
+        }
+
+        // Autosave the world every so often (unless a replay is playing, or the user has yet to pick whether to restore an autosave)
+        if settings.autosave_seconds > 0 && get_time() - last_autosave >= settings.autosave_seconds as f64 && playback.is_none() && recovery.is_none() {
+            last_autosave = get_time();
+            // ... with every stored chunk back in place, so the whole world is saved (they're stored away again next frame)
+            if let Err(error) = chunk_store.restore_all(&mut world) {
+                eprintln!("{}, the chunk was lost", error);
+            }
+            if let Err(error) = store_world(&world, &autosave_file(autosave_index)) {
+                eprintln!("{}, the world wasn't autosaved", error);
+            }
+            autosave_index = (autosave_index + 1) % AUTOSAVE_FILES;
+        }
+
+        if is_lighting_enabled {
+            update_light(&world, &mut light);
+        }
+
+        // Render the world: paint every particle into the world's image, then draw it as one texture
+        render::paint_world(&world, is_lighting_enabled.then_some(&light), world_image.get_image_data_mut());
+        world_texture.update(&world_image);
+
+        // Control: save a screenshot of the whole world, at a pixel per cell (whatever the zoom)
+        if is_pressed(Action::Screenshot) {
+            capture_status = match encode_png(&capture(&world_image)).and_then(|bytes| save_capture("screenshot", bytes)) {
+                Ok(path) => format!("saved {}", path),
+                Err(error) => {
+                    eprintln!("{}, the screenshot wasn't saved", error);
+                    "couldn't save the screenshot".to_owned()
+                }
+            };
+        }
+
+        // Control: start recording the world as an animated PNG, or stop (and save) the recording early
+        if is_pressed(Action::Record) {
+            match recording.take() {
+                Some((encoder, _)) => capture_status = finish_recording(encoder),
+                None => recording = Some((ApngEncoder::new(world_image.width, world_image.height, (1000.0 / RECORDING_FPS) as u16), get_time()))
+            }
+        }
+
+        // Capture the recording's frames as they come due, saving it once it's long enough (or the world changes size under it)
+        if let Some((encoder, start)) = &mut recording {
+            let frames_due = ((get_time() - *start) * RECORDING_FPS) as usize + 1;
+            let is_resized = encoder.frame_count() < frames_due && !encoder.add_frame(&capture(&world_image));
+            if is_resized || encoder.frame_count() as f64 >= settings.recording_seconds as f64 * RECORDING_FPS {
+                capture_status = finish_recording(recording.take().unwrap().0);
+            }
+        }
+        let zoomf = camera_zoom as f32;
+        draw_texture_ex(world_texture, camera_offset_x as f32 * zoomf, camera_offset_y as f32 * zoomf, WHITE, DrawTextureParams {
+            dest_size: Some(vec2(world_size.0 as f32 * zoomf, world_size.1 as f32 * zoomf)),
+            ..Default::default()
+        });
+
+        // UI: the selection's outline, and the line or shape being dragged out and the region being pasted as translucent ghosts of themselves
+        if let Some(((x0, y0), (x1, y1))) = selection.filter(|_| is_selecting) {
+            let (x, y) = (x0.min(x1) as f32 + camera_offset_x as f32, y0.min(y1) as f32 + camera_offset_y as f32);
+            draw_rectangle_lines(x * zoomf, y * zoomf, (x0.abs_diff(x1) + 1) as f32 * zoomf, (y0.abs_diff(y1) + 1) as f32 * zoomf, 2.0, YELLOW);
+        }
+        if let Some(from) = drag_start.filter(|_| tool.is_dragged() && is_mouse_button_down(MouseButton::Left)) {
+            for (x, y) in drag_cells(from) {
+                let (cell_x, cell_y) = (x as f32 + camera_offset_x as f32, y as f32 + camera_offset_y as f32);
+                draw_rectangle(cell_x * zoomf, cell_y * zoomf, zoomf, zoomf, Color { a: 0.5, ..render::get_variant_colour(selected_variant, false) });
+            }
+        }
+        if let Some(region) = &pasting {
+            let (paste_x, paste_y) = paste_position(region);
+            for (x, y) in (0..region.size().0).flat_map(|x| (0..region.size().1).map(move |y| (x, y))) {
+                if let Some(variant) = region.get(x, y) {
+                    let cell_x = (paste_x + x as isize) as f32 + camera_offset_x as f32;
+                    let cell_y = (paste_y + y as isize) as f32 + camera_offset_y as f32;
+                    draw_rectangle(cell_x * zoomf, cell_y * zoomf, zoomf, zoomf, Color { a: 0.5, ..render::get_variant_colour(variant, false) });
+                }
+            }
+        }
+
+        // Control: open (or close) the controls screen, where an action is rebound by clicking it, then pressing it's new key
+        if rebinding.is_none() && bindings.is_pressed(Action::Controls) {
+            is_controls_open = !is_controls_open;
+        }
+        if is_controls_open {
+            draw_rectangle(0.0, 0.0, screen_width(), screen_height(), Color::new(0.0, 0.0, 0.0, 0.8));
+            draw_text(format!("Controls: click an action, then press it's new key (Escape to cancel, {} to close)", key(Action::Controls)).as_str(), 25.0, 100.0, 25.0, BLUE);
+            for (i, action) in ACTIONS.iter().enumerate() {
+                let keys: Vec<String> = bindings.get_keys(*action).iter().map(|key| key_name(*key)).collect();
+                let label = if rebinding == Some(*action) {
+                    format!("{}: press a key...", action)
+                } else {
+                    format!("{}: {}", action, if keys.is_empty() { "unbound".to_owned() } else { keys.join(", ") })
+                };
+                let position = vec2(25.0 + (i / CONTROLS_ROWS) as f32 * 350.0, 125.0 + (i % CONTROLS_ROWS) as f32 * 30.0);
+                if macroquad::ui::root_ui().button(position, label.as_str()) {
+                    is_clicking_ui = true;
+                    rebinding = Some(*action);
+                }
+            }
+            if macroquad::ui::root_ui().button(vec2(25.0, 135.0 + CONTROLS_ROWS as f32 * 30.0), "Reset to defaults") {
+                is_clicking_ui = true;
+                bindings = KeyBindings::default();
+                rebinding = None;
+            }
+            if let Some(action) = rebinding {
+                if is_key_pressed(KeyCode::Escape) {
+                    rebinding = None;
+                } else if let Some(key) = get_pressed_key() {
+                    bindings.bind(action, vec![key]);
+                    rebinding = None;
+                }
+            }
+        }
+
+        // UI: the save menu, a button per save slot and then (while loading) per image to import
+        // ... saving happens straight away, while loading waits for the start of the next frame
+        if let Some((menu, labels, images)) = &slot_menu {
+            let menu = *menu;
+            draw_rectangle(0.0, 0.0, screen_width(), screen_height(), Color::new(0.0, 0.0, 0.0, 0.8));
+            let title = if menu == SlotMenu::Save { "Save the world into a slot" } else { "Load a world from a slot, or import an image" };
+            draw_text(format!("{} (Escape to cancel)", title).as_str(), 25.0, 100.0, 25.0, BLUE);
+            let mut picked = None;
+            let image_labels = images.iter().map(|image| format!("Import {}", image));
+            for (i, label) in labels.iter().cloned().chain(image_labels).enumerate() {
+                if macroquad::ui::root_ui().button(vec2(25.0, 125.0 + i as f32 * 30.0), label.as_str()) {
+                    is_clicking_ui = true;
+                    picked = Some(i);
+                }
+            }
+            match picked {
+                Some(i) if i >= SAVE_SLOTS => {
+                    pending_load = Some((images[i - SAVE_SLOTS].clone(), read_world(&images[i - SAVE_SLOTS], &options)));
+                    slot_menu = None;
+                },
+                Some(slot) if menu == SlotMenu::Save => {
+                    if let Err(error) = chunk_store.restore_all(&mut world) {
+                        eprintln!("{}, the chunk was lost", error);
+                    }
+                    slot_status = match save_to_slot(&world, slot) {
+                        Ok(()) => format!("saved to slot {}", slot + 1),
+                        Err(error) => {
+                            eprintln!("{}, the world wasn't saved", error);
+                            format!("couldn't save to slot {}", slot + 1)
+                        }
+                    };
+                    slot_menu = None;
+                },
+                Some(slot) => {
+                    pending_load = Some((format!("slot {}", slot + 1), load_from_slot(slot)));
+                    slot_menu = None;
+                },
+                None if is_key_pressed(KeyCode::Escape) => slot_menu = None,
+                None => {}
+            }
+        }
+
+        // UI: the stamp browser, a button per stamp to pick it up (to be placed like any other paste), then one to save the region
+        // ... last copied as a stamp, by typing it's name in
+        if let Some(stamps) = &stamp_menu {
+            draw_rectangle(0.0, 0.0, screen_width(), screen_height(), Color::new(0.0, 0.0, 0.0, 0.8));
+            let mut is_closing = false;
+            match (&mut stamp_name, &clipboard) {
+                (Some(name), Some(region)) => {
+                    while let Some(character) = get_char_pressed() {
+                        if character.is_ascii_alphanumeric() || " -_".contains(character) {
+                            name.push(character);
+                        }
+                    }
+                    if is_key_pressed(KeyCode::Backspace) {
+                        name.pop();
+                    }
+                    draw_text(format!("Name the stamp: {}_ (Enter to save it, Escape to cancel)", name).as_str(), 25.0, 100.0, 25.0, BLUE);
+                    if is_key_pressed(KeyCode::Enter) && is_stamp_name(name) {
+                        clipboard_status = match save_stamp(name, region) {
+                            Ok(()) => format!("saved the stamp '{}'", name),
+                            Err(error) => {
+                                eprintln!("{}, the stamp wasn't saved", error);
+                                format!("couldn't save the stamp '{}'", name)
+                            }
+                        };
+                        is_closing = true;
+                    } else if is_key_pressed(KeyCode::Escape) {
+                        stamp_name = None;
+                    }
+                },
+                _ => {
+                    draw_text("Pick a stamp to place (Escape to cancel)", 25.0, 100.0, 25.0, BLUE);
+                    for (i, (name, region)) in stamps.iter().enumerate() {
+                        let label = format!("{} ({}x{})", name, region.size().0, region.size().1);
+                        if macroquad::ui::root_ui().button(vec2(25.0, 125.0 + i as f32 * 30.0), label.as_str()) {
+                            is_clicking_ui = true;
+                            pasting = Some(region.clone());
+                            is_selecting = false;
+                            is_closing = true;
+                        }
+                    }
+                    let save_y = 135.0 + stamps.len() as f32 * 30.0;
+                    if clipboard.is_some() && macroquad::ui::root_ui().button(vec2(25.0, save_y), "Save the region last copied as a stamp") {
+                        is_clicking_ui = true;
+                        stamp_name = Some(String::new());
+                    } else if is_key_pressed(KeyCode::Escape) {
+                        is_closing = true;
+                    }
+                }
+            }
+            if is_closing {
+                stamp_menu = None;
+                stamp_name = None;
+            }
+        }
+
+        // UI: the offer to restore the latest autosave, after the last run didn't close cleanly (loading it at the start of the next frame)
+        if let Some((index, age)) = recovery {
+            draw_rectangle(0.0, 0.0, screen_width(), screen_height(), Color::new(0.0, 0.0, 0.0, 0.8));
+            let age_str = if age < 60 { format!("{}s", age) } else { format!("{}m", age / 60) };
+            draw_text(format!("Rusty Sandbox didn't close cleanly last time: restore the world autosaved {} ago?", age_str).as_str(), 25.0, 100.0, 25.0, BLUE);
+            if macroquad::ui::root_ui().button(vec2(25.0, 125.0), "Restore the autosave") {
+                is_clicking_ui = true;
+                pending_load = Some(("the latest autosave".to_owned(), load_stored_world(&autosave_file(index))));
+                recovery = None;
+            } else if macroquad::ui::root_ui().button(vec2(25.0, 155.0), "Start afresh") {
+                is_clicking_ui = true;
+                recovery = None;
+            } else if is_key_pressed(KeyCode::Escape) {
+                recovery = None;
+            }
+        }
+
+        // UI: Top-right, every plugin's panel
+        let mut panel_y = 100.0;
+        for (name, lines) in plugins.panels(&world) {
+            for (i, line) in std::iter::once(name.to_owned()).chain(lines).enumerate() {
+                let size = if i == 0 { 25.0 } else { 20.0 };
+                draw_text(line.as_str(), screen_width() - 25.0 - measure_text(line.as_str(), None, size as u16, 1.0).width, panel_y, size, BLUE);
+                panel_y += size;
+            }
+            panel_y += 10.0;
+        }
+
+        // Disable the UI lock if buttons were released (the palette can be right-clicked too)
+        if is_mouse_button_released(MouseButton::Left) || is_mouse_button_released(MouseButton::Right) {
+            is_clicking_ui = false;
+        }
+
+        // Debugging UI
+        if DEBUG {
+            let variant_counts = world.counts();
+            let counts: Vec<String> = variants().map(|v| format!("{}: {}", v, variant_counts[v.index()])).collect();
+            draw_text(counts.join(", ").as_str(), 25.0, screen_height() / 2.0, 20.0, BLUE);
+            draw_text(format!("Seed: {}", seed).as_str(), 25.0, screen_height() / 2.0 - 25.0, 20.0, BLUE);
+
+            // Show the temperature of the cell under the cursor
+            let (mouse_x, mouse_y) = mouse_position();
+            let cursor_x = (mouse_x / camera_zoom as f32) as isize - camera_offset_x as isize;
+            let cursor_y = (mouse_y / camera_zoom as f32) as isize - camera_offset_y as isize;
+            if cursor_x >= 0 && cursor_y >= 0 {
+                if let Some(cell) = world.get(cursor_x as usize, cursor_y as usize) {
+                    draw_text(format!("Temperature: {:.1}C", cell.temperature).as_str(), 25.0, screen_height() / 2.0 + 25.0, 20.0, BLUE);
+                }
+            }
+        }
+
+        // Pace rendering to the FPS cap, or to the low-power rate while minimised (or idle: no input, and every chunk settled)
+        // ... by sleeping away whatever's left of the frame's time (browsers pace WASM rendering themselves)
+        let is_idle = get_last_key_pressed().is_none() && mouse_position() == last_mouse && !is_mouse_button_down(MouseButton::Left)
+            && !is_mouse_button_down(MouseButton::Right) && state.chunk_awake.iter().flatten().all(|&is_awake| !is_awake);
+        last_mouse = mouse_position();
+        let fps_limit = if is_low_power && (is_minimised || is_idle) { Some(LOW_POWER_FPS) } else { FPS_CAPS[fps_cap] };
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(fps) = fps_limit {
+            let remaining = 1.0 / fps as f64 - (get_time() - frame_start);
+            if remaining > 0.0 {
+                std::thread::sleep(std::time::Duration::from_secs_f64(remaining));
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        let _ = (fps_limit, frame_start);
+
+        // Save the user's settings as the sandbox closes (keeping the last windowed size while fullscreen or minimised)
+        // ... or, on the web (where closing the page never asks the sandbox to quit), whenever they change
+        let is_quitting = is_quit_requested();
+        if is_quitting && !options.is_fullscreen && !is_minimised {
+            settings.window_size = (window_size.0 as u32, window_size.1 as u32);
+        }
+        let current_settings = Settings {
+            paint_radius,
+            brush_shape,
+            spray_density,
+            material: selected_variant,
+            spout_material: spout_variant,
+            fps_cap: FPS_CAPS[fps_cap],
+            is_low_power,
+            is_lighting: is_lighting_enabled,
+            budget_overflow,
+            keys: bindings.clone(),
+            ..settings.clone()
+        };
+        if is_quitting || (cfg!(target_arch = "wasm32") && current_settings != stored_settings) {
+            store_settings(&current_settings);
+            stored_settings = current_settings;
+        }
+
+        // ... as well as any recording still being captured, and the replay being recorded, then clear the running marker (as
+        // ... this run closed cleanly)
+        if is_quitting {
+            if is_marking_runs {
+                storage::remove(RUNNING_MARKER);
+            }
+            chunk_store.discard();
+            if let Some((encoder, _)) = recording.take() {
+                finish_recording(encoder);
+            }
+            if let (Some(mut replay), Some(path)) = (replay.take(), &options.record) {
+                replay.frames = state.frame();
+                replay.rules = fingerprint_rules(&state.scripts);
+                if let Err(error) = std::fs::write(path, save_replay(&replay)) {
+                    eprintln!("{}: {}, the replay wasn't saved", path, error);
+                }
+            }
+            break;
+        }
+
+        next_frame().await;
+        frame_start = get_time();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::VARIANTS;
+
+    #[test]
+    fn the_palette_groups_every_material_by_state() {
+        let groups = group_palette(&VARIANTS);
+        assert_eq!(groups.iter().map(|group| group.len()).sum::<usize>(), VARIANTS.len());
+        assert!(groups[0][0] == ParticleVariant::Sand && groups[1][0] == ParticleVariant::Water);
+        assert!(groups[2].contains(&ParticleVariant::Brick) && groups[3].contains(&ParticleVariant::Fire) && groups[3].contains(&ParticleVariant::Spark));
+    }
+
+    #[test]
+    fn speeds_are_shown_without_trailing_zeroes() {
+        assert!(format_speed(MIN_SPEED) == "0.25x" && format_speed(0.5) == "0.5x" && format_speed(MAX_SPEED) == "8x");
+    }
+}
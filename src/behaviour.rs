@@ -0,0 +1,306 @@
+use crate::{particle::*, sim::*, world::*};
+
+// The percentage (1-100) chance of flowing Water picking up an erodible particle it's flowing past, each frame
+static EROSION_CHANCE: u8 = 2;
+
+// How many frames Water must rest before it drops the sediment it's carrying
+static SEDIMENT_SETTLE_FRAMES: u16 = 30;
+
+// The percentage (1-100) chance of a Spout emitting a particle each frame
+static SPOUT_RATE: u8 = 25;
+
+// How many frames a Seed must rest on watered Dirt before it sprouts
+static SEED_SPROUT_FRAMES: u16 = 180;
+
+// How many generations of Dirt a sprouted Seed's Plant spreads through
+static PLANT_GROWTH: u16 = 6;
+
+// How many frames Mud must spend away from Water before it dries back into Dirt
+static MUD_DRY_FRAMES: u16 = 600;
+
+// How many frames each cell of a Fuse burns for before igniting the next, which sets the speed a Fuse burns along
+static FUSE_BURN_FRAMES: u16 = 6;
+
+// The blast radius (in particles) and power (see `explode`) of a single detonating Gunpowder grain
+static GUNPOWDER_BLAST_RADIUS: usize = 4;
+static GUNPOWDER_BLAST_POWER: f32 = 0.0;
+
+// The blast radius (in particles) and power of a single detonating TNT particle
+static TNT_BLAST_RADIUS: usize = 10;
+static TNT_BLAST_POWER: f32 = 0.8;
+
+// The blast radius (in particles) and power of a single powered Detonator
+static DETONATOR_BLAST_RADIUS: usize = 6;
+static DETONATOR_BLAST_POWER: f32 = 0.8;
+
+// The temperature (in °C) a powered Heater holds itself at
+static HEATER_TEMPERATURE: f32 = 300.0;
+
+// The blast radius (in particles) and power of a single deflagrating Gas particle
+static GAS_BLAST_RADIUS: usize = 3;
+static GAS_BLAST_POWER: f32 = 0.0;
+
+// Everything a particle's behaviour can reach while it's being updated
+pub struct Ctx<'c, 'w, 'p> {
+    pub world: &'c mut World<'w>,
+    pub pressure: &'c mut Grid<'p, f32>,
+    pub rng: &'c mut Rng,
+    // The (wrapping) number of the frame being simulated, for marking freshly spawned particles as already updated
+    pub frame: u32,
+    pub gravity: &'c Gravity,
+    // The position of the particle being updated
+    pub position: (usize, usize)
+}
+
+// A variant's own update logic, run each frame before the shared physics (burning, phase changes, movement) get their turn
+pub type Behaviour = fn(Ctx);
+
+impl ParticleVariant {
+    // Return the variant's own update logic, if it has any
+    // ... adding an element with unique behaviour means writing it's function below, and naming it within the element (see `Element::behaviour`)
+    pub(crate) fn get_behaviour(&self) -> Option<Behaviour> {
+        self.element().behaviour
+    }
+}
+
+// Gunpowder detonates when ignited
+pub(crate) fn update_gunpowder(ctx: Ctx) {
+    let Ctx { world, pressure, position: (px, py), .. } = ctx;
+    if is_igniting(world, px, py) {
+        explode(world, pressure, px, py, GUNPOWDER_BLAST_RADIUS, GUNPOWDER_BLAST_POWER);
+    }
+}
+
+// Detonators blow once they're powered
+pub(crate) fn update_detonator(ctx: Ctx) {
+    let Ctx { world, pressure, position: (px, py), .. } = ctx;
+    if world.data[(px, py)].powered {
+        explode(world, pressure, px, py, DETONATOR_BLAST_RADIUS, DETONATOR_BLAST_POWER);
+    }
+}
+
+// Powered Heaters warm their surroundings
+pub(crate) fn update_heater(ctx: Ctx) {
+    let Ctx { world, position: (px, py), .. } = ctx;
+    if world.data[(px, py)].powered {
+        world.data[(px, py)].temperature = HEATER_TEMPERATURE;
+    }
+}
+
+// TNT detonates when ignited (or struck by a Spark)
+pub(crate) fn update_tnt(ctx: Ctx) {
+    let Ctx { world, pressure, position: (px, py), .. } = ctx;
+    if is_igniting(world, px, py) {
+        explode(world, pressure, px, py, TNT_BLAST_RADIUS, TNT_BLAST_POWER);
+    }
+}
+
+// Flammable Gas deflagrates violently when ignited
+pub(crate) fn update_gas(ctx: Ctx) {
+    let Ctx { world, pressure, position: (px, py), .. } = ctx;
+    if is_igniting(world, px, py) {
+        explode(world, pressure, px, py, GAS_BLAST_RADIUS, GAS_BLAST_POWER);
+    }
+}
+
+// Saltwater evaporates once boiling, leaving Salt behind and releasing Steam into a free neighbouring cell
+pub(crate) fn update_saltwater(ctx: Ctx) {
+    let Ctx { world, position: (px, py), .. } = ctx;
+    if world.data[(px, py)].temperature >= 100.0 {
+        world.variants[(px, py)] = ParticleVariant::Salt;
+        if let Some((sx, sy)) = find_neighbour(world, px, py, |p| !p.active) {
+            world.spawn(sx, sy, ParticleVariant::Steam);
+        }
+    }
+}
+
+// Water carries sediment along as it flows, dropping it again once it comes to a rest
+pub(crate) fn update_water(ctx: Ctx) {
+    let Ctx { world, rng, frame, gravity, position: (px, py), .. } = ctx;
+    let data = &world.data[(px, py)];
+    if data.template.is_some() && data.rest >= SEDIMENT_SETTLE_FRAMES {
+        // Water which has slowed to a rest drops it's sediment into the open, where it sinks back down to the bed
+        if let Some((sx, sy)) = find_random_neighbour(world, rng, px, py, |p| !p.active) {
+            let sediment = world.data[(px, py)].template.take().unwrap();
+            world.spawn(sx, sy, sediment);
+            world.data[(sx, sy)].last_updated_frame = frame;
+        }
+    } else if data.template.is_none() && data.rest == 0 && data.direction != (0, 0) {
+        // Flowing Water slowly erodes the bed ahead of (and beneath) it, picking the sediment up and carrying it downstream
+        let (dx, dy) = data.direction;
+        let ahead = step(px, py, (dx as isize, dy as isize));
+        let beneath = step(ahead.0, ahead.1, gravity.get_vector());
+        for (ex, ey) in [ahead, beneath] {
+            if world.get(ex, ey).is_some_and(|p| p.active && p.variant.is_erodible()) && rng.gen_range(0, 100) < EROSION_CHANCE {
+                world.data[(px, py)].template = Some(world.variants[(ex, ey)]);
+                destroy_particle(world, ex, ey, false);
+                break;
+            }
+        }
+    }
+}
+
+// Cement hardens into Concrete once it's been left to rest for long enough
+pub(crate) fn update_cement(ctx: Ctx) {
+    let Ctx { world, position: (px, py), .. } = ctx;
+    if world.data[(px, py)].rest >= CEMENT_SET_FRAMES {
+        world.spawn(px, py, ParticleVariant::Concrete);
+    }
+}
+
+// Clone adopts the first material to touch it as it's template, then endlessly emits copies of it into empty neighbouring cells
+pub(crate) fn update_clone(ctx: Ctx) {
+    let Ctx { world, rng, frame, position: (px, py), .. } = ctx;
+    match world.data[(px, py)].template {
+        None => {
+            if let Some((tx, ty)) = find_neighbour(world, px, py, |p| p.active && p.variant != ParticleVariant::Clone && p.variant != ParticleVariant::Body) {
+                world.data[(px, py)].template = Some(world.variants[(tx, ty)]);
+            }
+        },
+        Some(template) => {
+            if rng.gen_range(0, 100) < 20 {
+                if let Some((cx, cy)) = find_random_neighbour(world, rng, px, py, |p| !p.active) {
+                    world.spawn(cx, cy, template);
+                    world.data[(cx, cy)].last_updated_frame = frame;
+                }
+            }
+        }
+    }
+}
+
+// Void deletes any (non-static) particle that wanders next to it
+pub(crate) fn update_void(ctx: Ctx) {
+    let Ctx { world, position: (px, py), .. } = ctx;
+    while let Some((vx, vy)) = find_neighbour(world, px, py, |p| p.active && p.variant.get_state() != MatterState::Solid) {
+        destroy_particle(world, vx, vy, false);
+    }
+}
+
+// Spouts steadily emit their chosen variant (Water, by default) into the cell below them
+pub(crate) fn update_spout(ctx: Ctx) {
+    let Ctx { world, rng, frame, gravity, position: (px, py), .. } = ctx;
+    if rng.gen_range(0, 100) < SPOUT_RATE {
+        let emitted = world.data[(px, py)].template.unwrap_or(ParticleVariant::Water);
+        let (ex, ey) = step(px, py, if *gravity == Gravity::Off { (0, 1) } else { gravity.get_vector() });
+        if world.get(ex, ey).is_some_and(|p| !p.active) {
+            world.spawn(ex, ey, emitted);
+            world.data[(ex, ey)].last_updated_frame = frame;
+        }
+    }
+}
+
+// Antimatter annihilates itself alongside any normal particle it touches, in a flash of light
+pub(crate) fn update_antimatter(ctx: Ctx) {
+    let Ctx { world, position: (px, py), .. } = ctx;
+    if let Some((ax, ay)) = find_neighbour(world, px, py, |p| p.active && p.variant != ParticleVariant::Antimatter && p.variant != ParticleVariant::Flash) {
+        destroy_particle(world, ax, ay, true);
+        destroy_particle(world, px, py, true);
+    }
+}
+
+// Sparks travel along conductors, igniting anything flammable they touch along the way
+pub(crate) fn update_spark(ctx: Ctx) {
+    let Ctx { world, frame, position: (px, py), .. } = ctx;
+    if let Some((sx, sy)) = step_spark(world, px, py) {
+        world.data[(sx, sy)].last_updated_frame = frame;
+    }
+}
+
+// Seeds resting on Dirt with Water nearby slowly germinate, then sprout into a Plant
+pub(crate) fn update_seed(ctx: Ctx) {
+    let Ctx { world, position: (px, py), .. } = ctx;
+    let is_on_dirt = world.get(px, py + 1).is_some_and(|p| p.active && p.variant == ParticleVariant::Dirt);
+    let is_watered = cells_in_radius(world, px, py, 3).iter().any(|(x, y, _)| world.is_active(*x, *y) && world.variants[(*x, *y)] == ParticleVariant::Water);
+    if is_on_dirt && is_watered {
+        world.data[(px, py)].contact += 1;
+        if world.data[(px, py)].contact >= SEED_SPROUT_FRAMES {
+            world.spawn(px, py, ParticleVariant::Plant);
+            world.data[(px, py)].life = PLANT_GROWTH;
+        }
+    } else {
+        world.data[(px, py)].contact = world.data[(px, py)].contact.saturating_sub(1);
+    }
+}
+
+// Young Plants spread into the Dirt around them, each generation growing a little less than the last (until they catch alight)
+pub(crate) fn update_plant(ctx: Ctx) {
+    let Ctx { world, rng, position: (px, py), .. } = ctx;
+    if world.data[(px, py)].life > 0 && !world.data[(px, py)].burning && rng.gen_range(0, 100) < 5 {
+        if let Some((dx, dy)) = find_neighbour(world, px, py, |p| p.active && p.variant == ParticleVariant::Dirt) {
+            world.spawn(dx, dy, ParticleVariant::Plant);
+            world.data[(dx, dy)].life = world.data[(px, py)].life - 1;
+        } else {
+            // Nowhere left to grow, so the Plant is fully grown
+            world.data[(px, py)].life = 0;
+        }
+    }
+}
+
+// Virus slowly infects a random neighbouring particle, converting it into more Virus
+pub(crate) fn update_virus(ctx: Ctx) {
+    let Ctx { world, rng, frame, position: (px, py), .. } = ctx;
+    if rng.gen_range(0, 100) < 3 {
+        if let Some((vx, vy)) = find_random_neighbour(world, rng, px, py, |p| p.active && p.variant != ParticleVariant::Virus && p.variant != ParticleVariant::Water) {
+            world.spawn(vx, vy, ParticleVariant::Virus);
+            // Prevent the fresh infection from spreading further until next frame
+            world.data[(vx, vy)].last_updated_frame = frame;
+        }
+    }
+}
+
+// Mud slowly dries back into Dirt while there's no Water around to keep it wet
+pub(crate) fn update_mud(ctx: Ctx) {
+    let Ctx { world, position: (px, py), .. } = ctx;
+    if find_neighbour(world, px, py, |p| p.active && p.variant == ParticleVariant::Water).is_some() {
+        world.data[(px, py)].contact = 0;
+    } else {
+        world.data[(px, py)].contact += 1;
+        if world.data[(px, py)].contact >= MUD_DRY_FRAMES {
+            world.spawn(px, py, ParticleVariant::Dirt);
+        }
+    }
+}
+
+// Fuse is lit by any source of heat... except for it's own burning cells, which light the Fuse at a fixed pace instead
+pub(crate) fn update_fuse(ctx: Ctx) {
+    let Ctx { world, position: (px, py), .. } = ctx;
+    if find_neighbour(world, px, py, |p| p.active && p.variant.is_hot() && p.variant != ParticleVariant::BurningFuse).is_some() {
+        world.spawn(px, py, ParticleVariant::BurningFuse);
+        world.data[(px, py)].life = FUSE_BURN_FRAMES;
+    }
+}
+
+// Burning Fuse burns down, then lights every connected Fuse cell before turning to Smoke
+pub(crate) fn update_burning_fuse(ctx: Ctx) {
+    let Ctx { world, frame, position: (px, py), .. } = ctx;
+    if world.data[(px, py)].life > 0 {
+        world.data[(px, py)].life -= 1;
+    } else {
+        while let Some((fx, fy)) = find_neighbour(world, px, py, |p| p.active && p.variant == ParticleVariant::Fuse) {
+            world.spawn(fx, fy, ParticleVariant::BurningFuse);
+            world.data[(fx, fy)].life = FUSE_BURN_FRAMES;
+            world.data[(fx, fy)].last_updated_frame = frame;
+        }
+        world.spawn(px, py, ParticleVariant::Smoke);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn behaviours_are_looked_up_per_variant() {
+        assert!(ParticleVariant::Sand.get_behaviour().is_none());
+
+        // Mud left away from Water dries out through it's own behaviour
+        let mut particles = Particles::new(1, [Particle::new(0, ParticleVariant::Mud, true)]);
+        let mut pressure_cells = vec![0.0];
+        let (mut world, mut pressure, mut rng) = (World::new(&mut particles), Grid::new(1, &mut pressure_cells), Rng::new(0));
+        let behaviour = ParticleVariant::Mud.get_behaviour().unwrap();
+        for _ in 0..MUD_DRY_FRAMES {
+            behaviour(Ctx { world: &mut world, pressure: &mut pressure, rng: &mut rng, frame: 0, gravity: &Gravity::Down, position: (0, 0) });
+        }
+        assert!(world.variants[(0, 0)] == ParticleVariant::Dirt);
+    }
+}
@@ -0,0 +1,112 @@
+use crate::sim::Rng;
+
+// The shape of the built-in brush's footprint, see `brush_cells`
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BrushShape {
+    Circle,
+    Square
+}
+
+// Every brush shape, in the order they're cycled through
+pub static BRUSH_SHAPES: [BrushShape; 2] = [BrushShape::Circle, BrushShape::Square];
+
+impl BrushShape {
+    // Return the brush shape after this one
+    pub fn next(&self) -> BrushShape {
+        match self {
+            BrushShape::Circle => BrushShape::Square,
+            BrushShape::Square => BrushShape::Circle
+        }
+    }
+}
+
+impl std::fmt::Display for BrushShape {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BrushShape::Circle => write!(f, "Circle"),
+            BrushShape::Square => write!(f, "Square")
+        }
+    }
+}
+
+// The biggest radius the built-in brush can be set to, so a brush (perhaps another player's, see `parse_remote_input`) can't
+// ... cover more cells than painting them could ever keep up with
+pub static MAX_BRUSH_RADIUS: usize = 256;
+
+// The spray densities (see `BrushOptions::density`) cycled through, starting from a solid brush
+pub static SPRAY_DENSITIES: [u8; 4] = [100, 50, 20, 5];
+
+// How the tools paint: the built-in brush's shape and radius, and the share of cells every tool paints (spraying, below 100%)
+// ... the plugin brushes keep to the radius alone, and paint as they see fit
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct BrushOptions {
+    pub shape: BrushShape,
+    pub radius: usize,
+    // The percentage (from 1 to 100) of the cells a tool covers which it paints, scattered at random (see `Spray`)
+    pub density: u8
+}
+
+// Which of the cells a tool covers it paints, at the given density (see `BrushOptions::density`)
+// ... the scatter is drawn from an RNG seeded by when and where the tool was used, so a replay (or every player in a network
+// ... session) scatters it just the same, without drawing from the simulation's own RNG
+pub struct Spray {
+    density: u8,
+    rng: Rng
+}
+
+impl Spray {
+    pub fn new(density: u8, frame: u64, (x, y): (isize, isize)) -> Spray {
+        let seed = frame.wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ (x as u64).wrapping_mul(0xBF58_476D_1CE4_E5B9) ^ (y as u64).wrapping_mul(0x94D0_49BB_1331_11EB);
+        Spray { density, rng: Rng::new(seed) }
+    }
+
+    // A spray which paints every cell
+    pub fn solid() -> Spray {
+        Spray { density: 100, rng: Rng::new(0) }
+    }
+
+    // Return whether the next cell is painted
+    pub fn paints(&mut self) -> bool {
+        self.density >= 100 || self.rng.gen_range(0, 100) < self.density
+    }
+}
+
+// Return the cells of a brush of the given shape centred on (x, y), reaching `radius` cells out from it in every direction
+// ... (so the footprint is symmetric, with the centre cell alone at a radius of zero), row by row from the top
+pub fn brush_cells(shape: BrushShape, (x, y): (isize, isize), radius: usize) -> Vec<(isize, isize)> {
+    let radius = radius as isize;
+    (-radius..=radius).flat_map(|dy| (-radius..=radius).map(move |dx| (dx, dy)))
+        .filter(|&(dx, dy)| match shape {
+            BrushShape::Circle => dx * dx + dy * dy <= radius * radius,
+            BrushShape::Square => true
+        })
+        .map(|(dx, dy)| (x + dx, y + dy))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn brushes_are_centred_and_symmetric() {
+        assert!(brush_cells(BrushShape::Circle, (4, 4), 0) == [(4, 4)] && brush_cells(BrushShape::Square, (4, 4), 0) == [(4, 4)]);
+        assert!(brush_cells(BrushShape::Circle, (0, 0), 1) == [(0, -1), (-1, 0), (0, 0), (1, 0), (0, 1)]);
+        assert_eq!(brush_cells(BrushShape::Square, (0, 0), 2).len(), 25);
+
+        // A circle reaches just as far in every direction, but leaves out the corners a square takes in
+        let circle = brush_cells(BrushShape::Circle, (10, 5), 3);
+        for (dx, dy) in [(3, 0), (-3, 0), (0, 3), (0, -3)] {
+            assert!(circle.contains(&(10 + dx, 5 + dy)));
+        }
+        assert!(!circle.contains(&(13, 8)) && circle.iter().all(|&(x, y)| circle.contains(&(20 - x, 10 - y))));
+    }
+
+    #[test]
+    fn sprays_scatter_about_their_density_the_same_way_every_time() {
+        let painted = |mut spray: Spray| (0..10_000).filter(|_| spray.paints()).count();
+        assert!(painted(Spray::solid()) == 10_000 && painted(Spray::new(100, 7, (3, 4))) == 10_000);
+        let sprayed = painted(Spray::new(20, 7, (3, 4)));
+        assert!((1_800..2_200).contains(&sprayed) && painted(Spray::new(20, 7, (3, 4))) == sprayed);
+    }
+}
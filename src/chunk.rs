@@ -0,0 +1,172 @@
+use std::collections::BTreeSet;
+
+use crate::{region::*, sim::*, snapshot::*, storage, world::*};
+
+// The width and height (in cells) of the chunks the world is streamed (see `Server`) and stored away (see `ChunkStore`) in
+pub static CHUNK_SIZE: usize = 32;
+
+// The folder (within the config folder, see `storage`) chunks are stored away in
+static STORED_CHUNKS_FOLDER: &str = "chunks";
+
+// How far (in chunks) beyond the camera's view chunks are stored away, and how near they're loaded back again
+// ... the gap between the two keeps a chunk on the edge from being stored and loaded over and over as the camera wavers
+static STORE_DISTANCE: usize = 3;
+static LOAD_DISTANCE: usize = 1;
+
+// The top-left (included) and bottom-right (not included) cells of a chunk, or of the camera's view
+pub type Bounds = ((usize, usize), (usize, usize));
+
+// Return the bounds of every chunk of a world of the given size (the chunks along the right and bottom edges may be cut short)
+pub fn chunks((width, height): (usize, usize)) -> impl Iterator<Item = Bounds> {
+    (0..width.div_ceil(CHUNK_SIZE)).flat_map(move |cx| (0..height.div_ceil(CHUNK_SIZE)).map(move |cy| {
+        chunk_at((cx * CHUNK_SIZE, cy * CHUNK_SIZE), (width, height)).unwrap()
+    }))
+}
+
+// Return the bounds of the chunk with the given top-left cell, if that's where a chunk of a world of the given size starts
+pub fn chunk_at((x0, y0): (usize, usize), (width, height): (usize, usize)) -> Option<Bounds> {
+    let is_corner = x0 < width && y0 < height && x0 % CHUNK_SIZE == 0 && y0 % CHUNK_SIZE == 0;
+    is_corner.then(|| ((x0, y0), ((x0 + CHUNK_SIZE).min(width), (y0 + CHUNK_SIZE).min(height))))
+}
+
+// Return the cells of a chunk column by column, as a snapshot holds them (see `Snapshot::cells`)
+pub(crate) fn read_chunk(snapshot: &Snapshot, ((x0, y0), (x1, y1)): Bounds) -> Vec<u8> {
+    let height = snapshot.size().1;
+    (x0..x1).flat_map(|x| snapshot.cells()[x * height + y0..x * height + y1].iter().copied()).collect()
+}
+
+// Lay a chunk's cells (see `read_chunk`) into the world, replacing every cell which differs
+pub(crate) fn write_chunk(world: &mut World, ((x0, y0), (x1, y1)): Bounds, cells: &[u8]) {
+    for (i, &cell) in cells.iter().enumerate().take((x1 - x0) * (y1 - y0)) {
+        let (x, y) = (x0 + i / (y1 - y0), y0 + i % (y1 - y0));
+        let particle = world.particle(x, y);
+        let current = if particle.active { particle.variant.index() as u8 + 1 } else { 0 };
+        if cell != current {
+            match decode(cell) {
+                Some(variant) => world.spawn(x, y, variant),
+                None => world.deactivate(x, y)
+            }
+        }
+    }
+}
+
+// Return how far (in cells) apart a chunk and the camera's view are, along whichever axis they're furthest apart on
+fn distance(((x0, y0), (x1, y1)): Bounds, ((view_x0, view_y0), (view_x1, view_y1)): Bounds) -> usize {
+    let gap_x = view_x0.saturating_sub(x1).max(x0.saturating_sub(view_x1));
+    let gap_y = view_y0.saturating_sub(y1).max(y0.saturating_sub(view_y1));
+    gap_x.max(gap_y)
+}
+
+// The chunks of the world far away from the camera's view, stored away on disk (see `storage`) and emptied out of the world until
+// ... the camera comes back near them, for worlds too big to comfortably keep in full (see `OffscreenMode::Stored`)
+// ... only each cell's material is stored, like a text world file (see `save_world`)
+#[derive(Default)]
+pub struct ChunkStore {
+    // The top-left cells of the chunks stored away
+    stored: BTreeSet<(usize, usize)>
+}
+
+impl ChunkStore {
+    // Create an empty store, clearing out any chunks left behind by a run which never got to load them back
+    pub fn new() -> ChunkStore {
+        for name in storage::list(STORED_CHUNKS_FOLDER) {
+            storage::remove(&format!("{}/{}", STORED_CHUNKS_FOLDER, name));
+        }
+        ChunkStore::default()
+    }
+
+    pub fn stored_count(&self) -> usize {
+        self.stored.len()
+    }
+
+    // Store away every chunk further than `STORE_DISTANCE` chunks from the camera's view, and load back every stored chunk within
+    // ... `LOAD_DISTANCE` chunks of it (chunks holding a rigid body are left be, as the body needs it's cells)
+    pub fn update(&mut self, world: &mut World, state: &SimulationState, view: Bounds) -> Result<(), String> {
+        let mut snapshot = None;
+        for chunk in chunks((world.width(), world.height())) {
+            let distance = distance(chunk, view);
+            if self.stored.contains(&chunk.0) {
+                if distance <= LOAD_DISTANCE * CHUNK_SIZE {
+                    self.load(world, chunk)?;
+                }
+            } else if distance > STORE_DISTANCE * CHUNK_SIZE && !is_holding_body(state, chunk) {
+                let snapshot = snapshot.get_or_insert_with(|| Snapshot::take(world));
+                let cells = read_chunk(snapshot, chunk);
+                // ... an empty chunk has nothing worth storing
+                if cells.iter().any(|&cell| cell != 0) {
+                    storage::write(&chunk_file(chunk.0), &encode_runs(cells.into_iter()))?;
+                    write_chunk(world, chunk, &vec![0; (chunk.1.0 - chunk.0.0) * (chunk.1.1 - chunk.0.1)]);
+                    self.stored.insert(chunk.0);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Load every stored chunk back into the world (ie: before the world is saved, or once chunks are no longer to be stored)
+    pub fn restore_all(&mut self, world: &mut World) -> Result<(), String> {
+        let mut result = Ok(());
+        let size = (world.width(), world.height());
+        let corners: Vec<(usize, usize)> = self.stored.iter().copied().collect();
+        for chunk in corners.into_iter().filter_map(|corner| chunk_at(corner, size)) {
+            result = result.and(self.load(world, chunk));
+        }
+        // ... (anything left was stored from a bigger world, and has nowhere to go)
+        self.discard();
+        result
+    }
+
+    // Forget every stored chunk (ie: once the world they were stored from is replaced)
+    pub fn discard(&mut self) {
+        for corner in std::mem::take(&mut self.stored) {
+            storage::remove(&chunk_file(corner));
+        }
+    }
+
+    // Load a stored chunk back into the world, forgetting it even if it can't be read (as it's cells are gone either way)
+    fn load(&mut self, world: &mut World, chunk: Bounds) -> Result<(), String> {
+        self.stored.remove(&chunk.0);
+        let file = chunk_file(chunk.0);
+        let bytes = storage::read(&file);
+        storage::remove(&file);
+        let cells = decode_runs(&bytes?, (chunk.1.0 - chunk.0.0) * (chunk.1.1 - chunk.0.1))
+            .map_err(|error| format!("{}: {}", storage::describe(&file), error))?;
+        write_chunk(world, chunk, &cells);
+        Ok(())
+    }
+}
+
+// Return the file a chunk (with the given top-left cell) is stored away in
+fn chunk_file((x, y): (usize, usize)) -> String {
+    format!("{}/{}-{}.chunk", STORED_CHUNKS_FOLDER, x, y)
+}
+
+// Return true if any rigid body's cells are within the chunk
+fn is_holding_body(state: &SimulationState, ((x0, y0), (x1, y1)): Bounds) -> bool {
+    state.bodies.iter().any(|body| body.cells.iter().any(|&(x, y)| (x0..x1).contains(&x) && (y0..y1).contains(&y)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::ParticleVariant;
+
+    #[test]
+    fn chunks_cover_the_world_and_survive_being_emptied_and_refilled() {
+        let mut simulation = crate::Simulation::new(40, 70);
+        simulation.paint(36, 66, 3, ParticleVariant::Water);
+        let bounds: Vec<Bounds> = chunks(simulation.size()).collect();
+        assert!(bounds.len() == 6 && bounds[5] == ((32, 64), (40, 70)) && chunk_at((32, 64), (40, 70)) == Some(bounds[5]) && chunk_at((8, 0), (40, 70)).is_none());
+
+        // A chunk read out, emptied, then written back in is just as it was
+        let before = simulation.snapshot();
+        let cells = read_chunk(&before, bounds[5]);
+        let (mut world, ..) = simulation.parts();
+        write_chunk(&mut world, bounds[5], &[0; 48]);
+        assert!(simulation.get(36, 66).is_none());
+        let (mut world, ..) = simulation.parts();
+        write_chunk(&mut world, bounds[5], &cells);
+        assert!(simulation.snapshot() == before);
+        assert!(distance(bounds[5], ((0, 0), (10, 10))) == 54 && distance(bounds[0], ((0, 0), (10, 10))) == 0);
+    }
+}
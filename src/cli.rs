@@ -0,0 +1,118 @@
+use clap::{ArgGroup, Parser};
+
+use crate::save::MAX_WORLD_CELLS;
+
+// The sandbox's launch options, as parsed from the command line (see `Options::from_env`)
+// ... clap checks which options can be used together, and shows the usage with `--help` (or alongside any mistake in the arguments)
+// ... a fixed-size world comes from `--size` or `--load`, a headless run's world from `--load` or `--replay`, and it's output
+// ... goes to `--save` or `--pipe`
+#[derive(Clone, Parser)]
+#[command(name = "rusty-sandbox", about = "A lightweight sandbox sim written in Rust.")]
+#[command(group(ArgGroup::new("world").args(["size", "load"]).multiple(true)))]
+#[command(group(ArgGroup::new("source").args(["load", "replay"]).multiple(true)))]
+#[command(group(ArgGroup::new("output").args(["save", "pipe"]).multiple(true)))]
+pub struct Options {
+    // A fixed world size, rather than one fitting the window
+    #[arg(long, value_name = "WIDTHxHEIGHT", value_parser = parse_size, conflicts_with = "load",
+        help = "Simulate a fixed-size world, rather than one fitting the window")]
+    pub size: Option<(usize, usize)>,
+    #[arg(long, value_name = "NUMBER", help = "Seed the simulation's RNG (the same seed and inputs reproduce the same run)")]
+    pub seed: Option<u64>,
+    // The materials file to load, rather than `MATERIALS_FILE`
+    #[arg(long, value_name = "FILE", help = "Load material definitions from this file, rather than materials.toml")]
+    pub materials: Option<String>,
+    // The world files to start from, and to write the world out to once a headless run (or benchmark) ends
+    #[arg(long, value_name = "WORLD_FILE", help = "Start from a saved world (or a PNG image), rather than an empty one")]
+    pub load: Option<String>,
+    #[arg(long, value_name = "WORLD_FILE", help = "Write the world out here once a headless run or benchmark ends")]
+    pub save: Option<String>,
+    // The palette file to import a `--load` image with, rather than `PALETTE_FILE`
+    #[arg(long, value_name = "FILE", help = "Match a --load image's colours to materials with this file, rather than palette.toml")]
+    pub palette: Option<String>,
+    // The replay file to record the run into, or to play back (see `Replay`)
+    // ... a recording needs a fixed-size world in a window, so resizing the window can't reshape it
+    #[arg(long, value_name = "REPLAY_FILE", requires = "world", conflicts_with_all = ["headless", "benchmark"],
+        help = "Record the seed and every input to this file as the sandbox closes (needs --size or --load)")]
+    pub record: Option<String>,
+    // ... while the replay played back sets the world and seed itself
+    #[arg(long, value_name = "REPLAY_FILE", conflicts_with_all = ["load", "size", "seed", "record", "benchmark"],
+        help = "Play a recording back, re-simulating it exactly (or without a window, with --headless)")]
+    pub replay: Option<String>,
+    // The address to host a network session at, or to join one hosted at (see `Session`)
+    // ... both need a window, and the host a fixed-size world for everyone to share, while the host sets the world and seed
+    #[arg(long, value_name = "ADDRESS", requires = "world", conflicts_with_all = ["join", "headless", "benchmark", "record", "replay"],
+        help = "Host a network session at this address (ie: 0.0.0.0:7878) to paint in together (needs --size or --load)")]
+    pub host: Option<String>,
+    #[arg(long, value_name = "ADDRESS", conflicts_with_all = ["load", "size", "seed", "headless", "benchmark", "record", "replay"],
+        help = "Join the network session hosted (or the world served) at this address, which sets the world")]
+    pub join: Option<String>,
+    // The address to serve the world at without a window, simulating it for everyone joining (see `Server`)
+    #[arg(long, value_name = "ADDRESS", requires = "world", conflicts_with_all = ["headless", "benchmark", "record", "replay", "host", "join"],
+        help = "Simulate the --load (or --size) world without a window for others to --join, saving it to --save")]
+    pub serve: Option<String>,
+    #[arg(long = "fullscreen", help = "Open the window fullscreen")]
+    pub is_fullscreen: bool,
+    #[arg(long = "no-vsync", help = "Leave frame pacing to the in-game FPS cap, rather than vsync")]
+    pub is_vsync_disabled: bool,
+    // How many frames to simulate without a window, either as a headless run or a benchmark
+    #[arg(long, value_name = "FRAMES", requires_all = ["source", "output"], conflicts_with = "benchmark",
+        help = "Simulate the --load world (or a --replay) for this many frames without a window, then --save it")]
+    pub headless: Option<u64>,
+    #[arg(long, value_name = "FRAMES", help = "Time this many frames of the --load world (or a canned one) without a window")]
+    pub benchmark: Option<u64>,
+    // Where to write every frame of a headless run out to as raw pixels (see `app::run_headless`), with "-" for stdout
+    // ... only headless, so every frame is captured regardless of how quickly it's simulated
+    #[arg(long, value_name = "FILE", requires = "headless",
+        help = "Write every frame of a headless run as raw RGBA pixels to this file (or named pipe, or - for stdout)")]
+    pub pipe: Option<String>
+}
+
+impl Options {
+    // Parse the options from the process' own command line, exiting with the usage if it's asked for (with `--help`), or
+    // ... alongside the mistake if the arguments can't be used
+    pub fn from_env() -> Options {
+        Options::parse()
+    }
+
+    // Return true if the sandbox runs without a window (see `app::run_headless`)
+    pub fn is_headless(&self) -> bool {
+        self.headless.is_some() || self.benchmark.is_some()
+    }
+}
+
+// Parse a `--size` (`<width>x<height>`), refusing worlds too big to allocate (see `MAX_WORLD_CELLS`)
+fn parse_size(size: &str) -> Result<(usize, usize), String> {
+    let (width, height) = size.split_once('x').and_then(|(width, height)| Some((width.parse::<usize>().ok()?, height.parse::<usize>().ok()?)))
+        .filter(|&(width, height)| width > 0 && height > 0)
+        .ok_or_else(|| "expected <width>x<height> (ie: 640x360)".to_owned())?;
+    if width.checked_mul(height).is_none_or(|cells| cells > MAX_WORLD_CELLS) {
+        return Err(format!("a {}x{} world is too large (over {} cells)", width, height, MAX_WORLD_CELLS));
+    }
+    Ok((width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Options, String> {
+        Options::try_parse_from(std::iter::once("rusty-sandbox").chain(args.split_whitespace())).map_err(|error| error.to_string())
+    }
+
+    #[test]
+    fn options_are_parsed_and_checked() {
+        let options = parse("--size 640x360 --seed 7 --fullscreen --benchmark 100").ok().unwrap();
+        assert!(options.size == Some((640, 360)) && options.seed == Some(7) && options.is_fullscreen && options.is_headless());
+        assert!(parse("").ok().is_some_and(|options| !options.is_headless() && options.size.is_none()));
+
+        assert!(parse("--size 640").is_err() && parse("--seed").is_err() && parse("--colour red").is_err());
+        assert!(parse("--size 100000x100000").err().is_some_and(|error| error.contains("too large")) && parse("--size 0x10").is_err());
+        assert!(parse("--headless 10 --load a.world").is_err() && parse("--headless 10 --load a.world --save b.world").is_ok());
+        assert!(parse("--headless 10 --load a.world --pipe -").is_ok() && parse("--pipe -").is_err() && parse("--benchmark 10 --pipe -").is_err());
+        assert!(parse("--headless 10 --replay a.replay --save b.world --pipe -").is_ok() && parse("--headless 10 --benchmark 10 --load a.world --save b.world").is_err());
+        assert!(parse("--record a.replay").is_err() && parse("--record a.replay --size 64x64").is_ok() && parse("--replay a.replay --seed 3").is_err());
+        assert!(parse("--host 0.0.0.0:7878 --size 64x64").is_ok() && parse("--host 0.0.0.0:7878").is_err() && parse("--join a:7878 --load a.world").is_err());
+        assert!(parse("--serve 0.0.0.0:7878 --size 64x64").is_ok() && parse("--serve 0.0.0.0:7878").is_err() && parse("--serve a:7878 --size 64x64 --host b:7878").is_err());
+        assert!(parse("--size 64x64 --load a.world").is_err() && parse("--help").err().is_some_and(|usage| usage.contains("--no-vsync")));
+    }
+}
@@ -0,0 +1,212 @@
+use crate::{brush::*, input::*, keys::*, material::*, particle::*};
+
+// The name of the file the sandbox keeps the user's settings in, within it's config folder (see `storage`)
+pub static CONFIG_FILE: &str = "config.toml";
+
+// The name of the sandbox's own folder, within the platform's config folder
+static CONFIG_FOLDER: &str = "rusty-sandbox";
+
+// The user's preferences, loaded as the sandbox starts and saved as it closes
+#[derive(Clone, PartialEq)]
+pub struct Settings {
+    // The paint radius, brush shape and spray density (see `BrushOptions`) the sandbox starts with
+    pub paint_radius: u16,
+    pub brush_shape: BrushShape,
+    pub spray_density: u8,
+    // The last material painted with, and the last material painted Spouts were set to emit
+    pub material: ParticleVariant,
+    pub spout_material: ParticleVariant,
+    pub is_vsync: bool,
+    // The render rate (in frames per second) rendering is capped to, if it's capped
+    pub fps_cap: Option<u32>,
+    pub is_low_power: bool,
+    pub is_lighting: bool,
+    pub budget_overflow: BudgetOverflow,
+    // The size (in pixels) of the window as it was last closed
+    pub window_size: (u32, u32),
+    // How long (in seconds) a recording runs for before it's saved, unless it's stopped sooner
+    pub recording_seconds: u32,
+    // How often (in seconds) the world is autosaved, or zero to never autosave
+    pub autosave_seconds: u32,
+    pub keys: KeyBindings
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            paint_radius: 1,
+            brush_shape: BrushShape::Circle,
+            spray_density: 100,
+            material: ParticleVariant::Sand,
+            spout_material: ParticleVariant::Water,
+            is_vsync: true,
+            fps_cap: None,
+            is_low_power: true,
+            is_lighting: false,
+            budget_overflow: BudgetOverflow::CullGases,
+            window_size: (800, 600),
+            recording_seconds: 10,
+            autosave_seconds: 60,
+            keys: KeyBindings::default()
+        }
+    }
+}
+
+// A problem with the config file, alongside the (1-based) line it was found on
+pub struct ConfigError {
+    pub line: usize,
+    pub message: String
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+// Return the sandbox's own folder within the platform's config folder, if the platform has one
+// ... %APPDATA% on Windows, ~/Library/Application Support on macOS, and $XDG_CONFIG_HOME (or ~/.config) everywhere else
+pub fn config_folder() -> Option<std::path::PathBuf> {
+    let env = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty()).map(std::path::PathBuf::from);
+    let folder = if cfg!(target_os = "windows") {
+        env("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env("HOME").map(|home| home.join("Library").join("Application Support"))
+    } else {
+        env("XDG_CONFIG_HOME").or_else(|| env("HOME").map(|home| home.join(".config")))
+    };
+    folder.map(|folder| folder.join(CONFIG_FOLDER))
+}
+
+// Parse the config file: `key = value` pairs in the same subset of TOML as the materials file (see `parse_materials`), followed
+// ... by a `[keys]` section binding actions to lists of key names (ie: `pause = ["Space"]`)
+// ... unlike a materials file, a broken line only loses that one setting (keeping it's default), so a typo can't reset everything
+// ... else, and every problem is returned alongside the settings
+pub fn parse_settings(text: &str) -> (Settings, Vec<ConfigError>) {
+    let mut settings = Settings::default();
+    let mut errors = Vec::new();
+    let mut is_keys = false;
+    for (index, raw) in text.lines().enumerate() {
+        let line = index + 1;
+        let content = strip_comment(raw).trim();
+        if content.is_empty() {
+            continue;
+        }
+        if let Some(name) = content.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+            is_keys = name.trim() == "keys";
+            if !is_keys {
+                errors.push(ConfigError { line, message: format!("unknown section [{}], only [keys] is allowed", name.trim()) });
+            }
+            continue;
+        }
+        let Some((key, value)) = content.split_once('=') else {
+            errors.push(ConfigError { line, message: format!("expected a `key = value` pair, found '{}'", content) });
+            continue;
+        };
+        let applied = parse_value(value.trim()).and_then(|value| if is_keys {
+            apply_binding(&mut settings.keys, key.trim(), value)
+        } else {
+            apply_setting(&mut settings, key.trim(), value)
+        });
+        if let Err(message) = applied {
+            errors.push(ConfigError { line, message });
+        }
+    }
+    (settings, errors)
+}
+
+// Set a single setting from the config file
+fn apply_setting(settings: &mut Settings, key: &str, value: Value) -> Result<(), String> {
+    let number = |value: &Value, range: std::ops::RangeInclusive<f64>| match value {
+        Value::Number(number) if number.fract() == 0.0 && range.contains(number) => Ok(*number),
+        _ => Err(format!("{} must be a whole number from {} to {}", key, range.start(), range.end()))
+    };
+    let material = |value: &Value| match value {
+        Value::Text(name) => find_variant(name).ok_or_else(|| format!("unknown material '{}'", name)),
+        _ => Err(format!("{} must be a quoted material name", key))
+    };
+    let flag = |value: &Value| match value {
+        Value::Bool(flag) => Ok(*flag),
+        _ => Err(format!("{} must be true or false", key))
+    };
+    match key {
+        "paint_radius" => settings.paint_radius = number(&value, 1.0..=MAX_BRUSH_RADIUS as f64)? as u16,
+        "brush_shape" => settings.brush_shape = match &value {
+            Value::Text(text) => BRUSH_SHAPES.iter().copied().find(|shape| shape.to_string().eq_ignore_ascii_case(text)),
+            _ => None
+        }.ok_or_else(|| "brush_shape must be \"circle\" or \"square\"".to_owned())?,
+        "spray_density" => settings.spray_density = number(&value, 1.0..=100.0)? as u8,
+        "material" => settings.material = material(&value)?,
+        "spout_material" => settings.spout_material = material(&value)?,
+        "vsync" => settings.is_vsync = flag(&value)?,
+        "fps_cap" => settings.fps_cap = Some(number(&value, 0.0..=1000.0)? as u32).filter(|&fps| fps > 0),
+        "low_power" => settings.is_low_power = flag(&value)?,
+        "lighting" => settings.is_lighting = flag(&value)?,
+        "budget_overflow" => settings.budget_overflow = match &value {
+            Value::Text(text) if text == "refuse" => BudgetOverflow::Refuse,
+            Value::Text(text) if text == "cull gases" => BudgetOverflow::CullGases,
+            _ => return Err("budget_overflow must be \"refuse\" or \"cull gases\"".to_owned())
+        },
+        "window_width" => settings.window_size.0 = number(&value, 1.0..=u16::MAX as f64)? as u32,
+        "window_height" => settings.window_size.1 = number(&value, 1.0..=u16::MAX as f64)? as u32,
+        "recording_seconds" => settings.recording_seconds = number(&value, 1.0..=600.0)? as u32,
+        "autosave_seconds" => settings.autosave_seconds = number(&value, 0.0..=3600.0)? as u32,
+        _ => return Err(format!("unknown setting '{}'", key))
+    }
+    Ok(())
+}
+
+// Bind an action to the keys from the config file's `[keys]` section
+fn apply_binding(bindings: &mut KeyBindings, name: &str, value: Value) -> Result<(), String> {
+    let action = ACTIONS.iter().find(|action| action.get_name() == name).ok_or_else(|| format!("unknown action '{}'", name))?;
+    let Value::List(names) = value else {
+        return Err(format!("{} must be a [list] of quoted key names", name));
+    };
+    let keys = names.iter().map(|key| find_key(key).ok_or_else(|| format!("unknown key '{}'", key))).collect::<Result<Vec<_>, String>>()?;
+    bindings.bind(*action, keys);
+    Ok(())
+}
+
+// Write the settings out as a config file, see `parse_settings`
+pub fn save_settings(settings: &Settings) -> String {
+    let budget_overflow = match settings.budget_overflow {
+        BudgetOverflow::Refuse    => "refuse",
+        BudgetOverflow::CullGases => "cull gases"
+    };
+    let keys: Vec<String> = ACTIONS.iter().map(|action| {
+        let names: Vec<String> = settings.keys.get_keys(*action).iter().map(|key| format!("\"{}\"", key_name(*key))).collect();
+        format!("{} = [{}]\n", action.get_name(), names.join(", "))
+    }).collect();
+    format!("# Rusty Sandbox's settings, saved whenever the sandbox closes\n\
+        paint_radius = {}\n# The brush's shape: \"circle\" or \"square\"\nbrush_shape = \"{}\"\n# The percentage of cells the tools paint, spraying below 100\nspray_density = {}\nmaterial = \"{}\"\nspout_material = \"{}\"\nvsync = {}\n\
+        # The render rate to cap rendering to, or 0 for no cap\nfps_cap = {}\n\
+        low_power = {}\nlighting = {}\n\
+        # What painting does once the world is full: \"refuse\" or \"cull gases\"\nbudget_overflow = \"{}\"\n\
+        window_width = {}\nwindow_height = {}\n\
+        # How long (in seconds) recordings run for, unless they're stopped sooner\nrecording_seconds = {}\n\
+        # How often (in seconds) the world is autosaved, or 0 to never autosave\nautosave_seconds = {}\n\n\
+        # The keys bound to each action (these can also be changed from the in-game controls screen)\n[keys]\n{}",
+        settings.paint_radius, settings.brush_shape.to_string().to_lowercase(), settings.spray_density, settings.material, settings.spout_material, settings.is_vsync, settings.fps_cap.unwrap_or(0),
+        settings.is_low_power, settings.is_lighting, budget_overflow, settings.window_size.0, settings.window_size.1,
+        settings.recording_seconds, settings.autosave_seconds, keys.concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use macroquad::prelude::KeyCode;
+
+    #[test]
+    fn settings_survive_a_save_and_load() {
+        let mut settings = Settings { paint_radius: 6, brush_shape: BrushShape::Square, spray_density: 20, material: ParticleVariant::BurningFuse, fps_cap: Some(60), budget_overflow: BudgetOverflow::Refuse, recording_seconds: 30, autosave_seconds: 0, ..Settings::default() };
+        settings.keys.bind(Action::Pause, vec![KeyCode::P, KeyCode::Kp0]);
+        let (loaded, errors) = parse_settings(&save_settings(&settings));
+        assert!(errors.is_empty() && loaded == settings);
+
+        // Broken lines only lose their own setting
+        let (loaded, errors) = parse_settings("paint_radius = 4\nmaterial = \"Sandd\"\nvsync = 1\nlighting = true\n[keys]\npause = [\"Enter\"]\nzoom_in = [\"Hyper\"]");
+        let lines: Vec<usize> = errors.iter().map(|error| error.line).collect();
+        assert!(lines == [2, 3, 7] && loaded.paint_radius == 4 && loaded.is_lighting && loaded.material == ParticleVariant::Sand);
+        assert!(loaded.keys.get_keys(Action::Pause) == [KeyCode::Enter] && loaded.keys.get_keys(Action::ZoomIn) == [KeyCode::PageUp]);
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{particle::*, world::*};
+
+// The most edits kept to undo, the oldest are forgotten first
+pub static HISTORY_LIMIT: usize = 64;
+
+// A single user edit (ie: a whole paint stroke): every cell it touched as it was before and after it, and the IDs of the particles
+// ... it brought into the world, so undoing it can find them wherever they've since moved to (ie: a flood of Water)
+#[derive(Default)]
+pub struct Edit {
+    cells: HashMap<(usize, usize), (Particle, Particle)>,
+    spawned: HashSet<u32>
+}
+
+impl Edit {
+    // Take the edit back: remove every particle it brought in, then put back anything it removed or replaced
+    fn undo(&self, world: &mut World) {
+        let spawned: Vec<(usize, usize)> = world.iter().filter(|(_, particle)| particle.active && self.spawned.contains(&particle.id)).map(|(position, _)| position).collect();
+        for (x, y) in spawned {
+            world.deactivate(x, y);
+        }
+        for (&(x, y), (before, _)) in self.cells.iter().filter(|(_, (before, _))| before.active) {
+            if world.get(x, y).is_some() {
+                world.restore(x, y, before);
+            }
+        }
+    }
+
+    // Make the edit again, noting the IDs of the particles it brings back in (see `Edit::undo`)
+    fn redo(&mut self, world: &mut World) {
+        self.spawned.clear();
+        for (&(x, y), (before, after)) in self.cells.iter() {
+            if world.get(x, y).is_none() {
+                continue;
+            }
+            if after.active {
+                world.restore(x, y, after);
+                if !before.active {
+                    self.spawned.insert(world.data[(x, y)].id);
+                }
+            } else {
+                world.deactivate(x, y);
+            }
+        }
+    }
+}
+
+// The edits made to the world which can be undone, and those undone which can be redone
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    // The edit still being made (ie: a paint stroke while the mouse is held), if any
+    current: Option<Edit>
+}
+
+impl History {
+    // Make a change to the world (ie: painting) as part of the current edit, starting a new edit if there isn't one
+    pub fn edit<T>(&mut self, world: &mut World, change: impl FnOnce(&mut World) -> T) -> T {
+        world.journal = Some(Vec::new());
+        let result = change(world);
+        let journal = world.journal.take().unwrap_or_default();
+
+        // Cells first touched by an earlier part of the edit keep their state from before the whole edit
+        let edit = self.current.get_or_insert_with(Edit::default);
+        let mut changed: HashMap<(usize, usize), bool> = HashMap::new();
+        for (position, before) in journal {
+            changed.entry(position).or_insert(before.active);
+            edit.cells.entry(position).or_insert_with(|| (before.clone(), before));
+        }
+        for (position, was_active) in changed {
+            let particle = world.particle(position.0, position.1);
+            let after = Particle { variant: particle.variant, active: particle.active, data: particle.data.clone() };
+            if after.active && !was_active {
+                edit.spawned.insert(after.data.id);
+            }
+            edit.cells.entry(position).and_modify(|(_, last)| *last = after);
+        }
+        result
+    }
+
+    // Finish the current edit (if it changed anything), making it the next to undo
+    pub fn finish(&mut self) {
+        if let Some(edit) = self.current.take().filter(|edit| !edit.cells.is_empty()) {
+            self.undo.push(edit);
+            if self.undo.len() > HISTORY_LIMIT {
+                self.undo.remove(0);
+            }
+            self.redo.clear();
+        }
+    }
+
+    // Undo the last edit, returning whether there was one to undo
+    pub fn undo(&mut self, world: &mut World) -> bool {
+        self.finish();
+        let Some(edit) = self.undo.pop() else { return false };
+        edit.undo(world);
+        self.redo.push(edit);
+        true
+    }
+
+    // Redo the last undone edit, returning whether there was one to redo
+    pub fn redo(&mut self, world: &mut World) -> bool {
+        self.finish();
+        let Some(mut edit) = self.redo.pop() else { return false };
+        edit.redo(world);
+        self.undo.push(edit);
+        true
+    }
+
+    // Return how many edits there are to undo, and to redo
+    pub fn counts(&self) -> (usize, usize) {
+        (self.undo.len(), self.redo.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brush::{BrushOptions, BrushShape};
+    use crate::input::*;
+    use crate::sim::move_particle;
+
+    #[test]
+    fn undo_removes_painted_particles_wherever_they_moved() {
+        let mut particles = Particles::new(4, (0..16).map(|i| Particle::new(i, ParticleVariant::Water, false)));
+        let mut world = World::new(&mut particles);
+        let mut budget = Budget::new(&world, BudgetOverflow::Refuse);
+        let mut history = History::default();
+        world.spawn(3, 3, ParticleVariant::Stone);
+
+        // A two-part stroke, one of it's particles flowing away between the parts
+        assert_eq!(history.edit(&mut world, |world| paint_brush(world, &mut budget, (1, 0), BrushOptions { shape: BrushShape::Circle, radius: 1, density: 100 }, ParticleVariant::Water, None, 0)), 4);
+        move_particle(&mut world, 0, 0, 0, 3);
+        history.edit(&mut world, |world| paint_cell(world, &mut budget, (0, 0), ParticleVariant::Water, None));
+        history.finish();
+        assert_eq!(world.active_count(), 6);
+
+        assert!(history.undo(&mut world) && history.counts() == (0, 1));
+        assert!(world.active_count() == 1 && world.is_active(3, 3));
+        assert!(history.redo(&mut world) && !history.redo(&mut world));
+        assert!(world.is_active(0, 0) && world.is_active(1, 0) && world.active_count() == 5);
+    }
+}
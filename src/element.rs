@@ -0,0 +1,569 @@
+use macroquad::color::{Color, colors::*};
+use macroquad::color_u8;
+use std::borrow::Cow;
+
+use crate::{behaviour::*, material::*, particle::*, sim::FLASH_FRAMES};
+
+// Everything which makes a variant what it is, defined once per variant within `ELEMENTS`
+// ... adding an element means adding it's variant, and it's entry below (alongside it's behaviour's function, if it has one)
+// ... while a material added at runtime gets an element of it's own, see `install_materials`
+#[derive(Clone)]
+pub(crate) struct Element {
+    // The name shown for this variant, and used to find it within files (see `find_variant`)
+    pub(crate) name: Cow<'static, str>,
+    // The state of matter, which decides how it's particles move around the world
+    pub(crate) state: MatterState,
+    // The colour of it's particles (and their colour while powered, if it differs), before any burning, murkiness or wetness is shown
+    pub(crate) colour: Color,
+    pub(crate) powered_colour: Option<Color>,
+    // A percentage (1-100) chance of it's particles moving each frame
+    pub(crate) movement_chance: u8,
+    // The furthest distance (in particles) it's particles can move sideways in a single movement
+    // Note: this only wanders particles around, see `pile_steepness` for how steep a pile each powder forms
+    pub(crate) lateral_spread: u8,
+    // How steep a pile this powder forms: how many cells lower a neighbouring column must be before a resting particle slips down into it
+    // ... 0 flattens out completely (like Ash), 1 forms gentle slopes (like Sand), and higher stacks steeper (like Gravel)
+    pub(crate) pile_steepness: u8,
+    // The relative density: denser particles sink through lighter fluids (and lighter particles float up through denser fluids)
+    pub(crate) density: u8,
+    // A percentage (0-100) chance of it's particles refusing to move away from their own kind (and the same again, while wet)
+    // ... wet powders can hold up overhangs, which collapse once they dry out
+    pub(crate) cohesion: u8,
+    pub(crate) wet_cohesion: u8,
+    // The viscosity (0-100): thicker liquids scan a shorter distance sideways when flowing, so they level out slower
+    pub(crate) viscosity: u8,
+    // A percentage (0-100) chance of this gas drifting upwards (rather than in a random direction) each time it moves
+    pub(crate) buoyancy: u8,
+    // A percentage (1-100) chance of it's particles falling into a free cell each frame
+    // ... anything below 100 falls slower than one cell per frame (on average), like drifting Snow
+    pub(crate) fall_chance: u8,
+    // How readily it conducts heat to it's neighbours (0.0 - 1.0)
+    pub(crate) conductivity: f32,
+    // The temperature (in °C) it's created at, if it isn't simply the temperature of the cell it appears in
+    pub(crate) spawn_temperature: Option<f32>,
+    // Whether it's a source of heat
+    pub(crate) is_hot: bool,
+    // How far (in cells) it's particles' light reaches (and how far while powered, if it differs)
+    pub(crate) light_reach: f32,
+    pub(crate) powered_light_reach: Option<f32>,
+    // How it decays, if it only lasts for a limited time
+    pub(crate) decay: Option<Decay>,
+    // How it burns, if it's flammable
+    pub(crate) flammability: Option<Flammability>,
+    // Whether flowing Water can wash it away, carrying it downstream as sediment
+    pub(crate) is_erodible: bool,
+    // Whether it has no behaviour besides moving, so it can sleep while buried in it's own kind (see `is_buried`)
+    pub(crate) can_sleep: bool,
+    // Whether electricity (ie: Sparks) can travel through it
+    pub(crate) is_conductive: bool,
+    // Whether a Battery's current can flow through it, powering any connected Lamps, Heaters and Detonators
+    pub(crate) conducts_current: bool,
+    // How it slowly weathers away while exposed to something (if at all), see `weather`
+    pub(crate) weathering: Option<Weathering>,
+    // The reaction it undergoes over long periods of contact with another variant (if any)
+    pub(crate) contact_reaction: Option<ContactReaction>,
+    // It's own update logic (if it has any), see `Behaviour`
+    pub(crate) behaviour: Option<Behaviour>
+}
+
+// The properties every element starts from, which each entry of `ELEMENTS` only overrides where it differs
+// Note: this is a `const` rather than a `static`, so entries can be built from it
+const ELEMENT: Element = Element {
+    name: Cow::Borrowed(""),
+    state: MatterState::Solid,
+    colour: WHITE,
+    powered_colour: None,
+    // Particles (ie: Brick) default to being still
+    movement_chance: 0,
+    lateral_spread: 2,
+    pile_steepness: 1,
+    // Static solids never move, so they're treated as infinitely dense
+    density: u8::MAX,
+    cohesion: 0,
+    wet_cohesion: 0,
+    viscosity: 0,
+    buoyancy: 50,
+    fall_chance: 100,
+    conductivity: 0.2,
+    spawn_temperature: None,
+    is_hot: false,
+    light_reach: 0.0,
+    powered_light_reach: None,
+    decay: None,
+    flammability: None,
+    is_erodible: false,
+    can_sleep: false,
+    is_conductive: false,
+    conducts_current: false,
+    weathering: None,
+    contact_reaction: None,
+    behaviour: None
+};
+
+// Every built-in variant's element, in order (see `VARIANTS`)
+pub(crate) static ELEMENTS: [Element; VARIANTS.len()] = [
+    Element {
+        name: Cow::Borrowed("Sand"),
+        state: MatterState::Powder,
+        colour: BEIGE,
+        movement_chance: 50,
+        density: 16,
+        wet_cohesion: 90,
+        is_erodible: true,
+        can_sleep: true,
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Dirt"),
+        state: MatterState::Powder,
+        colour: DARKBROWN,
+        movement_chance: 5,
+        pile_steepness: 2,
+        density: 15,
+        wet_cohesion: 80,
+        is_erodible: true,
+        can_sleep: true,
+        // Dirt soaking in Water for long enough mixes with it, turning both into Mud
+        contact_reaction: Some(ContactReaction {
+            reactant: ParticleVariant::Water,
+            frames: 240,
+            product: ParticleVariant::Mud,
+            reactant_product: Some(ParticleVariant::Mud)
+        }),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Water"),
+        state: MatterState::Liquid,
+        colour: BLUE,
+        movement_chance: 100,
+        density: 10,
+        conductivity: 0.5,
+        can_sleep: true,
+        conducts_current: true,
+        behaviour: Some(update_water),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Brick"),
+        colour: RED,
+        conductivity: 0.01,
+        can_sleep: true,
+        // Brick exposed to Acid is pitted away
+        weathering: Some(Weathering { agent: ParticleVariant::Acid, needs_flow: false, chance: 10, product: None }),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Gunpowder"),
+        state: MatterState::Powder,
+        colour: DARKGRAY,
+        movement_chance: 50,
+        density: 17,
+        // Explosives detonate the moment they ignite, rather than burning
+        flammability: Some(Flammability { ignition: 250.0, burn_frames: 0, ash: None }),
+        can_sleep: true,
+        behaviour: Some(update_gunpowder),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Fire"),
+        state: MatterState::Gas,
+        colour: ORANGE,
+        movement_chance: 100,
+        // Gases and energy are barely there at all
+        density: 1,
+        buoyancy: 80,
+        conductivity: 0.5,
+        spawn_temperature: Some(800.0),
+        is_hot: true,
+        light_reach: 12.0,
+        decay: Some(Decay { lifetime: (5, 20), product: Some(ParticleVariant::Smoke) }),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Smoke"),
+        state: MatterState::Gas,
+        colour: GRAY,
+        movement_chance: 50,
+        density: 1,
+        decay: Some(Decay { lifetime: (30, 100), product: None }),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Salt"),
+        state: MatterState::Powder,
+        colour: WHITE,
+        movement_chance: 50,
+        density: 21,
+        can_sleep: true,
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Saltwater"),
+        state: MatterState::Liquid,
+        colour: DARKBLUE,
+        movement_chance: 100,
+        density: 11,
+        viscosity: 5,
+        conductivity: 0.5,
+        can_sleep: true,
+        conducts_current: true,
+        behaviour: Some(update_saltwater),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Steam"),
+        state: MatterState::Gas,
+        colour: LIGHTGRAY,
+        movement_chance: 50,
+        density: 1,
+        buoyancy: 70,
+        conductivity: 0.01,
+        spawn_temperature: Some(150.0),
+        decay: Some(Decay { lifetime: (600, 1200), product: Some(ParticleVariant::Water) }),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Lava"),
+        state: MatterState::Liquid,
+        colour: color_u8!(255, 80, 0, 255),
+        movement_chance: 20,
+        density: 25,
+        viscosity: 85,
+        conductivity: 0.3,
+        spawn_temperature: Some(1200.0),
+        is_hot: true,
+        light_reach: 16.0,
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Glass"),
+        // Glass is semi-transparent, letting whatever is drawn behind it show through
+        colour: color_u8!(200, 230, 255, 90),
+        conductivity: 0.1,
+        can_sleep: true,
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Metal"),
+        colour: color_u8!(160, 165, 175, 255),
+        conductivity: 0.9,
+        can_sleep: true,
+        is_conductive: true,
+        conducts_current: true,
+        // Metal exposed to Water rusts
+        weathering: Some(Weathering { agent: ParticleVariant::Water, needs_flow: false, chance: 3, product: Some(ParticleVariant::Rust) }),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Rust"),
+        state: MatterState::Powder,
+        colour: color_u8!(140, 60, 20, 255),
+        movement_chance: 10,
+        density: 50,
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Spark"),
+        state: MatterState::Energy,
+        colour: YELLOW,
+        density: 1,
+        spawn_temperature: Some(1500.0),
+        is_hot: true,
+        light_reach: 10.0,
+        behaviour: Some(update_spark),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Seed"),
+        state: MatterState::Powder,
+        colour: color_u8!(200, 170, 90, 255),
+        movement_chance: 50,
+        density: 11,
+        flammability: Some(Flammability { ignition: 250.0, burn_frames: 20, ash: Some(ParticleVariant::Ash) }),
+        behaviour: Some(update_seed),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Plant"),
+        colour: GREEN,
+        flammability: Some(Flammability { ignition: 250.0, burn_frames: 60, ash: Some(ParticleVariant::Ash) }),
+        behaviour: Some(update_plant),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Virus"),
+        colour: MAGENTA,
+        flammability: Some(Flammability { ignition: 80.0, burn_frames: 0, ash: Some(ParticleVariant::Smoke) }),
+        behaviour: Some(update_virus),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Snow"),
+        state: MatterState::Powder,
+        colour: color_u8!(235, 245, 255, 255),
+        movement_chance: 20,
+        pile_steepness: 2,
+        density: 9,
+        fall_chance: 30,
+        conductivity: 0.001,
+        spawn_temperature: Some(-30.0),
+        can_sleep: true,
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Mud"),
+        state: MatterState::Liquid,
+        colour: color_u8!(90, 60, 30, 255),
+        movement_chance: 5,
+        density: 17,
+        viscosity: 80,
+        behaviour: Some(update_mud),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Fuse"),
+        colour: color_u8!(110, 140, 90, 255),
+        behaviour: Some(update_fuse),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Burning Fuse"),
+        colour: GOLD,
+        spawn_temperature: Some(600.0),
+        is_hot: true,
+        light_reach: 8.0,
+        behaviour: Some(update_burning_fuse),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("TNT"),
+        colour: MAROON,
+        flammability: Some(Flammability { ignition: 300.0, burn_frames: 0, ash: None }),
+        behaviour: Some(update_tnt),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Gas"),
+        state: MatterState::Gas,
+        colour: color_u8!(170, 220, 120, 120),
+        movement_chance: 100,
+        density: 1,
+        buoyancy: 20,
+        flammability: Some(Flammability { ignition: 150.0, burn_frames: 0, ash: None }),
+        behaviour: Some(update_gas),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Stone"),
+        colour: color_u8!(110, 110, 115, 255),
+        conductivity: 0.1,
+        can_sleep: true,
+        // Stone exposed to flowing Water is worn down into Gravel
+        weathering: Some(Weathering { agent: ParticleVariant::Water, needs_flow: true, chance: 1, product: Some(ParticleVariant::Gravel) }),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Gravel"),
+        state: MatterState::Powder,
+        colour: color_u8!(140, 135, 130, 255),
+        movement_chance: 15,
+        lateral_spread: 1,
+        pile_steepness: 3,
+        density: 20,
+        can_sleep: true,
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Wax"),
+        colour: color_u8!(240, 230, 200, 255),
+        conductivity: 0.1,
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Molten Wax"),
+        state: MatterState::Liquid,
+        colour: color_u8!(250, 235, 160, 255),
+        movement_chance: 10,
+        density: 9,
+        viscosity: 70,
+        flammability: Some(Flammability { ignition: 230.0, burn_frames: 150, ash: None }),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Cement"),
+        state: MatterState::Liquid,
+        colour: color_u8!(150, 150, 140, 255),
+        movement_chance: 15,
+        density: 20,
+        viscosity: 75,
+        behaviour: Some(update_cement),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Concrete"),
+        colour: color_u8!(185, 185, 175, 255),
+        can_sleep: true,
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Slime"),
+        state: MatterState::Liquid,
+        colour: LIME,
+        movement_chance: 3,
+        density: 12,
+        cohesion: 95,
+        viscosity: 95,
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Clone"),
+        colour: VIOLET,
+        behaviour: Some(update_clone),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Void"),
+        colour: DARKPURPLE,
+        behaviour: Some(update_void),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Spout"),
+        colour: color_u8!(70, 130, 180, 255),
+        behaviour: Some(update_spout),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Antimatter"),
+        state: MatterState::Powder,
+        colour: PINK,
+        movement_chance: 50,
+        density: 16,
+        behaviour: Some(update_antimatter),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Flash"),
+        state: MatterState::Energy,
+        colour: WHITE,
+        density: 1,
+        light_reach: 20.0,
+        decay: Some(Decay { lifetime: (FLASH_FRAMES, FLASH_FRAMES), product: None }),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Mercury"),
+        state: MatterState::Liquid,
+        colour: color_u8!(210, 215, 225, 255),
+        movement_chance: 100,
+        density: 135,
+        viscosity: 10,
+        conductivity: 0.9,
+        can_sleep: true,
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Ice"),
+        colour: color_u8!(170, 220, 255, 255),
+        conductivity: 0.3,
+        spawn_temperature: Some(-30.0),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Oil"),
+        state: MatterState::Liquid,
+        colour: color_u8!(120, 90, 30, 255),
+        movement_chance: 60,
+        density: 8,
+        viscosity: 40,
+        flammability: Some(Flammability { ignition: 200.0, burn_frames: 90, ash: None }),
+        can_sleep: true,
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Fan"),
+        colour: color_u8!(90, 110, 130, 255),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Ash"),
+        state: MatterState::Powder,
+        colour: color_u8!(170, 165, 160, 255),
+        movement_chance: 30,
+        pile_steepness: 0,
+        density: 6,
+        wet_cohesion: 60,
+        fall_chance: 60,
+        can_sleep: true,
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Rigid Body"),
+        colour: color_u8!(150, 110, 70, 255),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Battery"),
+        colour: color_u8!(200, 170, 40, 255),
+        conducts_current: true,
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Lamp"),
+        // Powered Lamps light up
+        colour: color_u8!(90, 90, 70, 255),
+        powered_colour: Some(color_u8!(255, 250, 200, 255)),
+        powered_light_reach: Some(24.0),
+        conducts_current: true,
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Heater"),
+        // Powered Heaters glow red-hot
+        colour: color_u8!(110, 60, 50, 255),
+        powered_colour: Some(color_u8!(255, 110, 60, 255)),
+        conducts_current: true,
+        behaviour: Some(update_heater),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Detonator"),
+        colour: color_u8!(130, 20, 20, 255),
+        conducts_current: true,
+        behaviour: Some(update_detonator),
+        ..ELEMENT
+    },
+    Element {
+        name: Cow::Borrowed("Acid"),
+        state: MatterState::Liquid,
+        colour: color_u8!(140, 255, 40, 255),
+        movement_chance: 100,
+        density: 11,
+        ..ELEMENT
+    }
+];
+
+impl ParticleVariant {
+    // Return this variant's element (as installed, see `install_materials`), see `Element`
+    pub(crate) fn element(&self) -> &'static Element {
+        match get_installed_elements() {
+            Some(elements) => &elements[self.index()],
+            None => &ELEMENTS[self.index()]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_variant_has_its_own_element() {
+        for (i, variant) in VARIANTS.iter().enumerate() {
+            assert!(!variant.element().name.is_empty(), "variant {} has no element", i);
+            assert_eq!(VARIANTS.iter().filter(|other| other.element().name == variant.element().name).count(), 1, "{} is named twice", variant);
+        }
+        assert!(ParticleVariant::Acid.to_string() == "Acid" && ParticleVariant::Body.to_string() == "Rigid Body");
+    }
+}
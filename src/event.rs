@@ -0,0 +1,81 @@
+use crate::particle::*;
+
+// Something notable that happened to the world's particles during a frame, see `EventBus`
+#[derive(Clone, Copy, PartialEq)]
+pub enum Event {
+    // A particle came into being during the simulation: emitted by another particle, or another particle turning into it
+    ParticleSpawned { position: (usize, usize), variant: ParticleVariant },
+    // A particle was destroyed, leaving it's cell empty (or to a brief Flash)
+    ParticleDestroyed { position: (usize, usize), variant: ParticleVariant },
+    // Two particles reacted with each other, the first being the one at `position`
+    ReactionOccurred { position: (usize, usize), reactants: (ParticleVariant, ParticleVariant) },
+    // An explosion was detonated, centred on `position`
+    ExplosionTriggered { position: (usize, usize), radius: usize }
+}
+
+impl Event {
+    // Return the event moved `columns` to the right, turning a position within a stripe's window into one within the world
+    pub(crate) fn shifted(self, columns: usize) -> Event {
+        let shift = |(x, y): (usize, usize)| (x + columns, y);
+        match self {
+            Event::ParticleSpawned { position, variant } => Event::ParticleSpawned { position: shift(position), variant },
+            Event::ParticleDestroyed { position, variant } => Event::ParticleDestroyed { position: shift(position), variant },
+            Event::ReactionOccurred { position, reactants } => Event::ReactionOccurred { position: shift(position), reactants },
+            Event::ExplosionTriggered { position, radius } => Event::ExplosionTriggered { position: shift(position), radius }
+        }
+    }
+}
+
+// A subscriber to the simulation's events, see `EventBus::subscribe`
+pub type Subscriber = Box<dyn FnMut(&Event)>;
+
+// The subscribers to the simulation's events, which are handed every event of a frame once that frame is simulated
+// ... events are only recorded while something is subscribed, so a simulation without subscribers pays nothing for them
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: Vec<Subscriber>
+}
+
+impl EventBus {
+    pub fn subscribe(&mut self, subscriber: impl FnMut(&Event) + 'static) {
+        self.subscribers.push(Box::new(subscriber));
+    }
+
+    // Return true if anything is subscribed, see `EventBus`
+    pub fn is_listening(&self) -> bool {
+        !self.subscribers.is_empty()
+    }
+
+    // Hand every event to every subscriber, in the order they happened
+    pub fn publish(&mut self, events: &[Event]) {
+        for event in events {
+            for subscriber in self.subscribers.iter_mut() {
+                subscriber(event);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    #[test]
+    fn subscribers_hear_about_explosions() {
+        let mut simulation = crate::Simulation::new(32, 32);
+        simulation.paint(16, 30, 2, ParticleVariant::Gunpowder);
+        simulation.paint(16, 28, 1, ParticleVariant::Lava);
+
+        let heard = Rc::new(RefCell::new(Vec::new()));
+        let log = heard.clone();
+        simulation.parts().3.events.subscribe(move |event| log.borrow_mut().push(*event));
+        for _ in 0..30 {
+            simulation.step();
+        }
+
+        let heard = heard.borrow();
+        assert!(heard.iter().any(|event| matches!(event, Event::ExplosionTriggered { .. })));
+        assert!(heard.iter().any(|event| matches!(event, Event::ParticleSpawned { variant: ParticleVariant::Fire, .. })));
+    }
+}
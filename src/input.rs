@@ -0,0 +1,270 @@
+use crate::{brush::*, particle::*, world::*};
+
+// The most active particles painting can fill the world up to, so it can't be painted into a slideshow (see `BudgetOverflow`)
+pub static PARTICLE_BUDGET: usize = 500_000;
+
+// The most cells a single flood fill can fill, so a click into open space can't flood the whole world (see `flood_region`)
+pub static MAX_FILL_CELLS: usize = 100_000;
+
+// What painting does once the world has reached the particle budget
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BudgetOverflow {
+    // Paint nothing more, warning the user instead
+    Refuse,
+    // Make room by culling the gases closest to the end of their lifetime first (see `cull_gases`)
+    CullGases
+}
+
+impl BudgetOverflow {
+    // Return the other overflow behaviour
+    pub fn next(&self) -> BudgetOverflow {
+        match self {
+            BudgetOverflow::Refuse    => BudgetOverflow::CullGases,
+            BudgetOverflow::CullGases => BudgetOverflow::Refuse
+        }
+    }
+}
+
+impl std::fmt::Display for BudgetOverflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BudgetOverflow::Refuse    => write!(f, "refuse to paint"),
+            BudgetOverflow::CullGases => write!(f, "cull gases")
+        }
+    }
+}
+
+// Cull up to `count` gas particles to make room within the particle budget, returning how many were culled
+// ... those closest to the end of their lifetime (the oldest) go first, and gases which never decay go last
+fn cull_gases(world: &mut World, count: usize) -> usize {
+    let mut gases: Vec<(u16, usize, usize)> = world.iter()
+        .filter(|(_, particle)| particle.active && particle.variant.get_state() == MatterState::Gas)
+        .map(|((x, y), particle)| (particle.lifetime.unwrap_or(u16::MAX), x, y))
+        .collect();
+    gases.sort_unstable();
+    gases.truncate(count);
+    for &(_, x, y) in gases.iter() {
+        world.deactivate(x, y);
+    }
+    gases.len()
+}
+
+// A shape which can be dragged out between two corners, see `shape_cells`
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+    Rectangle,
+    Ellipse
+}
+
+impl std::fmt::Display for Shape {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Shape::Rectangle => write!(f, "Rectangle"),
+            Shape::Ellipse   => write!(f, "Ellipse")
+        }
+    }
+}
+
+// The room left within the particle budget for painting into the world, what to do once it runs out, and whether any
+// ... painting has been refused for the lack of it
+pub struct Budget {
+    pub room: usize,
+    pub overflow: BudgetOverflow,
+    pub is_exceeded: bool
+}
+
+impl Budget {
+    // Measure the room left within `PARTICLE_BUDGET` in the world as it is now
+    pub fn new(world: &World, overflow: BudgetOverflow) -> Budget {
+        Budget { room: PARTICLE_BUDGET.saturating_sub(world.active_count()), overflow, is_exceeded: false }
+    }
+}
+
+// Paint a particle of the given variant into (x, y) if it's an empty cell within the world, and there's room for it in the budget
+// ... carrying the given template (ie: the variant a Spout emits), and returning whether it was painted
+pub fn paint_cell(world: &mut World, budget: &mut Budget, (x, y): (isize, isize), variant: ParticleVariant, template: Option<ParticleVariant>) -> bool {
+    let (Ok(x), Ok(y)) = (usize::try_from(x), usize::try_from(y)) else { return false };
+    if x >= world.width() || y >= world.height() || world.is_active(x, y) {
+        return false;
+    }
+    if budget.room == 0 {
+        budget.is_exceeded = true;
+        return false;
+    }
+    budget.room -= 1;
+    world.spawn(x, y, variant);
+    world.data[(x, y)].template = template;
+    true
+}
+
+// Paint particles into the given cells (those the spray paints, see `Spray`) like `paint_cell`, making room for all of them
+// ... first when the budget's running out (if it's allowed to cull), and returning how many were painted
+pub fn paint_cells(world: &mut World, budget: &mut Budget, cells: Vec<(isize, isize)>, variant: ParticleVariant, template: Option<ParticleVariant>, spray: &mut Spray) -> usize {
+    let cells: Vec<(isize, isize)> = cells.into_iter().filter(|_| spray.paints()).collect();
+    if budget.overflow == BudgetOverflow::CullGases && budget.room < cells.len() {
+        budget.room += cull_gases(world, cells.len() - budget.room);
+    }
+    cells.into_iter().filter(|&position| paint_cell(world, budget, position, variant, template)).count()
+}
+
+// Paint particles into the cells of a brush centred on (x, y) (see `brush_cells`) like `paint_cells`, sprayed at the brush's density
+// ... as of the given frame (see `Spray`)
+pub fn paint_brush(world: &mut World, budget: &mut Budget, position: (isize, isize), options: BrushOptions, variant: ParticleVariant, template: Option<ParticleVariant>, frame: u64) -> usize {
+    let mut spray = Spray::new(options.density, frame, position);
+    paint_cells(world, budget, brush_cells(options.shape, position, options.radius), variant, template, &mut spray)
+}
+
+// Return the cells along the straight line from one cell to another (both included), as stepped by Bresenham's line algorithm
+pub fn line_cells(from: (isize, isize), to: (isize, isize)) -> Vec<(isize, isize)> {
+    let (dx, dy) = ((to.0 - from.0).abs(), -(to.1 - from.1).abs());
+    let (step_x, step_y) = ((to.0 - from.0).signum(), (to.1 - from.1).signum());
+    let (mut x, mut y) = from;
+    let mut error = dx + dy;
+    let mut cells = vec![from];
+    while (x, y) != to {
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+        cells.push((x, y));
+    }
+    cells
+}
+
+// Paint a particle into every cell along the straight line from one cell to another (see `line_cells`) like `paint_cell`, not
+// ... including the cell it starts from (ie: to fill the gaps left by a fast flick of the mouse, where the last frame's paint
+// ... already covered the start), and returning how many were painted
+pub fn paint_path(world: &mut World, budget: &mut Budget, from: (isize, isize), to: (isize, isize), variant: ParticleVariant) -> usize {
+    line_cells(from, to).into_iter().skip(1).filter(|&position| paint_cell(world, budget, position, variant, None)).count()
+}
+
+// Return the cells connected (side by side) to (x, y) which hold what it holds: nothing, or the same material
+// ... or None if (x, y) is outside of the world, or the region is larger than `MAX_FILL_CELLS` (see `fill_region`)
+pub fn flood_region(world: &World, (x, y): (isize, isize)) -> Option<Vec<(usize, usize)>> {
+    let (Ok(x), Ok(y)) = (usize::try_from(x), usize::try_from(y)) else { return None };
+    if x >= world.width() || y >= world.height() {
+        return None;
+    }
+    let contents = |x: usize, y: usize| world.is_active(x, y).then(|| world.particle(x, y).variant);
+    let target = contents(x, y);
+    let mut is_visited = vec![false; world.width() * world.height()];
+    is_visited[x * world.height() + y] = true;
+    let mut region = vec![(x, y)];
+    let mut next = 0;
+    while let Some(&(x, y)) = region.get(next) {
+        next += 1;
+        for (nx, ny) in [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)] {
+            if nx < world.width() && ny < world.height() && !is_visited[nx * world.height() + ny] && contents(nx, ny) == target {
+                is_visited[nx * world.height() + ny] = true;
+                region.push((nx, ny));
+                if region.len() > MAX_FILL_CELLS {
+                    return None;
+                }
+            }
+        }
+    }
+    Some(region)
+}
+
+// Fill the region connected to (x, y) (see `flood_region`) with the given variant, or the cells of it the spray paints: empty
+// ... cells are painted like `paint_cells`, while a material's cells are replaced outright, taking no more of the budget
+// ... returning how many cells were filled
+pub fn fill_region(world: &mut World, budget: &mut Budget, position: (isize, isize), variant: ParticleVariant, spray: &mut Spray) -> usize {
+    let Some(region) = flood_region(world, position) else { return 0 };
+    let (x, y) = region[0];
+    if world.is_active(x, y) {
+        if world.particle(x, y).variant == variant {
+            return 0;
+        }
+        let region: Vec<(usize, usize)> = region.into_iter().filter(|_| spray.paints()).collect();
+        for &(x, y) in region.iter() {
+            world.spawn(x, y, variant);
+        }
+        return region.len();
+    }
+    paint_cells(world, budget, region.into_iter().map(|(x, y)| (x as isize, y as isize)).collect(), variant, None, spray)
+}
+
+// Return the cells of a shape fitting the box between two (opposite, included) corners: either every cell within it, or only
+// ... it's outline (the cells within it with a neighbour outside of it, which leaves no gaps for anything to leak through)
+pub fn shape_cells(shape: Shape, from: (isize, isize), to: (isize, isize), is_filled: bool) -> Vec<(isize, isize)> {
+    let (x0, x1, y0, y1) = (from.0.min(to.0), from.0.max(to.0), from.1.min(to.1), from.1.max(to.1));
+    // ... an ellipse takes in the cells whose centres lie within it
+    let (centre_x, centre_y) = ((x0 + x1 + 1) as f32 / 2.0, (y0 + y1 + 1) as f32 / 2.0);
+    let (radius_x, radius_y) = ((x1 - x0 + 1) as f32 / 2.0, (y1 - y0 + 1) as f32 / 2.0);
+    let is_inside = |x: isize, y: isize| (x0..=x1).contains(&x) && (y0..=y1).contains(&y) && match shape {
+        Shape::Rectangle => true,
+        Shape::Ellipse => ((x as f32 + 0.5 - centre_x) / radius_x).powi(2) + ((y as f32 + 0.5 - centre_y) / radius_y).powi(2) <= 1.0
+    };
+    (x0..=x1).flat_map(|x| (y0..=y1).map(move |y| (x, y)))
+        .filter(|&(x, y)| is_inside(x, y) && (is_filled || [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)].iter().any(|&(nx, ny)| !is_inside(nx, ny))))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn culling_takes_the_oldest_gases_first() {
+        let variants = [ParticleVariant::Sand, ParticleVariant::Gas, ParticleVariant::Smoke, ParticleVariant::Smoke];
+        let mut particles = Particles::new(4, variants.iter().enumerate().map(|(i, &variant)| Particle::new(i as u32, variant, true)));
+        let mut world = World::new(&mut particles);
+        world.data[(0, 2)].lifetime = Some(50);
+        world.data[(0, 3)].lifetime = Some(10);
+
+        // The Smoke closest to dissipating goes first, then the other Smoke, but never anything other than gas
+        assert_eq!(cull_gases(&mut world, 1), 1);
+        assert!(world.is_active(0, 2) && !world.is_active(0, 3));
+        assert_eq!(cull_gases(&mut world, 5), 2);
+        assert_eq!(world.active_count(), 1);
+    }
+
+    #[test]
+    fn filling_stops_at_other_materials_and_the_cap() {
+        let variants = [ParticleVariant::Brick, ParticleVariant::Sand, ParticleVariant::Sand, ParticleVariant::Brick];
+        let mut particles = Particles::new(4, (0..16).map(|i| Particle::new(i as u32, variants[i % 4], i % 4 == 0 || i % 4 == 3 || i < 8)));
+        let mut world = World::new(&mut particles);
+        let mut budget = Budget::new(&world, BudgetOverflow::Refuse);
+
+        // The empty cells walled in by Brick (and Sand) are filled, then the Water and the Sand are each replaced by Oil
+        assert_eq!(fill_region(&mut world, &mut budget, (2, 1), ParticleVariant::Water, &mut Spray::solid()), 4);
+        assert!(world.is_active(3, 2) && world.particle(3, 2).variant == ParticleVariant::Water && world.particle(0, 1).variant == ParticleVariant::Sand);
+        assert_eq!(fill_region(&mut world, &mut budget, (3, 1), ParticleVariant::Oil, &mut Spray::solid()), 4);
+        assert_eq!(fill_region(&mut world, &mut budget, (0, 1), ParticleVariant::Oil, &mut Spray::solid()), 4);
+        assert_eq!(fill_region(&mut world, &mut budget, (3, 2), ParticleVariant::Oil, &mut Spray::solid()), 0);
+        assert_eq!(flood_region(&world, (3, 2)).map(|region| region.len()), Some(8));
+        assert!(flood_region(&world, (4, 0)).is_none());
+        assert_eq!(flood_region(&world, (0, 0)).map(|region| region.len()), Some(4));
+
+        // A region bigger than the cap isn't filled at all
+        let mut particles = Particles::new(1, (0..MAX_FILL_CELLS + 1).map(|i| Particle::new(i as u32, ParticleVariant::Sand, false)));
+        let world = World::new(&mut particles);
+        assert!(flood_region(&world, (0, 0)).is_none());
+    }
+
+    #[test]
+    fn lines_step_evenly_between_their_ends() {
+        assert!(line_cells((0, 0), (5, 2)) == [(0, 0), (1, 0), (2, 1), (3, 1), (4, 2), (5, 2)]);
+        assert!(line_cells((2, 5), (2, 3)) == [(2, 5), (2, 4), (2, 3)] && line_cells((1, 1), (1, 1)) == [(1, 1)]);
+        assert!(line_cells((3, 0), (0, 3)) == [(3, 0), (2, 1), (1, 2), (0, 3)]);
+    }
+
+    #[test]
+    fn shapes_fill_or_outline_their_box() {
+        assert_eq!(shape_cells(Shape::Rectangle, (3, 2), (0, 0), true).len(), 12);
+        let outline = shape_cells(Shape::Rectangle, (0, 0), (3, 2), false);
+        assert!(outline.len() == 10 && !outline.contains(&(1, 1)) && outline.contains(&(3, 2)));
+
+        // A circle leaves out the box's corners, and it's outline leaves out it's middle
+        let circle = shape_cells(Shape::Ellipse, (0, 0), (4, 4), true);
+        assert!(circle.contains(&(2, 2)) && circle.contains(&(2, 0)) && !circle.contains(&(0, 0)));
+        let ring = shape_cells(Shape::Ellipse, (0, 0), (4, 4), false);
+        assert!(ring.contains(&(2, 0)) && !ring.contains(&(2, 2)) && ring.iter().all(|cell| circle.contains(cell)));
+    }
+}
@@ -0,0 +1,280 @@
+use macroquad::prelude::*;
+
+// A control of the sandbox which can be bound to keys
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    PanUp,
+    PanLeft,
+    PanDown,
+    PanRight,
+    ZoomIn,
+    ZoomOut,
+    BrushBigger,
+    BrushSmaller,
+    Pause,
+    // Simulate a single frame, while paused
+    Step,
+    // Halve (or double) the simulation's speed
+    SlowDown,
+    SpeedUp,
+    // Select the nth material of the material buttons (from zero)
+    Material(u8),
+    WindLeft,
+    WindRight,
+    Gravity,
+    DropBox,
+    DropBall,
+    Lighting,
+    GpuPowders,
+    Offscreen,
+    FpsCap,
+    LowPower,
+    BudgetOverflow,
+    Brush,
+    BrushShape,
+    Spray,
+    // Cycle what the left mouse button does (see `Tool`)
+    Tool,
+    // Select a region (to copy), and turn or flip the region being pasted
+    Select,
+    Rotate,
+    FlipHorizontal,
+    FlipVertical,
+    // Open the stamp browser
+    Stamps,
+    // Save a screenshot of the world, and start (or stop) recording an animation of it
+    Screenshot,
+    Record,
+    // Open (or close) the controls screen, where every other action can be rebound
+    Controls
+}
+
+// Every action, in the order the controls screen lists them
+pub static ACTIONS: [Action; 44] = [
+    Action::PanUp, Action::PanLeft, Action::PanDown, Action::PanRight, Action::ZoomIn, Action::ZoomOut,
+    Action::BrushBigger, Action::BrushSmaller, Action::Pause, Action::Step, Action::SlowDown, Action::SpeedUp,
+    Action::Material(0), Action::Material(1), Action::Material(2), Action::Material(3), Action::Material(4),
+    Action::Material(5), Action::Material(6), Action::Material(7), Action::Material(8),
+    Action::WindLeft, Action::WindRight, Action::Gravity, Action::DropBox, Action::DropBall, Action::Lighting,
+    Action::GpuPowders, Action::Offscreen, Action::FpsCap, Action::LowPower, Action::BudgetOverflow, Action::Brush,
+    Action::BrushShape, Action::Spray, Action::Tool, Action::Select, Action::Rotate, Action::FlipHorizontal, Action::FlipVertical, Action::Stamps,
+    Action::Screenshot, Action::Record, Action::Controls
+];
+
+// Every key which can be bound to an action, see `find_key`
+static BINDABLE_KEYS: &[KeyCode] = &[
+    KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F, KeyCode::G, KeyCode::H, KeyCode::I, KeyCode::J,
+    KeyCode::K, KeyCode::L, KeyCode::M, KeyCode::N, KeyCode::O, KeyCode::P, KeyCode::Q, KeyCode::R, KeyCode::S, KeyCode::T,
+    KeyCode::U, KeyCode::V, KeyCode::W, KeyCode::X, KeyCode::Y, KeyCode::Z,
+    KeyCode::Key0, KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4, KeyCode::Key5, KeyCode::Key6, KeyCode::Key7,
+    KeyCode::Key8, KeyCode::Key9,
+    KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4, KeyCode::F5, KeyCode::F6, KeyCode::F7, KeyCode::F8, KeyCode::F9,
+    KeyCode::F10, KeyCode::F11, KeyCode::F12,
+    KeyCode::Up, KeyCode::Down, KeyCode::Left, KeyCode::Right, KeyCode::PageUp, KeyCode::PageDown, KeyCode::Home, KeyCode::End,
+    KeyCode::Insert, KeyCode::Delete, KeyCode::Space, KeyCode::Tab, KeyCode::Enter, KeyCode::Backspace,
+    KeyCode::Apostrophe, KeyCode::Comma, KeyCode::Minus, KeyCode::Period, KeyCode::Slash, KeyCode::Semicolon, KeyCode::Equal,
+    KeyCode::LeftBracket, KeyCode::Backslash, KeyCode::RightBracket, KeyCode::GraveAccent,
+    KeyCode::Kp0, KeyCode::Kp1, KeyCode::Kp2, KeyCode::Kp3, KeyCode::Kp4, KeyCode::Kp5, KeyCode::Kp6, KeyCode::Kp7,
+    KeyCode::Kp8, KeyCode::Kp9, KeyCode::KpDecimal, KeyCode::KpDivide, KeyCode::KpMultiply, KeyCode::KpSubtract,
+    KeyCode::KpAdd, KeyCode::KpEnter, KeyCode::KpEqual
+];
+
+impl Action {
+    // Return the keys the action is bound to out of the box
+    fn get_default_keys(&self) -> Vec<KeyCode> {
+        match self {
+            Action::PanUp          => vec![KeyCode::W, KeyCode::Up],
+            Action::PanLeft        => vec![KeyCode::A, KeyCode::Left],
+            Action::PanDown        => vec![KeyCode::S, KeyCode::Down],
+            Action::PanRight       => vec![KeyCode::D, KeyCode::Right],
+            Action::ZoomIn         => vec![KeyCode::PageUp],
+            Action::ZoomOut        => vec![KeyCode::PageDown],
+            // Laptops without a Numpad can use the +/= and - keys instead
+            Action::BrushBigger    => vec![KeyCode::KpAdd, KeyCode::Equal],
+            Action::BrushSmaller   => vec![KeyCode::KpSubtract, KeyCode::Minus],
+            Action::Pause          => vec![KeyCode::Space],
+            Action::Step           => vec![KeyCode::Period],
+            Action::SlowDown       => vec![KeyCode::LeftBracket],
+            Action::SpeedUp        => vec![KeyCode::RightBracket],
+            Action::Material(n)    => vec![[KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4, KeyCode::Key5, KeyCode::Key6,
+                                           KeyCode::Key7, KeyCode::Key8, KeyCode::Key9][*n as usize]],
+            Action::WindLeft       => vec![KeyCode::Q],
+            Action::WindRight      => vec![KeyCode::E],
+            Action::Gravity        => vec![KeyCode::G],
+            Action::DropBox        => vec![KeyCode::B],
+            Action::DropBall       => vec![KeyCode::O],
+            Action::Lighting       => vec![KeyCode::L],
+            Action::GpuPowders     => vec![KeyCode::C],
+            Action::Offscreen      => vec![KeyCode::F],
+            Action::FpsCap         => vec![KeyCode::P],
+            Action::LowPower       => vec![KeyCode::Z],
+            Action::BudgetOverflow => vec![KeyCode::K],
+            Action::Brush          => vec![KeyCode::T],
+            Action::BrushShape     => vec![KeyCode::J],
+            Action::Spray          => vec![KeyCode::Y],
+            Action::Tool           => vec![KeyCode::M],
+            Action::Select         => vec![KeyCode::X],
+            Action::Rotate         => vec![KeyCode::R],
+            Action::FlipHorizontal => vec![KeyCode::H],
+            Action::FlipVertical   => vec![KeyCode::V],
+            Action::Stamps         => vec![KeyCode::N],
+            Action::Screenshot     => vec![KeyCode::F12],
+            Action::Record         => vec![KeyCode::F9],
+            Action::Controls       => vec![KeyCode::F1]
+        }
+    }
+
+    // Return the action's name within the config file (see `parse_settings`)
+    pub fn get_name(&self) -> String {
+        match self {
+            Action::PanUp          => "pan_up".to_owned(),
+            Action::PanLeft        => "pan_left".to_owned(),
+            Action::PanDown        => "pan_down".to_owned(),
+            Action::PanRight       => "pan_right".to_owned(),
+            Action::ZoomIn         => "zoom_in".to_owned(),
+            Action::ZoomOut        => "zoom_out".to_owned(),
+            Action::BrushBigger    => "brush_bigger".to_owned(),
+            Action::BrushSmaller   => "brush_smaller".to_owned(),
+            Action::Pause          => "pause".to_owned(),
+            Action::Step           => "step".to_owned(),
+            Action::SlowDown       => "slow_down".to_owned(),
+            Action::SpeedUp        => "speed_up".to_owned(),
+            Action::Material(n)    => format!("material_{}", n + 1),
+            Action::WindLeft       => "wind_left".to_owned(),
+            Action::WindRight      => "wind_right".to_owned(),
+            Action::Gravity        => "gravity".to_owned(),
+            Action::DropBox        => "drop_box".to_owned(),
+            Action::DropBall       => "drop_ball".to_owned(),
+            Action::Lighting       => "lighting".to_owned(),
+            Action::GpuPowders     => "gpu_powders".to_owned(),
+            Action::Offscreen      => "offscreen".to_owned(),
+            Action::FpsCap         => "fps_cap".to_owned(),
+            Action::LowPower       => "low_power".to_owned(),
+            Action::BudgetOverflow => "budget_overflow".to_owned(),
+            Action::Brush          => "brush".to_owned(),
+            Action::BrushShape     => "brush_shape".to_owned(),
+            Action::Spray          => "spray".to_owned(),
+            Action::Tool           => "tool".to_owned(),
+            Action::Select         => "select".to_owned(),
+            Action::Rotate         => "rotate".to_owned(),
+            Action::FlipHorizontal => "flip_horizontal".to_owned(),
+            Action::FlipVertical   => "flip_vertical".to_owned(),
+            Action::Stamps         => "stamps".to_owned(),
+            Action::Screenshot     => "screenshot".to_owned(),
+            Action::Record         => "record".to_owned(),
+            Action::Controls       => "controls".to_owned()
+        }
+    }
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Action::PanUp          => write!(f, "Pan up"),
+            Action::PanLeft        => write!(f, "Pan left"),
+            Action::PanDown        => write!(f, "Pan down"),
+            Action::PanRight       => write!(f, "Pan right"),
+            Action::ZoomIn         => write!(f, "Zoom in"),
+            Action::ZoomOut        => write!(f, "Zoom out"),
+            Action::BrushBigger    => write!(f, "Bigger brush"),
+            Action::BrushSmaller   => write!(f, "Smaller brush"),
+            Action::Pause          => write!(f, "Pause"),
+            Action::Step           => write!(f, "Step a frame (paused)"),
+            Action::SlowDown       => write!(f, "Slow down"),
+            Action::SpeedUp        => write!(f, "Speed up"),
+            Action::Material(n)    => write!(f, "Material {}", n + 1),
+            Action::WindLeft       => write!(f, "Blow wind left"),
+            Action::WindRight      => write!(f, "Blow wind right"),
+            Action::Gravity        => write!(f, "Rotate gravity"),
+            Action::DropBox        => write!(f, "Drop a box"),
+            Action::DropBall       => write!(f, "Drop a ball"),
+            Action::Lighting       => write!(f, "Toggle lighting"),
+            Action::GpuPowders     => write!(f, "Toggle GPU powders"),
+            Action::Offscreen      => write!(f, "Change off-screen mode"),
+            Action::FpsCap         => write!(f, "Change FPS cap"),
+            Action::LowPower       => write!(f, "Toggle low-power"),
+            Action::BudgetOverflow => write!(f, "Change particle budget"),
+            Action::Brush          => write!(f, "Change brush"),
+            Action::BrushShape     => write!(f, "Change brush shape"),
+            Action::Spray          => write!(f, "Change spray density"),
+            Action::Tool           => write!(f, "Change tool"),
+            Action::Select         => write!(f, "Select a region"),
+            Action::Rotate         => write!(f, "Rotate the paste"),
+            Action::FlipHorizontal => write!(f, "Flip the paste across"),
+            Action::FlipVertical   => write!(f, "Flip the paste over"),
+            Action::Stamps         => write!(f, "Browse the stamps"),
+            Action::Screenshot     => write!(f, "Take a screenshot"),
+            Action::Record         => write!(f, "Record an animation"),
+            Action::Controls       => write!(f, "Controls")
+        }
+    }
+}
+
+// Find the bindable key with the given name (as written by `key_name`), ignoring case
+pub fn find_key(name: &str) -> Option<KeyCode> {
+    BINDABLE_KEYS.iter().copied().find(|key| key_name(*key).eq_ignore_ascii_case(name))
+}
+
+// Return the name of a key, as shown on the controls screen and written to the config file
+pub fn key_name(key: KeyCode) -> String {
+    format!("{:?}", key).replace("Key", "")
+}
+
+// Return the bindable key pressed this frame, if any (ie: the key to rebind an action to)
+pub fn get_pressed_key() -> Option<KeyCode> {
+    BINDABLE_KEYS.iter().copied().find(|key| is_key_pressed(*key))
+}
+
+// Which keys each action is bound to (an action can have several keys, or none at all)
+#[derive(Clone, PartialEq)]
+pub struct KeyBindings {
+    bindings: Vec<(Action, Vec<KeyCode>)>
+}
+
+impl Default for KeyBindings {
+    fn default() -> KeyBindings {
+        KeyBindings { bindings: ACTIONS.iter().map(|action| (*action, action.get_default_keys())).collect() }
+    }
+}
+
+impl KeyBindings {
+    pub fn get_keys(&self, action: Action) -> &[KeyCode] {
+        self.bindings.iter().find(|(a, _)| *a == action).map_or(&[], |(_, keys)| keys)
+    }
+
+    // Bind an action to the given keys, replacing it's previous keys (and unbinding them from any other action)
+    pub fn bind(&mut self, action: Action, keys: Vec<KeyCode>) {
+        for (_, other_keys) in self.bindings.iter_mut().filter(|(other, _)| *other != action) {
+            other_keys.retain(|key| !keys.contains(key));
+        }
+        match self.bindings.iter_mut().find(|(a, _)| *a == action) {
+            Some((_, bound)) => *bound = keys,
+            None => self.bindings.push((action, keys))
+        }
+    }
+
+    // Return true if any of the action's keys was pressed this frame
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.get_keys(action).iter().any(|key| is_key_pressed(*key))
+    }
+
+    // Return true if any of the action's keys is held down
+    pub fn is_down(&self, action: Action) -> bool {
+        self.get_keys(action).iter().any(|key| is_key_down(*key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binding_a_key_takes_it_from_other_actions() {
+        let mut bindings = KeyBindings::default();
+        assert!(bindings.get_keys(Action::Material(0)) == [KeyCode::Key1] && bindings.get_keys(Action::PanUp) == [KeyCode::W, KeyCode::Up]);
+
+        bindings.bind(Action::Pause, vec![KeyCode::W]);
+        assert!(bindings.get_keys(Action::Pause) == [KeyCode::W] && bindings.get_keys(Action::PanUp) == [KeyCode::Up]);
+        assert!(find_key(&key_name(KeyCode::Key7)) == Some(KeyCode::Key7) && find_key("kpadd") == Some(KeyCode::KpAdd) && find_key("Escape").is_none());
+    }
+}
@@ -0,0 +1,201 @@
+pub mod app;
+pub mod behaviour;
+pub mod brush;
+pub mod chunk;
+pub mod cli;
+pub mod config;
+pub mod edit;
+pub mod element;
+pub mod event;
+pub mod input;
+pub mod keys;
+pub mod material;
+pub mod net;
+pub mod particle;
+pub mod plugin;
+pub mod png;
+pub mod region;
+pub mod render;
+pub mod replay;
+pub mod save;
+pub mod script;
+pub mod sim;
+pub mod snapshot;
+pub mod storage;
+pub mod world;
+
+use brush::{BrushOptions, BrushShape};
+use input::{paint_brush, Budget, BudgetOverflow};
+use particle::{Particle, ParticleVariant};
+use save::{parse_world, save_world, save_world_binary, WorldError, WorldFile};
+use sim::{tick, GpuBackend, SimulationState};
+use snapshot::Snapshot;
+use world::{resize_grid, step, Grid, Particles, World};
+
+// A whole sandbox simulation: the world's particles, the fields laid over them, and the state carried between frames
+// ... this is the engine's front door, for stepping a world without (or alongside) the sandbox's own window
+pub struct Simulation {
+    particles: Particles,
+    // The pressure and wind fields' cells (see `Grid`), matching the shape of the particle grid
+    pressure_cells: Vec<f32>,
+    wind_cells: Vec<f32>,
+    size: (usize, usize),
+    state: SimulationState,
+    // The last particle ID generated
+    last_id: u32
+}
+
+impl Simulation {
+    // Create an empty world of the given size, with it's RNG seeded with zero (see `Simulation::with_seed`)
+    pub fn new(width: usize, height: usize) -> Simulation {
+        Simulation::with_seed(width, height, 0)
+    }
+
+    // Create an empty world of the given size, with it's RNG seeded with `seed` (the same seed and inputs reproduce the same run)
+    pub fn with_seed(width: usize, height: usize, seed: u64) -> Simulation {
+        let mut simulation = Simulation {
+            particles: Particles::new(0, []),
+            pressure_cells: Vec::new(),
+            wind_cells: Vec::new(),
+            size: (0, 0),
+            state: SimulationState::new(seed),
+            last_id: 0
+        };
+        simulation.resize((width, height), (0, 0));
+        simulation
+    }
+
+    pub fn width(&self) -> usize {
+        self.size.0
+    }
+
+    pub fn height(&self) -> usize {
+        self.size.1
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        self.size
+    }
+
+    // Resize the world (and it's fields), shifting everything within by `offset` like `resize_grid`
+    // ... rigid bodies move along with the world, dropping any which are cropped out of it entirely
+    pub fn resize(&mut self, size: (usize, usize), offset: (isize, isize)) {
+        // Fill any new space with non-interactive placeholder particles
+        let last_id = &mut self.last_id;
+        let particles = std::mem::replace(&mut self.particles, Particles::new(0, []));
+        self.particles = particles.resize(size, offset, || {
+            *last_id += 1;
+            Particle::new(*last_id, ParticleVariant::Sand, false)
+        });
+        self.pressure_cells = resize_grid(std::mem::take(&mut self.pressure_cells), self.size, size, offset, || 0.0);
+        self.wind_cells = resize_grid(std::mem::take(&mut self.wind_cells), self.size, size, offset, || 0.0);
+        self.size = size;
+
+        for body in self.state.bodies.iter_mut() {
+            body.position = (body.position.0 + offset.0 as f32, body.position.1 + offset.1 as f32);
+            body.cells = body.cells.iter().map(|&(x, y)| step(x, y, offset)).filter(|&(x, y)| x < size.0 && y < size.1).collect();
+        }
+        self.state.bodies.retain(|body| body.footprint(body.position).iter().any(|&(x, y)| x >= 0 && y >= 0 && (x as usize) < size.0 && (y as usize) < size.1));
+    }
+
+    // Borrow the world, it's pressure and wind fields, and the simulation's state all at once
+    pub fn parts(&mut self) -> (World<'_>, Grid<'_, f32>, Grid<'_, f32>, &mut SimulationState) {
+        (
+            World::new(&mut self.particles),
+            Grid::new(self.size.1, &mut self.pressure_cells),
+            Grid::new(self.size.1, &mut self.wind_cells),
+            &mut self.state
+        )
+    }
+
+    // Simulate one frame
+    pub fn step(&mut self) {
+        self.step_with_gpu(None);
+    }
+
+    // Simulate one frame, leaving the plain powders to the GPU (see `GpuBackend`) if one is given
+    pub fn step_with_gpu(&mut self, gpu: Option<&mut GpuBackend>) {
+        let (mut world, mut pressure, mut wind, state) = self.parts();
+        tick(&mut world, &mut pressure, &mut wind, state, gpu);
+    }
+
+    // Paint particles of the given variant into the empty cells of a solid, circular brush centred on (x, y), like `paint_brush`
+    // ... refusing to paint beyond the particle budget, and returning how many were painted
+    pub fn paint(&mut self, x: isize, y: isize, radius: usize, variant: ParticleVariant) -> usize {
+        let (mut world, _, _, state) = self.parts();
+        let mut budget = Budget::new(&world, BudgetOverflow::Refuse);
+        let options = BrushOptions { shape: BrushShape::Circle, radius, density: 100 };
+        paint_brush(&mut world, &mut budget, (x, y), options, variant, None, state.frame())
+    }
+
+    // Create a world from a world file (see `save_world`), with it's RNG seeded with zero (see `Simulation::load_with_seed`)
+    pub fn load(text: &str) -> Result<Simulation, WorldError> {
+        Simulation::load_with_seed(text, 0)
+    }
+
+    // Create a world from a world file, with it's RNG seeded with `seed`
+    pub fn load_with_seed(text: &str, seed: u64) -> Result<Simulation, WorldError> {
+        let file = parse_world(text)?;
+        let mut simulation = Simulation::with_seed(file.size.0, file.size.1, seed);
+        let (mut world, ..) = simulation.parts();
+        file.paint_into(&mut world);
+        Ok(simulation)
+    }
+
+    // Write the world out as a world file, see `save_world`
+    pub fn save(&mut self) -> String {
+        let (world, ..) = self.parts();
+        save_world(&world)
+    }
+
+    // Write the world out as a binary world file, see `save_world_binary`
+    pub fn save_binary(&mut self) -> Vec<u8> {
+        let (world, ..) = self.parts();
+        save_world_binary(&world)
+    }
+
+    // Replace the world with a world file's, resized to match it (the rest of the simulation's state, ie: gravity, is kept)
+    pub fn replace(&mut self, file: &WorldFile) {
+        self.particles = Particles::new(0, []);
+        self.pressure_cells.clear();
+        self.wind_cells.clear();
+        self.size = (0, 0);
+        self.state.bodies.clear();
+        self.resize(file.size, (0, 0));
+        let (mut world, ..) = self.parts();
+        file.paint_into(&mut world);
+    }
+
+    // Take a snapshot of what's in every cell of the world, to compare against later (see `Snapshot::diff`)
+    pub fn snapshot(&mut self) -> Snapshot {
+        let (world, ..) = self.parts();
+        Snapshot::take(&world)
+    }
+
+    // Return the variant of the particle at (x, y), if there's one there
+    pub fn get(&self, x: usize, y: usize) -> Option<ParticleVariant> {
+        self.particles.get(x, y).filter(|particle| particle.active).map(|particle| particle.variant)
+    }
+
+    // Return the temperature (in °C) of the cell at (x, y), if it's within the world
+    pub fn temperature(&self, x: usize, y: usize) -> Option<f32> {
+        self.particles.get(x, y).map(|particle| particle.temperature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simulation_paints_and_steps_through_the_api() {
+        let mut simulation = Simulation::new(8, 8);
+        assert_eq!(simulation.paint(4, 0, 1, ParticleVariant::Sand), 4);
+        assert!(simulation.get(3, 0) == Some(ParticleVariant::Sand) && simulation.get(3, 1).is_none());
+
+        // Painted Sand falls, and nothing outside of the world is there to get
+        simulation.step();
+        assert!(simulation.get(3, 0).is_none() && simulation.get(3, 1) == Some(ParticleVariant::Sand));
+        assert!(simulation.get(8, 0).is_none() && simulation.temperature(0, 8).is_none());
+    }
+}
@@ -0,0 +1,764 @@
+use macroquad::prelude::Color;
+use serde::de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use std::sync::OnceLock;
+use toml::Spanned;
+
+use crate::{behaviour::*, element::*, particle::*};
+
+// The file the sandbox loads it's material definitions from at startup (if it exists), relative to the working directory
+pub static MATERIALS_FILE: &str = "materials.toml";
+
+// The longest an added material's name can be
+static MAX_MATERIAL_NAME: usize = 40;
+
+// The installed material definitions, laid over the built-in ones (see `install_materials`)
+static MATERIALS: OnceLock<InstalledMaterials> = OnceLock::new();
+
+// A material's properties as defined by a materials file, where anything left unset keeps it's built-in value
+#[derive(Clone, Default)]
+pub(crate) struct MaterialOverride {
+    pub(crate) colour: Option<Color>,
+    pub(crate) movement_chance: Option<u8>,
+    pub(crate) density: Option<u8>,
+    // `Some(None)` makes a flammable material fireproof
+    pub(crate) flammability: Option<Option<Flammability>>
+}
+
+// A material added from scratch by a materials file: a copy of a built-in material (it's base), with a name and overrides of it's own
+pub(crate) struct AddedMaterial {
+    pub(crate) name: String,
+    pub(crate) base: ParticleVariant,
+    pub(crate) properties: MaterialOverride,
+    // The added material's own update logic, run in place of it's base's (see `MaterialDefinition::behaviour`)
+    pub(crate) behaviour: Option<Behaviour>
+}
+
+// A material added from code (ie: by a plugin, see `Plugin::added_materials`), defined like a materials file's added material,
+// ... but able to bring update logic of it's own
+pub struct MaterialDefinition {
+    pub name: String,
+    // The built-in material this one copies every property from, besides those set below
+    pub base: ParticleVariant,
+    pub colour: Option<Color>,
+    pub movement_chance: Option<u8>,
+    pub density: Option<u8>,
+    // Run each frame in place of the base's own behaviour, if set (the shared physics still apply afterwards)
+    pub behaviour: Option<Behaviour>
+}
+
+impl MaterialDefinition {
+    // A material copying every property of it's base (including it's behaviour), under a name of it's own
+    pub fn new(name: &str, base: ParticleVariant) -> MaterialDefinition {
+        MaterialDefinition { name: name.to_owned(), base, colour: None, movement_chance: None, density: None, behaviour: None }
+    }
+}
+
+// A set of material definitions parsed from a materials file (see `parse_materials`), ready to be installed
+pub struct Materials {
+    // Every built-in variant's overrides, indexed by the variant
+    overrides: Vec<MaterialOverride>,
+    // The added materials, numbered after the built-in variants in the order they're listed
+    added: Vec<AddedMaterial>,
+    // Extra reactions, which take priority over the built-in reaction registry
+    reactions: Vec<Reaction>
+}
+
+// The material definitions installed for the rest of the run: every variant's element (the built-in variants', with any overrides
+// ... laid over them, then the added materials'), and the extra reactions
+struct InstalledMaterials {
+    elements: Vec<Element>,
+    reactions: Vec<Reaction>
+}
+
+// A problem with a materials file, alongside the (1-based) line it was found on
+pub struct MaterialError {
+    pub line: usize,
+    pub message: String
+}
+
+impl std::fmt::Display for MaterialError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Default for Materials {
+    // No overrides, added materials, nor extra reactions: just the built-in materials
+    fn default() -> Materials {
+        Materials { overrides: vec![MaterialOverride::default(); VARIANTS.len()], added: Vec::new(), reactions: Vec::new() }
+    }
+}
+
+impl Materials {
+    // Lay another set of material definitions over these, with it's overrides (and reactions) taking priority
+    // ... an added material sharing the name of one already here replaces it's definition (keeping it's number), while the rest
+    // ... are numbered after those already here, refusing the whole set (returning why) if that's more materials than there's room for
+    pub fn merge(&mut self, other: Materials) -> Result<(), String> {
+        let is_new = |added: &&AddedMaterial| !self.added.iter().any(|existing| is_same_name(&existing.name, &added.name));
+        let new_count = other.added.iter().filter(is_new).count();
+        if VARIANTS.len() + self.added.len() + new_count > MAX_VARIANTS {
+            return Err(format!("there's only room for {} added materials", MAX_VARIANTS - VARIANTS.len()));
+        }
+
+        // Renumber the other set's added materials to their place among ours, wherever it names them
+        let mut numbers = Vec::new();
+        for added in other.added.iter() {
+            let index = self.added.iter().position(|existing| is_same_name(&existing.name, &added.name)).unwrap_or_else(|| {
+                self.added.push(AddedMaterial { name: added.name.clone(), base: added.base, properties: MaterialOverride::default(), behaviour: None });
+                self.added.len() - 1
+            });
+            numbers.push(ParticleVariant((VARIANTS.len() + index) as u8));
+        }
+        let renumber = |variant: ParticleVariant| numbers.get(variant.index().wrapping_sub(VARIANTS.len())).copied().unwrap_or(variant);
+        let renumber_override = |material: MaterialOverride| MaterialOverride {
+            flammability: material.flammability.map(|flammability| flammability.map(|f| Flammability { ash: f.ash.map(renumber), ..f })),
+            ..material
+        };
+
+        for (material, other) in self.overrides.iter_mut().zip(other.overrides) {
+            material.merge(renumber_override(other));
+        }
+        for (added, number) in other.added.into_iter().zip(numbers.iter()) {
+            let existing = &mut self.added[number.index() - VARIANTS.len()];
+            existing.base = added.base;
+            existing.properties = renumber_override(added.properties);
+            // A materials file can't give a material behaviour, so redefining one added from code keeps it's behaviour
+            existing.behaviour = added.behaviour.or(existing.behaviour);
+        }
+        let reactions = other.reactions.into_iter().map(|reaction| Reaction {
+            reactants: (renumber(reaction.reactants.0), renumber(reaction.reactants.1)),
+            products: (reaction.products.0.map(renumber), reaction.products.1.map(renumber)),
+            chance: reaction.chance
+        });
+        self.reactions.splice(0..0, reactions);
+        Ok(())
+    }
+
+    // Add a material defined from code, see `MaterialDefinition`
+    // ... it's name follows the materials file's rules (and replaces any added material sharing it), and it's base must be built-in
+    pub fn add(&mut self, definition: MaterialDefinition) -> Result<(), String> {
+        check_name(&definition.name)?;
+        if find_built_in(&definition.name).is_some() {
+            return Err(format!("[{}] is a built-in material, so it can't be added", definition.name));
+        }
+        if definition.base.index() >= VARIANTS.len() {
+            return Err(format!("[{}] base: expected a built-in material", definition.name));
+        }
+        let properties = MaterialOverride { colour: definition.colour, movement_chance: definition.movement_chance, density: definition.density, flammability: None };
+        let added = AddedMaterial { name: definition.name.trim().to_owned(), base: definition.base, properties, behaviour: definition.behaviour };
+        self.merge(Materials { added: vec![added], ..Materials::default() })
+    }
+
+    // Return every variant's element: the built-in elements with their overrides laid over them, then the added materials'
+    fn elements(&self) -> Vec<Element> {
+        let built_in = VARIANTS.iter().zip(self.overrides.iter()).map(|(variant, material)| material.apply(ELEMENTS[variant.index()].clone()));
+        let added = self.added.iter().map(|added| {
+            let base = self.overrides[added.base.index()].apply(ELEMENTS[added.base.index()].clone());
+            added.properties.apply(Element { name: added.name.clone().into(), behaviour: added.behaviour.or(base.behaviour), ..base })
+        });
+        built_in.chain(added).collect()
+    }
+}
+
+impl MaterialOverride {
+    // Lay another material's overrides over these, with it's overrides taking priority
+    fn merge(&mut self, other: MaterialOverride) {
+        self.colour = other.colour.or(self.colour);
+        self.movement_chance = other.movement_chance.or(self.movement_chance);
+        self.density = other.density.or(self.density);
+        self.flammability = other.flammability.or(self.flammability);
+    }
+
+    // Return the element with these overrides laid over it's properties (a new colour replaces it's powered colour, too)
+    fn apply(&self, mut element: Element) -> Element {
+        if let Some(colour) = self.colour {
+            element.colour = colour;
+            element.powered_colour = None;
+        }
+        element.movement_chance = self.movement_chance.unwrap_or(element.movement_chance);
+        element.density = self.density.unwrap_or(element.density);
+        element.flammability = self.flammability.unwrap_or(element.flammability);
+        element
+    }
+}
+
+// A single value within a materials file (or the config file, see `parse_settings`)
+pub(crate) enum Value {
+    Text(String),
+    Number(f64),
+    Bool(bool),
+    List(Vec<String>)
+}
+
+// A `[Material]` or `[[reaction]]` section of a materials file, with it's `key = value` entries (and the lines they're on)
+struct Section {
+    name: String,
+    is_reaction: bool,
+    line: usize,
+    entries: Vec<(usize, String, Value)>
+}
+
+// A table's `key = value` entries as TOML reads them, in the order they're listed, alongside where each is within the file
+struct Entries(Vec<(Spanned<String>, Spanned<Value>)>);
+
+// A top-level item of a materials file as TOML reads it: a `[Material]` table, an array of `[[reaction]]` tables, or anything else
+// ... (ie: a `key = value` outside of any section)
+enum Item {
+    Table(Entries),
+    Tables(Vec<Spanned<Entries>>),
+    Other
+}
+
+// Every top-level item of a materials file as TOML reads it, in the order they're listed, alongside where each is named
+struct Document(Vec<(Spanned<String>, Item)>);
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Value, D::Error> {
+        struct ValueVisitor;
+        impl<'de> Visitor<'de> for ValueVisitor {
+            type Value = Value;
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a number, a quoted string, true/false, or a [list] of quoted names")
+            }
+            fn visit_bool<E>(self, flag: bool) -> Result<Value, E> {
+                Ok(Value::Bool(flag))
+            }
+            fn visit_i64<E>(self, number: i64) -> Result<Value, E> {
+                Ok(Value::Number(number as f64))
+            }
+            fn visit_u64<E>(self, number: u64) -> Result<Value, E> {
+                Ok(Value::Number(number as f64))
+            }
+            // ... non-finite numbers (`nan` and `inf`) are read, so they can be refused alongside every other problem in the file
+            fn visit_f64<E>(self, number: f64) -> Result<Value, E> {
+                Ok(Value::Number(number))
+            }
+            fn visit_str<E>(self, text: &str) -> Result<Value, E> {
+                Ok(Value::Text(text.to_owned()))
+            }
+            fn visit_seq<A: SeqAccess<'de>>(self, mut items: A) -> Result<Value, A::Error> {
+                let mut names = Vec::new();
+                while let Some(name) = items.next_element()? {
+                    names.push(name);
+                }
+                Ok(Value::List(names))
+            }
+        }
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for Entries {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Entries, D::Error> {
+        struct EntriesVisitor;
+        impl<'de> Visitor<'de> for EntriesVisitor {
+            type Value = Entries;
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a [section] of `key = value` pairs")
+            }
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Entries, A::Error> {
+                read_entries(map)
+            }
+        }
+        deserializer.deserialize_map(EntriesVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for Item {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Item, D::Error> {
+        struct ItemVisitor;
+        impl<'de> Visitor<'de> for ItemVisitor {
+            type Value = Item;
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a [Material] or [[reaction]] section")
+            }
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Item, A::Error> {
+                read_entries(map).map(Item::Table)
+            }
+            fn visit_seq<A: SeqAccess<'de>>(self, mut items: A) -> Result<Item, A::Error> {
+                let mut tables = Vec::new();
+                while let Some(table) = items.next_element()? {
+                    tables.push(table);
+                }
+                Ok(Item::Tables(tables))
+            }
+            fn visit_bool<E>(self, _: bool) -> Result<Item, E> {
+                Ok(Item::Other)
+            }
+            fn visit_i64<E>(self, _: i64) -> Result<Item, E> {
+                Ok(Item::Other)
+            }
+            fn visit_u64<E>(self, _: u64) -> Result<Item, E> {
+                Ok(Item::Other)
+            }
+            fn visit_f64<E>(self, _: f64) -> Result<Item, E> {
+                Ok(Item::Other)
+            }
+            fn visit_str<E>(self, _: &str) -> Result<Item, E> {
+                Ok(Item::Other)
+            }
+        }
+        deserializer.deserialize_any(ItemVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for Document {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Document, D::Error> {
+        struct DocumentVisitor;
+        impl<'de> Visitor<'de> for DocumentVisitor {
+            type Value = Document;
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a materials file")
+            }
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Document, A::Error> {
+                let mut items = Vec::new();
+                while let Some(name) = map.next_key()? {
+                    items.push((name, map.next_value()?));
+                }
+                Ok(Document(items))
+            }
+        }
+        deserializer.deserialize_map(DocumentVisitor)
+    }
+}
+
+// Read a table's `key = value` entries, in the order they're listed (see `Entries`)
+fn read_entries<'de, A: MapAccess<'de>>(mut map: A) -> Result<Entries, A::Error> {
+    let mut entries = Vec::new();
+    while let Some(key) = map.next_key()? {
+        entries.push((key, map.next_value()?));
+    }
+    Ok(Entries(entries))
+}
+
+// Install a set of material definitions for the rest of the run, returning false if some were already installed
+pub fn install_materials(materials: Materials) -> bool {
+    MATERIALS.set(InstalledMaterials { elements: materials.elements(), reactions: materials.reactions }).is_ok()
+}
+
+// Return every variant's element, if any material definitions were installed
+pub(crate) fn get_installed_elements() -> Option<&'static [Element]> {
+    MATERIALS.get().map(|materials| materials.elements.as_slice())
+}
+
+// Return how many variants there are: the built-in variants, and any added materials
+pub(crate) fn variant_count() -> usize {
+    MATERIALS.get().map_or(VARIANTS.len(), |materials| materials.elements.len())
+}
+
+// Return the installed extra reactions (if any)
+pub(crate) fn get_material_reactions() -> &'static [Reaction] {
+    MATERIALS.get().map_or(&[], |materials| &materials.reactions)
+}
+
+// Return a hash of everything the installed materials change about how the world simulates (their names, properties and reactions)
+// ... so peers (or a replay) simulating with different materials can be told apart before they drift apart
+// ... colours are left out as they don't change the simulation, as are behaviours' functions (whose addresses differ between runs)
+pub fn fingerprint_materials() -> u64 {
+    let Some(materials) = MATERIALS.get() else {
+        return 0;
+    };
+    let mut values = Vec::new();
+    for element in materials.elements.iter() {
+        values.extend(element.name.bytes().map(u64::from));
+        let flammability = element.flammability.map_or([u64::MAX; 3], |flammability| {
+            [flammability.ignition.to_bits() as u64, flammability.burn_frames as u64, flammability.ash.map_or(u64::MAX, |ash| ash.index() as u64)]
+        });
+        values.extend([element.state as u64, element.movement_chance as u64, element.density as u64, element.behaviour.is_some() as u64]);
+        values.extend(flammability);
+    }
+    for reaction in materials.reactions.iter() {
+        let product = |product: Option<ParticleVariant>| product.map_or(u64::MAX, |product| product.index() as u64);
+        values.extend([reaction.reactants.0.index() as u64, reaction.reactants.1.index() as u64, product(reaction.products.0), product(reaction.products.1), reaction.chance as u64]);
+    }
+    values.iter().fold(0xcbf29ce484222325, |hash, value| (hash ^ value).wrapping_mul(0x100000001b3))
+}
+
+// Parse a materials file: a TOML file with a `[Material]` section per material and a `[[reaction]]` section per extra reaction
+// ... a section naming a built-in material overrides it's properties, while one naming a new material (alongside a `base` built-in
+// ... material to start out as a copy of) adds it, numbered after the built-in variants in the order they're listed
+// ... every problem with the file's materials is reported (rather than just the first), so a broken file can be fixed in one go
+// ... though a file which isn't valid TOML only reports where it stops being so
+pub fn parse_materials(text: &str) -> Result<Materials, Vec<MaterialError>> {
+    let line_at = |offset: usize| text[..offset.min(text.len())].matches('\n').count() + 1;
+    let document: Document = toml::from_str(text).map_err(|error| {
+        vec![MaterialError { line: error.span().map_or(1, |span| line_at(span.start)), message: error.message().trim().to_owned() }]
+    })?;
+    let read = |entries: Entries| entries.0.into_iter().map(|(key, value)| (line_at(key.span().start), key.into_inner(), value.into_inner())).collect();
+    let mut errors = Vec::new();
+    let mut sections: Vec<Section> = Vec::new();
+    for (name, item) in document.0 {
+        let line = line_at(name.span().start);
+        let name = name.into_inner();
+        match item {
+            Item::Table(entries) => sections.push(Section { name, is_reaction: false, line, entries: read(entries) }),
+            Item::Tables(tables) => {
+                if name != "reaction" {
+                    errors.push(MaterialError { line, message: format!("unknown section [[{}]], only [[reaction]] may repeat", name) });
+                }
+                for table in tables {
+                    let line = line_at(table.span().start);
+                    sections.push(Section { name: name.clone(), is_reaction: true, line, entries: read(table.into_inner()) });
+                }
+            },
+            Item::Other => errors.push(MaterialError { line, message: format!("'{}' is set outside of any [Material] or [[reaction]] section", name) })
+        }
+    }
+
+    // Number the added materials up front, so any section (ie: a reaction) can name them, wherever they're listed
+    // ... any beyond the last number there's room for are left unnumbered
+    let mut added_names: Vec<&str> = Vec::new();
+    for section in sections.iter().filter(|section| !section.is_reaction && find_built_in(&section.name).is_none()) {
+        if get_entry(section, "base").is_some() && !added_names.iter().any(|name| is_same_name(name, &section.name)) {
+            added_names.push(&section.name);
+        }
+    }
+    let find = |name: &str| find_built_in(name).map(|variant| variant.index())
+        .or_else(|| added_names.iter().position(|added| is_same_name(added, name)).map(|index| VARIANTS.len() + index))
+        .filter(|&index| index < MAX_VARIANTS).map(|index| ParticleVariant(index as u8));
+
+    let mut materials = Materials::default();
+    let mut defined: Vec<ParticleVariant> = Vec::new();
+    for section in sections.iter() {
+        if section.is_reaction {
+            if let Some(reaction) = parse_reaction(section, &find, &mut errors) {
+                materials.reactions.push(reaction);
+            }
+            continue;
+        }
+        let Some(variant) = find(&section.name) else {
+            let message = if get_entry(section, "base").is_some() {
+                format!("[{}] is one material too many, there's only room for {} added materials", section.name, MAX_VARIANTS - VARIANTS.len())
+            } else {
+                format!("unknown material [{}], give it a base material to add it as a new one", section.name)
+            };
+            errors.push(MaterialError { line: section.line, message });
+            continue;
+        };
+        if defined.contains(&variant) {
+            errors.push(MaterialError { line: section.line, message: format!("[{}] is defined more than once", section.name) });
+            continue;
+        }
+        defined.push(variant);
+        if variant.index() < VARIANTS.len() {
+            if let Some((line, ..)) = get_entry(section, "base") {
+                errors.push(MaterialError { line: *line, message: format!("[{}] is a built-in material, so it can't be given a base", section.name) });
+            }
+            materials.overrides[variant.index()] = parse_override(variant, section, &find, &mut errors);
+        } else if let Some(added) = parse_added(section, &find, &mut errors) {
+            materials.added.push(added);
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(materials)
+    } else {
+        Err(errors)
+    }
+}
+
+// Return a section's entry for the given key (and the line it's on), if it has one
+fn get_entry<'s>(section: &'s Section, key: &str) -> Option<&'s (usize, String, Value)> {
+    section.entries.iter().find(|(_, entry, _)| entry == key)
+}
+
+// Return true if two material names are the same, ignoring case and spaces (so `BurningFuse` is Burning Fuse)
+fn is_same_name(a: &str, b: &str) -> bool {
+    a.replace(' ', "").eq_ignore_ascii_case(&b.replace(' ', ""))
+}
+
+// Find the built-in variant with the given name, ignoring case and spaces
+fn find_built_in(name: &str) -> Option<ParticleVariant> {
+    VARIANTS.iter().copied().find(|variant| is_same_name(&variant.to_string(), name))
+}
+
+// Return the line without it's trailing `#` comment (if any), leaving any `#` within quotes (ie: colours) alone
+pub(crate) fn strip_comment(line: &str) -> &str {
+    let mut is_quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => is_quoted = !is_quoted,
+            '#' if !is_quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+// Parse a single value: a quoted string, a number, true/false, or a [list] of quoted strings
+pub(crate) fn parse_value(text: &str) -> Result<Value, String> {
+    if let Some(inner) = text.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+        let items = inner.split(',').map(str::trim).filter(|item| !item.is_empty());
+        return items.map(|item| match parse_value(item)? {
+            Value::Text(text) => Ok(text),
+            _ => Err(format!("lists may only hold quoted names, found {}", item))
+        }).collect::<Result<Vec<String>, String>>().map(Value::List);
+    }
+    if let Some(inner) = text.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')) {
+        return Ok(Value::Text(inner.to_owned()));
+    }
+    match text {
+        "true" => Ok(Value::Bool(true)),
+        "false" => Ok(Value::Bool(false)),
+        _ => text.parse().ok().filter(|number: &f64| number.is_finite()).map(Value::Number)
+            .ok_or_else(|| format!("'{}' isn't a number, a quoted string, true/false, or a [list]", text))
+    }
+}
+
+// Find the variant (built-in or added) with the given name, ignoring case and spaces (so `BurningFuse` finds Burning Fuse)
+pub fn find_variant(name: &str) -> Option<ParticleVariant> {
+    variants().find(|variant| is_same_name(&variant.to_string(), name))
+}
+
+// Find the product with the given name (through `find`), where "None" means the particle vanishes
+fn find_product(name: &str, find: &dyn Fn(&str) -> Option<ParticleVariant>) -> Result<Option<ParticleVariant>, String> {
+    if name.eq_ignore_ascii_case("none") {
+        return Ok(None);
+    }
+    find(name).map(Some).ok_or_else(|| format!("unknown material '{}'", name))
+}
+
+// Read a whole number within the given range
+fn as_integer(value: &Value, range: std::ops::RangeInclusive<f64>) -> Result<f64, String> {
+    match value {
+        Value::Number(number) if number.fract() == 0.0 && range.contains(number) => Ok(*number),
+        _ => Err(format!("expected a whole number from {} to {}", range.start(), range.end()))
+    }
+}
+
+// Read an added material's section: it's name, base material, and overrides of the base's properties, reporting any problems with them
+fn parse_added(section: &Section, find: &dyn Fn(&str) -> Option<ParticleVariant>, errors: &mut Vec<MaterialError>) -> Option<AddedMaterial> {
+    let error_count = errors.len();
+    let name = &section.name;
+    if let Err(message) = check_name(name) {
+        errors.push(MaterialError { line: section.line, message });
+    }
+    let base = match get_entry(section, "base") {
+        Some((line, _, Value::Text(base))) => find_built_in(base).ok_or_else(|| (*line, format!("unknown built-in material '{}'", base))),
+        Some((line, ..)) => Err((*line, "expected a quoted built-in material name".to_owned())),
+        None => Err((section.line, "is missing it's base material".to_owned()))
+    };
+    let base = match base {
+        Ok(base) => base,
+        Err((line, message)) => {
+            errors.push(MaterialError { line, message: format!("[{}] base: {}", name, message) });
+            return None;
+        }
+    };
+    let properties = parse_override(base, section, find, errors);
+    (errors.len() == error_count).then(|| AddedMaterial { name: name.trim().to_owned(), base, properties, behaviour: None })
+}
+
+// Check an added material's name is up to `MAX_MATERIAL_NAME` letters, numbers and spaces (and not a reserved name), returning why not
+fn check_name(name: &str) -> Result<(), String> {
+    let is_reserved = name.trim().eq_ignore_ascii_case("none") || name.trim().eq_ignore_ascii_case("empty");
+    if name.len() > MAX_MATERIAL_NAME || is_reserved || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == ' ') || name.trim().is_empty() {
+        return Err(format!("[{}] isn't a valid name, use up to {} letters, numbers and spaces (but not None or Empty)", name, MAX_MATERIAL_NAME));
+    }
+    Ok(())
+}
+
+// Read a `[Material]` section's overrides of the given variant's properties, reporting any problems with them
+fn parse_override(variant: ParticleVariant, section: &Section, find: &dyn Fn(&str) -> Option<ParticleVariant>, errors: &mut Vec<MaterialError>) -> MaterialOverride {
+    let mut material = MaterialOverride::default();
+    let (mut flammable, mut ignition, mut burn_frames, mut ash) = (None, None, None, None);
+    for (line, key, value) in &section.entries {
+        let result = match key.as_str() {
+            "colour" => parse_colour(value).map(|colour| material.colour = Some(colour)),
+            "movement_chance" => as_integer(value, 0.0..=100.0).map(|chance| material.movement_chance = Some(chance as u8)),
+            "density" => as_integer(value, 1.0..=255.0).map(|density| material.density = Some(density as u8)),
+            "flammable" => match value {
+                Value::Bool(is_flammable) => {
+                    flammable = Some(*is_flammable);
+                    Ok(())
+                },
+                _ => Err("expected true or false".to_owned())
+            },
+            "ignition" => match value {
+                Value::Number(temperature) if temperature.is_finite() => {
+                    ignition = Some(*temperature as f32);
+                    Ok(())
+                },
+                _ => Err("expected a temperature (in °C)".to_owned())
+            },
+            "burn_frames" => as_integer(value, 0.0..=u16::MAX as f64).map(|frames| burn_frames = Some(frames as u16)),
+            "ash" => match value {
+                Value::Text(name) => find_product(name, find).map(|product| ash = Some(product)),
+                _ => Err("expected a quoted material name (or \"None\")".to_owned())
+            },
+            // An added material's base is read alongside it's name, see `parse_added`
+            "base" => Ok(()),
+            _ => Err(format!("unknown property '{}' (expected base, colour, movement_chance, density, flammable, ignition, burn_frames or ash)", key))
+        };
+        if let Err(message) = result {
+            errors.push(MaterialError { line: *line, message: format!("[{}] {}: {}", section.name, key, message) });
+        }
+    }
+
+    // Burning properties are laid over the built-in ones, so a material which doesn't burn already needs to be told both how and for how long
+    let has_burning = ignition.is_some() || burn_frames.is_some() || ash.is_some();
+    if flammable == Some(false) {
+        if has_burning {
+            errors.push(MaterialError { line: section.line, message: format!("[{}] is set as not flammable, but also given burning properties", section.name) });
+        }
+        material.flammability = Some(None);
+    } else if has_burning || flammable == Some(true) {
+        let base = variant.get_flammability();
+        match (ignition.or(base.map(|f| f.ignition)), burn_frames.or(base.map(|f| f.burn_frames))) {
+            (Some(ignition), Some(burn_frames)) => {
+                let ash = ash.unwrap_or(base.and_then(|f| f.ash));
+                material.flammability = Some(Some(Flammability { ignition, burn_frames, ash }));
+            },
+            _ => errors.push(MaterialError { line: section.line, message: format!("[{}] isn't flammable yet, so needs both an ignition and burn_frames", section.name) })
+        }
+    }
+    material
+}
+
+// Read a "#RRGGBB" (or "#RRGGBBAA") hex colour
+pub(crate) fn parse_colour(value: &Value) -> Result<Color, String> {
+    let error = || "expected a quoted \"#RRGGBB\" or \"#RRGGBBAA\" hex colour".to_owned();
+    let Value::Text(text) = value else {
+        return Err(error());
+    };
+    let hex = text.strip_prefix('#').filter(|hex| (hex.len() == 6 || hex.len() == 8) && hex.is_ascii()).ok_or_else(error)?;
+    let channels = (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16)).collect::<Result<Vec<u8>, _>>().map_err(|_| error())?;
+    Ok(Color::from_rgba(channels[0], channels[1], channels[2], channels.get(3).copied().unwrap_or(255)))
+}
+
+// Read a `[[reaction]]` section, reporting any problems with it
+fn parse_reaction(section: &Section, find: &dyn Fn(&str) -> Option<ParticleVariant>, errors: &mut Vec<MaterialError>) -> Option<Reaction> {
+    let error_count = errors.len();
+    let (mut reactants, mut products, mut chance) = (None, None, None);
+    for (line, key, value) in &section.entries {
+        let result = match (key.as_str(), value) {
+            ("reactants", Value::List(names)) if names.len() == 2 => {
+                match (find(&names[0]), find(&names[1])) {
+                    (Some(a), Some(b)) => {
+                        reactants = Some((a, b));
+                        Ok(())
+                    },
+                    _ => Err(format!("unknown material in [{}]", names.join(", ")))
+                }
+            },
+            ("products", Value::List(names)) if names.len() == 2 => {
+                find_product(&names[0], find).and_then(|a| find_product(&names[1], find).map(|b| products = Some((a, b))))
+            },
+            ("reactants" | "products", _) => Err("expected a list of two quoted material names".to_owned()),
+            ("chance", value) => as_integer(value, 1.0..=100.0).map(|percent| chance = Some(percent as u8)),
+            _ => Err(format!("unknown property '{}' (expected reactants, products or chance)", key))
+        };
+        if let Err(message) = result {
+            errors.push(MaterialError { line: *line, message: format!("[[reaction]] {}: {}", key, message) });
+        }
+    }
+
+    match (reactants, products, chance) {
+        (Some(reactants), Some(products), Some(chance)) => Some(Reaction { reactants, products, chance }),
+        _ => {
+            // Only complain about missing properties when they're missing, rather than malformed
+            if errors.len() == error_count {
+                errors.push(MaterialError { line: section.line, message: "[[reaction]] needs all of reactants, products and chance".to_owned() });
+            }
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn materials_files_override_and_extend_the_built_ins() {
+        let materials = parse_materials(r##"
+            # Slower, pinker Sand
+            [Sand]
+            colour = "#ff8080"   # a comment after a value
+            movement_chance = 20
+
+            ["Burning Fuse"]
+            flammable = false
+
+            [Stone]
+            ignition = 900
+            burn_frames = 30
+            ash = "Gravel"
+
+            [[reaction]]
+            reactants = ["Acid", "Metal"]
+            products = ["None", "Rust"]
+            chance = 5
+        "##).ok().unwrap();
+
+        let sand = &materials.overrides[ParticleVariant::Sand.index()];
+        assert!(sand.movement_chance == Some(20) && sand.density.is_none());
+        assert!(sand.colour == Some(Color::from_rgba(255, 128, 128, 255)));
+        assert!(matches!(materials.overrides[ParticleVariant::BurningFuse.index()].flammability, Some(None)));
+        let stone = materials.overrides[ParticleVariant::Stone.index()].flammability.unwrap().unwrap();
+        assert!(stone.ignition == 900.0 && stone.burn_frames == 30 && stone.ash == Some(ParticleVariant::Gravel));
+        assert!(materials.reactions.len() == 1 && materials.reactions[0].products == (None, Some(ParticleVariant::Rust)));
+    }
+
+    #[test]
+    fn malformed_materials_files_report_every_problem() {
+        let errors = parse_materials(r#"
+            density = 4
+            [Sandd]
+            [Water]
+            density = 300
+            colour = "blue"
+            [Brick]
+            ignition = 500
+            [[reaction]]
+            reactants = ["Acid"]
+            [Glue]
+            [Dirt]
+            base = "Sand"
+            [Gl-ue]
+            base = "Slime"
+            [Goo]
+            base = "Gooo"
+            [Stone]
+            ignition = nan
+            ["Molten Wax"]
+            ignition = -inf
+        "#).err().unwrap();
+        let lines: Vec<usize> = errors.iter().map(|error| error.line).collect();
+        assert_eq!(lines, vec![2, 3, 5, 6, 7, 10, 11, 13, 14, 17, 19, 21]);
+
+        // A file which isn't TOML at all reports where it stops being so
+        let errors = parse_materials("[Sand]\ndensity = 4\ncolour = #ff0000\n").err().unwrap();
+        assert!(errors.len() == 1 && errors[0].line == 3);
+    }
+
+    #[test]
+    fn materials_files_add_materials_based_on_built_in_ones() {
+        // Reactions may name an added material before it's defined
+        let mut materials = parse_materials(r##"
+            [[reaction]]
+            reactants = ["Glow Sand", "Water"]
+            products = ["Glow Sand", "Steam"]
+            chance = 10
+
+            ["Glow Sand"]
+            base = "Sand"
+            colour = "#ffee88"
+            density = 14
+        "##).ok().unwrap();
+        let glow_sand = ParticleVariant(VARIANTS.len() as u8);
+        assert!(materials.reactions[0].reactants == (glow_sand, ParticleVariant::Water));
+
+        // A later set's added materials are numbered after ours, wherever it names them
+        let glue = ParticleVariant(VARIANTS.len() as u8 + 1);
+        let later = parse_materials("[Glue]\nbase = \"Slime\"\n[[reaction]]\nreactants = [\"Glue\", \"Sand\"]\nproducts = [\"Glue\", \"Glue\"]\nchance = 1").ok().unwrap();
+        assert!(materials.merge(later).is_ok() && materials.reactions[0].products == (Some(glue), Some(glue)));
+
+        // Each added material starts out as a copy of it's base, under it's own name
+        let elements = materials.elements();
+        let (glow_sand, glue) = (&elements[glow_sand.index()], &elements[glue.index()]);
+        assert!(glow_sand.name == "Glow Sand" && glow_sand.state == MatterState::Powder && glow_sand.density == 14);
+        assert!(glow_sand.colour == Color::from_rgba(255, 238, 136, 255) && glow_sand.wet_cohesion == 90);
+        assert!(glue.name == "Glue" && glue.cohesion == 95 && elements.len() == VARIANTS.len() + 2);
+    }
+}
@@ -0,0 +1,672 @@
+use crate::{chunk::*, edit::*, plugin::*, region::*, replay::*, save::*, sim::*, snapshot::*, world::*};
+use crate::Simulation;
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+
+// The first line each side of a connection sends, marking it (and it's protocol version) as a sandbox session
+// ... version 2 brought in the fingerprint of the host's rules within the session's start (see `fingerprint_rules`)
+static NET_HEADER: &str = "rusty-sandbox net 2";
+
+// The message the host sends as it simulates each frame
+static TICK_MESSAGE: &str = "tick";
+
+// How long (in seconds) joining waits to hear the session's world from the host (or server) before giving up
+static JOIN_TIMEOUT: u64 = 10;
+
+// The most a server lets pile up waiting to be sent to a viewer (in bytes) before giving up on it, so a viewer who can't keep up
+// ... can't eat all of the server's memory
+static MAX_STREAM_BACKLOG: usize = 256 * 1024 * 1024;
+
+// The longest line a connection will buffer while waiting for it's end, so a broken (or hostile) peer can't eat all our memory
+static MAX_LINE_LENGTH: usize = 64 * 1024 * 1024;
+
+// A connection to the other side of a session, sending and receiving a line of text per message, without ever blocking
+// ... the host sends the session's start (`start <seed> <fingerprint of it's rules> <binary world file in base64>`), every input
+// ... alongside the frame it was made before (like a replay file's, see `save_replay`), and a tick as it simulates each frame,
+// ... while a client sends it's own inputs without a frame (as the host decides when they're made)
+struct Connection {
+    stream: TcpStream,
+    // What's been received but doesn't yet make a whole line, and what's waiting to be sent
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+    // Whether the other side's header has been received (and checked)
+    is_greeted: bool
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> std::io::Result<Connection> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        let mut connection = Connection { stream, incoming: Vec::new(), outgoing: Vec::new(), is_greeted: false };
+        connection.send(NET_HEADER);
+        Ok(connection)
+    }
+
+    fn send(&mut self, line: &str) {
+        self.outgoing.extend(line.as_bytes());
+        self.outgoing.push(b'\n');
+    }
+
+    // Send as much of what's waiting as the connection takes, then return every whole line received since, or why the
+    // ... connection is no longer usable
+    fn exchange(&mut self) -> Result<Vec<String>, String> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err("the connection closed".to_owned()),
+                Ok(written) => { self.outgoing.drain(..written); },
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) if error.kind() == ErrorKind::Interrupted => {},
+                Err(error) => return Err(error.to_string())
+            }
+        }
+        let mut buffer = [0; 16 * 1024];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Err("the connection closed".to_owned()),
+                Ok(read) => self.incoming.extend(&buffer[..read]),
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) if error.kind() == ErrorKind::Interrupted => {},
+                Err(error) => return Err(error.to_string())
+            }
+        }
+
+        let mut lines = Vec::new();
+        while let Some(end) = self.incoming.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.incoming.drain(..=end).collect();
+            lines.push(String::from_utf8_lossy(&line).trim().to_owned());
+        }
+        if self.incoming.len() > MAX_LINE_LENGTH {
+            return Err("received a message too long to be one of ours".to_owned());
+        }
+        if !self.is_greeted && !lines.is_empty() {
+            if lines[0] != NET_HEADER {
+                return Err(format!("not a sandbox session (expected '{}')", NET_HEADER));
+            }
+            self.is_greeted = true;
+            lines.remove(0);
+        }
+        Ok(lines)
+    }
+}
+
+// Create the simulation a session starts from: a binary world file (see `save_world_binary`), with it's RNG seeded with `seed`
+// ... both sides start from this, so they're simulating exactly the same thing
+fn start_simulation(bytes: &[u8], seed: u64) -> Result<Simulation, String> {
+    let file = parse_world_binary(bytes).map_err(|error| format!("the session's world is broken: {}", error))?;
+    let mut simulation = Simulation::with_seed(file.size.0, file.size.1, seed);
+    simulation.replace(&file);
+    Ok(simulation)
+}
+
+// A network session: hosting the simulation for others to join, or joining one hosted elsewhere
+// ... every peer simulates the same world in lockstep (like a replay, see `Playback`), so only the inputs are ever sent
+// ... or viewing a world simulated by a server (see `Server`), which streams the world as it changes instead
+pub enum Session {
+    Host(Host),
+    Client(Client),
+    Viewer(Viewer)
+}
+
+// Join the session hosted (or the world served) at the given address, waiting (up to `JOIN_TIMEOUT`) for it's world, and
+// ... returning the session alongside the simulation it starts from
+// ... a session is only joined if the host simulates by the same rules as ours, see `fingerprint_rules`
+pub fn join(address: &str, fingerprint: u64) -> Result<(Session, Simulation), String> {
+    let error = |error: std::io::Error| format!("couldn't join {}: {}", address, error);
+    let socket = address.to_socket_addrs().map_err(error)?.next().ok_or_else(|| format!("couldn't find {}", address))?;
+    let stream = TcpStream::connect_timeout(&socket, std::time::Duration::from_secs(JOIN_TIMEOUT)).map_err(error)?;
+    let mut connection = Connection::new(stream).map_err(error)?;
+    let start = std::time::Instant::now();
+    while start.elapsed().as_secs() < JOIN_TIMEOUT {
+        let mut lines = connection.exchange()?.into_iter();
+        if let Some(first) = lines.next() {
+            // A server starts by sending the world's size, then streams it in (see `Viewer`)
+            if let Some(size) = first.strip_prefix("stream ") {
+                let size = parse_stream_size(size)?;
+                let mut simulation = Simulation::new(size.0, size.1);
+                let mut viewer = Viewer { connection, size };
+                let (mut world, ..) = simulation.parts();
+                for line in lines {
+                    viewer.handle(&line, &mut world)?;
+                }
+                return Ok((Session::Viewer(viewer), simulation));
+            }
+            // ... while a host starts by sending the session's start (see `Client`)
+            let mut client = Client { connection, inputs: VecDeque::new(), frames: 0, fingerprint };
+            let mut started = client.handle(&first)?.ok_or_else(|| format!("{} didn't start with it's world", address))?;
+            for line in lines {
+                started = client.handle(&line)?.unwrap_or(started);
+            }
+            return Ok((Session::Client(client), started));
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    Err(format!("{} didn't send it's world in time", address))
+}
+
+// Read the world size a server starts streaming with (`<width> <height>`), refusing worlds too big to allocate (see `MAX_WORLD_CELLS`)
+fn parse_stream_size(size: &str) -> Result<(usize, usize), String> {
+    let (width, height) = size.split_once(' ').and_then(|(width, height)| Some((width.parse::<usize>().ok()?, height.parse::<usize>().ok()?)))
+        .ok_or_else(|| format!("'{}' isn't a valid world size", size))?;
+    if width.checked_mul(height).is_none_or(|cells| cells > MAX_WORLD_CELLS) {
+        return Err(format!("the server's {}x{} world is too large (over {} cells)", width, height, MAX_WORLD_CELLS));
+    }
+    Ok((width, height))
+}
+
+// The host of a session, who decides when every input (it's own, and each client's) is made, and simulates ahead of the clients
+pub struct Host {
+    listener: TcpListener,
+    clients: Vec<Connection>,
+    // The simulation's settings as last sent (see `Input::State`)
+    last_state: Option<Input>
+}
+
+impl Host {
+    // Start listening for clients at the given address (ie: `0.0.0.0:7878`)
+    pub fn new(address: &str) -> Result<Host, String> {
+        let listener = TcpListener::bind(address).map_err(|error| format!("couldn't host at {}: {}", address, error))?;
+        listener.set_nonblocking(true).map_err(|error| error.to_string())?;
+        Ok(Host { listener, clients: Vec::new(), last_state: None })
+    }
+
+    pub fn address(&self) -> Option<SocketAddr> {
+        self.listener.local_addr().ok()
+    }
+
+    pub fn client_count(&self) -> usize {
+        self.clients.len()
+    }
+
+    // Let in anyone new joining, returning true if anyone did (in which case the session has to be restarted, see `Host::restart`)
+    pub fn accept(&mut self) -> bool {
+        let mut is_joined = false;
+        loop {
+            match self.listener.accept().and_then(|(stream, _)| Connection::new(stream)) {
+                Ok(client) => {
+                    self.clients.push(client);
+                    is_joined = true;
+                },
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => {
+                    eprintln!("Couldn't let a client join: {}", error);
+                    break;
+                }
+            }
+        }
+        is_joined
+    }
+
+    // Restart the session from the simulation's world as it is now (only each cell's material is kept, like a world file), with
+    // ... a fresh seed and the same settings, sending it to every client so they all start from exactly where the host does
+    pub fn restart(&mut self, simulation: &mut Simulation, seed: u64) -> Result<Simulation, String> {
+        let bytes = simulation.save_binary();
+        let settings = Input::state(simulation.parts().3);
+        let fingerprint = fingerprint_rules(&simulation.parts().3.scripts);
+        let mut restarted = start_simulation(&bytes, seed)?;
+        let (mut world, _, _, state) = restarted.parts();
+        settings.apply(&mut world, state, &mut History::default(), &mut Plugins::default());
+        let message = format!("start {} {:016x} {}", seed, fingerprint, encode_base64(&bytes));
+        for client in self.clients.iter_mut() {
+            client.send(&message);
+        }
+        self.last_state = None;
+        Ok(restarted)
+    }
+
+    // Send an input the host made (or a client made through the host) before the given frame
+    pub fn record(&mut self, frame: u64, input: &Input) {
+        let message = format!("{} {}", frame, input);
+        for client in self.clients.iter_mut() {
+            client.send(&message);
+        }
+    }
+
+    // Send the simulation's settings before the frame about to be simulated, if they've changed since they were last sent
+    pub fn record_state(&mut self, state: &SimulationState) {
+        let input = Input::state(state);
+        if self.last_state.as_ref() != Some(&input) {
+            self.record(state.frame(), &input);
+            self.last_state = Some(input);
+        }
+    }
+
+    // Let the clients simulate another frame, once the host has
+    pub fn tick(&mut self) {
+        for client in self.clients.iter_mut() {
+            client.send(TICK_MESSAGE);
+        }
+    }
+
+    // Send everything waiting to be sent, and return every input the clients have made since, dropping any client who's left
+    // ... (or sent something unreadable, or beyond what the sandbox allows, see `parse_remote_input`)
+    pub fn receive(&mut self) -> Vec<Input> {
+        let mut inputs = Vec::new();
+        self.clients.retain_mut(|client| {
+            let received = client.exchange().and_then(|lines| lines.iter().map(|line| parse_remote_input(line)).collect::<Result<Vec<Input>, String>>());
+            match received {
+                Ok(received) => {
+                    inputs.extend(received);
+                    true
+                },
+                Err(error) => {
+                    eprintln!("A client left the session: {}", error);
+                    false
+                }
+            }
+        });
+        inputs
+    }
+}
+
+// A client of a session, simulating each frame only once the host has (alongside the inputs the host made before it)
+pub struct Client {
+    connection: Connection,
+    // The inputs received which are yet to be made (alongside the frame each was made before), and how many frames the host
+    // ... has simulated since the session (re)started
+    inputs: VecDeque<(u64, Input)>,
+    frames: u64,
+    // The fingerprint of our own rules, which the host's has to match (see `fingerprint_rules`)
+    fingerprint: u64
+}
+
+impl Client {
+    // Send an input for the host to make, see `Host::receive`
+    pub fn send(&mut self, input: &Input) {
+        self.connection.send(&input.to_string());
+    }
+
+    // Send everything waiting to be sent, and take in everything the host has sent since, returning the simulation to switch to
+    // ... if the host restarted the session (ie: someone else joined), or why the session is over
+    pub fn receive(&mut self) -> Result<Option<Simulation>, String> {
+        let mut restarted = None;
+        for line in self.connection.exchange()? {
+            restarted = self.handle(&line)?.or(restarted);
+        }
+        Ok(restarted)
+    }
+
+    // Take in a message from the host, returning the simulation to switch to if it's the session's (re)start
+    fn handle(&mut self, line: &str) -> Result<Option<Simulation>, String> {
+        if line == TICK_MESSAGE {
+            self.frames += 1;
+        } else if let Some(start) = line.strip_prefix("start ") {
+            let mut parts = start.splitn(3, ' ');
+            let (seed, fingerprint, world) = (parts.next().unwrap_or_default(), parts.next().unwrap_or_default(), parts.next().ok_or("the host's start is missing it's world")?);
+            let seed = seed.parse().map_err(|_| format!("'{}' isn't a valid seed", seed))?;
+            let fingerprint = u64::from_str_radix(fingerprint, 16).map_err(|_| format!("'{}' isn't a valid fingerprint", fingerprint))?;
+            if fingerprint != self.fingerprint {
+                return Err("the host's materials or scripts differ from ours, so our worlds would drift apart".to_owned());
+            }
+            let bytes = decode_base64(world).ok_or("the host's world isn't valid base64")?;
+            self.inputs.clear();
+            self.frames = 0;
+            return start_simulation(&bytes, seed).map(Some);
+        } else {
+            let (frame, input) = line.split_once(' ').unwrap_or((line, ""));
+            let frame = frame.parse().map_err(|_| format!("'{}' isn't a valid frame number", frame))?;
+            self.inputs.push_back((frame, parse_bounded_input(input)?));
+        }
+        Ok(None)
+    }
+
+    // Make every input the host made before the frame about to be simulated, in the order the host made them
+    pub fn apply_due(&mut self, world: &mut crate::world::World, state: &mut SimulationState, history: &mut History, plugins: &mut Plugins) {
+        while self.inputs.front().is_some_and(|(frame, _)| *frame <= state.frame()) {
+            if let Some((_, input)) = self.inputs.pop_front() {
+                input.apply(world, state, history, plugins);
+            }
+        }
+    }
+
+    // Return true once every frame the host has simulated has been simulated here too
+    pub fn is_caught_up(&self, state: &SimulationState) -> bool {
+        state.frame() >= self.frames
+    }
+}
+
+// A server, simulating a world without a window for viewers to watch and paint in (see `app::run_server`)
+// ... it's the one simulating, streaming each chunk of the world to it's viewers whenever anything within it changes
+pub struct Server {
+    listener: TcpListener,
+    viewers: Vec<Connection>,
+    // The world as it was last streamed to the viewers, so only the chunks which have changed since are sent
+    streamed: Option<Snapshot>
+}
+
+impl Server {
+    // Start listening for viewers at the given address (ie: `0.0.0.0:7878`)
+    pub fn new(address: &str) -> Result<Server, String> {
+        let listener = TcpListener::bind(address).map_err(|error| format!("couldn't serve at {}: {}", address, error))?;
+        listener.set_nonblocking(true).map_err(|error| error.to_string())?;
+        Ok(Server { listener, viewers: Vec::new(), streamed: None })
+    }
+
+    pub fn address(&self) -> Option<SocketAddr> {
+        self.listener.local_addr().ok()
+    }
+
+    pub fn viewer_count(&self) -> usize {
+        self.viewers.len()
+    }
+
+    // Let in anyone new joining, sending them the world's size and every chunk of the world as it was last streamed (so the
+    // ... changes streamed next bring them up to date alongside everyone else)
+    pub fn accept(&mut self, world: &World) {
+        loop {
+            match self.listener.accept().and_then(|(stream, _)| Connection::new(stream)) {
+                Ok(mut viewer) => {
+                    let streamed = self.streamed.get_or_insert_with(|| Snapshot::take(world));
+                    viewer.send(&format!("stream {} {}", streamed.size().0, streamed.size().1));
+                    for chunk in chunks(streamed.size()) {
+                        viewer.send(&encode_chunk(streamed, chunk));
+                    }
+                    self.viewers.push(viewer);
+                },
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => {
+                    eprintln!("Couldn't let a viewer join: {}", error);
+                    break;
+                }
+            }
+        }
+    }
+
+    // Stream every chunk of the world which has changed since it was last streamed to the viewers
+    pub fn stream(&mut self, world: &World) {
+        let snapshot = Snapshot::take(world);
+        if let Some(streamed) = self.streamed.as_ref().filter(|streamed| streamed.size() == snapshot.size()) {
+            let changed: Vec<String> = chunks(snapshot.size()).filter(|&chunk| read_chunk(streamed, chunk) != read_chunk(&snapshot, chunk))
+                .map(|chunk| encode_chunk(&snapshot, chunk)).collect();
+            for viewer in self.viewers.iter_mut() {
+                for message in changed.iter() {
+                    viewer.send(message);
+                }
+            }
+        }
+        self.streamed = Some(snapshot);
+    }
+
+    // Send everything waiting to be sent, and return every input the viewers have made since, dropping any viewer who's left
+    // ... (or sent something unreadable or beyond what the sandbox allows, see `parse_remote_input`, or fallen too far behind)
+    pub fn receive(&mut self) -> Vec<Input> {
+        let mut inputs = Vec::new();
+        self.viewers.retain_mut(|viewer| {
+            let received = viewer.exchange().and_then(|lines| lines.iter().map(|line| parse_remote_input(line)).collect::<Result<Vec<Input>, String>>());
+            match received {
+                Ok(_) if viewer.outgoing.len() > MAX_STREAM_BACKLOG => {
+                    eprintln!("A viewer left: it couldn't keep up with the world's changes");
+                    false
+                },
+                Ok(received) => {
+                    inputs.extend(received);
+                    true
+                },
+                Err(error) => {
+                    eprintln!("A viewer left: {}", error);
+                    false
+                }
+            }
+        });
+        inputs
+    }
+}
+
+// Write a chunk of a snapshot out as a message: it's top-left cell, then it's cells (see `read_chunk`) as runs (see `encode_runs`)
+fn encode_chunk(snapshot: &Snapshot, chunk: Bounds) -> String {
+    format!("chunk {} {} {}", chunk.0.0, chunk.0.1, encode_base64(&encode_runs(read_chunk(snapshot, chunk).into_iter())))
+}
+
+// A viewer of a world simulated by a server, which only ever shows the world as it's streamed in (never simulating it)
+pub struct Viewer {
+    connection: Connection,
+    size: (usize, usize)
+}
+
+impl Viewer {
+    // Send an input for the server to make
+    pub fn send(&mut self, input: &Input) {
+        self.connection.send(&input.to_string());
+    }
+
+    // Send everything waiting to be sent, and lay every chunk the server has streamed since into the world (which has to be the
+    // ... world the viewer joined with), or return why the session is over
+    pub fn receive(&mut self, world: &mut World) -> Result<(), String> {
+        for line in self.connection.exchange()? {
+            self.handle(&line, world)?;
+        }
+        Ok(())
+    }
+
+    // Lay a streamed chunk (see `encode_chunk`) into the world, replacing every cell which differs
+    fn handle(&mut self, line: &str, world: &mut World) -> Result<(), String> {
+        let mut tokens = line.split(' ');
+        let (Some("chunk"), Some(x0), Some(y0), Some(runs), None) = (tokens.next(), tokens.next(), tokens.next(), tokens.next(), tokens.next()) else {
+            return Err(format!("the server sent something unexpected ('{}')", line.chars().take(40).collect::<String>()));
+        };
+        let corner = (x0.parse().ok(), y0.parse().ok());
+        let Some(((x0, y0), (x1, y1))) = corner.0.zip(corner.1).and_then(|corner| chunk_at(corner, self.size)) else {
+            return Err("the server sent a chunk outside of the world".to_owned());
+        };
+        let bytes = decode_base64(runs).ok_or("the server sent a chunk which isn't valid base64")?;
+        let cells = decode_runs(&bytes, (x1 - x0) * (y1 - y0))?;
+        write_chunk(world, ((x0, y0), (x1, y1)), &cells);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brush::{BrushOptions, BrushShape};
+    use crate::input::BudgetOverflow;
+    use crate::particle::ParticleVariant;
+    use crate::script::{parse_script, Scripts};
+
+    #[test]
+    fn clients_simulate_exactly_what_the_host_does() {
+        let mut host = Host::new("127.0.0.1:0").ok().unwrap();
+        let stream = TcpStream::connect(host.address().unwrap()).ok().unwrap();
+        let mut client = Client { connection: Connection::new(stream).ok().unwrap(), inputs: VecDeque::new(), frames: 0, fingerprint: 0 };
+
+        // The host lets the client in, restarting the session for it
+        let mut hosted = Simulation::with_seed(32, 32, 1);
+        hosted.paint(16, 4, 3, ParticleVariant::Sand);
+        while !host.accept() {
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        hosted = host.restart(&mut hosted, 7).ok().unwrap();
+        host.receive();
+        let mut joined = loop {
+            if let Some(simulation) = client.receive().ok().unwrap() {
+                break simulation;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        };
+
+        // The client paints through the host, which makes it's input (and one of it's own) while simulating ahead
+        client.send(&Input::Paint { position: (6, 2), options: BrushOptions { shape: BrushShape::Circle, radius: 2, density: 100 }, variant: ParticleVariant::Water, template: None, brush: None, overflow: BudgetOverflow::Refuse });
+        client.receive().ok().unwrap();
+        let mut plugins = Plugins::default();
+        let mut history = History::default();
+        for frame in 0..40 {
+            let (mut world, mut pressure, mut wind, state) = hosted.parts();
+            let mut inputs = Vec::new();
+            while inputs.is_empty() && frame == 3 {
+                std::thread::sleep(std::time::Duration::from_millis(5));
+                inputs = host.receive();
+            }
+            if frame == 10 {
+                inputs.push(Input::Drop { shape: BodyShape::Ball(3), position: (20.0, 10.0) });
+            }
+            for input in inputs {
+                input.apply(&mut world, state, &mut history, &mut plugins);
+                host.record(state.frame(), &input);
+            }
+            host.record_state(state);
+            tick(&mut world, &mut pressure, &mut wind, state, None);
+            host.tick();
+        }
+        host.receive();
+
+        // ... and the client, simulating everything the host has, ends up exactly where it did
+        let mut history = History::default();
+        while joined.parts().3.frame() < 40 {
+            assert!(client.receive().ok().unwrap().is_none());
+            let (mut world, mut pressure, mut wind, state) = joined.parts();
+            client.apply_due(&mut world, state, &mut history, &mut plugins);
+            if !client.is_caught_up(state) {
+                tick(&mut world, &mut pressure, &mut wind, state, None);
+            }
+        }
+        let is_watered = (0..32).any(|x| (0..32).any(|y| joined.get(x, y) == Some(ParticleVariant::Water)));
+        assert!(joined.snapshot() == hosted.snapshot() && joined.temperature(16, 28) == hosted.temperature(16, 28) && is_watered);
+    }
+
+    #[test]
+    fn sessions_are_only_joined_alongside_the_same_rules() {
+        let mut host = Host::new("127.0.0.1:0").ok().unwrap();
+        let address = host.address().unwrap().to_string();
+        let mut hosted = Simulation::with_seed(16, 16, 1);
+        hosted.parts().3.scripts = parse_script("on(\"Sand\", \"rust\");\nfn rust() { set(0, 0, \"Rust\"); }").ok().unwrap();
+        let fingerprint = fingerprint_rules(&hosted.parts().3.scripts);
+        assert!(fingerprint != fingerprint_rules(&Scripts::default()));
+
+        // A client without the host's scripts is refused once the host (re)starts the session, while one with them joins
+        for (expected, is_joined) in [(fingerprint_rules(&Scripts::default()), false), (fingerprint, true)] {
+            let joining = std::thread::spawn({
+                let address = address.clone();
+                move || join(&address, expected).err()
+            });
+            while !host.accept() {
+                std::thread::sleep(std::time::Duration::from_millis(5));
+            }
+            hosted = host.restart(&mut hosted, 7).ok().unwrap();
+            hosted.parts().3.scripts = parse_script("on(\"Sand\", \"rust\");\nfn rust() { set(0, 0, \"Rust\"); }").ok().unwrap();
+            while !joining.is_finished() {
+                host.receive();
+                std::thread::sleep(std::time::Duration::from_millis(5));
+            }
+            let error = joining.join().ok().unwrap();
+            assert!(error.is_none() == is_joined && error.is_none_or(|error| error.contains("scripts differ")));
+        }
+    }
+
+    #[test]
+    fn hosts_refuse_inputs_bigger_than_the_sandbox_allows() {
+        let mut host = Host::new("127.0.0.1:0").ok().unwrap();
+        let stream = TcpStream::connect(host.address().unwrap()).ok().unwrap();
+        let mut client = Client { connection: Connection::new(stream).ok().unwrap(), inputs: VecDeque::new(), frames: 0, fingerprint: 0 };
+        while !host.accept() {
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+
+        // A brush far bigger than the UI's own is refused, dropping the client who sent it, rather than hanging the host
+        client.send(&Input::Paint { position: (0, 0), options: BrushOptions { shape: BrushShape::Circle, radius: 4_000_000_000, density: 100 }, variant: ParticleVariant::Sand, template: None, brush: None, overflow: BudgetOverflow::Refuse });
+        client.receive().ok().unwrap();
+        while host.client_count() > 0 {
+            assert!(host.receive().is_empty());
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+
+        // ... as are bodies bigger than the UI drops, positions impossibly far away, and settings (which only the host changes)
+        for line in ["drop box 1000000000 1000000000 0 0", "drop ball 3 NaN 0", "fill 0 -4611686018427387904 100 Sand RefuseToPaint", "state Off 0 Full Full none"] {
+            assert!(parse_remote_input(line).is_err());
+        }
+        assert!(parse_remote_input("drop ball 4 10.5 -2").is_ok() && parse_remote_input("paint 3 4 Square 256 20 Water - - CullGases").is_ok());
+
+        // Lines and shapes reaching far beyond the world are clamped to it, rather than stepping through every cell along the way
+        let mut simulation = Simulation::new(16, 16);
+        let (mut world, _, _, state) = simulation.parts();
+        let line = parse_remote_input("line -1000000000 -1000000000 1000000000 1000000000 100 Sand RefuseToPaint").ok().unwrap();
+        let shape = parse_remote_input("shape Ellipse outline 1000000000 -1000000000 -1000000000 1000000000 100 Stone RefuseToPaint").ok().unwrap();
+        let mut history = History::default();
+        line.apply(&mut world, state, &mut history, &mut Plugins::default());
+        shape.apply(&mut world, state, &mut history, &mut Plugins::default());
+        assert!(world.is_active(0, 0) && world.is_active(15, 15) && world.active_count() > 16);
+    }
+
+    #[test]
+    fn viewers_see_exactly_what_the_server_simulates() {
+        let mut server = Server::new("127.0.0.1:0").ok().unwrap();
+        let stream = TcpStream::connect(server.address().unwrap()).ok().unwrap();
+        let mut viewer = Viewer { connection: Connection::new(stream).ok().unwrap(), size: (40, 36) };
+
+        // The server lets the viewer in, telling it the world's size before streaming the whole world to it
+        let mut served = Simulation::with_seed(40, 36, 1);
+        served.paint(20, 4, 3, ParticleVariant::Sand);
+        while server.viewer_count() == 0 {
+            server.accept(&served.parts().0);
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        let mut viewed = Simulation::new(40, 36);
+        let mut lines = Vec::new();
+        while lines.is_empty() {
+            server.receive();
+            lines = viewer.connection.exchange().ok().unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        assert_eq!(lines[0], "stream 40 36");
+        for line in lines.iter().skip(1) {
+            viewer.handle(line, &mut viewed.parts().0).ok().unwrap();
+        }
+
+        // The viewer paints through the server, which simulates on (streaming only what changes)
+        viewer.send(&Input::Paint { position: (6, 2), options: BrushOptions { shape: BrushShape::Circle, radius: 2, density: 100 }, variant: ParticleVariant::Water, template: None, brush: None, overflow: BudgetOverflow::Refuse });
+        let mut plugins = Plugins::default();
+        let mut history = History::default();
+        let mut inputs = Vec::new();
+        while inputs.is_empty() {
+            viewer.receive(&mut viewed.parts().0).ok().unwrap();
+            inputs = server.receive();
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        for frame in 0..20 {
+            let (mut world, _, _, state) = served.parts();
+            for input in std::mem::take(&mut inputs) {
+                input.apply(&mut world, state, &mut history, &mut plugins);
+            }
+            served.step();
+            if frame % 2 == 0 {
+                server.stream(&served.parts().0);
+            }
+        }
+        server.stream(&served.parts().0);
+
+        // ... and the viewer, without simulating anything itself, ends up seeing exactly the world the server has
+        for _ in 0..200 {
+            server.receive();
+            viewer.receive(&mut viewed.parts().0).ok().unwrap();
+            if viewed.snapshot() == served.snapshot() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        let is_watered = (0..40).any(|x| (0..36).any(|y| viewed.get(x, y) == Some(ParticleVariant::Water)));
+        assert!(viewed.snapshot() == served.snapshot() && is_watered);
+    }
+
+    #[test]
+    fn servers_refuse_oversized_worlds_and_inputs() {
+        // Viewers refuse to allocate a world bigger than any world file could hold
+        assert!(parse_stream_size("640 360") == Ok((640, 360)) && parse_stream_size("640").is_err());
+        assert!(parse_stream_size("100000 100000").err().unwrap().contains("too large"));
+        assert!(parse_stream_size(&format!("{} 2", usize::MAX)).err().unwrap().contains("too large"));
+
+        // ... while the server drops a viewer sending an input the sandbox never would, rather than making it
+        let mut server = Server::new("127.0.0.1:0").ok().unwrap();
+        let stream = TcpStream::connect(server.address().unwrap()).ok().unwrap();
+        let mut viewer = Viewer { connection: Connection::new(stream).ok().unwrap(), size: (8, 8) };
+        let mut served = Simulation::new(8, 8);
+        while server.viewer_count() == 0 {
+            server.accept(&served.parts().0);
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        viewer.send(&Input::Drop { shape: BodyShape::Box(1_000_000_000, 1_000_000_000), position: (0.0, 0.0) });
+        viewer.connection.exchange().ok().unwrap();
+        while server.viewer_count() > 0 {
+            assert!(server.receive().is_empty());
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+    }
+}
@@ -0,0 +1,694 @@
+use crate::{material::*, sim::*};
+
+// A kind of particle: one of the built-in variants below, or a material added at runtime (see `install_materials`), numbered after them
+// ... the number is kept to a byte, so every variant fits a snapshot's cell (see `Snapshot`)
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ParticleVariant(pub(crate) u8);
+
+#[allow(non_upper_case_globals)]
+impl ParticleVariant {
+    pub const Sand: ParticleVariant = ParticleVariant(0);
+    pub const Dirt: ParticleVariant = ParticleVariant(1);
+    pub const Water: ParticleVariant = ParticleVariant(2);
+    pub const Brick: ParticleVariant = ParticleVariant(3);
+    pub const Gunpowder: ParticleVariant = ParticleVariant(4);
+    pub const Fire: ParticleVariant = ParticleVariant(5);
+    pub const Smoke: ParticleVariant = ParticleVariant(6);
+    pub const Salt: ParticleVariant = ParticleVariant(7);
+    pub const Saltwater: ParticleVariant = ParticleVariant(8);
+    pub const Steam: ParticleVariant = ParticleVariant(9);
+    pub const Lava: ParticleVariant = ParticleVariant(10);
+    pub const Glass: ParticleVariant = ParticleVariant(11);
+    pub const Metal: ParticleVariant = ParticleVariant(12);
+    pub const Rust: ParticleVariant = ParticleVariant(13);
+    pub const Spark: ParticleVariant = ParticleVariant(14);
+    pub const Seed: ParticleVariant = ParticleVariant(15);
+    pub const Plant: ParticleVariant = ParticleVariant(16);
+    pub const Virus: ParticleVariant = ParticleVariant(17);
+    pub const Snow: ParticleVariant = ParticleVariant(18);
+    pub const Mud: ParticleVariant = ParticleVariant(19);
+    pub const Fuse: ParticleVariant = ParticleVariant(20);
+    pub const BurningFuse: ParticleVariant = ParticleVariant(21);
+    pub const Tnt: ParticleVariant = ParticleVariant(22);
+    pub const Gas: ParticleVariant = ParticleVariant(23);
+    pub const Stone: ParticleVariant = ParticleVariant(24);
+    pub const Gravel: ParticleVariant = ParticleVariant(25);
+    pub const Wax: ParticleVariant = ParticleVariant(26);
+    pub const MoltenWax: ParticleVariant = ParticleVariant(27);
+    pub const Cement: ParticleVariant = ParticleVariant(28);
+    pub const Concrete: ParticleVariant = ParticleVariant(29);
+    pub const Slime: ParticleVariant = ParticleVariant(30);
+    pub const Clone: ParticleVariant = ParticleVariant(31);
+    pub const Void: ParticleVariant = ParticleVariant(32);
+    pub const Spout: ParticleVariant = ParticleVariant(33);
+    pub const Antimatter: ParticleVariant = ParticleVariant(34);
+    pub const Flash: ParticleVariant = ParticleVariant(35);
+    pub const Mercury: ParticleVariant = ParticleVariant(36);
+    pub const Ice: ParticleVariant = ParticleVariant(37);
+    pub const Oil: ParticleVariant = ParticleVariant(38);
+    pub const Fan: ParticleVariant = ParticleVariant(39);
+    pub const Ash: ParticleVariant = ParticleVariant(40);
+    // The cells covered by a rigid body (see `RigidBody`), which aren't painted directly
+    pub const Body: ParticleVariant = ParticleVariant(41);
+    pub const Battery: ParticleVariant = ParticleVariant(42);
+    pub const Lamp: ParticleVariant = ParticleVariant(43);
+    pub const Heater: ParticleVariant = ParticleVariant(44);
+    pub const Detonator: ParticleVariant = ParticleVariant(45);
+    pub const Acid: ParticleVariant = ParticleVariant(46);
+}
+
+// The most variants there can be (built-in and added alike), leaving room for a snapshot's empty cell
+pub static MAX_VARIANTS: usize = u8::MAX as usize;
+
+// Every built-in particle variant, in order (see `variants` for the added materials too)
+pub static VARIANTS: [ParticleVariant; 47] = [
+    ParticleVariant::Sand,
+    ParticleVariant::Dirt,
+    ParticleVariant::Water,
+    ParticleVariant::Brick,
+    ParticleVariant::Gunpowder,
+    ParticleVariant::Fire,
+    ParticleVariant::Smoke,
+    ParticleVariant::Salt,
+    ParticleVariant::Saltwater,
+    ParticleVariant::Steam,
+    ParticleVariant::Lava,
+    ParticleVariant::Glass,
+    ParticleVariant::Metal,
+    ParticleVariant::Rust,
+    ParticleVariant::Spark,
+    ParticleVariant::Seed,
+    ParticleVariant::Plant,
+    ParticleVariant::Virus,
+    ParticleVariant::Snow,
+    ParticleVariant::Mud,
+    ParticleVariant::Fuse,
+    ParticleVariant::BurningFuse,
+    ParticleVariant::Tnt,
+    ParticleVariant::Gas,
+    ParticleVariant::Stone,
+    ParticleVariant::Gravel,
+    ParticleVariant::Wax,
+    ParticleVariant::MoltenWax,
+    ParticleVariant::Cement,
+    ParticleVariant::Concrete,
+    ParticleVariant::Slime,
+    ParticleVariant::Clone,
+    ParticleVariant::Void,
+    ParticleVariant::Spout,
+    ParticleVariant::Antimatter,
+    ParticleVariant::Flash,
+    ParticleVariant::Mercury,
+    ParticleVariant::Ice,
+    ParticleVariant::Oil,
+    ParticleVariant::Fan,
+    ParticleVariant::Ash,
+    ParticleVariant::Body,
+    ParticleVariant::Battery,
+    ParticleVariant::Lamp,
+    ParticleVariant::Heater,
+    ParticleVariant::Detonator,
+    ParticleVariant::Acid
+];
+
+impl ParticleVariant {
+    // Return this variant's number, which indexes into per-variant tables
+    pub fn index(&self) -> usize {
+        self.0 as usize
+    }
+
+    // Return the variant numbered `index`, if there is one
+    pub fn from_index(index: usize) -> Option<ParticleVariant> {
+        (index < variant_count()).then_some(ParticleVariant(index as u8))
+    }
+}
+
+// Return every particle variant: the built-in variants, then any added materials (see `install_materials`)
+pub fn variants() -> impl Iterator<Item = ParticleVariant> {
+    (0..variant_count()).map(|index| ParticleVariant(index as u8))
+}
+
+// The physical state of a particle, which decides how it moves around the world
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MatterState {
+    Solid,
+    Powder,
+    Liquid,
+    Gas,
+    // Energy isn't pulled by gravity, it travels by it's own rules
+    Energy
+}
+
+impl ParticleVariant {
+    // Return a percentage (1-100) chance of this particle moving, based on it's variant
+    pub(crate) fn get_movement_chance(&self) -> u8 {
+        self.element().movement_chance
+    }
+
+    // Return the furthest distance (in particles) this particle can move sideways in a single movement, based on it's variant
+    fn get_lateral_spread(&self) -> u8 {
+        self.element().lateral_spread
+    }
+
+    // Return how steep a pile this powder forms, see `Element::pile_steepness`
+    pub(crate) fn get_pile_steepness(&self) -> u8 {
+        self.element().pile_steepness
+    }
+
+    // Return the relative density of this variant: denser particles sink through lighter fluids (and lighter particles float up through denser fluids)
+    fn get_density(&self) -> u8 {
+        self.element().density
+    }
+
+    // Return true if this variant is dense enough to sink through (and swap places with) the other variant
+    // ... this only happens when a fluid (liquid or gas) is involved, and never to static solids or energy
+    pub(crate) fn can_displace(&self, other: &ParticleVariant) -> bool {
+        let is_fluid = |state: MatterState| state == MatterState::Liquid || state == MatterState::Gas;
+        let is_movable = matches!(other.get_state(), MatterState::Powder | MatterState::Liquid | MatterState::Gas);
+        let is_fluid_pair = is_fluid(self.get_state()) || is_fluid(other.get_state());
+        is_movable && is_fluid_pair && self.get_density() > other.get_density()
+    }
+
+    // Return a percentage (0-100) chance of this particle refusing to move away from it's own kind, based on it's variant
+    fn get_cohesion(&self) -> u8 {
+        self.element().cohesion
+    }
+
+    // Return a percentage (0-100) chance of this powder clinging to it's own kind while wet, based on it's variant
+    pub(crate) fn get_wet_cohesion(&self) -> u8 {
+        self.element().wet_cohesion
+    }
+
+    // Return how far (in cells) this variant scans sideways when flowing, based on it's viscosity (always at least one cell)
+    pub(crate) fn get_flow_distance(&self) -> usize {
+        (LIQUID_FLOW_DISTANCE * (100 - self.element().viscosity as usize)).div_ceil(100).max(1)
+    }
+
+    // Return a percentage (0-100) chance of this gas drifting upwards (rather than in a random direction) each time it moves
+    pub(crate) fn get_buoyancy(&self) -> u8 {
+        self.element().buoyancy
+    }
+
+    // Return a percentage (1-100) chance of this particle falling into a free cell each frame, based on it's variant
+    pub(crate) fn get_fall_chance(&self) -> u8 {
+        self.element().fall_chance
+    }
+
+    // Return the state of matter of this variant
+    pub(crate) fn get_state(&self) -> MatterState {
+        self.element().state
+    }
+
+    // Return how readily this variant conducts heat to it's neighbours (0.0 - 1.0)
+    fn get_conductivity(&self) -> f32 {
+        self.element().conductivity
+    }
+
+    // Return the temperature (in °C) this variant is created at, if it isn't simply the temperature of the cell it appears in
+    pub(crate) fn get_spawn_temperature(&self) -> Option<f32> {
+        self.element().spawn_temperature
+    }
+
+    // Return the variant this variant turns into once heated above a temperature (in °C), alongside that temperature, from the phase tables
+    pub(crate) fn get_heated_form(&self) -> Option<(ParticleVariant, f32)> {
+        PHASE_TABLES.iter().find_map(|table| {
+            if table.solid.as_ref() == Some(self) {
+                table.liquid.map(|liquid| (liquid, table.melting.0))
+            } else if table.liquid.as_ref() == Some(self) {
+                table.gas.map(|gas| (gas, table.boiling.0))
+            } else {
+                None
+            }
+        })
+    }
+
+    // Return the variant this variant turns into once cooled below a temperature (in °C), alongside that temperature, from the phase tables
+    pub(crate) fn get_cooled_form(&self) -> Option<(ParticleVariant, f32)> {
+        PHASE_TABLES.iter().find_map(|table| {
+            if table.gas.as_ref() == Some(self) {
+                table.liquid.map(|liquid| (liquid, table.boiling.1))
+            } else if table.liquid.as_ref() == Some(self) && table.melting.1 > f32::NEG_INFINITY {
+                table.solid.map(|solid| (solid, table.melting.1))
+            } else {
+                None
+            }
+        })
+    }
+
+    // Return true if this variant is a source of heat
+    pub(crate) fn is_hot(&self) -> bool {
+        self.element().is_hot
+    }
+
+    // Return how this variant decays, if it only lasts for a limited time
+    pub(crate) fn get_decay(&self) -> Option<&'static Decay> {
+        self.element().decay.as_ref()
+    }
+
+    // Return how this variant burns, if it's flammable
+    pub(crate) fn get_flammability(&self) -> Option<Flammability> {
+        self.element().flammability
+    }
+
+    // Return true if flowing Water can wash this variant away, carrying it downstream as sediment
+    pub(crate) fn is_erodible(&self) -> bool {
+        self.element().is_erodible
+    }
+
+    // Return true if this variant has no behaviour besides moving, so it can sleep while buried in it's own kind (see `is_buried`)
+    pub(crate) fn can_sleep(&self) -> bool {
+        self.element().can_sleep
+    }
+
+    // Return true if electricity (ie: Sparks) can travel through this variant
+    pub(crate) fn is_conductive(&self) -> bool {
+        self.element().is_conductive
+    }
+
+    // Return true if a Battery's current can flow through this variant, powering any connected Lamps, Heaters and Detonators
+    pub(crate) fn conducts_current(&self) -> bool {
+        self.element().conducts_current
+    }
+
+    // Return true if this variant takes part in any reaction within the reaction registry
+    pub(crate) fn is_reactive(&self) -> bool {
+        get_material_reactions().iter().chain(REACTIONS).any(|r| r.reactants.0 == *self || r.reactants.1 == *self)
+    }
+
+    // Return how this variant slowly weathers away while exposed to something (if at all), see `weather`
+    pub(crate) fn get_weathering(&self) -> Option<&'static Weathering> {
+        self.element().weathering.as_ref()
+    }
+
+    // Return the reaction this variant undergoes over long periods of contact with another variant (if any)
+    pub(crate) fn get_contact_reaction(&self) -> Option<&'static ContactReaction> {
+        self.element().contact_reaction.as_ref()
+    }
+}
+
+// How a short-lived variant decays once it's lifetime runs out
+#[derive(Clone)]
+pub(crate) struct Decay {
+    // The range (in frames) a particle's lifetime is randomly picked from
+    pub(crate) lifetime: (u16, u16),
+    // What the particle decays into (if anything)
+    pub(crate) product: Option<ParticleVariant>
+}
+
+// How a flammable variant burns once ignited
+#[derive(Clone, Copy)]
+pub(crate) struct Flammability {
+    // The temperature (in °C) this variant catches alight at (it also catches alight when touched by a source of heat)
+    pub(crate) ignition: f32,
+    // How many frames this variant burns for before it's consumed
+    pub(crate) burn_frames: u16,
+    // What this variant leaves behind once consumed (if anything)
+    pub(crate) ash: Option<ParticleVariant>
+}
+
+// The phases of a single material, alongside the temperatures (in °C) it transitions between them at
+// ... each transition has a separate heating and cooling temperature, so particles don't flicker back and forth right at the threshold
+pub(crate) struct PhaseTable {
+    solid: Option<ParticleVariant>,
+    liquid: Option<ParticleVariant>,
+    gas: Option<ParticleVariant>,
+    // The temperatures the solid melts at, and the liquid freezes at (negative infinity never freezes)
+    melting: (f32, f32),
+    // The temperatures the liquid boils at, and the gas condenses at
+    boiling: (f32, f32)
+}
+
+// Every material's phase table (earlier tables take priority when a variant appears in more than one)
+static PHASE_TABLES: &[PhaseTable] = &[
+    PhaseTable {
+        solid: Some(ParticleVariant::Ice),
+        liquid: Some(ParticleVariant::Water),
+        gas: Some(ParticleVariant::Steam),
+        melting: (1.0, -1.0),
+        boiling: (100.0, 40.0)
+    },
+    PhaseTable {
+        solid: Some(ParticleVariant::Stone),
+        liquid: Some(ParticleVariant::Lava),
+        gas: None,
+        melting: (1100.0, 650.0),
+        boiling: (f32::INFINITY, f32::INFINITY)
+    },
+    PhaseTable {
+        solid: Some(ParticleVariant::Wax),
+        liquid: Some(ParticleVariant::MoltenWax),
+        gas: None,
+        melting: (60.0, 55.0),
+        boiling: (f32::INFINITY, f32::INFINITY)
+    },
+    // Snow melts into Water, which freezes back into Ice rather than Snow
+    PhaseTable {
+        solid: Some(ParticleVariant::Snow),
+        liquid: Some(ParticleVariant::Water),
+        gas: None,
+        melting: (1.0, f32::NEG_INFINITY),
+        boiling: (f32::INFINITY, f32::INFINITY)
+    },
+    // Sand fuses into Glass, which never turns back into Sand
+    PhaseTable {
+        solid: Some(ParticleVariant::Sand),
+        liquid: Some(ParticleVariant::Glass),
+        gas: None,
+        melting: (400.0, f32::NEG_INFINITY),
+        boiling: (f32::INFINITY, f32::INFINITY)
+    }
+];
+
+// A reaction between two touching particles, which each turn into their product (or vanish, if they have none)
+#[derive(Clone)]
+pub(crate) struct Reaction {
+    pub(crate) reactants: (ParticleVariant, ParticleVariant),
+    pub(crate) products: (Option<ParticleVariant>, Option<ParticleVariant>),
+    // The percentage (1-100) chance of the reaction happening each frame the reactants touch
+    pub(crate) chance: u8
+}
+
+// The reaction registry: every simple reaction between two touching particles
+static REACTIONS: &[Reaction] = &[
+    // Salt dissolves into Water, forming Saltwater
+    Reaction {
+        reactants: (ParticleVariant::Salt, ParticleVariant::Water),
+        products: (None, Some(ParticleVariant::Saltwater)),
+        chance: 5
+    },
+    // Water washes Virus away
+    Reaction {
+        reactants: (ParticleVariant::Virus, ParticleVariant::Water),
+        products: (None, Some(ParticleVariant::Water)),
+        chance: 100
+    },
+    // Water flash-boils against Lava, cooling it into Stone
+    Reaction {
+        reactants: (ParticleVariant::Water, ParticleVariant::Lava),
+        products: (Some(ParticleVariant::Steam), Some(ParticleVariant::Stone)),
+        chance: 20
+    }
+];
+
+// Look up the reaction between two variants (in either order) within the reaction registry, returning the chance of it happening
+// ... alongside each variant's product, in the same order as the variants were given (reactions from the materials file come first)
+pub(crate) fn get_reaction(a: &ParticleVariant, b: &ParticleVariant) -> Option<(Option<ParticleVariant>, Option<ParticleVariant>, u8)> {
+    get_material_reactions().iter().chain(REACTIONS).find_map(|r| {
+        if r.reactants.0 == *a && r.reactants.1 == *b {
+            Some((r.products.0, r.products.1, r.chance))
+        } else if r.reactants.0 == *b && r.reactants.1 == *a {
+            Some((r.products.1, r.products.0, r.chance))
+        } else {
+            None
+        }
+    })
+}
+
+// How a variant slowly weathers away while exposed to an agent
+#[derive(Clone)]
+pub(crate) struct Weathering {
+    pub(crate) agent: ParticleVariant,
+    // Whether the agent only weathers us while it's flowing (ie: moved last frame)
+    pub(crate) needs_flow: bool,
+    // The percentage (1-100) chance of weathering each time the weathering pass visits an exposed particle
+    pub(crate) chance: u8,
+    // What the weathered particle becomes (if anything)
+    pub(crate) product: Option<ParticleVariant>
+}
+
+// A reaction which takes place once a particle has spent long enough touching it's reactant
+#[derive(Clone)]
+pub(crate) struct ContactReaction {
+    // The variant which must be touched for the reaction to progress
+    pub(crate) reactant: ParticleVariant,
+    // How many frames of contact it takes for the reaction to happen
+    pub(crate) frames: u16,
+    // The variant the particle turns into
+    pub(crate) product: ParticleVariant,
+    // The variant the touching reactant turns into (if it's affected at all)
+    pub(crate) reactant_product: Option<ParticleVariant>
+}
+
+impl std::fmt::Display for ParticleVariant {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.element().name)
+    }
+}
+
+// A single particle, as a whole (the world itself stores it's particles split apart, see `Particles`)
+#[derive(Clone)]
+pub(crate) struct Particle {
+    pub(crate) variant: ParticleVariant,
+    pub(crate) active: bool,
+    pub(crate) data: ParticleData
+}
+
+// Everything about a particle besides it's variant and whether it's active, which most of the simulation never needs to look at
+#[derive(Clone)]
+pub struct ParticleData {
+    pub(crate) id: u32,
+    // The temperature (in °C) of this particle's cell, which diffuses between neighbouring cells every frame
+    pub temperature: f32,
+    // How many frames this particle has spent in contact with (or away from) it's reactants
+    // ... ie: see `get_contact_reaction`, Seeds on wet Dirt, or Mud drying out
+    pub(crate) contact: u16,
+    // The direction this particle is travelling in, for particles that move by their own rules (ie: Spark)
+    pub(crate) direction: (i8, i8),
+    // How much 'life' this particle has left (ie: steps a Spark can travel, generations a Plant can grow, or frames a Fuse burns for)
+    // ... see `lifetime` for particles which simply decay over time
+    pub(crate) life: u16,
+    // How many frames this particle has been resting in the same cell
+    pub(crate) rest: u16,
+    // A variant this particle carries around as data (ie: the material a Clone copies, a Spout emits, or sediment suspended in Water)
+    pub(crate) template: Option<ParticleVariant>,
+    // The velocity (in cells per frame) this particle is travelling at, along the X and Y axis
+    pub(crate) velocity: (f32, f32),
+    // Whether this particle is alight, and how many more frames it can burn for before it's consumed
+    pub(crate) burning: bool,
+    pub(crate) fuel: u16,
+    // How many more frames this particle lasts before it decays, for variants with a limited lifetime (see `get_decay`)
+    // ... this is picked the first time the particle is simulated as a decaying variant
+    pub(crate) lifetime: Option<u16>,
+    // How many more frames this particle stays wet for (ie: Sand that's been touching Water)
+    pub(crate) wetness: u16,
+    // Whether a Battery's current is flowing through this particle, which is recalculated every frame (see `update_power`)
+    pub(crate) powered: bool,
+    // The fingerprint of this particle's neighbourhood when it fell asleep, it sleeps until the neighbourhood changes (see `is_buried`)
+    pub(crate) sleep: Option<u64>,
+    // The (wrapping) number of the last frame this particle was simulated in, so it isn't simulated again as it moves through the world
+    // ... this travels with the particle as it moves, and fresh particles start at u32::MAX so they're never mistaken as updated
+    pub(crate) last_updated_frame: u32
+}
+
+impl Particle {
+    pub(crate) fn new(id: u32, variant: ParticleVariant, active: bool) -> Particle {
+        let data = ParticleData { id, temperature: AMBIENT_TEMPERATURE, contact: 0, direction: (0, 0), life: 0, rest: 0, template: None, velocity: (0.0, 0.0), burning: false, fuel: 0, lifetime: None, wetness: 0, powered: false, sleep: None, last_updated_frame: u32::MAX };
+        Particle { variant, active, data }
+    }
+}
+
+impl ParticleData {
+    // Reset all per-particle state back to it's defaults
+    // Note: the temperature belongs to the cell rather than the particle, so it's left alone
+    pub(crate) fn reset_state(&mut self) {
+        self.contact = 0;
+        self.direction = (0, 0);
+        self.life = 0;
+        self.rest = 0;
+        self.template = None;
+        self.velocity = (0.0, 0.0);
+        self.burning = false;
+        self.fuel = 0;
+        self.lifetime = None;
+        self.wetness = 0;
+        self.powered = false;
+        self.sleep = None;
+    }
+}
+
+// A copy of everything public about a cell of the world (see `World::cell`), for tools and checks outside of the engine
+#[derive(Clone, Copy, PartialEq)]
+pub struct Cell {
+    // The variant of the particle in the cell, if there is one
+    pub variant: Option<ParticleVariant>,
+    // The temperature (in °C) of the cell
+    pub temperature: f32,
+    // The velocity (in cells per frame) the particle is travelling at
+    pub velocity: (f32, f32),
+    pub is_burning: bool,
+    pub is_wet: bool,
+    pub is_powered: bool,
+    // The variant the particle carries around (ie: the material a Spout emits), if any
+    pub template: Option<ParticleVariant>
+}
+
+// A particle borrowed from the world (see `World::particle`), which reads like a whole particle
+// ... the variant and active flag are copied out, and every other field is read through to the particle's `ParticleData`
+#[derive(Clone, Copy)]
+pub struct ParticleRef<'a> {
+    pub variant: ParticleVariant,
+    pub active: bool,
+    pub(crate) data: &'a ParticleData
+}
+
+impl std::ops::Deref for ParticleRef<'_> {
+    type Target = ParticleData;
+
+    fn deref(&self) -> &ParticleData {
+        self.data
+    }
+}
+
+impl ParticleRef<'_> {
+    // Copy the particle out as a `Cell`
+    pub fn to_cell(&self) -> Cell {
+        Cell {
+            variant: self.active.then_some(self.variant),
+            temperature: self.temperature,
+            velocity: self.velocity,
+            is_burning: self.active && self.burning,
+            is_wet: self.active && self.wetness > 0,
+            is_powered: self.active && self.powered,
+            template: self.template.filter(|_| self.active)
+        }
+    }
+
+    // Return a potential (non-guarenteed) movement delta for this particle, based on it's properties
+    pub(crate) fn try_generate_movement(&self, rng: &mut Rng) -> usize {
+        if rng.gen_range(0, 100) < self.variant.get_movement_chance() {
+            let spread = self.variant.get_lateral_spread() as i32;
+            rng.gen_range(-spread, spread + 1) as usize
+        } else { 0 }
+    }
+
+    // Return how far (in cells) this particle's light reaches, if it emits any
+    pub(crate) fn get_light_reach(&self) -> f32 {
+        if !self.active {
+            return 0.0;
+        }
+        let element = self.variant.element();
+        match element.powered_light_reach {
+            Some(reach) if self.powered => reach,
+            _ if self.burning => element.light_reach.max(8.0),
+            _ => element.light_reach
+        }
+    }
+
+    // Return a percentage (0-100) chance of this particle refusing to move away from it's own kind, based on it's variant and wetness
+    pub(crate) fn get_cohesion(&self) -> u8 {
+        if self.wetness > 0 {
+            self.variant.get_cohesion().max(self.variant.get_wet_cohesion())
+        } else {
+            self.variant.get_cohesion()
+        }
+    }
+
+    // Return whether this is a plain powder the GPU can move (see `GpuBackend`): dry, unburning, and not carrying any momentum
+    pub(crate) fn is_gpu_powder(&self) -> bool {
+        self.active && self.variant.get_state() == MatterState::Powder && self.variant.can_sleep()
+            && self.wetness == 0 && !self.burning && self.velocity == (0.0, 0.0)
+    }
+
+    // Return a cheap hash of everything about this particle that can change while it's simulated, used to spot settled chunks
+    // ... `rest` is capped, so resting particles stop changing once every rest-based rule (ie: Cement setting) has fired
+    pub(crate) fn fingerprint(&self) -> u64 {
+        if !self.active {
+            return 0;
+        }
+        let state = [
+            self.id as u64,
+            self.variant.index() as u64,
+            self.temperature.round() as i64 as u64,
+            self.contact as u64,
+            self.life as u64,
+            self.rest.min(CEMENT_SET_FRAMES) as u64,
+            self.template.as_ref().map_or(u64::MAX, |template| template.index() as u64),
+            self.velocity.0.to_bits() as u64 ^ ((self.velocity.1.to_bits() as u64) << 32),
+            self.burning as u64 | (self.powered as u64) << 1 | (self.fuel as u64) << 2,
+            self.lifetime.map_or(u64::MAX, |lifetime| lifetime as u64),
+            self.wetness as u64
+        ];
+        state.iter().fold(0xcbf29ce484222325, |hash, value| (hash ^ value).wrapping_mul(0x100000001b3))
+    }
+
+    // Return how readily this particle conducts heat to it's neighbours (0.0 - 1.0)
+    pub(crate) fn get_conductivity(&self) -> f32 {
+        if self.active { self.variant.get_conductivity() } else { AIR_CONDUCTIVITY }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reactions_are_found_in_either_order() {
+        let (lava, water, chance) = get_reaction(&ParticleVariant::Lava, &ParticleVariant::Water).unwrap();
+        assert!(lava == Some(ParticleVariant::Stone));
+        assert!(water == Some(ParticleVariant::Steam));
+        assert_eq!(chance, 20);
+
+        let (water, lava, _) = get_reaction(&ParticleVariant::Water, &ParticleVariant::Lava).unwrap();
+        assert!(water == Some(ParticleVariant::Steam));
+        assert!(lava == Some(ParticleVariant::Stone));
+    }
+
+    #[test]
+    fn unrelated_variants_do_not_react() {
+        assert!(get_reaction(&ParticleVariant::Sand, &ParticleVariant::Water).is_none());
+        assert!(!ParticleVariant::Brick.is_reactive());
+        assert!(ParticleVariant::Salt.is_reactive());
+    }
+
+    #[test]
+    fn thicker_liquids_flow_shorter_distances() {
+        assert_eq!(ParticleVariant::Water.get_flow_distance(), LIQUID_FLOW_DISTANCE);
+        assert!(ParticleVariant::Oil.get_flow_distance() < ParticleVariant::Water.get_flow_distance());
+        assert!(ParticleVariant::Lava.get_flow_distance() < ParticleVariant::Oil.get_flow_distance());
+        assert_eq!(ParticleVariant::Slime.get_flow_distance(), 1);
+    }
+
+    #[test]
+    fn decay_lifetimes_are_valid() {
+        for variant in VARIANTS.iter() {
+            if let Some(decay) = variant.get_decay() {
+                assert!(decay.lifetime.0 <= decay.lifetime.1, "{} has an inverted lifetime range", variant);
+                assert!(decay.product.as_ref() != Some(variant), "{} decays into itself", variant);
+            }
+        }
+    }
+
+    #[test]
+    fn phase_tables_chain_both_ways() {
+        let (water, melting) = ParticleVariant::Ice.get_heated_form().unwrap();
+        assert!(water == ParticleVariant::Water && melting == 1.0);
+        assert!(ParticleVariant::Water.get_heated_form().unwrap().0 == ParticleVariant::Steam);
+        assert!(ParticleVariant::Steam.get_cooled_form().unwrap().0 == ParticleVariant::Water);
+        assert!(ParticleVariant::Water.get_cooled_form().unwrap().0 == ParticleVariant::Ice);
+        assert!(ParticleVariant::Stone.get_heated_form().unwrap().0 == ParticleVariant::Lava);
+        assert!(ParticleVariant::Lava.get_cooled_form().unwrap().0 == ParticleVariant::Stone);
+        // One-way transitions never reverse
+        assert!(ParticleVariant::Glass.get_cooled_form().is_none());
+    }
+
+    #[test]
+    fn phase_transitions_cool_below_where_they_heat() {
+        for table in PHASE_TABLES {
+            assert!(table.melting.1 < table.melting.0);
+            assert!(table.boiling.1 <= table.boiling.0);
+        }
+    }
+
+    #[test]
+    fn reaction_registry_is_valid() {
+        for (i, reaction) in REACTIONS.iter().enumerate() {
+            assert!(reaction.chance > 0 && reaction.chance <= 100, "reaction {} has an invalid chance", i);
+            // Each pair of reactants may only be registered once (in either order)
+            let duplicates = REACTIONS.iter().filter(|r| {
+                (r.reactants.0 == reaction.reactants.0 && r.reactants.1 == reaction.reactants.1) ||
+                (r.reactants.0 == reaction.reactants.1 && r.reactants.1 == reaction.reactants.0)
+            }).count();
+            assert_eq!(duplicates, 1, "reaction {} is registered more than once", i);
+        }
+    }
+}
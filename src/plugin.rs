@@ -0,0 +1,175 @@
+use crate::{input::*, material::*, particle::*, sim::*, world::*};
+
+// A third-party extension to the sandbox, compiled in and registered before the sandbox starts (see `app::run`)
+// ... every hook but the name is optional, so a plugin only implements what it needs
+pub trait Plugin {
+    // The plugin's name, shown above it's panel and alongside any problems with it
+    fn name(&self) -> &str;
+
+    // Material definitions (in the materials file's format, see `parse_materials`) to lay over the built-in materials
+    fn materials(&self) -> Option<&str> {
+        None
+    }
+
+    // Materials this plugin adds from code (after it's `materials`), each numbered at runtime like a materials file's added materials
+    // ... unlike those, they can bring their own update logic (see `MaterialDefinition::behaviour`)
+    fn added_materials(&self) -> Vec<MaterialDefinition> {
+        Vec::new()
+    }
+
+    // The names of the brushes this plugin adds, see `Plugin::paint`
+    fn brushes(&self) -> &[&'static str] {
+        &[]
+    }
+
+    // Paint with the plugin's `brush`th brush, centred on `position`, returning how many particles were painted
+    // ... plugin brushes should paint through `paint_cell`, so they respect the particle budget like the built-in brush
+    fn paint(&mut self, _brush: usize, _world: &mut World, _budget: &mut Budget, _position: (isize, isize), _radius: usize, _variant: ParticleVariant) -> usize {
+        0
+    }
+
+    // Run the plugin's own logic over the world, after every simulated frame
+    fn tick(&mut self, _world: &mut World, _state: &mut SimulationState) {}
+
+    // Return the lines of text for the plugin's UI panel (none hides the panel)
+    fn panel(&self, _world: &World) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+// A brush added by a plugin: the plugin's index, and the brush's index within that plugin
+#[derive(Clone, Copy, PartialEq)]
+pub struct PluginBrush {
+    plugin: usize,
+    brush: usize
+}
+
+// Every registered plugin, in the order they were registered
+#[derive(Default)]
+pub struct Plugins {
+    plugins: Vec<Box<dyn Plugin>>
+}
+
+impl Plugins {
+    pub fn register(&mut self, plugin: impl Plugin + 'static) {
+        self.plugins.push(Box::new(plugin));
+    }
+
+    // Lay every plugin's material definitions (then it's added materials) over `materials`, later plugins taking priority over earlier ones
+    // ... malformed definitions are skipped, returning their problems instead
+    pub fn load_materials(&self, materials: &mut Materials) -> Vec<String> {
+        let mut problems = Vec::new();
+        for plugin in self.plugins.iter() {
+            match plugin.materials().map(parse_materials) {
+                Some(Ok(definitions)) => if let Err(error) = materials.merge(definitions) {
+                    problems.push(format!("{} plugin materials: {}", plugin.name(), error));
+                },
+                Some(Err(errors)) => problems.extend(errors.iter().map(|error| format!("{} plugin materials {}", plugin.name(), error))),
+                None => {}
+            }
+            for definition in plugin.added_materials() {
+                if let Err(error) = materials.add(definition) {
+                    problems.push(format!("{} plugin materials: {}", plugin.name(), error));
+                }
+            }
+        }
+        problems
+    }
+
+    // Return every brush added by a plugin
+    pub fn brushes(&self) -> Vec<PluginBrush> {
+        self.plugins.iter().enumerate().flat_map(|(plugin, p)| (0..p.brushes().len()).map(move |brush| PluginBrush { plugin, brush })).collect()
+    }
+
+    pub fn brush_name(&self, brush: PluginBrush) -> &str {
+        self.plugins[brush.plugin].brushes()[brush.brush]
+    }
+
+    // Paint with a plugin's brush, see `Plugin::paint`
+    pub fn paint(&mut self, brush: PluginBrush, world: &mut World, budget: &mut Budget, position: (isize, isize), radius: usize, variant: ParticleVariant) -> usize {
+        self.plugins[brush.plugin].paint(brush.brush, world, budget, position, radius, variant)
+    }
+
+    // Run every plugin's own logic over the world, see `Plugin::tick`
+    pub fn tick(&mut self, world: &mut World, state: &mut SimulationState) {
+        for plugin in self.plugins.iter_mut() {
+            plugin.tick(world, state);
+        }
+    }
+
+    // Return every plugin's name alongside it's panel's lines, skipping plugins without a panel
+    pub fn panels(&self, world: &World) -> Vec<(&str, Vec<String>)> {
+        self.plugins.iter().map(|plugin| (plugin.name(), plugin.panel(world))).filter(|(_, lines)| !lines.is_empty()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Paints a single line of cells, and counts the frames it's seen
+    struct LinePlugin {
+        ticks: usize
+    }
+
+    impl Plugin for LinePlugin {
+        fn name(&self) -> &str {
+            "Line"
+        }
+
+        fn materials(&self) -> Option<&str> {
+            Some("[Sand]\ndensity = 30")
+        }
+
+        fn brushes(&self) -> &[&'static str] {
+            &["Line"]
+        }
+
+        fn paint(&mut self, _brush: usize, world: &mut World, budget: &mut Budget, (x, y): (isize, isize), radius: usize, variant: ParticleVariant) -> usize {
+            (x..x + radius as isize).filter(|&cx| paint_cell(world, budget, (cx, y), variant, None)).count()
+        }
+
+        fn tick(&mut self, _world: &mut World, _state: &mut SimulationState) {
+            self.ticks += 1;
+        }
+
+        fn panel(&self, _world: &World) -> Vec<String> {
+            vec![format!("Ticks: {}", self.ticks)]
+        }
+    }
+
+    // Only names itself, but with malformed materials
+    struct BrokenPlugin;
+
+    impl Plugin for BrokenPlugin {
+        fn name(&self) -> &str {
+            "Broken"
+        }
+
+        fn materials(&self) -> Option<&str> {
+            Some("[Sandd]")
+        }
+    }
+
+    #[test]
+    fn plugins_add_brushes_panels_and_materials() {
+        let mut plugins = Plugins::default();
+        plugins.register(BrokenPlugin);
+        plugins.register(LinePlugin { ticks: 0 });
+
+        let mut materials = Materials::default();
+        let problems = plugins.load_materials(&mut materials);
+        assert!(problems.len() == 1 && problems[0].starts_with("Broken"));
+
+        let mut particles = Particles::new(4, (0..16).map(|i| Particle::new(i, ParticleVariant::Sand, false)));
+        let mut world = World::new(&mut particles);
+        let mut budget = Budget::new(&world, BudgetOverflow::Refuse);
+        let brushes = plugins.brushes();
+        assert!(brushes.len() == 1 && plugins.brush_name(brushes[0]) == "Line");
+        assert_eq!(plugins.paint(brushes[0], &mut world, &mut budget, (1, 2), 5, ParticleVariant::Water), 3);
+
+        plugins.tick(&mut world, &mut SimulationState::new(0));
+        let panels = plugins.panels(&world);
+        assert!(panels.len() == 1 && panels[0].0 == "Line" && panels[0].1 == ["Ticks: 1"]);
+    }
+}
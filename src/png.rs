@@ -0,0 +1,184 @@
+use macroquad::prelude::Image;
+use miniz_oxide::{deflate::compress_to_vec, inflate::decompress_to_vec_with_limit};
+use png::{BitDepth, ColorType, Decoder, DecodingError, Encoder, Limits, Transformations};
+
+// The eight bytes every PNG file starts with
+static PNG_SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+
+// The most bytes an image can decode to (as RGBA), so a small file can't claim a huge image and exhaust memory opening it
+static MAX_IMAGE_BYTES: usize = 16384 * 16384 * 4;
+
+// How hard a recording's frames are compressed while it's being captured: fast, as they're compressed properly once it's finished
+static FRAME_COMPRESSION_LEVEL: u8 = 1;
+
+// Return true if the bytes are (the start of) a PNG file
+pub fn is_png(bytes: &[u8]) -> bool {
+    bytes.starts_with(PNG_SIGNATURE)
+}
+
+// Describe why a PNG file couldn't be decoded
+fn describe_error(error: DecodingError) -> String {
+    match error {
+        DecodingError::IoError(_) => "the file ends too early".to_owned(),
+        DecodingError::Format(error) => format!("the file is corrupt ({})", error),
+        DecodingError::LimitsExceeded => "the image is too big to open".to_owned(),
+        DecodingError::Parameter(error) => error.to_string()
+    }
+}
+
+// Decode a PNG file into an RGBA image
+// ... every colour type and bit depth is supported (16-bit channels keep their high byte), and animated PNGs open at their first frame
+pub fn decode_png(bytes: &[u8]) -> Result<Image, String> {
+    if !is_png(bytes) {
+        return Err("not a PNG file".to_owned());
+    }
+    let mut decoder = Decoder::new_with_limits(bytes, Limits { bytes: MAX_IMAGE_BYTES });
+    decoder.set_transformations(Transformations::normalize_to_color8() | Transformations::ALPHA);
+    let mut reader = decoder.read_info().map_err(describe_error)?;
+
+    // Check the image's size before any of it's data is decompressed
+    let (width, height) = reader.info().size();
+    let is_too_big = (width as u64 * height as u64 * 4) > MAX_IMAGE_BYTES as u64;
+    if width > u16::MAX as u32 || height > u16::MAX as u32 || is_too_big {
+        return Err(format!("a {}x{} image is too big to open (the most is {} pixels, up to {}x{})", width, height, MAX_IMAGE_BYTES / 4, u16::MAX, u16::MAX));
+    }
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut pixels).map_err(describe_error)?;
+    pixels.truncate(frame.buffer_size());
+
+    // Every format comes out as 8-bit RGBA, or 8-bit grey with alpha
+    let bytes = match frame.color_type {
+        ColorType::Rgba => pixels,
+        ColorType::GrayscaleAlpha => pixels.chunks_exact(2).flat_map(|pixel| [pixel[0], pixel[0], pixel[0], pixel[1]]).collect(),
+        colour_type => return Err(format!("{:?} images aren't supported", colour_type))
+    };
+    Ok(Image { bytes, width: width as u16, height: height as u16 })
+}
+
+// Encode an RGBA image as a PNG file
+pub fn encode_png(image: &Image) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let mut encoder = Encoder::new(&mut bytes, image.width as u32, image.height as u32);
+    encoder.set_color(ColorType::Rgba);
+    encoder.set_depth(BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|error| error.to_string())?;
+    writer.write_image_data(&image.bytes).map_err(|error| error.to_string())?;
+    writer.finish().map_err(|error| error.to_string())?;
+    Ok(bytes)
+}
+
+// Encodes an animated PNG (APNG) a frame at a time, compressing each frame as it's added so a long recording isn't kept raw
+// ... viewers without APNG support show the first frame as a still image
+pub struct ApngEncoder {
+    size: (u16, u16),
+    // How long (in milliseconds) each frame is shown for
+    delay: u16,
+    // Each frame's RGBA bytes, compressed (see `FRAME_COMPRESSION_LEVEL`)
+    frames: Vec<Vec<u8>>
+}
+
+impl ApngEncoder {
+    pub fn new(width: u16, height: u16, delay: u16) -> ApngEncoder {
+        ApngEncoder { size: (width, height), delay, frames: Vec::new() }
+    }
+
+    pub fn size(&self) -> (u16, u16) {
+        self.size
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    // Add a frame to the end of the animation, returning false (leaving it out) if it's not the animation's size
+    pub fn add_frame(&mut self, image: &Image) -> bool {
+        if (image.width, image.height) != self.size {
+            return false;
+        }
+        self.frames.push(compress_to_vec(&image.bytes, FRAME_COMPRESSION_LEVEL));
+        true
+    }
+
+    // Write the animation out as an APNG file, looping forever
+    // ... each frame is only decompressed while it's being written, so just one is ever held raw
+    pub fn finish(self) -> Result<Vec<u8>, String> {
+        let frame_size = self.size.0 as usize * self.size.1 as usize * 4;
+        let mut bytes = Vec::new();
+        let mut encoder = Encoder::new(&mut bytes, self.size.0 as u32, self.size.1 as u32);
+        encoder.set_color(ColorType::Rgba);
+        encoder.set_depth(BitDepth::Eight);
+        encoder.set_animated(self.frames.len() as u32, 0).map_err(|error| error.to_string())?;
+        encoder.set_frame_delay(self.delay, 1000).map_err(|error| error.to_string())?;
+        let mut writer = encoder.write_header().map_err(|error| error.to_string())?;
+        for frame in self.frames.iter() {
+            let frame = decompress_to_vec_with_limit(frame, frame_size).map_err(|error| error.to_string())?;
+            writer.write_image_data(&frame).map_err(|error| error.to_string())?;
+        }
+        writer.finish().map_err(|error| error.to_string())?;
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pngs_decode_through_their_filters_and_palettes() {
+        // A 3x2 two-bit palette image, it's last palette entry transparent (made with zlib's fixed codes)
+        let palette = decode_png(&[
+            0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x02,
+            0x02, 0x03, 0x00, 0x00, 0x00, 0xe0, 0x1a, 0x8e, 0x89, 0x00, 0x00, 0x00, 0x09, 0x50, 0x4c, 0x54, 0x45, 0x00, 0x00, 0x00, 0xc8, 0xb4, 0x78, 0x00,
+            0x00, 0xff, 0x18, 0x16, 0xbc, 0x33, 0x00, 0x00, 0x00, 0x03, 0x74, 0x52, 0x4e, 0x53, 0xff, 0xff, 0x00, 0xd7, 0xca, 0x0d, 0x41, 0x00, 0x00, 0x00,
+            0x0c, 0x49, 0x44, 0x41, 0x54, 0x78, 0xda, 0x63, 0x90, 0x60, 0x98, 0x00, 0x00, 0x00, 0xdc, 0x00, 0xa9, 0x52, 0x1a, 0x13, 0x8f, 0x00, 0x00, 0x00,
+            0x00, 0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82
+        ]).ok().unwrap();
+        assert!(palette.width == 3 && palette.height == 2);
+        assert_eq!(palette.bytes[..12], [0, 0, 0, 255, 200, 180, 120, 255, 0, 0, 255, 0]);
+        assert_eq!(palette.bytes[12..16], [0, 0, 255, 0]);
+
+        // A 2x3 RGB image, filtered by the Sub, Up and Paeth filters
+        let rgb = decode_png(&[
+            0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x03,
+            0x08, 0x02, 0x00, 0x00, 0x00, 0x36, 0x88, 0x49, 0xd6, 0x00, 0x00, 0x00, 0x13, 0x49, 0x44, 0x41, 0x54, 0x78, 0xda, 0x63, 0xe4, 0x12, 0x91, 0x03,
+            0x02, 0x26, 0x46, 0x30, 0x60, 0x81, 0x50, 0x00, 0x0a, 0xb6, 0x00, 0xaa, 0x3e, 0xf4, 0xe4, 0x13, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44,
+            0xae, 0x42, 0x60, 0x82
+        ]).ok().unwrap();
+        assert_eq!(rgb.bytes[16..24], [12, 22, 32, 255, 42, 52, 62, 255]);
+
+        // Our own PNGs read back, and corrupt files are refused
+        let image = Image { bytes: (0..8 * 3 * 4).map(|i| i as u8).collect(), width: 8, height: 3 };
+        let encoded = encode_png(&image).ok().unwrap();
+        assert!(decode_png(&encoded).ok().unwrap().bytes == image.bytes);
+        let mut corrupt = encoded.clone();
+        corrupt[40] ^= 1;
+        assert!(decode_png(&corrupt).err().unwrap().contains("corrupt") && decode_png(b"GIF89a").is_err());
+    }
+
+    #[test]
+    fn pngs_too_big_to_open_are_refused_before_decompressing() {
+        // A valid header claiming a 65535x65535 image (16GiB as RGBA), with hardly any data behind it
+        let mut bomb = Vec::new();
+        let mut encoder = Encoder::new(&mut bomb, u16::MAX as u32, u16::MAX as u32);
+        encoder.set_color(ColorType::Rgba);
+        let mut writer = encoder.write_header().ok().unwrap();
+        writer.write_chunk(png::chunk::IDAT, &compress_to_vec(&[0; 1024], 9)).ok().unwrap();
+        drop(writer);
+        assert!(decode_png(&bomb).err().unwrap().contains("too big"));
+    }
+
+    #[test]
+    fn flat_images_squash_down_and_animations_keep_their_frames() {
+        let image = Image { bytes: (0..320 * 180).flat_map(|i| if i % 320 < 200 { [200, 180, 120, 255] } else { [0, 0, 0, 0] }).collect(), width: 320, height: 180 };
+        let encoded = encode_png(&image).ok().unwrap();
+        assert!(encoded.len() < image.bytes.len() / 50 && decode_png(&encoded).ok().unwrap().bytes == image.bytes);
+
+        // Animations keep every frame (of the right size), and show their first frame as a still image
+        let mut animation = ApngEncoder::new(320, 180, 50);
+        let blank = Image { bytes: vec![0; 320 * 180 * 4], width: 320, height: 180 };
+        assert!(animation.add_frame(&image) && animation.add_frame(&blank) && !animation.add_frame(&Image::empty()));
+        let apng = animation.finish().ok().unwrap();
+        assert!(apng.windows(4).filter(|kind| *kind == b"fcTL").count() == 2 && decode_png(&apng).ok().unwrap().bytes == image.bytes);
+        assert!(ApngEncoder::new(320, 180, 50).finish().is_err());
+    }
+}
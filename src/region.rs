@@ -0,0 +1,206 @@
+use crate::{input::*, particle::*, snapshot::*, world::*};
+
+// The start of every region code, marking it (and it's format version) as one (see `Region::encode`)
+static REGION_CODE_PREFIX: &str = "rsr1:";
+
+// The 64 characters of the URL-safe base64 alphabet, which region codes are written in (so they survive being pasted anywhere)
+static BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+// The widest (and tallest) a region can be, so it's size fits within it's code
+static MAX_REGION_SIZE: usize = u16::MAX as usize;
+
+// A rectangle of the world's particles, copied out to be pasted back in elsewhere: it's size, and the variant in each cell (if
+// ... it's not empty), column by column like the world itself
+// ... like a world file, only each cell's material is kept
+#[derive(Clone, PartialEq)]
+pub struct Region {
+    size: (usize, usize),
+    cells: Vec<Option<ParticleVariant>>
+}
+
+impl Region {
+    // Copy the cells within the rectangle between two corners (in any order, both included), cropped to the world (and to
+    // ... `MAX_REGION_SIZE`)
+    // ... rigid bodies are left behind, as their cells only mean anything alongside the body itself
+    pub fn copy(world: &World, from: (usize, usize), to: (usize, usize)) -> Region {
+        if world.width() == 0 || world.height() == 0 {
+            return Region { size: (0, 0), cells: Vec::new() };
+        }
+        let x0 = from.0.min(to.0).min(world.width() - 1);
+        let y0 = from.1.min(to.1).min(world.height() - 1);
+        let x1 = from.0.max(to.0).min(world.width() - 1).min(x0 + MAX_REGION_SIZE - 1);
+        let y1 = from.1.max(to.1).min(world.height() - 1).min(y0 + MAX_REGION_SIZE - 1);
+        let cells = (x0..=x1).flat_map(|x| (y0..=y1).map(move |y| (x, y))).map(|(x, y)| {
+            let particle = world.particle(x, y);
+            Some(particle.variant).filter(|variant| particle.active && *variant != ParticleVariant::Body)
+        }).collect();
+        Region { size: (x1 - x0 + 1, y1 - y0 + 1), cells }
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        self.size
+    }
+
+    // Return the variant in the cell at (x, y), if it's within the region and isn't empty
+    pub fn get(&self, x: usize, y: usize) -> Option<ParticleVariant> {
+        if x >= self.size.0 || y >= self.size.1 {
+            return None;
+        }
+        self.cells[x * self.size.1 + y]
+    }
+
+    // Return the region laid out afresh at the given size, taking each of it's cells from the given cell of this region
+    fn remap(&self, size: (usize, usize), source: impl Fn(usize, usize) -> (usize, usize)) -> Region {
+        let cells = (0..size.0).flat_map(|x| (0..size.1).map(move |y| (x, y))).map(|(x, y)| {
+            let (sx, sy) = source(x, y);
+            self.get(sx, sy)
+        }).collect();
+        Region { size, cells }
+    }
+
+    // Return the region turned a quarter turn clockwise
+    pub fn rotate(&self) -> Region {
+        let (width, height) = self.size;
+        self.remap((height, width), |x, y| (y, height - 1 - x))
+    }
+
+    // Return the region mirrored left to right
+    pub fn flip_horizontal(&self) -> Region {
+        self.remap(self.size, |x, y| (self.size.0 - 1 - x, y))
+    }
+
+    // Return the region mirrored top to bottom
+    pub fn flip_vertical(&self) -> Region {
+        self.remap(self.size, |x, y| (x, self.size.1 - 1 - y))
+    }
+
+    // Paste the region's particles into the world with it's top-left corner at `position`, like `paint_cell` (so only into empty
+    // ... cells, and within the particle budget), returning how many were pasted
+    pub fn paste(&self, world: &mut World, budget: &mut Budget, position: (isize, isize)) -> usize {
+        (0..self.size.0).flat_map(|x| (0..self.size.1).map(move |y| (x, y)))
+            .filter_map(|(x, y)| self.get(x, y).map(|variant| ((position.0 + x as isize, position.1 + y as isize), variant)))
+            .filter(|&(cell, variant)| paint_cell(world, budget, cell, variant, None))
+            .count()
+    }
+
+    // Write the region out as a region code: a line of text to share it through the system clipboard (or a replay file)
+    // ... it's the prefix, then in base64: the width and height (u16s), then the cells column by column as runs of a length (u8)
+    // ... and a cell (a `Snapshot` byte), all little-endian
+    pub fn encode(&self) -> String {
+        let mut bytes = Vec::new();
+        bytes.extend((self.size.0 as u16).to_le_bytes());
+        bytes.extend((self.size.1 as u16).to_le_bytes());
+        bytes.extend(encode_runs(self.cells.iter().map(|cell| cell.map_or(0, |variant| variant.index() as u8 + 1))));
+        format!("{}{}", REGION_CODE_PREFIX, encode_base64(&bytes))
+    }
+
+    // Read a region code (ignoring any whitespace around it), see `Region::encode`
+    pub fn decode(code: &str) -> Result<Region, String> {
+        let Some(body) = code.trim().strip_prefix(REGION_CODE_PREFIX) else {
+            return Err(format!("not a region code (expected it to start with '{}')", REGION_CODE_PREFIX));
+        };
+        let bytes = decode_base64(body).ok_or("the region code isn't valid base64")?;
+        if bytes.len() < 4 {
+            return Err("the region code ends too early".to_owned());
+        }
+        let size = (u16::from_le_bytes([bytes[0], bytes[1]]) as usize, u16::from_le_bytes([bytes[2], bytes[3]]) as usize);
+        let cells = decode_runs(&bytes[4..], size.0 * size.1)?.into_iter().map(|cell| match cell {
+            0 => Ok(None),
+            byte => decode(byte).map(Some).ok_or_else(|| format!("unknown material {}", byte))
+        }).collect::<Result<_, String>>()?;
+        Ok(Region { size, cells })
+    }
+}
+
+// Write cells (`Snapshot` bytes) out as runs of a length (u8) and a cell, see `Region::encode`
+pub(crate) fn encode_runs(cells: impl Iterator<Item = u8>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut cells = cells.peekable();
+    while let Some(cell) = cells.next() {
+        let mut length: u8 = 1;
+        while length < u8::MAX && cells.next_if_eq(&cell).is_some() {
+            length += 1;
+        }
+        bytes.extend([length, cell]);
+    }
+    bytes
+}
+
+// Read runs (see `encode_runs`) back into exactly `count` cells
+pub(crate) fn decode_runs(bytes: &[u8], count: usize) -> Result<Vec<u8>, String> {
+    let mut cells = Vec::new();
+    for run in bytes.chunks(2) {
+        let &[length, cell] = run else { return Err("the cells end mid-run".to_owned()) };
+        // Check the run fits before laying it out, so corrupt runs can't ask for more memory than the cells need
+        if length == 0 || cells.len() + length as usize > count {
+            return Err("a run doesn't fit within the cells".to_owned());
+        }
+        cells.extend(std::iter::repeat_n(cell, length as usize));
+    }
+    if cells.len() != count {
+        return Err(format!("there are {} cells, but should be {}", cells.len(), count));
+    }
+    Ok(cells)
+}
+
+// Encode bytes as unpadded base64, see `BASE64_ALPHABET`
+pub(crate) fn encode_base64(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &byte)| bits | ((byte as u32) << (16 - 8 * i)));
+        for i in 0..=chunk.len() {
+            text.push(BASE64_ALPHABET[((bits >> (18 - 6 * i)) & 63) as usize] as char);
+        }
+    }
+    text
+}
+
+// Decode unpadded base64 (see `encode_base64`), or nothing if it's malformed
+pub(crate) fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    for chunk in text.as_bytes().chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+        let mut bits = 0u32;
+        for (i, character) in chunk.iter().enumerate() {
+            let value = BASE64_ALPHABET.iter().position(|c| c == character)? as u32;
+            bits |= value << (18 - 6 * i);
+        }
+        bytes.extend((0..chunk.len() - 1).map(|i| (bits >> (16 - 8 * i)) as u8));
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regions_turn_flip_and_survive_their_codes() {
+        let mut particles = Particles::new(4, (0..16).map(|i| Particle::new(i, ParticleVariant::Sand, false)));
+        let mut world = World::new(&mut particles);
+        world.spawn(1, 1, ParticleVariant::Stone);
+        world.spawn(2, 1, ParticleVariant::Water);
+        world.spawn(1, 2, ParticleVariant::Sand);
+
+        // The 2x2 region around them, however the corners are picked (or however far past the world they go)
+        let region = Region::copy(&world, (2, 2), (1, 1));
+        assert!(region.size() == (2, 2) && Region::copy(&world, (2, 1), (9, 9)).size() == (2, 3));
+        assert!(region.get(0, 0) == Some(ParticleVariant::Stone) && region.get(1, 0) == Some(ParticleVariant::Water) && region.get(1, 1).is_none());
+
+        let turned = region.rotate();
+        assert!(turned.get(0, 0) == Some(ParticleVariant::Sand) && turned.get(1, 0) == Some(ParticleVariant::Stone) && turned.get(1, 1) == Some(ParticleVariant::Water));
+        assert!(turned.rotate().rotate().rotate() == region && region.flip_horizontal().get(0, 0) == Some(ParticleVariant::Water));
+        assert!(region.flip_vertical().flip_vertical() == region && region.flip_vertical().get(0, 0) == Some(ParticleVariant::Sand));
+
+        // Pasted into a fresh world (only the cells that fit), and shared as a code
+        let mut particles = Particles::new(4, (0..16).map(|i| Particle::new(i, ParticleVariant::Sand, false)));
+        let mut pasted = World::new(&mut particles);
+        let mut budget = Budget::new(&pasted, BudgetOverflow::Refuse);
+        assert_eq!(region.paste(&mut pasted, &mut budget, (3, 0)), 2);
+        assert!(pasted.particle(3, 0).variant == ParticleVariant::Stone && pasted.particle(3, 1).variant == ParticleVariant::Sand);
+        assert!(Region::decode(&format!(" {}\n", region.encode())).ok() == Some(region.clone()) && Region::decode("rsr1:AAA").is_err());
+        assert!(decode_base64(&encode_base64(b"sandbox")).is_some_and(|bytes| bytes == b"sandbox"));
+    }
+}
@@ -0,0 +1,121 @@
+use macroquad::prelude::*;
+
+use crate::{particle::*, world::*};
+
+// How bright (0.0 - 1.0) particles far away from any light source are, while lighting is enabled
+static AMBIENT_LIGHT: f32 = 0.15;
+
+// How many cells a light source's light fades out over, at the far end of it's reach
+static LIGHT_FULL_REACH: f32 = 8.0;
+
+// Everything which decides the colour of a cell, so that neighbouring cells with equal keys (under equal light) share a colour
+#[derive(PartialEq)]
+enum ColourKey {
+    Empty,
+    // A particle's variant, and whether it's carrying a template (ie: murky Water), powered, or wet
+    Particle(ParticleVariant, bool, bool, bool),
+    // Flickering (burning) particles never share their colour, not even with each other
+    Unique
+}
+
+// Return the colour of a variant's particles (powered or not), before any burning, murkiness or wetness is shown
+pub(crate) fn get_variant_colour(variant: ParticleVariant, is_powered: bool) -> Color {
+    let element = variant.element();
+    match element.powered_colour {
+        Some(colour) if is_powered => colour,
+        _ => element.colour
+    }
+}
+
+impl ParticleRef<'_> {
+    // Return a colour for this particle, based on it's properties
+    // Note: `Color::new(r, g, b, a)` takes floats from 0.0 to 1.0, use `Color::from_rgba(r, g, b, a)` for 0-255 values!
+    fn get_colour(&self) -> Color {
+        // Burning particles flicker with flames
+        // Note: this is purely visual, so it uses macroquad's RNG rather than the simulation's
+        if self.burning && rand::gen_range(0, 3) == 0 {
+            return if rand::gen_range(0, 2) == 0 { ORANGE } else { YELLOW };
+        }
+        // Water carrying sediment is murky
+        if self.variant == ParticleVariant::Water && self.template.is_some() {
+            return Color::from_rgba(60, 85, 150, 255);
+        }
+        let colour = get_variant_colour(self.variant, self.powered);
+        // Wet particles are darker
+        if self.wetness > 0 {
+            Color::new(colour.r * 0.7, colour.g * 0.7, colour.b * 0.7, colour.a)
+        } else {
+            colour
+        }
+    }
+
+    // Return the key deciding this particle's colour, see `ColourKey`
+    fn get_colour_key(&self) -> ColourKey {
+        if !self.active {
+            ColourKey::Empty
+        } else if self.burning {
+            ColourKey::Unique
+        } else {
+            ColourKey::Particle(self.variant, self.template.is_some(), self.powered, self.wetness > 0)
+        }
+    }
+}
+
+// Paint every particle in the world (shaded by the light around it, if lit) into the pixels of the world's image
+// ... each horizontal run of cells sharing a colour key (and light) only has it's colour worked out once, then is filled in one go,
+// ... which spares most of the per-pixel work across big piles, pools, and empty sky
+pub fn paint_world(world: &World, light: Option<&Grid<f32>>, pixels: &mut [[u8; 4]]) {
+    let width = world.width();
+    if width == 0 {
+        return;
+    }
+    let brightness_at = |x: usize, y: usize| {
+        light.map_or(1.0, |light| AMBIENT_LIGHT + (1.0 - AMBIENT_LIGHT) * (light[(x, y)] / LIGHT_FULL_REACH).clamp(0.0, 1.0))
+    };
+
+    for (y, row) in pixels.chunks_exact_mut(width).enumerate().take(world.height()) {
+        let mut x = 0;
+        while x < width {
+            let particle = world.particle(x, y);
+            let (key, brightness) = (particle.get_colour_key(), brightness_at(x, y));
+
+            // Find the end of the run (empty cells are blank under any light)
+            let mut end = x + 1;
+            if key != ColourKey::Unique {
+                while end < width && world.particle(end, y).get_colour_key() == key && (key == ColourKey::Empty || brightness_at(end, y) == brightness) {
+                    end += 1;
+                }
+            }
+
+            let colour = if particle.active {
+                let colour = particle.get_colour();
+                Color::new(colour.r * brightness, colour.g * brightness, colour.b * brightness, colour.a)
+            } else {
+                BLANK
+            };
+            row[x..end].fill(colour.into());
+            x = end;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::WET_FRAMES;
+
+    #[test]
+    fn painting_fills_runs_of_the_same_colour() {
+        let variants = [ParticleVariant::Sand, ParticleVariant::Sand, ParticleVariant::Sand, ParticleVariant::Sand, ParticleVariant::Water];
+        let mut particles = Particles::new(1, variants.iter().enumerate().map(|(i, &variant)| Particle::new(i as u32, variant, i != 3)));
+        let mut world = World::new(&mut particles);
+        world.data[(2, 0)].wetness = WET_FRAMES;
+
+        // Wet Sand breaks up the run of Sand, and empty cells are left blank
+        let mut pixels = vec![[0; 4]; 5];
+        paint_world(&world, None, &mut pixels);
+        let (sand, blank, water): ([u8; 4], [u8; 4], [u8; 4]) = (BEIGE.into(), BLANK.into(), BLUE.into());
+        assert!(pixels[0] == sand && pixels[1] == sand && pixels[2] != sand);
+        assert!(pixels[3] == blank && pixels[4] == water);
+    }
+}
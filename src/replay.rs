@@ -0,0 +1,482 @@
+use crate::{brush::*, edit::*, input::*, particle::*, plugin::*, region::*, save::*, sim::*, world::*};
+use crate::{material::fingerprint_materials, script::Scripts};
+use crate::Simulation;
+
+// The first line of every replay file, marking it (and it's format version) as one
+// ... version 2 brought in brush shapes, whose footprints replays from version 1 can't be re-simulated without, and version 3
+// ... spray densities (see `BrushOptions`), and version 4 the fingerprint of the rules it was recorded by (see `fingerprint_rules`)
+static REPLAY_FILE_HEADER: &str = "rusty-sandbox replay 4";
+
+// The line of a replay file ending it's inputs, after which the world it started from is written out as a world file
+static REPLAY_WORLD_MARKER: &str = "world";
+
+// The furthest (in cells, along either axis) a position in another player's input can be from the world's corner, leaving room
+// ... for a brush or region to reach past it without overflowing (even on the web, where positions are 32-bit)
+static MAX_REMOTE_POSITION: usize = 1 << 30;
+
+// Something the user did to the simulation between two frames, recorded so a replay can do it again exactly as it was done
+#[derive(Clone, PartialEq)]
+pub enum Input {
+    // The simulation's settings from this frame on, recorded whenever any of them changes (see `Replay::record_state`)
+    State { gravity: Gravity, global_wind: f32, offscreen: OffscreenMode, quality: Quality, viewport: Option<((usize, usize), (usize, usize))> },
+    // A dab of the built-in brush (see `paint_brush`), or of the nth plugin brush (see `Plugins::brushes`)
+    Paint { position: (isize, isize), options: BrushOptions, variant: ParticleVariant, template: Option<ParticleVariant>, brush: Option<usize>, overflow: BudgetOverflow },
+    // A line painted from one cell to another (past the cell it starts from) within a stroke, see `paint_path`
+    Path { from: (isize, isize), to: (isize, isize), variant: ParticleVariant, overflow: BudgetOverflow },
+    // A straight line (see `line_cells`) painted from one cell to another at a spray density, as an edit of it's own
+    Line { from: (isize, isize), to: (isize, isize), density: u8, variant: ParticleVariant, overflow: BudgetOverflow },
+    // A shape (filled, or only it's outline, see `shape_cells`) painted between two corners at a spray density, as an edit of it's own
+    Shape { shape: Shape, from: (isize, isize), to: (isize, isize), is_filled: bool, density: u8, variant: ParticleVariant, overflow: BudgetOverflow },
+    // The region connected to a cell flood-filled with a material at a spray density, as an edit of it's own (see `fill_region`)
+    Fill { position: (isize, isize), density: u8, variant: ParticleVariant, overflow: BudgetOverflow },
+    // A region pasted with it's top-left corner at the given position, as an edit of it's own (see `Region::paste`)
+    Paste { region: Region, position: (isize, isize), overflow: BudgetOverflow },
+    // Finishing the current edit, or undoing or redoing the last one (see `History`)
+    Finish,
+    Undo,
+    Redo,
+    // A rigid body dropped with it's top-left corner at the given position, if there's room for it
+    Drop { shape: BodyShape, position: (f32, f32) }
+}
+
+impl Input {
+    // Return the simulation's settings as an input, see `Input::State`
+    pub fn state(state: &SimulationState) -> Input {
+        Input::State { gravity: state.gravity.clone(), global_wind: state.global_wind, offscreen: state.offscreen, quality: state.quality, viewport: state.viewport }
+    }
+
+    // Do what the input does to the simulation, returning true if any painting was refused for exceeding the particle budget
+    // ... the windowed sandbox makes it's own edits through this too, so a replay can't drift from what was really done
+    pub fn apply(&self, world: &mut World, state: &mut SimulationState, history: &mut History, plugins: &mut Plugins) -> bool {
+        match self {
+            Input::State { gravity, global_wind, offscreen, quality, viewport } => {
+                state.gravity = gravity.clone();
+                state.global_wind = *global_wind;
+                state.offscreen = *offscreen;
+                state.quality = *quality;
+                state.viewport = *viewport;
+            },
+            Input::Paint { position, options, variant, template, brush, overflow } => {
+                let mut budget = Budget::new(world, *overflow);
+                let brush = brush.and_then(|index| plugins.brushes().get(index).copied());
+                history.edit(world, |world| match brush {
+                    Some(brush) => plugins.paint(brush, world, &mut budget, *position, options.radius, *variant),
+                    None => paint_brush(world, &mut budget, *position, *options, *variant, *template, state.frame())
+                });
+                return budget.is_exceeded;
+            },
+            Input::Path { from, to, variant, overflow } => {
+                let mut budget = Budget::new(world, *overflow);
+                let (from, to) = (clamp_to_world(world, *from), clamp_to_world(world, *to));
+                history.edit(world, |world| paint_path(world, &mut budget, from, to, *variant));
+                return budget.is_exceeded;
+            },
+            Input::Line { from, to, density, variant, overflow } => {
+                let (mut budget, mut spray) = (Budget::new(world, *overflow), Spray::new(*density, state.frame(), *from));
+                let cells = line_cells(clamp_to_world(world, *from), clamp_to_world(world, *to));
+                history.finish();
+                history.edit(world, |world| paint_cells(world, &mut budget, cells, *variant, None, &mut spray));
+                history.finish();
+                return budget.is_exceeded;
+            },
+            Input::Shape { shape, from, to, is_filled, density, variant, overflow } => {
+                let (mut budget, mut spray) = (Budget::new(world, *overflow), Spray::new(*density, state.frame(), *from));
+                let cells = shape_cells(*shape, clamp_to_world(world, *from), clamp_to_world(world, *to), *is_filled);
+                history.finish();
+                history.edit(world, |world| paint_cells(world, &mut budget, cells, *variant, None, &mut spray));
+                history.finish();
+                return budget.is_exceeded;
+            },
+            Input::Fill { position, density, variant, overflow } => {
+                let (mut budget, mut spray) = (Budget::new(world, *overflow), Spray::new(*density, state.frame(), *position));
+                history.finish();
+                history.edit(world, |world| fill_region(world, &mut budget, *position, *variant, &mut spray));
+                history.finish();
+                return budget.is_exceeded;
+            },
+            Input::Paste { region, position, overflow } => {
+                let mut budget = Budget::new(world, *overflow);
+                history.finish();
+                history.edit(world, |world| region.paste(world, &mut budget, *position));
+                history.finish();
+                return budget.is_exceeded;
+            },
+            Input::Finish => history.finish(),
+            Input::Undo => { history.undo(world); },
+            Input::Redo => { history.redo(world); },
+            Input::Drop { shape, position } => {
+                let body = RigidBody::new(*shape, position.0, position.1);
+                if !is_footprint_blocked(world, &body.footprint(body.position)) {
+                    state.bodies.push(body);
+                }
+            }
+        }
+        false
+    }
+}
+
+impl std::fmt::Display for Input {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_owned());
+        match self {
+            Input::State { gravity, global_wind, offscreen, quality, viewport } => {
+                let viewport = viewport.map_or("none".to_owned(), |((x0, y0), (x1, y1))| format!("{} {} {} {}", x0, y0, x1, y1));
+                write!(f, "state {} {} {} {} {}", name(gravity), global_wind, name(offscreen), name(quality), viewport)
+            },
+            Input::Paint { position, options, variant, template, brush, overflow } => write!(f, "paint {} {} {} {} {} {} {} {} {}",
+                position.0, position.1, name(options.shape), options.radius, options.density, name(variant), or_dash(template.map(name)), or_dash(brush.map(|brush| brush.to_string())), name(overflow)),
+            Input::Path { from, to, variant, overflow } => write!(f, "path {} {} {} {} {} {}", from.0, from.1, to.0, to.1, name(variant), name(overflow)),
+            Input::Line { from, to, density, variant, overflow } => write!(f, "line {} {} {} {} {} {} {}", from.0, from.1, to.0, to.1, density, name(variant), name(overflow)),
+            Input::Shape { shape, from, to, is_filled, density, variant, overflow } => write!(f, "shape {} {} {} {} {} {} {} {} {}",
+                name(shape), if *is_filled { "filled" } else { "outline" }, from.0, from.1, to.0, to.1, density, name(variant), name(overflow)),
+            Input::Fill { position, density, variant, overflow } => write!(f, "fill {} {} {} {} {}", position.0, position.1, density, name(variant), name(overflow)),
+            Input::Paste { region, position, overflow } => write!(f, "paste {} {} {} {}", position.0, position.1, name(overflow), region.encode()),
+            Input::Finish => write!(f, "finish"),
+            Input::Undo => write!(f, "undo"),
+            Input::Redo => write!(f, "redo"),
+            Input::Drop { shape: BodyShape::Box(width, height), position } => write!(f, "drop box {} {} {} {}", width, height, position.0, position.1),
+            Input::Drop { shape: BodyShape::Ball(radius), position } => write!(f, "drop ball {} {} {}", radius, position.0, position.1)
+        }
+    }
+}
+
+// Return the cell within the world nearest to (x, y), so a line or shape reaching far beyond the world (ie: another player's)
+// ... only steps through about as many cells as the world has
+fn clamp_to_world(world: &World, (x, y): (isize, isize)) -> (isize, isize) {
+    (x.min(world.width() as isize - 1).max(0), y.min(world.height() as isize - 1).max(0))
+}
+
+// Return the name a value is written to a replay file with: it's display name, without any spaces (ie: `BurningFuse`)
+fn name(value: impl std::fmt::Display) -> String {
+    value.to_string().replace(' ', "")
+}
+
+// Read a replay file's (or a network message's) input, without it's frame number, see `Input`'s Display
+pub(crate) fn parse_input(text: &str) -> Result<Input, String> {
+    let mut tokens = text.split_whitespace();
+    let mut next = |what: &str| tokens.next().ok_or_else(|| format!("expected {}", what));
+    fn number<T: std::str::FromStr>(token: &str) -> Result<T, String> {
+        token.parse().map_err(|_| format!("'{}' isn't a valid number here", token))
+    }
+    fn density(token: &str) -> Result<u8, String> {
+        number(token).ok().filter(|density| (1..=100).contains(density)).ok_or_else(|| format!("'{}' isn't a spray density (from 1 to 100)", token))
+    }
+    fn find<T: std::fmt::Display>(values: impl IntoIterator<Item = T>, token: &str) -> Result<T, String> {
+        values.into_iter().find(|value| name(value).eq_ignore_ascii_case(token)).ok_or_else(|| format!("unknown name '{}'", token))
+    }
+    let overflows = [BudgetOverflow::Refuse, BudgetOverflow::CullGases];
+    let input = match next("an input")? {
+        "state" => Input::State {
+            gravity: find([Gravity::Down, Gravity::Left, Gravity::Up, Gravity::Right, Gravity::Off], next("a gravity")?)?,
+            global_wind: number(next("a wind strength")?)?,
+            offscreen: find([OffscreenMode::Full, OffscreenMode::Throttled, OffscreenMode::Frozen, OffscreenMode::Stored], next("an off-screen mode")?)?,
+            quality: find([Quality::Full, Quality::Reduced, Quality::Low, Quality::Minimal], next("a quality")?)?,
+            viewport: match next("a viewport")? {
+                "none" => None,
+                x0 => Some(((number(x0)?, number(next("a viewport")?)?), (number(next("a viewport")?)?, number(next("a viewport")?)?)))
+            }
+        },
+        "paint" => Input::Paint {
+            position: (number(next("a position")?)?, number(next("a position")?)?),
+            options: BrushOptions {
+                shape: find(BRUSH_SHAPES, next("a brush shape")?)?,
+                radius: number(next("a radius")?)?,
+                density: density(next("a spray density")?)?
+            },
+            variant: find(variants(), next("a material")?)?,
+            template: match next("a template")? { "-" => None, template => Some(find(variants(), template)?) },
+            brush: match next("a brush")? { "-" => None, brush => Some(number(brush)?) },
+            overflow: find(overflows, next("a budget overflow")?)?
+        },
+        "path" => Input::Path {
+            from: (number(next("a position")?)?, number(next("a position")?)?),
+            to: (number(next("a position")?)?, number(next("a position")?)?),
+            variant: find(variants(), next("a material")?)?,
+            overflow: find(overflows, next("a budget overflow")?)?
+        },
+        "line" => Input::Line {
+            from: (number(next("a position")?)?, number(next("a position")?)?),
+            to: (number(next("a position")?)?, number(next("a position")?)?),
+            density: density(next("a spray density")?)?,
+            variant: find(variants(), next("a material")?)?,
+            overflow: find(overflows, next("a budget overflow")?)?
+        },
+        "shape" => Input::Shape {
+            shape: find([Shape::Rectangle, Shape::Ellipse], next("a shape")?)?,
+            is_filled: match next("a fill")? {
+                "filled" => true,
+                "outline" => false,
+                fill => return Err(format!("unknown fill '{}'", fill))
+            },
+            from: (number(next("a position")?)?, number(next("a position")?)?),
+            to: (number(next("a position")?)?, number(next("a position")?)?),
+            density: density(next("a spray density")?)?,
+            variant: find(variants(), next("a material")?)?,
+            overflow: find(overflows, next("a budget overflow")?)?
+        },
+        "fill" => Input::Fill {
+            position: (number(next("a position")?)?, number(next("a position")?)?),
+            density: density(next("a spray density")?)?,
+            variant: find(variants(), next("a material")?)?,
+            overflow: find(overflows, next("a budget overflow")?)?
+        },
+        "paste" => Input::Paste {
+            position: (number(next("a position")?)?, number(next("a position")?)?),
+            overflow: find(overflows, next("a budget overflow")?)?,
+            region: Region::decode(next("a region code")?)?
+        },
+        "finish" => Input::Finish,
+        "undo" => Input::Undo,
+        "redo" => Input::Redo,
+        "drop" => {
+            let shape = match next("a shape")? {
+                "box" => BodyShape::Box(number(next("a width")?)?, number(next("a height")?)?),
+                "ball" => BodyShape::Ball(number(next("a radius")?)?),
+                shape => return Err(format!("unknown shape '{}'", shape))
+            };
+            Input::Drop { shape, position: (number(next("a position")?)?, number(next("a position")?)?) }
+        },
+        input => return Err(format!("unknown input '{}'", input))
+    };
+    match tokens.next() {
+        Some(token) => Err(format!("unexpected '{}' after the input", token)),
+        None => Ok(input)
+    }
+}
+
+// Read an input made elsewhere (see `parse_input`), within a replay file or sent over the network, refusing any the sandbox's own
+// ... UI couldn't have made: brushes or bodies bigger than the UI's, or positions impossibly far from the world
+pub(crate) fn parse_bounded_input(text: &str) -> Result<Input, String> {
+    let input = parse_input(text)?;
+    let is_near = |(x, y): (isize, isize)| x.unsigned_abs() <= MAX_REMOTE_POSITION && y.unsigned_abs() <= MAX_REMOTE_POSITION;
+    let is_allowed = match &input {
+        Input::State { .. } => true,
+        Input::Paint { position, options, .. } => is_near(*position) && options.radius <= MAX_BRUSH_RADIUS,
+        Input::Path { from, to, .. } | Input::Line { from, to, .. } | Input::Shape { from, to, .. } => is_near(*from) && is_near(*to),
+        Input::Fill { position, .. } | Input::Paste { position, .. } => is_near(*position),
+        Input::Finish | Input::Undo | Input::Redo => true,
+        Input::Drop { shape, position } => {
+            let is_small = match *shape {
+                BodyShape::Box(width, height) => width <= BODY_BOX_SIZE && height <= BODY_BOX_SIZE,
+                BodyShape::Ball(radius) => radius <= BODY_BALL_RADIUS
+            };
+            // ... NaN (or infinite) positions are never near
+            is_small && position.0.abs() <= MAX_REMOTE_POSITION as f32 && position.1.abs() <= MAX_REMOTE_POSITION as f32
+        }
+    };
+    if is_allowed {
+        Ok(input)
+    } else {
+        Err(format!("'{}' is bigger (or further away) than the sandbox allows", text.chars().take(40).collect::<String>()))
+    }
+}
+
+// Read an input another player sent over the network (see `parse_bounded_input`), also refusing changes to the simulation's
+// ... settings (which only the host or server makes)
+pub(crate) fn parse_remote_input(text: &str) -> Result<Input, String> {
+    match parse_bounded_input(text)? {
+        Input::State { .. } => Err("only the host changes the simulation's settings".to_owned()),
+        input => Ok(input)
+    }
+}
+
+// Return a hash of the rules the simulation runs by beyond the built-in ones: the installed materials, and the loaded scripts
+// ... a replay (or network session) only re-simulates the same run alongside the same rules, so this is checked before one starts
+// Note: plugins' behaviours aren't covered (beyond whether an added material has one), as only their code decides what they do
+pub fn fingerprint_rules(scripts: &Scripts) -> u64 {
+    (fingerprint_materials() ^ scripts.fingerprint()).wrapping_mul(0x100000001b3)
+}
+
+// A recording of a run of the simulation: the seed it's RNG started from, the world it started with, and every input made to it
+// ... along the way, which (alongside the same materials, scripts and plugins) re-simulates the run exactly, frame for frame
+pub struct Replay {
+    pub seed: u64,
+    // The world the run started with, as a world file (see `save_world`)
+    pub world: String,
+    // Every input, alongside the frame it was made before (ie: zero for those made before the first frame was simulated)
+    pub inputs: Vec<(u64, Input)>,
+    // How many frames were simulated by the time the recording ended
+    pub frames: u64,
+    // The fingerprint of the materials and scripts the run was simulated by (see `fingerprint_rules`), as of when the recording ended
+    pub rules: u64,
+    // The settings last recorded, so they're only recorded again once they change
+    last_state: Option<Input>
+}
+
+// A problem with a replay file, alongside the (1-based) line it was found on
+pub struct ReplayError {
+    pub line: usize,
+    pub message: String
+}
+
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Replay {
+    // Start recording a run from the world as it is now, with the simulation's RNG seeded with `seed`
+    // Note: only each cell's material is kept (like a world file), so recordings should start before anything is simulated
+    pub fn new(seed: u64, world: &World) -> Replay {
+        Replay { seed, world: save_world(world), inputs: Vec::new(), frames: 0, rules: 0, last_state: None }
+    }
+
+    // Record an input, made before the given frame
+    pub fn record(&mut self, frame: u64, input: Input) {
+        self.inputs.push((frame, input));
+    }
+
+    // Record the simulation's settings before the frame about to be simulated, if they've changed since they were last recorded
+    pub fn record_state(&mut self, state: &SimulationState) {
+        let input = Input::state(state);
+        if self.last_state.as_ref() != Some(&input) {
+            self.record(state.frame(), input.clone());
+            self.last_state = Some(input);
+        }
+    }
+
+    // Create the simulation the run started from, to play the replay back with (see `Playback`)
+    pub fn start(&self) -> Result<Simulation, WorldError> {
+        Simulation::load_with_seed(&self.world, self.seed)
+    }
+}
+
+// Write a replay out as a replay file: a header, the seed, the number of frames, the fingerprint of it's rules (in hex), a line per
+// ... input (starting with the frame it was made before, ie: `120 paint 64 32 3 Sand - - CullGases`), then the world it started with
+pub fn save_replay(replay: &Replay) -> String {
+    let inputs: Vec<String> = replay.inputs.iter().map(|(frame, input)| format!("{} {}\n", frame, input)).collect();
+    format!("{}\nseed {}\nframes {}\nrules {:016x}\n{}{}\n{}", REPLAY_FILE_HEADER, replay.seed, replay.frames, replay.rules, inputs.concat(), REPLAY_WORLD_MARKER, replay.world)
+}
+
+// Read a replay file, see `save_replay`
+// ... it's inputs are checked like another player's (see `parse_bounded_input`), so a tampered file can't hang playing it back
+pub fn parse_replay(text: &str) -> Result<Replay, ReplayError> {
+    let mut lines = text.lines().enumerate().map(|(index, line)| (index + 1, line.trim()));
+    if lines.next().map(|(_, line)| line) != Some(REPLAY_FILE_HEADER) {
+        return Err(ReplayError { line: 1, message: format!("not a replay file (expected '{}')", REPLAY_FILE_HEADER) });
+    }
+    let mut header = |line: usize, key: &str, radix: u32| lines.next()
+        .and_then(|(_, text)| u64::from_str_radix(text.strip_prefix(key)?.trim(), radix).ok())
+        .ok_or_else(|| ReplayError { line, message: format!("expected the replay's {} (ie: '{} 42')", key, key) });
+    let seed = header(2, "seed", 10)?;
+    let frames = header(3, "frames", 10)?;
+    let rules = header(4, "rules", 16)?;
+
+    let mut replay = Replay { seed, world: String::new(), inputs: Vec::new(), frames, rules, last_state: None };
+    let mut world_line = None;
+    for (line, text) in lines.by_ref() {
+        if text == REPLAY_WORLD_MARKER {
+            world_line = Some(line);
+            break;
+        }
+        let (frame, input) = text.split_once(' ').unwrap_or((text, ""));
+        let frame: u64 = frame.parse().map_err(|_| ReplayError { line, message: format!("'{}' isn't a valid frame number", frame) })?;
+        if frame > frames || replay.inputs.last().is_some_and(|(last, _)| frame < *last) {
+            return Err(ReplayError { line, message: format!("frame {} is out of order, or past the end of the replay", frame) });
+        }
+        let input = parse_bounded_input(input).map_err(|message| ReplayError { line, message })?;
+        replay.inputs.push((frame, input));
+    }
+    let Some(world_line) = world_line else {
+        return Err(ReplayError { line: text.lines().count(), message: format!("expected the '{}' the replay started with", REPLAY_WORLD_MARKER) });
+    };
+
+    // Check the world is readable now, rather than once it's played back
+    replay.world = lines.map(|(_, text)| format!("{}\n", text)).collect();
+    parse_world(&replay.world).map_err(|error| ReplayError { line: world_line + error.line, message: error.message })?;
+    Ok(replay)
+}
+
+// A replay being played back, and how far through it's inputs it's got
+pub struct Playback {
+    pub replay: Replay,
+    next: usize
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Playback {
+        Playback { replay, next: 0 }
+    }
+
+    // Apply every input made before the frame about to be simulated (see `SimulationState::frame`), in the order they were made
+    pub fn apply_due(&mut self, world: &mut World, state: &mut SimulationState, history: &mut History, plugins: &mut Plugins) {
+        while let Some((_, input)) = self.replay.inputs.get(self.next).filter(|(frame, _)| *frame <= state.frame()) {
+            input.apply(world, state, history, plugins);
+            self.next += 1;
+        }
+    }
+
+    // Return true once every frame of the replay has been simulated
+    pub fn is_finished(&self, state: &SimulationState) -> bool {
+        state.frame() >= self.replay.frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replays_re_simulate_their_run_exactly() {
+        let mut plugins = Plugins::default();
+        let mut live = Simulation::with_seed(24, 24, 9);
+        let (world, ..) = live.parts();
+        let mut replay = Replay::new(9, &world);
+
+        // Make (and record) a few inputs along the way, much like the windowed sandbox does
+        let mut history = History::default();
+        let inputs = [
+            (0, Input::Paint { position: (12, 0), options: BrushOptions { shape: BrushShape::Circle, radius: 4, density: 100 }, variant: ParticleVariant::Water, template: None, brush: None, overflow: BudgetOverflow::Refuse }),
+            (0, Input::Path { from: (0, 20), to: (23, 22), variant: ParticleVariant::Brick, overflow: BudgetOverflow::Refuse }),
+            (5, Input::Finish),
+            (6, Input::Line { from: (2, 10), to: (20, 14), density: 100, variant: ParticleVariant::Metal, overflow: BudgetOverflow::Refuse }),
+            (6, Input::Shape { shape: Shape::Ellipse, from: (4, 4), to: (11, 9), is_filled: false, density: 100, variant: ParticleVariant::Glass, overflow: BudgetOverflow::Refuse }),
+            (6, Input::Fill { position: (1, 23), density: 20, variant: ParticleVariant::Oil, overflow: BudgetOverflow::Refuse }),
+            (8, Input::Paint { position: (6, 2), options: BrushOptions { shape: BrushShape::Square, radius: 2, density: 50 }, variant: ParticleVariant::Sand, template: None, brush: None, overflow: BudgetOverflow::CullGases }),
+            (12, Input::Undo),
+            (15, Input::Drop { shape: BodyShape::Box(3, 3), position: (16.5, 1.25) })
+        ];
+        for frame in 0..40 {
+            let (mut world, _, _, state) = live.parts();
+            if frame == 20 {
+                state.gravity = Gravity::Left;
+            }
+            if frame == 25 {
+                let input = Input::Paste { region: Region::copy(&world, (0, 12), (23, 23)).rotate(), position: (4, -6), overflow: BudgetOverflow::Refuse };
+                input.apply(&mut world, state, &mut history, &mut plugins);
+                replay.record(frame, input);
+            }
+            for (_, input) in inputs.iter().filter(|(at, _)| *at == frame) {
+                input.apply(&mut world, state, &mut history, &mut plugins);
+                replay.record(frame, input.clone());
+            }
+            replay.record_state(state);
+            live.step();
+        }
+        replay.frames = 40;
+        replay.rules = 0xfedcba9876543210;
+
+        // Played back from it's file, the replay ends up just where the run did
+        let mut playback = Playback::new(parse_replay(&save_replay(&replay)).ok().unwrap());
+        assert!(playback.replay.inputs == replay.inputs && playback.replay.inputs.len() == inputs.len() + 3 && playback.replay.rules == replay.rules);
+        let mut replayed = playback.replay.start().ok().unwrap();
+        let mut history = History::default();
+        loop {
+            let (mut world, _, _, state) = replayed.parts();
+            playback.apply_due(&mut world, state, &mut history, &mut plugins);
+            if playback.is_finished(state) {
+                break;
+            }
+            replayed.step();
+        }
+        assert!(replayed.snapshot() == live.snapshot());
+
+        // Broken (or tampered) inputs are refused as the file's read, rather than once (or while) they're played back
+        let header = "rusty-sandbox replay 4\nseed 1\nframes 5\nrules 0000000000000000\n";
+        assert!(parse_replay(&format!("{}2 paint 1 1 Circle 1 100 Sandd - - Refuse\nworld\n", header)).err().is_some_and(|error| error.line == 5));
+        let huge = parse_replay(&format!("{}0 paint 0 0 Circle 4000000000 100 Sand - - RefuseToPaint\nworld\n", header));
+        assert!(huge.err().is_some_and(|error| error.line == 5 && error.message.contains("bigger")));
+        assert!(parse_replay("rusty-sandbox replay 3\nseed 1\nframes 5\nworld\n").err().is_some_and(|error| error.line == 1));
+    }
+}
@@ -0,0 +1,591 @@
+use macroquad::prelude::Image;
+use std::collections::HashMap;
+
+use miniz_oxide::{deflate::compress_to_vec_zlib, inflate::{decompress_to_vec_zlib_with_limit, TINFLStatus}};
+
+use crate::{material::*, particle::*, region::*, render::*, world::*};
+
+// The first line of every world file, marking it (and it's format version) as one
+static WORLD_FILE_HEADER: &str = "rusty-sandbox world 1";
+
+// The first bytes of every binary world file, marking it as one, followed by it's format version (see `save_world_binary`)
+// ... older versions are still read, and migrated to the current one as they're loaded (see `parse_world_binary`)
+static WORLD_BINARY_MAGIC: &[u8; 4] = b"RSWB";
+static WORLD_BINARY_VERSION: u16 = 2;
+
+// The materials a version 1 binary world file's cells stand for (from 1, with 0 an empty cell)
+// ... version 1 files held each material's position within `VARIANTS`, which shifts as materials are added, so it's frozen here
+static V1_MATERIALS: [&str; 47] = [
+    "Sand", "Dirt", "Water", "Brick", "Gunpowder", "Fire", "Smoke", "Salt", "Saltwater", "Steam", "Lava", "Glass", "Metal", "Rust",
+    "Spark", "Seed", "Plant", "Virus", "Snow", "Mud", "Fuse", "BurningFuse", "TNT", "Gas", "Stone", "Gravel", "Wax", "MoltenWax",
+    "Cement", "Concrete", "Slime", "Clone", "Void", "Spout", "Antimatter", "Flash", "Mercury", "Ice", "Oil", "Fan", "Ash", "RigidBody",
+    "Battery", "Lamp", "Heater", "Detonator", "Acid"
+];
+
+// The tags of the sections which follow a binary world file's cells, each holding another of every cell's properties
+// ... a file missing a section (ie: one saved before it existed) leaves that property as a freshly spawned particle's, while a
+// ... section this version doesn't know of (ie: one saved by a newer version) is skipped over
+static TEMPERATURE_SECTION: &[u8; 4] = b"TEMP";
+static VELOCITY_SECTION: &[u8; 4] = b"VELO";
+
+// The start of every world code (see `encode_world_code`), marking it (and it's format version) as one
+static WORLD_CODE_PREFIX: &str = "rsw1:";
+
+// The longest world code (in characters) the sandbox offers to share, as anything longer won't fit in a chat message or a post
+pub static MAX_WORLD_CODE_LENGTH: usize = 32768;
+
+// The most bytes a world code inflates to: as many as a code of `MAX_WORLD_CODE_LENGTH` characters could hold at DEFLATE's best
+// ... ratio (1032:1), so a pasted code can't inflate into more memory than a shareable code ever would
+static MAX_WORLD_CODE_BYTES: usize = MAX_WORLD_CODE_LENGTH / 4 * 3 * 1032;
+
+// The most cells a binary world file (or world code) can have, so a corrupt or hostile file can't claim a world too big to allocate
+pub static MAX_WORLD_CELLS: usize = 8192 * 8192;
+
+// How many save slots the in-game save menu offers, and the folder (within the config folder) their files are kept in
+pub static SAVE_SLOTS: usize = 5;
+static SAVES_FOLDER: &str = "saves";
+
+// How many autosave files the world is autosaved into in turn (within the saves folder), overwriting the oldest each time
+pub static AUTOSAVE_FILES: usize = 3;
+
+// The folder (within the config folder) stamps are kept in, as a file of a region code each (see `Region::encode`), and the
+// ... extension of those files
+pub static STAMPS_FOLDER: &str = "stamps";
+pub static STAMP_EXTENSION: &str = "stamp";
+
+// The longest a stamp's name can be
+static MAX_STAMP_NAME: usize = 40;
+
+// The file (within the config folder) marking the sandbox as running, which is only removed once it closes cleanly: if it's
+// ... already there as the sandbox starts, the last run didn't close cleanly (ie: it crashed), and it's latest autosave is worth
+// ... offering back
+pub static RUNNING_MARKER: &str = "running";
+
+// The palette file imported images are matched against, if it's in the working directory (see `parse_palette`)
+pub static PALETTE_FILE: &str = "palette.toml";
+
+// How opaque (0 - 255) an imported image's pixel must be to become a particle, rather than an empty cell
+static IMPORT_ALPHA_THRESHOLD: u8 = 128;
+
+// A world as read from a world file: it's size, and the variant of each cell (if it's not empty), column by column
+// ... alongside each cell's temperature and velocity, if the file kept them (see `save_world_binary`)
+pub struct WorldFile {
+    pub size: (usize, usize),
+    cells: Vec<Option<ParticleVariant>>,
+    temperatures: Option<Vec<f32>>,
+    velocities: Option<Vec<(f32, f32)>>
+}
+
+// A problem with a world file, alongside the (1-based) line it was found on
+pub struct WorldError {
+    pub line: usize,
+    pub message: String
+}
+
+impl std::fmt::Display for WorldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+// Write the world out as a world file: a header, it's size, then a line per row (from the top down) of the material in each cell
+// ... runs of the same material are written once alongside their length (ie: `Empty*40 Sand*12`), keeping files of mostly-empty worlds small
+// Note: only each cell's material is kept, everything else (ie: temperature, momentum) starts afresh once loaded
+pub fn save_world(world: &World) -> String {
+    let mut text = format!("{}\nsize {} {}\n", WORLD_FILE_HEADER, world.width(), world.height());
+    for y in 0..world.height() {
+        let mut runs: Vec<(String, usize)> = Vec::new();
+        for x in 0..world.width() {
+            let particle = world.particle(x, y);
+            let name = if particle.active { particle.variant.to_string().replace(' ', "") } else { "Empty".to_owned() };
+            match runs.last_mut() {
+                Some((last, length)) if *last == name => *length += 1,
+                _ => runs.push((name, 1))
+            }
+        }
+        let tokens: Vec<String> = runs.into_iter().map(|(name, length)| if length == 1 { name } else { format!("{}*{}", name, length) }).collect();
+        text.push_str(&tokens.join(" "));
+        text.push('\n');
+    }
+    text
+}
+
+// Read a world file, see `save_world`
+pub fn parse_world(text: &str) -> Result<WorldFile, WorldError> {
+    let mut lines = text.lines().enumerate().map(|(index, line)| (index + 1, line.trim()));
+    if lines.next().map(|(_, line)| line) != Some(WORLD_FILE_HEADER) {
+        return Err(WorldError { line: 1, message: format!("not a world file (expected '{}')", WORLD_FILE_HEADER) });
+    }
+    let size = match lines.next() {
+        Some((_, line)) => line.strip_prefix("size ").and_then(|size| size.split_once(' '))
+            .and_then(|(width, height)| Some((width.parse::<usize>().ok()?, height.parse::<usize>().ok()?))),
+        None => None
+    };
+    let Some((width, height)) = size else {
+        return Err(WorldError { line: 2, message: "expected the world's size (ie: 'size 320 180')".to_owned() });
+    };
+    if width.checked_mul(height).is_none_or(|area| area > MAX_WORLD_CELLS) {
+        return Err(WorldError { line: 2, message: format!("the world is too large (over {} cells)", MAX_WORLD_CELLS) });
+    }
+
+    // Read the rows, then lay them out column by column (like `Particles`)
+    let mut rows: Vec<Vec<Option<ParticleVariant>>> = Vec::new();
+    for (line, text) in lines.filter(|(_, text)| !text.is_empty()) {
+        if rows.len() == height {
+            return Err(WorldError { line, message: format!("world has more than {} rows", height) });
+        }
+        let mut row = Vec::new();
+        for token in text.split_whitespace() {
+            let (name, length) = token.split_once('*').unwrap_or((token, "1"));
+            let length: usize = length.parse().map_err(|_| WorldError { line, message: format!("'{}' isn't a valid run length", length) })?;
+            let cell = if name.eq_ignore_ascii_case("empty") {
+                None
+            } else {
+                Some(find_variant(name).ok_or_else(|| WorldError { line, message: format!("unknown material '{}'", name) })?)
+            };
+            // Check the run fits before laying it out, so a corrupt length can't ask for more memory than the world needs
+            if length > width - row.len() {
+                return Err(WorldError { line, message: format!("row is more than {} cells wide, but the world is {} wide", width, width) });
+            }
+            row.extend(std::iter::repeat_n(cell, length));
+        }
+        if row.len() != width {
+            return Err(WorldError { line, message: format!("row is {} cells wide, but the world is {} wide", row.len(), width) });
+        }
+        rows.push(row);
+    }
+    if rows.len() != height {
+        return Err(WorldError { line: text.lines().count(), message: format!("world has {} rows, but should have {}", rows.len(), height) });
+    }
+    let cells = (0..width).flat_map(|x| rows.iter().map(move |row| row[x])).collect();
+    Ok(WorldFile { size: (width, height), cells, temperatures: None, velocities: None })
+}
+
+// A problem with a binary world file, alongside the byte it was found at
+pub struct BinaryWorldError {
+    pub offset: usize,
+    pub message: String
+}
+
+impl std::fmt::Display for BinaryWorldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "byte {}: {}", self.offset, self.message)
+    }
+}
+
+// Write the world out as a binary world file: the magic bytes, the format version (u16), the width and height (u32s), the
+// ... materials the world holds (a u16 count, then each one's name as a u8 length and it's bytes), then the cells column by column
+// ... as runs of a length (u16) and a cell (0 for an empty cell, otherwise a material's place in the list from 1), all little-endian
+// ... then a section per property of every cell (see `TEMPERATURE_SECTION`): a tag, a length (u32), then runs of the property
+// ... naming the materials keeps files readable as materials are added, and the sections as properties are
+pub fn save_world_binary(world: &World) -> Vec<u8> {
+    let mut bytes = save_world_cells(world);
+    let cells = || (0..world.width()).flat_map(|x| (0..world.height()).map(move |y| world.particle(x, y)));
+    let mut section = Vec::new();
+    write_runs(&mut section, cells().map(|particle| particle.temperature.to_bits()), |bytes, bits| bytes.extend(bits.to_le_bytes()));
+    write_section(&mut bytes, TEMPERATURE_SECTION, section);
+    let mut section = Vec::new();
+    write_runs(&mut section, cells().map(|particle| (particle.velocity.0.to_bits(), particle.velocity.1.to_bits())), |bytes, (x, y)| {
+        bytes.extend(x.to_le_bytes());
+        bytes.extend(y.to_le_bytes());
+    });
+    write_section(&mut bytes, VELOCITY_SECTION, section);
+    bytes
+}
+
+// Write the world out as a binary world file without any sections, keeping only each cell's material (see `save_world_binary`)
+fn save_world_cells(world: &World) -> Vec<u8> {
+    let mut bytes = WORLD_BINARY_MAGIC.to_vec();
+    bytes.extend(WORLD_BINARY_VERSION.to_le_bytes());
+    bytes.extend((world.width() as u32).to_le_bytes());
+    bytes.extend((world.height() as u32).to_le_bytes());
+
+    let cells = || (0..world.width()).flat_map(|x| (0..world.height()).map(move |y| world.particle(x, y)));
+    let mut materials: Vec<ParticleVariant> = Vec::new();
+    for particle in cells().filter(|particle| particle.active) {
+        if !materials.contains(&particle.variant) {
+            materials.push(particle.variant);
+        }
+    }
+    bytes.extend((materials.len() as u16).to_le_bytes());
+    for variant in materials.iter() {
+        let name = variant.to_string().replace(' ', "");
+        bytes.push(name.len() as u8);
+        bytes.extend(name.bytes());
+    }
+    write_runs(&mut bytes, cells().map(|particle| {
+        if particle.active { materials.iter().position(|&variant| variant == particle.variant).unwrap() as u8 + 1 } else { 0 }
+    }), |bytes, cell| bytes.push(cell));
+    bytes
+}
+
+// Write values out as runs of a length (u16) and a value (written by `write`), splitting runs at the u16 limit
+fn write_runs<T: PartialEq + Copy>(bytes: &mut Vec<u8>, values: impl Iterator<Item = T>, write: impl Fn(&mut Vec<u8>, T)) {
+    let mut values = values.peekable();
+    while let Some(value) = values.next() {
+        let mut length: u16 = 1;
+        while length < u16::MAX && values.next_if_eq(&value).is_some() {
+            length += 1;
+        }
+        bytes.extend(length.to_le_bytes());
+        write(bytes, value);
+    }
+}
+
+// Write a section out: it's tag, it's length (u32), then it's bytes
+fn write_section(bytes: &mut Vec<u8>, tag: &[u8; 4], section: Vec<u8>) {
+    bytes.extend(tag);
+    bytes.extend((section.len() as u32).to_le_bytes());
+    bytes.extend(section);
+}
+
+// Return true if the bytes are (the start of) a binary world file
+pub fn is_binary_world(bytes: &[u8]) -> bool {
+    bytes.starts_with(WORLD_BINARY_MAGIC)
+}
+
+// Read a binary world file, see `save_world_binary`
+// ... a version 1 file (which numbered it's materials by `V1_MATERIALS`, and kept nothing but each cell's material) is migrated
+// ... as it's read, it's cells' temperatures and velocities left as freshly spawned particles' are
+pub fn parse_world_binary(bytes: &[u8]) -> Result<WorldFile, BinaryWorldError> {
+    let error = |offset: usize, message: &str| BinaryWorldError { offset, message: message.to_owned() };
+    if !is_binary_world(bytes) {
+        return Err(error(0, "not a binary world file"));
+    }
+    let read = |offset: usize, length: usize| bytes.get(offset..offset + length).ok_or_else(|| error(offset, "the file ends too early"));
+    let version = u16::from_le_bytes(read(4, 2)?.try_into().unwrap());
+    if version == 0 || version > WORLD_BINARY_VERSION {
+        return Err(BinaryWorldError { offset: 4, message: format!("unsupported format version {} (expected {} or older)", version, WORLD_BINARY_VERSION) });
+    }
+    let width = u32::from_le_bytes(read(6, 4)?.try_into().unwrap()) as usize;
+    let height = u32::from_le_bytes(read(10, 4)?.try_into().unwrap()) as usize;
+    let area = width.checked_mul(height).filter(|&area| area <= MAX_WORLD_CELLS).ok_or_else(|| error(6, &format!("the world is too large (over {} cells)", MAX_WORLD_CELLS)))?;
+    let mut offset = 14;
+
+    // The materials the cells stand for, listed by name in the file since version 2
+    let materials = if version == 1 {
+        V1_MATERIALS.iter().map(|name| find_variant(name)).collect::<Vec<_>>()
+    } else {
+        let count = u16::from_le_bytes(read(offset, 2)?.try_into().unwrap());
+        offset += 2;
+        let mut materials = Vec::new();
+        for _ in 0..count {
+            let length = read(offset, 1)?[0] as usize;
+            let name = String::from_utf8_lossy(read(offset + 1, length)?);
+            materials.push(Some(find_variant(&name).ok_or_else(|| error(offset + 1, &format!("unknown material '{}'", name)))?));
+            offset += 1 + length;
+        }
+        materials
+    };
+    let (cells, length) = read_runs(&bytes[offset..], offset, area, 1, |value| match value[0] {
+        0 => Ok(None),
+        byte => materials.get(byte as usize - 1).copied().flatten().ok_or_else(|| format!("unknown material {}", byte)).map(Some)
+    })?;
+    offset += length;
+
+    // Then the sections, until the end of the file
+    let mut file = WorldFile { size: (width, height), cells, temperatures: None, velocities: None };
+    while offset < bytes.len() {
+        let tag = read(offset, 4)?;
+        let length = u32::from_le_bytes(read(offset + 4, 4)?.try_into().unwrap()) as usize;
+        let section = read(offset + 8, length)?;
+        let value = |value: &[u8]| f32::from_le_bytes(value[..4].try_into().unwrap());
+        if tag == TEMPERATURE_SECTION {
+            file.temperatures = Some(read_section(section, offset + 8, area, 4, |bytes| Ok(value(bytes)))?);
+        } else if tag == VELOCITY_SECTION {
+            file.velocities = Some(read_section(section, offset + 8, area, 8, |bytes| Ok((value(bytes), value(&bytes[4..]))))?);
+        }
+        offset += 8 + length;
+    }
+    Ok(file)
+}
+
+// Read a section's runs, which have to fill the whole section
+fn read_section<T: Clone>(section: &[u8], offset: usize, area: usize, size: usize, parse: impl Fn(&[u8]) -> Result<T, String>) -> Result<Vec<T>, BinaryWorldError> {
+    let (values, length) = read_runs(section, offset, area, size, parse)?;
+    if length != section.len() {
+        return Err(BinaryWorldError { offset: offset + length, message: "unexpected bytes after the section's runs".to_owned() });
+    }
+    Ok(values)
+}
+
+// Read runs of a length (u16) and a value (of `size` bytes, read by `parse`) until there's a value for every cell, returning them
+// ... alongside how many bytes they took (`offset` is where the bytes start within the file, for errors)
+fn read_runs<T: Clone>(bytes: &[u8], offset: usize, area: usize, size: usize, parse: impl Fn(&[u8]) -> Result<T, String>) -> Result<(Vec<T>, usize), BinaryWorldError> {
+    let mut values = Vec::new();
+    let mut position = 0;
+    while values.len() < area {
+        let error = |message: String| BinaryWorldError { offset: offset + position, message };
+        let run = bytes.get(position..position + 2 + size).ok_or_else(|| error("the file ends too early".to_owned()))?;
+        let length = u16::from_le_bytes([run[0], run[1]]) as usize;
+        let value = parse(&run[2..]).map_err(|message| BinaryWorldError { offset: offset + position + 2, message })?;
+        // Check the run fits before laying it out, so a corrupt length can't ask for more memory than the world needs
+        if length == 0 || values.len() + length > area {
+            return Err(error("run doesn't fit within the world".to_owned()));
+        }
+        values.extend(std::iter::repeat_n(value, length));
+        position += 2 + size;
+    }
+    Ok((values, position))
+}
+
+// Write the world out as a world code, to share small worlds as text: `WORLD_CODE_PREFIX`, then the world's binary world file
+// ... (keeping only each cell's material, see `save_world_cells`) deflated and written out in URL-safe base64
+pub fn encode_world_code(world: &World) -> String {
+    format!("{}{}", WORLD_CODE_PREFIX, encode_base64(&compress_to_vec_zlib(&save_world_cells(world), 9)))
+}
+
+// Return true if the text is a world code, see `encode_world_code`
+pub fn is_world_code(text: &str) -> bool {
+    text.trim().starts_with(WORLD_CODE_PREFIX)
+}
+
+// Read a world code, see `encode_world_code` (surrounding whitespace, ie: from copying it out of a post, is ignored)
+pub fn decode_world_code(code: &str) -> Result<WorldFile, String> {
+    let data = code.trim().strip_prefix(WORLD_CODE_PREFIX).ok_or("not a world code")?;
+    let deflated = decode_base64(data).ok_or("the world code isn't valid base64")?;
+    let bytes = decompress_to_vec_zlib_with_limit(&deflated, MAX_WORLD_CODE_BYTES).map_err(|error| match error.status {
+        TINFLStatus::HasMoreOutput => "the world code is too big to open".to_owned(),
+        _ => "the world code is corrupt".to_owned()
+    })?;
+    parse_world_binary(&bytes).map_err(|error| format!("the world code's world is corrupt ({})", error))
+}
+
+// The colours an imported image's pixels are matched against, and what each colour becomes (`None` being an empty cell)
+pub struct Palette {
+    pub colours: Vec<([u8; 3], Option<ParticleVariant>)>
+}
+
+impl Default for Palette {
+    // Every material's own colour (besides the fleeting Flash, and Body which only rigid bodies are made of), on black for empty cells
+    fn default() -> Palette {
+        let materials = variants().filter(|v| *v != ParticleVariant::Flash && *v != ParticleVariant::Body).map(|variant| {
+            let colour = get_variant_colour(variant, false);
+            ([(colour.r * 255.0) as u8, (colour.g * 255.0) as u8, (colour.b * 255.0) as u8], Some(variant))
+        });
+        Palette { colours: std::iter::once(([0, 0, 0], None)).chain(materials).collect() }
+    }
+}
+
+// A problem with a palette file, alongside the (1-based) line it was found on
+pub struct PaletteError {
+    pub line: usize,
+    pub message: String
+}
+
+impl std::fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+// Parse a palette file: a `Material = "#RRGGBB"` line (or a [list] of colours) per material, with `Empty` for empty cells
+// ... it replaces the default palette entirely, so an image only needs to use the colours it's palette lists
+pub fn parse_palette(text: &str) -> Result<Palette, Vec<PaletteError>> {
+    let mut colours = Vec::new();
+    let mut errors = Vec::new();
+    for (index, raw) in text.lines().enumerate() {
+        let line = index + 1;
+        let content = strip_comment(raw).trim();
+        if content.is_empty() {
+            continue;
+        }
+        let Some((name, value)) = content.split_once('=') else {
+            errors.push(PaletteError { line, message: format!("expected a `Material = \"#RRGGBB\"` pair, found '{}'", content) });
+            continue;
+        };
+        let name = name.trim();
+        let variant = if name.eq_ignore_ascii_case("empty") {
+            None
+        } else if let Some(variant) = find_variant(name) {
+            Some(variant)
+        } else {
+            errors.push(PaletteError { line, message: format!("unknown material '{}'", name) });
+            continue;
+        };
+        let values = match parse_value(value.trim()) {
+            Ok(Value::List(items)) => items.into_iter().map(Value::Text).collect(),
+            Ok(value) => vec![value],
+            Err(message) => {
+                errors.push(PaletteError { line, message });
+                continue;
+            }
+        };
+        for value in values {
+            match parse_colour(&value) {
+                Ok(colour) => colours.push(([(colour.r * 255.0) as u8, (colour.g * 255.0) as u8, (colour.b * 255.0) as u8], variant)),
+                Err(message) => errors.push(PaletteError { line, message })
+            }
+        }
+    }
+    if colours.is_empty() && errors.is_empty() {
+        errors.push(PaletteError { line: text.lines().count().max(1), message: "the palette has no colours".to_owned() });
+    }
+    if errors.is_empty() {
+        Ok(Palette { colours })
+    } else {
+        Err(errors)
+    }
+}
+
+// Create a world from an image, a cell per pixel: each (opaque enough) pixel becomes whatever the palette's closest colour stands for
+pub fn import_image(image: &Image, palette: &Palette) -> WorldFile {
+    let (width, height) = (image.width as usize, image.height as usize);
+    let mut matches: HashMap<[u8; 3], Option<ParticleVariant>> = HashMap::new();
+    let cells = (0..width).flat_map(|x| (0..height).map(move |y| (y * width + x) * 4)).map(|i| {
+        let pixel = &image.bytes[i..i + 4];
+        if pixel[3] < IMPORT_ALPHA_THRESHOLD {
+            return None;
+        }
+        let rgb = [pixel[0], pixel[1], pixel[2]];
+        *matches.entry(rgb).or_insert_with(|| {
+            let distance = |colour: &[u8; 3]| colour.iter().zip(rgb).map(|(&a, b)| (a as i32 - b as i32).pow(2)).sum::<i32>();
+            palette.colours.iter().min_by_key(|(colour, _)| distance(colour)).and_then(|(_, variant)| *variant)
+        })
+    }).collect();
+    WorldFile { size: (width, height), cells, temperatures: None, velocities: None }
+}
+
+// Return the name (within the sandbox's storage, see `storage`) of a save slot's binary world file (from zero)
+pub fn slot_file(slot: usize) -> String {
+    format!("{}/slot-{}.world", SAVES_FOLDER, slot + 1)
+}
+
+// Return the name of an autosave's binary world file (from zero, up to `AUTOSAVE_FILES`)
+pub fn autosave_file(index: usize) -> String {
+    format!("{}/autosave-{}.world", SAVES_FOLDER, index + 1)
+}
+
+// Return the name of the stamp file with the given stamp name, or nothing if the name isn't fit to be a file name (see `is_stamp_name`)
+pub fn stamp_file(name: &str) -> Option<String> {
+    is_stamp_name(name).then(|| format!("{}/{}.{}", STAMPS_FOLDER, name, STAMP_EXTENSION))
+}
+
+// Return true if the name is fit to name a stamp: not blank, not too long, and only letters, digits, spaces, dashes and underscores
+// ... (so it's a file name on every platform, and can't lead outside of the stamps folder)
+pub fn is_stamp_name(name: &str) -> bool {
+    !name.trim().is_empty() && name == name.trim() && name.len() <= MAX_STAMP_NAME && name.chars().all(|c| c.is_ascii_alphanumeric() || " -_".contains(c))
+}
+
+impl WorldFile {
+    // Spawn the file's particles into a world of the same size, then lay in their temperatures and velocities (if the file kept them)
+    pub fn paint_into(&self, world: &mut World) {
+        for (i, cell) in self.cells.iter().enumerate() {
+            let (x, y) = (i / self.size.1, i % self.size.1);
+            if let Some(variant) = cell {
+                world.spawn(x, y, *variant);
+            }
+            if let Some(temperatures) = &self.temperatures {
+                world.data[(x, y)].temperature = temperatures[i];
+            }
+            if let Some(velocities) = self.velocities.as_ref().filter(|_| cell.is_some()) {
+                world.data[(x, y)].velocity = velocities[i];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn worlds_survive_a_save_and_load() {
+        let mut simulation = crate::Simulation::new(6, 3);
+        simulation.paint(3, 1, 1, ParticleVariant::BurningFuse);
+        let text = simulation.save();
+        assert!(text.ends_with("size 6 3\nEmpty*3 BurningFuse Empty*2\nEmpty*2 BurningFuse*3 Empty\nEmpty*3 BurningFuse Empty*2\n"));
+
+        let mut loaded = crate::Simulation::load(&text).ok().unwrap();
+        assert_eq!(loaded.save(), text);
+        assert!(loaded.get(2, 1) == Some(ParticleVariant::BurningFuse) && loaded.get(1, 1).is_none());
+
+        let error = parse_world("rusty-sandbox world 1\nsize 2 1\nSand Lava*2").err().unwrap();
+        assert!(error.line == 3 && error.message.contains("is 2 wide"));
+
+        // Runs too long to add up, worlds too big to allocate, and rows past the world's height are all refused
+        let error = parse_world("rusty-sandbox world 1\nsize 4 1\nSand Sand*18446744073709551615").err().unwrap();
+        assert!(error.line == 3 && error.message.contains("more than 4 cells wide"));
+        let error = parse_world("rusty-sandbox world 1\nsize 100000 100000\nSand*100000").err().unwrap();
+        assert!(error.line == 2 && error.message.contains("too large"));
+        assert!(parse_world("rusty-sandbox world 1\nsize 1 1\nSand\nSand").err().unwrap().message.contains("more than 1 rows"));
+    }
+
+    #[test]
+    fn images_import_through_their_palette() {
+        let palette = parse_palette("Empty = \"#000000\"\nSand = [\"#ffff00\", \"#ffcc00\"]  # two shades\nstone = \"#808080\"").ok().unwrap();
+        // A 3x2 image: near-yellow, grey and black on top, then dark grey, transparent yellow and orange below
+        let image = Image { bytes: vec![250, 250, 10, 255, 128, 128, 128, 255, 0, 0, 0, 255, 100, 100, 100, 255, 255, 255, 0, 0, 255, 200, 0, 255], width: 3, height: 2 };
+        let file = import_image(&image, &palette);
+        let mut simulation = crate::Simulation::new(1, 1);
+        simulation.replace(&file);
+        assert!(simulation.get(0, 0) == Some(ParticleVariant::Sand) && simulation.get(1, 0) == Some(ParticleVariant::Stone) && simulation.get(2, 0).is_none());
+        assert!(simulation.get(0, 1) == Some(ParticleVariant::Stone) && simulation.get(1, 1).is_none() && simulation.get(2, 1) == Some(ParticleVariant::Sand));
+
+        let errors = parse_palette("Sand = \"#ffff00\"\nSandy = \"#ffff00\"\nWater = \"blue\"").err().unwrap();
+        assert!(errors.iter().map(|error| error.line).eq([2, 3]));
+        assert!(parse_palette("# nothing here").is_err() && Palette::default().colours.len() == VARIANTS.len() - 1);
+    }
+
+    #[test]
+    fn worlds_survive_a_binary_save_and_load() {
+        let mut simulation = crate::Simulation::new(300, 300);
+        simulation.paint(150, 150, 20, ParticleVariant::Lava);
+        simulation.step();
+        let bytes = simulation.save_binary();
+        // Runs carry on across columns (splitting at the u16 limit), so only the painted columns take more than a run or two (in the
+        // ... cells, and in each of the sections)
+        assert!(bytes.len() < 14 + 3 * 301 * 3 + 2 * 11 * 301 * 3);
+
+        // ... and everything kept (each cell's material, temperature and velocity) is just as it was once it's loaded
+        let mut loaded = crate::Simulation::new(1, 1);
+        loaded.replace(&parse_world_binary(&bytes).ok().unwrap());
+        assert!(loaded.size() == (300, 300) && loaded.get(150, 150) == Some(ParticleVariant::Lava) && loaded.get(0, 0).is_none());
+        assert!(loaded.temperature(150, 128) == simulation.temperature(150, 128) && loaded.save_binary() == bytes);
+
+        let error = parse_world_binary(&bytes[..bytes.len() - 1]).err().unwrap();
+        assert!(error.message.contains("ends too early"));
+        assert!(parse_world_binary(b"RSWB\x03\x00").err().unwrap().message.contains("version 3"));
+    }
+
+    #[test]
+    fn version_1_binary_worlds_are_migrated() {
+        // A 2x2 version 1 world: Lava (the 11th material then) in the top-left cell, Acid (the 47th) below it, then empty cells
+        let bytes = b"RSWB\x01\x00\x02\x00\x00\x00\x02\x00\x00\x00\x01\x00\x0b\x01\x00\x2f\x02\x00\x00";
+        let mut loaded = crate::Simulation::new(1, 1);
+        loaded.replace(&parse_world_binary(bytes).ok().unwrap());
+        assert!(loaded.get(0, 0) == Some(ParticleVariant::Lava) && loaded.get(0, 1) == Some(ParticleVariant::Acid) && loaded.get(1, 0).is_none());
+        // ... with the temperatures it never kept left as freshly spawned particles'
+        assert!(loaded.temperature(0, 0) == ParticleVariant::Lava.get_spawn_temperature());
+        assert!(V1_MATERIALS.iter().all(|name| find_variant(name).is_some()));
+    }
+
+    #[test]
+    fn world_codes_are_short_and_load_back() {
+        let mut simulation = crate::Simulation::new(200, 100);
+        simulation.paint(100, 80, 20, ParticleVariant::Sand);
+        simulation.paint(40, 20, 6, ParticleVariant::Water);
+        let (world, ..) = simulation.parts();
+        let (code, mut huge) = (encode_world_code(&world), save_world_cells(&world));
+        assert!(is_world_code(&format!(" {}\n", code)) && code.len() < 1024);
+
+        let mut loaded = crate::Simulation::new(1, 1);
+        loaded.replace(&decode_world_code(&format!("{}\n", code)).ok().unwrap());
+        assert!(loaded.snapshot() == simulation.snapshot());
+        assert!(decode_world_code("rsw1:AAAA").is_err() && decode_world_code("rsr1:AAAA").is_err());
+
+        // Codes inflating past anything shareable, or claiming a world too big to allocate, are refused
+        let bomb = format!("{}{}", WORLD_CODE_PREFIX, encode_base64(&compress_to_vec_zlib(&vec![0; MAX_WORLD_CODE_BYTES + 1], 1)));
+        assert!(decode_world_code(&bomb).err().unwrap().contains("too big"));
+        huge[6..14].copy_from_slice(&[0, 0, 1, 0, 0, 0, 1, 0]);
+        let huge = format!("{}{}", WORLD_CODE_PREFIX, encode_base64(&compress_to_vec_zlib(&huge, 9)));
+        assert!(decode_world_code(&huge).err().unwrap().contains("too large"));
+    }
+
+    #[test]
+    fn stamp_names_stay_within_the_stamps_folder() {
+        assert!(is_stamp_name("Bomb rig") && is_stamp_name("house_2-b"));
+        assert!(!is_stamp_name("") && !is_stamp_name(" house") && !is_stamp_name("../config") && !is_stamp_name("a/b") && !is_stamp_name(&"x".repeat(41)));
+        assert!(stamp_file("Bomb rig").as_deref() == Some("stamps/Bomb rig.stamp") && stamp_file("../config").is_none());
+    }
+}
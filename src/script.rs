@@ -0,0 +1,419 @@
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, Position, Scope, AST, FLOAT, INT};
+use std::{cell::RefCell, sync::{atomic::{AtomicBool, Ordering}, Arc, OnceLock}};
+
+use crate::{material::find_variant, particle::*, sim::*, world::*};
+
+// The folder the sandbox loads (and hot-reloads) element scripts from, relative to the working directory
+pub static SCRIPTS_FOLDER: &str = "scripts";
+
+// The extension of the files within the scripts folder which are loaded as scripts
+pub static SCRIPT_EXTENSION: &str = "rhai";
+
+// The temperatures (in °C) a script can heat (or cool) a particle between
+static MIN_TEMPERATURE: f32 = -273.15;
+static MAX_TEMPERATURE: f32 = 10_000.0;
+
+// How many operations a script can take to load, or to update a single particle, before it's stopped (so a script stuck in a loop
+// ... can't freeze the sandbox)
+static MAX_OPERATIONS: u64 = 10_000;
+
+// How deeply a script's functions can call one another
+static MAX_CALL_LEVELS: usize = 16;
+
+// The longest string, array or object map a script can build
+static MAX_COLLECTION_SIZE: usize = 1024;
+
+// The engine every script runs within, shared by every thread (scripts only reach the world through the functions it registers)
+static ENGINE: OnceLock<Engine> = OnceLock::new();
+
+thread_local! {
+    // What the handler running on this thread can see and has asked for, if one is running (see `run_scripts`)
+    static CONTEXT: RefCell<Option<Context>> = const { RefCell::new(None) };
+    // The handlers registered (through `on`) by the script loading on this thread, alongside where they were registered
+    static REGISTERED: RefCell<Vec<(ParticleVariant, String, Position)>> = const { RefCell::new(Vec::new()) };
+}
+
+// A cell around a scripted particle, as it's handler sees it
+#[derive(Clone, Copy, PartialEq)]
+enum Nearby {
+    // Outside of the world
+    Edge,
+    Empty,
+    Particle(ParticleVariant)
+}
+
+impl Nearby {
+    // Return the name scripts know the cell by (`Edge`, `Empty` or the material's name)
+    fn name(&self) -> String {
+        match self {
+            Nearby::Edge => "Edge".to_owned(),
+            Nearby::Empty => "Empty".to_owned(),
+            Nearby::Particle(variant) => variant.to_string()
+        }
+    }
+}
+
+// A change a handler asks for, made once it's finished (and only if it finished without an error)
+enum Command {
+    // The cell at the offset turns into the variant (or empties, for `None`)
+    Set((isize, isize), Option<ParticleVariant>),
+    // The variant is spawned into a random empty neighbouring cell
+    Spawn(ParticleVariant),
+    // The particle's temperature changes by this much (in °C)
+    Heat(f32)
+}
+
+// What a running handler can see of the world (the particle and the 8 cells around it), and the changes it's asked for so far
+struct Context {
+    // The particle's cell and it's neighbours, row by row from the top-left (see `get_offset`)
+    cells: [Nearby; 9],
+    temperature: f32,
+    // The direction of gravity (or down, while it's off)
+    down: (isize, isize),
+    // Seeded from the simulation's own RNG, so scripted chances replay exactly
+    rng: Rng,
+    commands: Vec<Command>
+}
+
+// A function run each frame for every particle of a variant
+struct Handler {
+    // The script the handler is defined in (without it's top-level statements, which only run as it loads)
+    ast: Arc<AST>,
+    name: String,
+    // Set once the handler has failed, so only it's first error is reported (rather than one every frame)
+    has_failed: AtomicBool
+}
+
+// Every loaded script handler, grouped by the variant they belong to
+// ... scripts can only see a particle's own neighbourhood, and only change it through `Command`s checked as they're made, so a
+// ... broken script can't corrupt the world (and one stuck in a loop is stopped after `MAX_OPERATIONS`)
+#[derive(Default)]
+pub struct Scripts {
+    handlers: Vec<(ParticleVariant, Vec<Handler>)>,
+    // A hash of the scripts' text (0 if there are none), see `Scripts::fingerprint`
+    fingerprint: u64
+}
+
+// A problem with a script, alongside the (1-based) line it was found on
+pub struct ScriptError {
+    pub line: usize,
+    pub message: String
+}
+
+impl std::fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl ScriptError {
+    fn new(mut error: EvalAltResult) -> ScriptError {
+        let line = error.take_position().line().unwrap_or(0);
+        ScriptError { line, message: error.to_string() }
+    }
+}
+
+impl Scripts {
+    // Return true if no handlers are loaded
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+
+    // Return a hash of the scripts' text, in the order they were loaded, so peers (or a replay) running different scripts can be told apart
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    // Add another script's handlers after these ones
+    pub fn merge(&mut self, other: Scripts) {
+        self.fingerprint = (self.fingerprint ^ other.fingerprint).wrapping_mul(0x100000001b3);
+        for (variant, handlers) in other.handlers {
+            match self.handlers.iter_mut().find(|(v, _)| *v == variant) {
+                Some((_, existing)) => existing.extend(handlers),
+                None => self.handlers.push((variant, handlers))
+            }
+        }
+    }
+
+    // Return the handlers scripted for a variant (if any)
+    fn get_handlers(&self, variant: ParticleVariant) -> &[Handler] {
+        self.handlers.iter().find(|(v, _)| *v == variant).map_or(&[], |(_, handlers)| handlers)
+    }
+}
+
+// Find the cell with the given name, where `Empty` (or `None`) means an empty cell and `Edge` means outside of the world
+fn find_cell(name: &str) -> Result<Nearby, String> {
+    if name.eq_ignore_ascii_case("none") || name.eq_ignore_ascii_case("empty") {
+        return Ok(Nearby::Empty);
+    }
+    if name.eq_ignore_ascii_case("edge") {
+        return Ok(Nearby::Edge);
+    }
+    find_variant(name).map(Nearby::Particle).ok_or_else(|| format!("unknown material '{}'", name))
+}
+
+// Return the index (within `Context::cells`) of the offset from the particle, which must be the particle itself or a neighbour
+fn get_offset(dx: INT, dy: INT) -> Result<usize, String> {
+    if !(-1..=1).contains(&dx) || !(-1..=1).contains(&dy) {
+        return Err(format!("({}, {}) isn't the particle or one of it's 8 neighbours", dx, dy));
+    }
+    Ok(((dy + 1) * 3 + dx + 1) as usize)
+}
+
+// Run `f` with the running handler's context, failing if no handler is running (ie: a script's top-level statements, as it loads)
+fn with_context<T>(f: impl FnOnce(&mut Context) -> Result<T, String>) -> Result<T, Box<EvalAltResult>> {
+    CONTEXT.with(|context| match context.borrow_mut().as_mut() {
+        Some(context) => f(context).map_err(Into::into),
+        None => Err("the world can only be seen and changed from within a handler".into())
+    })
+}
+
+// Change the running handler's particle's temperature, refusing NaN and infinities (which would spread through the world's heat,
+// ... never to leave it)
+fn heat(amount: FLOAT) -> Result<(), Box<EvalAltResult>> {
+    if !amount.is_finite() {
+        return Err(format!("{} isn't a temperature change", amount).into());
+    }
+    with_context(|context| {
+        context.commands.push(Command::Heat(amount.clamp(-MAX_TEMPERATURE as FLOAT, MAX_TEMPERATURE as FLOAT) as f32));
+        Ok(())
+    })
+}
+
+// Return the engine scripts run within, creating it the first time
+// ... the world is read through `get`, `above`, `below`, `touching`, `temperature` and `chance`, and changed through `set`, `spawn`
+// ... and `heat`, while `on` registers a handler as the script loads
+fn engine() -> &'static Engine {
+    ENGINE.get_or_init(|| {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS)
+            .set_max_call_levels(MAX_CALL_LEVELS)
+            .set_max_string_size(MAX_COLLECTION_SIZE)
+            .set_max_array_size(MAX_COLLECTION_SIZE)
+            .set_max_map_size(MAX_COLLECTION_SIZE)
+            .disable_symbol("eval");
+
+        engine.register_fn("on", |context: rhai::NativeCallContext, material: &str, handler: &str| -> Result<(), Box<EvalAltResult>> {
+            let variant = find_variant(material).ok_or_else(|| format!("unknown material '{}'", material))?;
+            REGISTERED.with(|registered| registered.borrow_mut().push((variant, handler.to_owned(), context.call_position())));
+            Ok(())
+        });
+        engine.register_fn("get", |dx: INT, dy: INT| with_context(|context| Ok(context.cells[get_offset(dx, dy)?].name())));
+        engine.register_fn("above", || with_context(|context| Ok(context.cells[get_offset(-context.down.0 as INT, -context.down.1 as INT)?].name())));
+        engine.register_fn("below", || with_context(|context| Ok(context.cells[get_offset(context.down.0 as INT, context.down.1 as INT)?].name())));
+        engine.register_fn("touching", |name: &str| {
+            let cell = find_cell(name)?;
+            with_context(|context| Ok(context.cells.iter().enumerate().any(|(index, nearby)| index != 4 && *nearby == cell)))
+        });
+        engine.register_fn("temperature", || with_context(|context| Ok(context.temperature as FLOAT)));
+        engine.register_fn("chance", |percent: INT| with_context(|context| {
+            if !(0..=100).contains(&percent) {
+                return Err(format!("a chance must be from 0 to 100, not {}", percent));
+            }
+            Ok(context.rng.gen_range(0, 100) < percent)
+        }));
+        engine.register_fn("set", |dx: INT, dy: INT, name: &str| with_context(|context| {
+            let index = get_offset(dx, dy)?;
+            let product = match find_cell(name)? {
+                Nearby::Edge => return Err("the edge of the world can't be placed".to_owned()),
+                Nearby::Empty => None,
+                Nearby::Particle(variant) => Some(variant)
+            };
+            if context.cells[index] != Nearby::Edge {
+                context.commands.push(Command::Set((dx as isize, dy as isize), product));
+            }
+            Ok(())
+        }));
+        engine.register_fn("spawn", |name: &str| with_context(|context| {
+            let variant = find_variant(name).ok_or_else(|| format!("unknown material '{}'", name))?;
+            context.commands.push(Command::Spawn(variant));
+            Ok(())
+        }));
+        engine.register_fn("heat", heat);
+        engine.register_fn("heat", |amount: INT| heat(amount as FLOAT));
+        engine
+    })
+}
+
+// Parse (and load) a script, a Rhai program whose top-level statements register functions to run each frame for every particle
+// ... of a material, through `on`:
+// ...    on("Sand", "rust");
+// ...    fn rust() { if touching("Metal") && chance(5) { set(0, 0, "Rust"); } }
+// ... the script's syntax errors (or a problem registering it's handlers) are reported alongside the line they're on
+pub fn parse_script(text: &str) -> Result<Scripts, Vec<ScriptError>> {
+    let engine = engine();
+    let ast = engine.compile(text).map_err(|error| vec![ScriptError { line: error.1.line().unwrap_or(0), message: error.0.to_string() }])?;
+    REGISTERED.with(|registered| registered.borrow_mut().clear());
+    let result = engine.run_ast(&ast);
+    let registered = REGISTERED.with(|registered| std::mem::take(&mut *registered.borrow_mut()));
+    result.map_err(|error| vec![ScriptError::new(*error)])?;
+
+    // Every handler has to be a function of the script's own, taking no parameters
+    let functions = Arc::new(ast.clone_functions_only());
+    let mut errors = Vec::new();
+    let mut scripts = Scripts::default();
+    for (variant, name, position) in registered {
+        if !ast.iter_functions().any(|function| function.name == name && function.params.is_empty()) {
+            errors.push(ScriptError { line: position.line().unwrap_or(0), message: format!("there's no function {}() to handle {}", name, variant) });
+            continue;
+        }
+        scripts.merge(Scripts { handlers: vec![(variant, vec![Handler { ast: functions.clone(), name, has_failed: AtomicBool::new(false) }])], fingerprint: 0 });
+    }
+    scripts.fingerprint = text.bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+
+    if errors.is_empty() {
+        Ok(scripts)
+    } else {
+        Err(errors)
+    }
+}
+
+// Return every script file within the scripts folder alongside when it was last modified, sorted by path
+// ... comparing this against the previous scan spots scripts being added, edited or removed (see `load_scripts`)
+pub fn scan_scripts() -> Vec<(std::path::PathBuf, std::time::SystemTime)> {
+    let Ok(entries) = std::fs::read_dir(SCRIPTS_FOLDER) else {
+        return Vec::new();
+    };
+    let mut files: Vec<_> = entries.flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|extension| extension == SCRIPT_EXTENSION))
+        .filter_map(|entry| Some((entry.path(), entry.metadata().ok()?.modified().ok()?)))
+        .collect();
+    files.sort();
+    files
+}
+
+// Load and parse every scanned script file into one set of rules, or return every problem found across them
+pub fn load_scripts(files: &[(std::path::PathBuf, std::time::SystemTime)]) -> Result<Scripts, Vec<String>> {
+    let mut scripts = Scripts::default();
+    let mut problems = Vec::new();
+    for (path, _) in files {
+        match std::fs::read_to_string(path).map_err(|error| vec![error.to_string()]).and_then(|text| {
+            parse_script(&text).map_err(|errors| errors.iter().map(ScriptError::to_string).collect())
+        }) {
+            Ok(script) => scripts.merge(script),
+            Err(errors) => problems.extend(errors.into_iter().map(|error| format!("{} {}", path.display(), error)))
+        }
+    }
+    if problems.is_empty() {
+        Ok(scripts)
+    } else {
+        Err(problems)
+    }
+}
+
+// Return the particle at (px, py) and it's neighbours as a handler sees them (see `Context::cells`)
+fn look_around(world: &World, px: usize, py: usize) -> [Nearby; 9] {
+    std::array::from_fn(|index| {
+        let (x, y) = step(px, py, (index as isize % 3 - 1, index as isize / 3 - 1));
+        match world.get(x, y) {
+            None => Nearby::Edge,
+            Some(p) if p.active => Nearby::Particle(p.variant),
+            Some(_) => Nearby::Empty
+        }
+    })
+}
+
+// Run the handlers scripted for the particle at (px, py), until one of them turns it into something else
+// ... each handler sees the world as it was before it ran, and only if it finishes without an error are it's changes made
+pub(crate) fn run_scripts(scripts: &Scripts, world: &mut World, rng: &mut Rng, frame: u32, gravity: &Gravity, (px, py): (usize, usize)) {
+    let variant = world.variants[(px, py)];
+    let down = if *gravity == Gravity::Off { (0, 1) } else { gravity.get_vector() };
+    for handler in scripts.get_handlers(variant) {
+        if !world.is_active(px, py) || world.variants[(px, py)] != variant {
+            return;
+        }
+        let context = Context { cells: look_around(world, px, py), temperature: world.data[(px, py)].temperature, down, rng: Rng::new(rng.gen_range(0, u64::MAX)), commands: Vec::new() };
+        CONTEXT.with(|current| *current.borrow_mut() = Some(context));
+        let options = CallFnOptions::new().eval_ast(false);
+        let result = engine().call_fn_with_options::<Dynamic>(options, &mut Scope::new(), &handler.ast, &handler.name, ());
+        let Some(context) = CONTEXT.with(|current| current.borrow_mut().take()) else {
+            return;
+        };
+        if let Err(error) = result {
+            if !handler.has_failed.swap(true, Ordering::Relaxed) {
+                eprintln!("{}() failed handling {} (it's changes are skipped): {}", handler.name, variant, error);
+            }
+            continue;
+        }
+
+        for command in context.commands {
+            match command {
+                Command::Set(offset, product) => {
+                    let (x, y) = step(px, py, offset);
+                    match product {
+                        Some(product) => world.spawn(x, y, product),
+                        None if world.is_active(x, y) => destroy_particle(world, x, y, false),
+                        None => continue
+                    }
+                    if (x, y) != (px, py) {
+                        world.data[(x, y)].last_updated_frame = frame;
+                    }
+                },
+                Command::Spawn(spawned) => {
+                    if let Some((sx, sy)) = find_random_neighbour(world, rng, px, py, |p| !p.active) {
+                        world.spawn(sx, sy, spawned);
+                        world.data[(sx, sy)].last_updated_frame = frame;
+                    }
+                },
+                Command::Heat(amount) if world.is_active(px, py) => {
+                    let temperature = &mut world.data[(px, py)].temperature;
+                    *temperature = (*temperature + amount).clamp(MIN_TEMPERATURE, MAX_TEMPERATURE);
+                },
+                Command::Heat(_) => ()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scripts_run_their_handlers_and_report_mistakes() {
+        let scripts = parse_script(r#"
+            // Sand rusts any Metal it touches, then turns to Glass
+            on("Sand", "rust");
+            on("Sand", "melt");
+            fn rust() {
+                for dx in -1..=1 { for dy in -1..=1 { if get(dx, dy) == "Metal" { set(dx, dy, "Rust"); } } }
+            }
+            fn melt() {
+                if below() == "Empty" && chance(100) { set(0, 0, "Glass"); }
+            }
+        "#).ok().unwrap();
+
+        let mut particles = Particles::new(2, [
+            Particle::new(0, ParticleVariant::Sand, true), Particle::new(1, ParticleVariant::Sand, false),
+            Particle::new(2, ParticleVariant::Metal, true), Particle::new(3, ParticleVariant::Sand, false)
+        ]);
+        let mut world = World::new(&mut particles);
+        run_scripts(&scripts, &mut world, &mut Rng::new(0), 0, &Gravity::Down, (0, 0));
+        assert!(world.variants[(1, 0)] == ParticleVariant::Rust && world.variants[(0, 0)] == ParticleVariant::Glass);
+
+        // Mistakes are reported alongside their line
+        let line = |text: &str| parse_script(text).err().unwrap()[0].line;
+        assert_eq!(line("\nfn broken( {"), 2);
+        assert_eq!(line("on(\"Sand\", \"f\");\non(\"Sandd\", \"f\");\nfn f() {}"), 2);
+        assert_eq!(line("\n\non(\"Sand\", \"missing\");"), 3);
+
+        // A handler which fails (or runs forever) changes nothing, and NaN or infinite heat is refused
+        let scripts = parse_script(r#"
+            on("Glass", "fail");
+            on("Glass", "spin");
+            on("Glass", "overheat");
+            fn fail() { set(0, 0, "Sand"); set(2, 0, "Sand"); }
+            fn spin() { set(0, 0, "Sand"); loop {} }
+            fn overheat() { heat(10000); heat(parse_float("NaN")); }
+        "#).ok().unwrap();
+        run_scripts(&scripts, &mut world, &mut Rng::new(0), 0, &Gravity::Down, (0, 0));
+        assert!(world.variants[(0, 0)] == ParticleVariant::Glass && world.data[(0, 0)].temperature < MAX_TEMPERATURE);
+
+        // Heating stops at the hottest temperature a script can reach
+        let scripts = parse_script("on(\"Glass\", \"warm\");\nfn warm() { heat(10000); }").ok().unwrap();
+        for _ in 0..3 {
+            run_scripts(&scripts, &mut world, &mut Rng::new(0), 0, &Gravity::Down, (0, 0));
+        }
+        assert_eq!(world.data[(0, 0)].temperature, MAX_TEMPERATURE);
+    }
+}
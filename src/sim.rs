@@ -0,0 +1,1968 @@
+use macroquad::prelude::*;
+use std::collections::{HashSet, VecDeque};
+
+use crate::{behaviour::*, event::*, particle::*, script::*, world::*};
+
+// The temperature (in °C) that burning particles hold themselves at
+static BURN_TEMPERATURE: f32 = 700.0;
+
+// The temperature (in °C) that the world starts at, and that open air slowly returns to
+pub(crate) static AMBIENT_TEMPERATURE: f32 = 20.0;
+
+// How readily open air (inactive cells) conducts heat (0.0 - 1.0)
+pub(crate) static AIR_CONDUCTIVITY: f32 = 0.02;
+
+// How quickly open air returns to the ambient temperature (0.0 - 1.0)
+static AIR_COOLING: f32 = 0.02;
+
+// How much pressure is kept when diffusing each frame (the rest bleeds away)
+static PRESSURE_DECAY: f32 = 0.98;
+
+// How much pressure each gas particle pushes outwards with every frame
+static GAS_PRESSURE: f32 = 0.05;
+
+// How much pressure each liquid particle adds to the liquid below it
+static LIQUID_PRESSURE: f32 = 0.1;
+
+// How much pressure the centre of an explosion's shockwave creates
+static BLAST_PRESSURE: f32 = 40.0;
+
+// How often (in frames) throttled off-screen chunks are simulated, see `OffscreenMode`
+static OFFSCREEN_INTERVAL: u64 = 4;
+
+// How much faster (in cells per frame) airborne particles fall with every frame
+static GRAVITY: f32 = 0.15;
+
+// The fastest speed (in cells per frame) a particle can travel at
+static TERMINAL_VELOCITY: f32 = 6.0;
+
+// How much of a particle's sideways velocity is kept each frame while it slides along the ground (0.0 - 1.0)
+static FRICTION: f32 = 0.8;
+
+// How much of a particle's falling velocity is deflected sideways when it lands (0.0 - 1.0)
+static IMPACT_DEFLECTION: f32 = 0.3;
+
+// How much velocity an explosion's shockwave imparts on powders, per cell of radius
+static FLING_SPEED: f32 = 0.5;
+
+// How much more pressure a particle must be under than a free neighbouring cell before it's pushed into it
+static PRESSURE_PUSH_THRESHOLD: f32 = 2.0;
+
+// How many frames Cement must rest without moving before it hardens into Concrete
+pub(crate) static CEMENT_SET_FRAMES: u16 = 180;
+
+// How far (in cells) a Fan blows air out of either side of itself
+static FAN_REACH: usize = 12;
+
+// The wind strength (0.0 - 1.0) right beside a Fan, fading out with distance
+static FAN_STRENGTH: f32 = 0.8;
+
+// How far (in cells) a perfectly runny liquid scans sideways for somewhere lower to flow to, each frame
+pub(crate) static LIQUID_FLOW_DISTANCE: usize = 8;
+
+// How much of a rigid body's speed is kept (and reversed) when it bounces off something
+static BODY_BOUNCE: f32 = 0.3;
+
+// How much of a rigid body's speed along a surface is kept each frame it's touching that surface
+static BODY_FRICTION: f32 = 0.9;
+
+// How strongly the pressure around a rigid body pushes it (liquids buoy it up, explosions blast it away)
+static BODY_PRESSURE_PUSH: f32 = 1.0;
+
+// How far (in cells) a rigid body searches for somewhere to push the fluids it moves into
+static BODY_DISPLACE_REACH: isize = 64;
+
+// How many frames a powder stays wet for after it's last touched Water
+pub(crate) static WET_FRAMES: u16 = 600;
+
+// How many wet neighbours of it's own kind a wet powder needs to cling to, to hang in mid-air
+static WET_HOLD_NEIGHBOURS: usize = 2;
+
+// How many frames the weathering pass takes to visit the whole world (it visits every Nth column each frame, to spread out the cost)
+static WEATHERING_INTERVAL: usize = 30;
+
+// The width and height (in cells) of the chunks the world is divided into, so settled regions can skip simulation
+static CHUNK_SIZE: usize = 32;
+
+// The most liquid cells searched through when looking for somewhere to push a displaced liquid particle
+static LIQUID_DISPLACE_SEARCH: usize = 256;
+
+// How many frames the Flash left behind by a destroyed particle lingers for
+pub(crate) static FLASH_FRAMES: u16 = 4;
+
+// How many conductor cells a Spark can travel along before it dies out
+static SPARK_DISTANCE: u16 = 250;
+
+// The simulation's own seedable pseudo-random number generator (SplitMix64), so that the same seed (and inputs) always reproduces the same run
+pub struct Rng {
+    state: u64
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    // Return the next pseudo-random 64-bit number in the sequence
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // Return a pseudo-random number from `low` (inclusive) up to `high` (exclusive)
+    pub(crate) fn gen_range<T: RandomRange>(&mut self, low: T, high: T) -> T {
+        T::gen_range(self, low, high)
+    }
+}
+
+// A type the simulation's RNG can generate within a range
+pub(crate) trait RandomRange {
+    fn gen_range(rng: &mut Rng, low: Self, high: Self) -> Self;
+}
+
+macro_rules! impl_random_range {
+    ($($int:ty),*) => {$(
+        impl RandomRange for $int {
+            fn gen_range(rng: &mut Rng, low: $int, high: $int) -> $int {
+                let span = (high as i128 - low as i128).max(1) as u64;
+                (low as i128 + (rng.next_u64() % span) as i128) as $int
+            }
+        }
+    )*};
+}
+impl_random_range!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+impl RandomRange for f32 {
+    fn gen_range(rng: &mut Rng, low: f32, high: f32) -> f32 {
+        low + (high - low) * ((rng.next_u64() >> 40) as f32 / (1u64 << 24) as f32)
+    }
+}
+
+// The direction gravity pulls falling particles (powders and liquids) towards, which gases rise away from
+#[derive(Clone, PartialEq, Eq)]
+pub enum Gravity {
+    Down,
+    Left,
+    Up,
+    Right,
+    // Zero-g: nothing falls, nor rises
+    Off
+}
+
+impl Gravity {
+    // Return the (x, y) step of a single cell in the direction gravity pulls
+    pub fn get_vector(&self) -> (isize, isize) {
+        match self {
+            Gravity::Down  => (0, 1),
+            Gravity::Left  => (-1, 0),
+            Gravity::Up    => (0, -1),
+            Gravity::Right => (1, 0),
+            Gravity::Off   => (0, 0)
+        }
+    }
+
+    // Return the next gravity direction, rotating clockwise and finishing with zero-g
+    pub fn next(&self) -> Gravity {
+        match self {
+            Gravity::Down  => Gravity::Left,
+            Gravity::Left  => Gravity::Up,
+            Gravity::Up    => Gravity::Right,
+            Gravity::Right => Gravity::Off,
+            Gravity::Off   => Gravity::Down
+        }
+    }
+}
+
+impl std::fmt::Display for Gravity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Gravity::Down  => write!(f, "Down"),
+            Gravity::Left  => write!(f, "Left"),
+            Gravity::Up    => write!(f, "Up"),
+            Gravity::Right => write!(f, "Right"),
+            Gravity::Off   => write!(f, "Off")
+        }
+    }
+}
+
+// How the chunks of the world outside of the camera's view are simulated, from the most to the least work
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OffscreenMode {
+    // At the full rate, just like the chunks on-screen
+    Full,
+    // Only every `OFFSCREEN_INTERVAL`th frame
+    Throttled,
+    // Not at all, until they're back in view
+    Frozen,
+    // Not at all, with the ones far away stored away on disk until the camera comes back near them (see `ChunkStore`)
+    Stored
+}
+
+impl OffscreenMode {
+    // Return the next mode, cycling back round to the full rate
+    pub fn next(&self) -> OffscreenMode {
+        match self {
+            OffscreenMode::Full      => OffscreenMode::Throttled,
+            OffscreenMode::Throttled => OffscreenMode::Frozen,
+            OffscreenMode::Frozen    => OffscreenMode::Stored,
+            OffscreenMode::Stored    => OffscreenMode::Full
+        }
+    }
+}
+
+impl std::fmt::Display for OffscreenMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            OffscreenMode::Full      => write!(f, "Full rate"),
+            OffscreenMode::Throttled => write!(f, "Throttled"),
+            OffscreenMode::Frozen    => write!(f, "Frozen"),
+            OffscreenMode::Stored    => write!(f, "Stored on disk")
+        }
+    }
+}
+
+// How much of the simulation's quality is traded away to keep up under load, lowered and restored automatically by the render time
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Quality {
+    Full,
+    // Off-screen chunks are throttled
+    Reduced,
+    // Off-screen chunks are frozen
+    Low,
+    // Off-screen chunks are frozen, and the simulation steps at half the rate
+    Minimal
+}
+
+impl Quality {
+    // Return the next level down, or up
+    pub fn degrade(&self) -> Quality {
+        match self {
+            Quality::Full    => Quality::Reduced,
+            Quality::Reduced => Quality::Low,
+            _                => Quality::Minimal
+        }
+    }
+
+    pub fn restore(&self) -> Quality {
+        match self {
+            Quality::Minimal => Quality::Low,
+            Quality::Low     => Quality::Reduced,
+            _                => Quality::Full
+        }
+    }
+
+    // Return the least the chunks out of view are cut back to at this level (the user can always cut back further)
+    pub fn get_offscreen(&self) -> OffscreenMode {
+        match self {
+            Quality::Full                   => OffscreenMode::Full,
+            Quality::Reduced                => OffscreenMode::Throttled,
+            Quality::Low | Quality::Minimal => OffscreenMode::Frozen
+        }
+    }
+
+    // Return how many fixed-rate steps pass per frame simulated
+    pub fn get_tick_interval(&self) -> u32 {
+        match self {
+            Quality::Minimal => 2,
+            _                => 1
+        }
+    }
+}
+
+impl std::fmt::Display for Quality {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Quality::Full    => write!(f, "Full"),
+            Quality::Reduced => write!(f, "Reduced"),
+            Quality::Low     => write!(f, "Low"),
+            Quality::Minimal => write!(f, "Minimal")
+        }
+    }
+}
+
+// Return the position of the first of the 8 particles surrounding (x, y) which matches the predicate, if any
+pub fn find_neighbour(world: &World, x: usize, y: usize, predicate: impl Fn(ParticleRef) -> bool) -> Option<(usize, usize)> {
+    for nx in x.saturating_sub(1)..=x + 1 {
+        for ny in y.saturating_sub(1)..=y + 1 {
+            if nx == x && ny == y {
+                continue;
+            }
+            if let Some(neighbour) = world.get(nx, ny) {
+                if predicate(neighbour) {
+                    return Some((nx, ny));
+                }
+            }
+        }
+    }
+    None
+}
+
+// Return the position of a random one of the 8 particles surrounding (x, y) which matches the predicate, if any
+pub(crate) fn find_random_neighbour(world: &World, rng: &mut Rng, x: usize, y: usize, predicate: impl Fn(ParticleRef) -> bool) -> Option<(usize, usize)> {
+    let mut matches = Vec::new();
+    for nx in x.saturating_sub(1)..=x + 1 {
+        for ny in y.saturating_sub(1)..=y + 1 {
+            if nx == x && ny == y {
+                continue;
+            }
+            if let Some(neighbour) = world.get(nx, ny) {
+                if predicate(neighbour) {
+                    matches.push((nx, ny));
+                }
+            }
+        }
+    }
+    if matches.is_empty() {
+        return None;
+    }
+    Some(matches[rng.gen_range(0, matches.len())])
+}
+
+// Return true if any of the 8 particles surrounding (x, y) is a source of heat
+fn is_near_heat(world: &World, x: usize, y: usize) -> bool {
+    find_neighbour(world, x, y, |p| p.active && (p.variant.is_hot() || p.burning)).is_some()
+}
+
+// Return true if the particle at (x, y) is flammable, and either hot enough to ignite or touching a source of heat
+pub(crate) fn is_igniting(world: &World, x: usize, y: usize) -> bool {
+    world.variants[(x, y)].get_flammability().is_some_and(|f| world.data[(x, y)].temperature >= f.ignition || is_near_heat(world, x, y))
+}
+
+// Diffuse heat between every cell within the area and it's 4 direct neighbours, at the rate of the poorer conductor of each pair
+// ... open air slowly returns to the ambient temperature, while sources of heat (ie: Fire, Lava) hold their own temperature
+fn diffuse_heat(world: &mut World, area: &FieldArea) {
+    for x in 0..world.width() {
+        for y in area.rows(x, world.height()).flatten() {
+            let conductivity = world.particle(x, y).get_conductivity();
+            let mut temperature = world.data[(x, y)].temperature;
+            for (nx, ny) in [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)] {
+                if let Some(neighbour) = world.get(nx, ny) {
+                    temperature += (neighbour.temperature - temperature) * conductivity.min(neighbour.get_conductivity()) * 0.25;
+                }
+            }
+
+            let variant = world.variants[(x, y)];
+            if !world.is_active(x, y) {
+                temperature += (AMBIENT_TEMPERATURE - temperature) * AIR_COOLING;
+            } else if variant.is_hot() && variant.get_cooled_form().is_none() {
+                // Note: sources of heat which can cool into another phase (ie: Lava into Stone) are left to cool down naturally
+                temperature = variant.get_spawn_temperature().unwrap_or(temperature);
+            } else if world.data[(x, y)].burning {
+                temperature = temperature.max(BURN_TEMPERATURE);
+            }
+            world.data[(x, y)].temperature = temperature;
+        }
+    }
+}
+
+// Run the slow weathering pass over a slice of the world (every `WEATHERING_INTERVAL`th column, offset by the frame number)
+// ... particles exposed to their weathering agent have a chance to weather away (ie: Metal rusting in Water), see `get_weathering`
+fn weather(world: &mut World, rng: &mut Rng, frame: u64) {
+    for x in ((frame % WEATHERING_INTERVAL as u64) as usize..world.width()).step_by(WEATHERING_INTERVAL) {
+        let mut from = 0;
+        while let Some(y) = world.next_active(x, from) {
+            from = y + 1;
+            let Some(weathering) = world.variants[(x, y)].get_weathering() else { continue };
+            let is_exposed = find_neighbour(world, x, y, |p| p.active && p.variant == weathering.agent && (!weathering.needs_flow || p.rest == 0)).is_some();
+            if is_exposed && rng.gen_range(0, 100) < weathering.chance {
+                match weathering.product {
+                    Some(product) => world.spawn(x, y, product),
+                    None => destroy_particle(world, x, y, false)
+                }
+            }
+        }
+    }
+}
+
+// Recalculate which particles are powered: current flows out of every Battery, through any connected conductors
+// ... only Batteries and conductors can be powered, so only their cells are visited (found through the variants alone), and the
+// ... chunks where the power comes on or goes off are woken (see `World::mark_changed`)
+fn update_power(world: &mut World) {
+    let mut is_wired = [false; 256];
+    for variant in variants() {
+        is_wired[variant.index()] = variant == ParticleVariant::Battery || variant.conducts_current();
+    }
+    let height = world.height();
+    let (mut was_powered, mut frontier) = (Vec::new(), Vec::new());
+    for i in (0..world.variants.cells.len()).filter(|&i| is_wired[world.variants.cells[i].index()]) {
+        let (x, y) = (i / height, i % height);
+        if world.data[(x, y)].powered {
+            was_powered.push((x, y));
+        }
+        let is_battery = world.is_active(x, y) && world.variants[(x, y)] == ParticleVariant::Battery;
+        world.data[(x, y)].powered = is_battery;
+        if is_battery {
+            frontier.push((x, y));
+        }
+    }
+
+    let mut powered = frontier.clone();
+    while let Some((x, y)) = frontier.pop() {
+        for (nx, ny) in [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)] {
+            if world.get(nx, ny).is_some_and(|neighbour| neighbour.active && !neighbour.powered && neighbour.variant.conducts_current()) {
+                world.data[(nx, ny)].powered = true;
+                frontier.push((nx, ny));
+                powered.push((nx, ny));
+            }
+        }
+    }
+
+    // ... (`was_powered` is in column order, so it can be searched)
+    for &(x, y) in powered.iter().filter(|cell| was_powered.binary_search(cell).is_err()) {
+        world.mark_changed(x, y);
+    }
+    for (x, y) in was_powered {
+        if !world.data[(x, y)].powered {
+            world.mark_changed(x, y);
+        }
+    }
+}
+
+// Recalculate the light field: each cell holds how much further the light of it's nearest (or strongest) light source could reach
+// ... this is a cheap two-pass distance sweep (down-right, then up-left) rather than proper ray-casting, so light passes through walls
+pub fn update_light(world: &World, light: &mut Grid<f32>) {
+    for ((x, y), cell) in light.iter_mut() {
+        *cell = world.particle(x, y).get_light_reach();
+    }
+
+    let diagonal = std::f32::consts::SQRT_2;
+    let forwards = [(-1, 0, 1.0), (0, -1, 1.0), (-1, -1, diagonal), (1, -1, diagonal)];
+    let backwards = [(1, 0, 1.0), (0, 1, 1.0), (1, 1, diagonal), (-1, 1, diagonal)];
+    let sweep = |light: &mut Grid<f32>, x: usize, y: usize, neighbours: &[(isize, isize, f32)]| {
+        for &(dx, dy, cost) in neighbours {
+            let (nx, ny) = step(x, y, (dx, dy));
+            if let Some(&reach) = light.get(nx, ny) {
+                light[(x, y)] = light[(x, y)].max(reach - cost);
+            }
+        }
+    };
+    for x in 0..light.width() {
+        for y in 0..light.height() {
+            sweep(light, x, y, &forwards);
+        }
+    }
+    for x in (0..light.width()).rev() {
+        for y in (0..light.height()).rev() {
+            sweep(light, x, y, &backwards);
+        }
+    }
+}
+
+// The chunks the fields (and the chunks' fingerprints) are kept up to date within each frame: every chunk awake, and those next to
+// ... them, as nothing simulated can reach any further (besides changes made outside of the simulation's stripes, which are
+// ... tracked by the world, see `World::mark_changed`), so the fields of a settled world are left as they are
+struct FieldArea {
+    // Whether each chunk is within the area, or `None` when every chunk is (ie: while every chunk is awake, see `step_world`)
+    chunks: Option<Vec<Vec<bool>>>
+}
+
+impl FieldArea {
+    fn new(chunk_awake: &[Vec<bool>]) -> FieldArea {
+        if chunk_awake.is_empty() {
+            return FieldArea { chunks: None };
+        }
+        let is_awake = |cx: usize, cy: usize| chunk_awake.get(cx).and_then(|column| column.get(cy)).is_some_and(|&is_awake| is_awake);
+        let chunks = chunk_awake.iter().enumerate().map(|(cx, column)| (0..column.len()).map(|cy| {
+            (-1..=1).any(|dx| (-1..=1).any(|dy| {
+                let (nx, ny) = step(cx, cy, (dx, dy));
+                is_awake(nx, ny)
+            }))
+        }).collect()).collect();
+        FieldArea { chunks: Some(chunks) }
+    }
+
+    // Return whether the chunk (in the given chunk column and row) is within the area
+    fn contains(&self, cx: usize, cy: usize) -> bool {
+        self.chunks.as_ref().is_none_or(|chunks| chunks.get(cx).and_then(|column| column.get(cy)).is_some_and(|&is_within| is_within))
+    }
+
+    // Return the runs of rows of column x (of a world of the given height) within the area, from the top down
+    fn rows(&self, x: usize, height: usize) -> impl Iterator<Item = std::ops::Range<usize>> + '_ {
+        let (cx, rows) = (x / CHUNK_SIZE, height.div_ceil(CHUNK_SIZE));
+        let mut cy = 0;
+        std::iter::from_fn(move || {
+            while cy < rows && !self.contains(cx, cy) {
+                cy += 1;
+            }
+            let start = cy;
+            while cy < rows && self.contains(cx, cy) {
+                cy += 1;
+            }
+            (start < cy).then(|| start * CHUNK_SIZE..(cy * CHUNK_SIZE).min(height))
+        })
+    }
+}
+
+// Fingerprint the chunks which could have changed (those within the frame's field area, or changed from outside of the simulation),
+// ... waking those which did change (alongside their neighbours) and putting the rest to sleep
+// ... the chunk grids are rebuilt (fully awake) whenever the world changes size
+fn update_chunks(world: &mut World, fingerprints: &mut Vec<Vec<u64>>, awake: &mut Vec<Vec<bool>>) {
+    let width = world.width().div_ceil(CHUNK_SIZE);
+    let height = world.height().div_ceil(CHUNK_SIZE);
+    let resized = fingerprints.len() != width || fingerprints.first().is_some_and(|column| column.len() != height);
+    if resized {
+        *fingerprints = vec![vec![0; height]; width];
+        *awake = vec![vec![true; height]; width];
+    }
+
+    let area = FieldArea::new(awake);
+    let mut changed = vec![false; width * height];
+    for cx in 0..width {
+        for cy in 0..height {
+            // ... (every chunk's changes are taken, so none linger to wake it later)
+            let is_changed = world.take_changed(cx, cy);
+            if !area.contains(cx, cy) && !is_changed {
+                continue;
+            }
+            // ... only the active particles (and where they are) are hashed, as empty cells have nothing to change
+            let mut hash = 0u64;
+            for x in cx * CHUNK_SIZE..((cx + 1) * CHUNK_SIZE).min(world.width()) {
+                for y in world.active_rows(x, cy * CHUNK_SIZE..((cy + 1) * CHUNK_SIZE).min(world.height())) {
+                    hash = (hash ^ ((x as u64) << 32 | y as u64)).wrapping_mul(0x100000001b3);
+                    hash = (hash ^ world.particle(x, y).fingerprint()).wrapping_mul(0x100000001b3);
+                }
+            }
+            changed[cx * height + cy] = resized || is_changed || fingerprints[cx][cy] != hash;
+            fingerprints[cx][cy] = hash;
+        }
+    }
+
+    // A chunk stays awake while it, or any chunk next to it, is still changing (so activity can spill across chunk borders)
+    for (cx, column) in awake.iter_mut().enumerate() {
+        for (cy, is_awake) in column.iter_mut().enumerate() {
+            *is_awake = (-1..=1).any(|dx| (-1..=1).any(|dy| {
+                let (nx, ny) = step(cx, cy, (dx, dy));
+                nx < width && ny < height && changed[nx * height + ny]
+            }));
+        }
+    }
+}
+
+// Return true if the particle at (x, y) is held in place below and to either side (relative to gravity), with nothing but it's
+// ... own kind or empty space around it, so it has nothing to do until it's neighbourhood changes (the world's edges count as held)
+fn is_buried(world: &World, x: usize, y: usize, fall: (isize, isize), side: (isize, isize)) -> bool {
+    let variant = &world.variants[(x, y)];
+    let is_held = |offset| {
+        let (nx, ny) = step(x, y, offset);
+        world.get(nx, ny).is_none_or(|p| p.active)
+    };
+    let (sx, sy) = side;
+    is_held(fall) && is_held(side) && is_held((-sx, -sy)) && find_neighbour(world, x, y, |p| p.active && p.variant != *variant).is_none()
+}
+
+// Return a cheap hash of the 3x3 neighbourhood around (x, y) and the direction of gravity, which changes whenever anything
+// ... a sleeping particle could react to does
+fn neighbourhood_fingerprint(world: &World, x: usize, y: usize, gravity: &Gravity) -> u64 {
+    let mut hash = 0xcbf29ce484222325 ^ gravity.get_vector().0 as u64 ^ (gravity.get_vector().1 as u64) << 8;
+    for nx in x.saturating_sub(1)..=x + 1 {
+        for ny in y.saturating_sub(1)..=y + 1 {
+            if let Some(p) = world.get(nx, ny) {
+                let state = [p.active as u64, p.id as u64, p.variant.index() as u64, p.temperature.round() as i64 as u64, p.burning as u64];
+                hash = state.iter().fold(hash, |hash, value| (hash ^ value).wrapping_mul(0x100000001b3));
+            }
+        }
+    }
+    hash
+}
+
+// Return every in-bounds cell within a circular radius of (cx, cy), alongside its distance from the centre (0.0 - 1.0)
+// ... this is the engine's area-effect primitive: explosions, heat, etc should all be built on top of it!
+pub(crate) fn cells_in_radius(world: &World, cx: usize, cy: usize, radius: usize) -> Vec<(usize, usize, f32)> {
+    let mut cells = Vec::new();
+    let radius_sq = (radius * radius) as isize;
+    for x in cx.saturating_sub(radius)..=cx + radius {
+        for y in cy.saturating_sub(radius)..=cy + radius {
+            let (dx, dy) = (x as isize - cx as isize, y as isize - cy as isize);
+            let dist_sq = dx * dx + dy * dy;
+            if dist_sq <= radius_sq && x < world.width() && y < world.height() {
+                cells.push((x, y, (dist_sq as f32).sqrt() / radius.max(1) as f32));
+            }
+        }
+    }
+    cells
+}
+
+// Move the active particle at (x, y) into the empty cell at (x_new, y_new)
+// ... the particle (and all of it's state) simply swaps places with the empty cell, which is left behind as thin air
+pub(crate) fn move_particle(world: &mut World, x: usize, y: usize, x_new: usize, y_new: usize) {
+    world.swap((x, y), (x_new, y_new));
+    world.data[(x_new, y_new)].rest = 0;
+    world.deactivate(x, y);
+    world.data[(x, y)].reset_state();
+}
+
+// Step the Spark at (x, y) along it's conductor path, returning the ID of the particle it travelled into (if any)
+// ... a freshly painted Spark jumps into a neighbouring conductor (or fizzles out), while a travelling Spark
+// ... keeps moving forwards (never backwards) until it runs out of life or reaches a dead-end, restoring the conductor behind it.
+pub(crate) fn step_spark(world: &mut World, x: usize, y: usize) -> Option<(usize, usize)> {
+    let (direction, life) = (world.data[(x, y)].direction, world.data[(x, y)].life);
+    let is_travelling = direction != (0, 0);
+
+    // Find the next conductor: prefer continuing straight ahead, otherwise any conductor that isn't behind us
+    let (dx, dy) = (direction.0 as isize, direction.1 as isize);
+    let ahead = ((x as isize + dx) as usize, (y as isize + dy) as usize);
+    let is_ahead_conductive = is_travelling && world.get(ahead.0, ahead.1).is_some_and(|p| p.active && p.variant.is_conductive());
+    let next = if is_ahead_conductive {
+        Some(ahead)
+    } else {
+        find_neighbour(world, x, y, |p| p.active && p.variant.is_conductive())
+            .filter(|(nx, ny)| (*nx as isize - x as isize) * dx + (*ny as isize - y as isize) * dy >= 0)
+    };
+
+    // Restore the conductor we were travelling through (or vanish, if we were never in one)
+    if is_travelling {
+        world.spawn(x, y, ParticleVariant::Metal);
+    } else {
+        world.deactivate(x, y);
+    }
+
+    // Travel into the next conductor, if we've still got the energy for it
+    let (nx, ny) = next?;
+    if is_travelling && life == 0 {
+        return None;
+    }
+    world.spawn(nx, ny, ParticleVariant::Spark);
+    world.data[(nx, ny)].direction = ((nx as isize - x as isize) as i8, (ny as isize - y as isize) as i8);
+    world.data[(nx, ny)].life = if is_travelling { life - 1 } else { SPARK_DISTANCE };
+    Some((nx, ny))
+}
+
+// Sink the particle at (x, y) into the lighter fluid at (x_new, y_new)
+// ... a displaced liquid is pushed out to the nearest free cell along it's body's surface (so sealed bodies of liquid spurt out of any
+// ... openings, rather than being buried), otherwise the two particles simply swap places
+fn sink_particle(world: &mut World, x: usize, y: usize, x_new: usize, y_new: usize) {
+    if world.variants[(x_new, y_new)].get_state() == MatterState::Liquid {
+        if let Some((sx, sy)) = find_liquid_surface(world, x_new, y_new) {
+            move_particle(world, x_new, y_new, sx, sy);
+            move_particle(world, x, y, x_new, y_new);
+            return;
+        }
+    }
+    swap_particles(world, x, y, x_new, y_new)
+}
+
+// Search outwards through the body of liquid containing (x, y) for the nearest free cell touching it, if any are close enough
+fn find_liquid_surface(world: &World, x: usize, y: usize) -> Option<(usize, usize)> {
+    let mut visited = HashSet::from([(x, y)]);
+    let mut frontier = VecDeque::from([(x, y)]);
+    while let Some((cx, cy)) = frontier.pop_front() {
+        for (nx, ny) in [(cx, cy.wrapping_sub(1)), (cx.wrapping_sub(1), cy), (cx + 1, cy), (cx, cy + 1)] {
+            let Some(cell) = world.get(nx, ny) else { continue };
+            if !visited.insert((nx, ny)) {
+                continue;
+            }
+            if !cell.active {
+                return Some((nx, ny));
+            }
+            if cell.variant.get_state() == MatterState::Liquid && visited.len() < LIQUID_DISPLACE_SEARCH {
+                frontier.push_back((nx, ny));
+            }
+        }
+    }
+    None
+}
+
+// Swap the particle at (x, y) with the particle at (x_new, y_new)
+fn swap_particles(world: &mut World, x: usize, y: usize, x_new: usize, y_new: usize) {
+    world.swap((x, y), (x_new, y_new));
+    world.data[(x_new, y_new)].rest = 0;
+    world.data[(x, y)].rest = 0;
+}
+
+// Destroy the particle at (x, y), optionally leaving a brief Flash of light in it's place
+pub(crate) fn destroy_particle(world: &mut World, x: usize, y: usize, flash: bool) {
+    world.record(Event::ParticleDestroyed { position: (x, y), variant: world.variants[(x, y)] });
+    if flash {
+        world.spawn(x, y, ParticleVariant::Flash);
+    } else {
+        world.deactivate(x, y);
+    }
+}
+
+// Detonate an explosion centred on (cx, cy), with a power (0.0 - 1.0) deciding how much of the blast is blown clean away:
+// ... everything within the inner `power` of the radius is destroyed, leaving a crater, while the rest of the blast becomes Fire
+// ... towards the core and Smoke towards the edge, and loose powders just outside of the blast are flung away from it.
+// Brick is indestructible, and anything flammable caught outside of the crater ignites (allowing chain-reactions).
+pub(crate) fn explode(world: &mut World, pressure: &mut Grid<f32>, cx: usize, cy: usize, radius: usize, power: f32) {
+    world.record(Event::ExplosionTriggered { position: (cx, cy), radius });
+    let fire_reach = power + (1.0 - power) / 2.0;
+    for (x, y, dist) in cells_in_radius(world, cx, cy, radius) {
+        let (active, variant) = (world.is_active(x, y), world.variants[(x, y)]);
+        if active && variant == ParticleVariant::Brick {
+            continue;
+        }
+        if dist < power {
+            if active {
+                destroy_particle(world, x, y, false);
+            }
+        } else if dist < fire_reach || (active && variant.get_flammability().is_some()) {
+            world.spawn(x, y, ParticleVariant::Fire);
+        } else {
+            world.spawn(x, y, ParticleVariant::Smoke);
+        }
+    }
+    fling_powders(world, cx, cy, radius * 2);
+    add_blast_pressure(world, pressure, cx, cy, radius * 2);
+}
+
+// Add a shockwave of pressure within a radius of (cx, cy), strongest at the centre, which then ripples outwards via the pressure field
+fn add_blast_pressure(world: &World, pressure: &mut Grid<f32>, cx: usize, cy: usize, radius: usize) {
+    for (x, y, dist) in cells_in_radius(world, cx, cy, radius) {
+        pressure[(x, y)] += BLAST_PRESSURE * (1.0 - dist);
+    }
+}
+
+// Update the pressure field within the area: pressure diffuses between neighbouring non-solid cells while slowly bleeding away,
+// ... gases constantly push outwards, and liquids press down with the weight of the liquid above them
+fn update_pressure(world: &World, pressure: &mut Grid<f32>, area: &FieldArea) {
+    let is_liquid = |x, y| world.is_active(x, y) && world.variants[(x, y)].get_state() == MatterState::Liquid;
+    for x in 0..world.width() {
+        for rows in area.rows(x, world.height()) {
+            // ... a run of rows starting partway down the column carries on with the weight of the liquid above it
+            let mut liquid_depth = (0..rows.start).rev().take_while(|&y| is_liquid(x, y)).count() as f32;
+            for y in rows {
+                let ptr = world.particle(x, y);
+                let state = ptr.variant.get_state();
+
+                // Solids are walls: pressure can't exist within them
+                if ptr.active && state == MatterState::Solid {
+                    pressure[(x, y)] = 0.0;
+                    liquid_depth = 0.0;
+                    continue;
+                }
+
+                // Average with our non-solid neighbours
+                let mut total = pressure[(x, y)];
+                let mut count = 1.0;
+                for (nx, ny) in [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)] {
+                    if let Some(neighbour) = world.get(nx, ny) {
+                        if !neighbour.active || neighbour.variant.get_state() != MatterState::Solid {
+                            total += pressure[(nx, ny)];
+                            count += 1.0;
+                        }
+                    }
+                }
+                let mut cell_pressure = (total / count) * PRESSURE_DECAY;
+
+                // Add the pressure sources
+                if ptr.active && state == MatterState::Gas {
+                    cell_pressure += GAS_PRESSURE;
+                }
+                if ptr.active && state == MatterState::Liquid {
+                    liquid_depth += 1.0;
+                    cell_pressure = cell_pressure.max(liquid_depth * LIQUID_PRESSURE);
+                } else {
+                    liquid_depth = 0.0;
+                }
+                pressure[(x, y)] = cell_pressure;
+            }
+        }
+    }
+}
+
+// Recalculate the wind field within the area: the global wind blows everywhere, while Fans blow outwards from either side (until
+// ... blocked by a solid), so every Fan which can reach a chunk awake is within the area (as they reach less than a chunk)
+fn update_wind(world: &World, wind: &mut Grid<f32>, global_wind: f32, area: &FieldArea) {
+    let height = world.height();
+    for x in 0..world.width() {
+        for rows in area.rows(x, height) {
+            wind.cells[x * height + rows.start..x * height + rows.end].fill(global_wind);
+        }
+    }
+
+    for x in 0..world.width() {
+        for y in area.rows(x, height).flat_map(|rows| world.active_rows(x, rows)) {
+            if world.variants[(x, y)] != ParticleVariant::Fan {
+                continue;
+            }
+            // Blow leftwards out of our left side, and rightwards out of our right side
+            for sign in [-1isize, 1] {
+                for distance in 1..=FAN_REACH {
+                    let nx = x.wrapping_add_signed(sign * distance as isize);
+                    let Some(cell) = world.get(nx, y) else { break };
+                    if cell.active && cell.variant.get_state() == MatterState::Solid {
+                        break;
+                    }
+                    let strength = FAN_STRENGTH * (1.0 - distance as f32 / (FAN_REACH + 1) as f32);
+                    wind[(nx, y)] = (wind[(nx, y)] + strength * sign as f32).clamp(-1.0, 1.0);
+                }
+            }
+        }
+    }
+}
+
+// Return the free neighbouring cell of (x, y) with the lowest pressure, if any
+fn find_lowest_pressure_neighbour(world: &World, pressure: &Grid<f32>, x: usize, y: usize) -> Option<(usize, usize)> {
+    let mut lowest: Option<(usize, usize)> = None;
+    for nx in x.saturating_sub(1)..=x + 1 {
+        for ny in y.saturating_sub(1)..=y + 1 {
+            if world.is_free(nx, ny) && lowest.is_none_or(|(lx, ly)| pressure[(nx, ny)] < pressure[(lx, ly)]) {
+                lowest = Some((nx, ny));
+            }
+        }
+    }
+    lowest
+}
+
+// Fling powders within a shockwave radius of (cx, cy) outwards by giving them velocity, stronger the closer they are to the centre
+fn fling_powders(world: &mut World, cx: usize, cy: usize, radius: usize) {
+    for (x, y, dist) in cells_in_radius(world, cx, cy, radius) {
+        if !world.is_active(x, y) || world.variants[(x, y)].get_state() != MatterState::Powder {
+            continue;
+        }
+        let power = (1.0 - dist) * radius as f32 * FLING_SPEED;
+        let dir_x = (x as f32 - cx as f32).signum();
+        // Bias the fling upwards, so that powders 'pop' out of the crater and arc back down
+        let ptr = &mut world.data[(x, y)];
+        ptr.velocity.0 = (ptr.velocity.0 + dir_x * power).clamp(-TERMINAL_VELOCITY, TERMINAL_VELOCITY);
+        ptr.velocity.1 = (ptr.velocity.1 - power).clamp(-TERMINAL_VELOCITY, TERMINAL_VELOCITY);
+    }
+}
+
+// Trace the path of the particle at (x, y) along it's velocity, returning the furthest free cell it reaches this frame,
+// ... and whether it was stopped short by an obstruction (or the edge of the world)
+fn trace_velocity(world: &World, x: usize, y: usize) -> ((usize, usize), bool) {
+    let (vx, vy) = world.data[(x, y)].velocity;
+    let steps = vx.abs().max(vy.abs()).ceil() as usize;
+    let mut last = (x, y);
+    for step in 1..=steps {
+        let progress = step as f32 / steps as f32;
+        let (tx, ty) = ((x as f32 + vx * progress).round(), (y as f32 + vy * progress).round());
+        if tx < 0.0 || ty < 0.0 {
+            return (last, true);
+        }
+        let (tx, ty) = (tx as usize, ty as usize);
+        if (tx, ty) == last {
+            continue;
+        }
+        if !world.is_free(tx, ty) {
+            return (last, true);
+        }
+        last = (tx, ty);
+    }
+    (last, false)
+}
+
+// Find where the gas at (x, y) drifts to: a random walk, biased towards `rise` (wandering sideways as it goes) by it's buoyancy
+// ... so gases slowly diffuse to fill whatever space they're released into
+fn diffuse_gas(world: &World, rng: &mut Rng, x: usize, y: usize, rise: (isize, isize)) -> Option<(usize, usize)> {
+    let side = (rise.1.abs(), rise.0.abs());
+    let (dx, dy) = if rng.gen_range(0, 100) < world.variants[(x, y)].get_buoyancy() {
+        let wander = rng.gen_range(-1, 2);
+        (rise.0 + side.0 * wander, rise.1 + side.1 * wander)
+    } else {
+        [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)][rng.gen_range(0, 8)]
+    };
+    let (tx, ty) = step(x, y, (dx, dy));
+    world.is_free(tx, ty).then_some((tx, ty))
+}
+
+// Find where the liquid at (x, y) flows to along it's surface, scanning up to `distance` cells to either side (in a random order)
+// ... a cell with a drop beneath it is taken straight away (so liquids pour off ledges), otherwise the furthest free cell is chosen (so liquids level out)
+fn flow_liquid(world: &World, rng: &mut Rng, x: usize, y: usize, fall: (isize, isize), side: (isize, isize), distance: usize) -> Option<(usize, usize)> {
+    let first = if rng.gen_range(0, 2) == 0 { -1 } else { 1 };
+    let mut furthest = [None, None];
+    for (i, direction) in [first, -first].into_iter().enumerate() {
+        for d in 1..=distance as isize {
+            let (fx, fy) = step(x, y, (side.0 * direction * d, side.1 * direction * d));
+            if !world.is_free(fx, fy) {
+                break;
+            }
+            let (dx, dy) = step(fx, fy, fall);
+            if world.is_free(dx, dy) {
+                return Some((fx, fy));
+            }
+            furthest[i] = Some((fx, fy));
+        }
+    }
+    furthest[0].or(furthest[1])
+}
+
+// Find where the resting powder at (x, y) slips to, trying either side (in a random order) for a column at least `steepness` cells lower
+// ... the particle slips diagonally down into that column, or simply sideways for powders which flatten out completely
+fn find_slip(world: &World, rng: &mut Rng, x: usize, y: usize, fall: (isize, isize), side: (isize, isize), steepness: u8) -> Option<(usize, usize)> {
+    let first = if rng.gen_range(0, 2) == 0 { -1 } else { 1 };
+    [first, -first].into_iter().find_map(|direction| {
+        let (sx, sy) = step(x, y, (side.0 * direction, side.1 * direction));
+        let is_open = (0..=steepness as isize).all(|depth| {
+            let (cx, cy) = step(sx, sy, (fall.0 * depth, fall.1 * depth));
+            world.is_free(cx, cy)
+        });
+        is_open.then(|| if steepness == 0 { (sx, sy) } else { step(sx, sy, fall) })
+    })
+}
+
+// The shape of a rigid body
+#[derive(Clone, Copy, PartialEq)]
+pub enum BodyShape {
+    // A box of the given width and height (in cells)
+    Box(usize, usize),
+    // A ball of the given radius (in cells)
+    Ball(usize)
+}
+
+// The width and height (in cells) of the rigid box the sandbox drops, and the radius of it's rigid ball: the biggest bodies
+// ... another player can drop (see `parse_remote_input`)
+pub static BODY_BOX_SIZE: usize = 8;
+pub static BODY_BALL_RADIUS: usize = 4;
+
+// A solid object which moves as one piece: it's stamped into the world as Body cells each frame, so particles collide with (and rest on) it
+pub struct RigidBody {
+    shape: BodyShape,
+    // The position (in cells) of the body's top-left corner
+    pub position: (f32, f32),
+    // The velocity (in cells per frame) the body is travelling at, along the X and Y axis
+    velocity: (f32, f32),
+    // The cells this body was stamped into last frame
+    pub(crate) cells: Vec<(usize, usize)>
+}
+
+impl RigidBody {
+    pub fn new(shape: BodyShape, x: f32, y: f32) -> RigidBody {
+        RigidBody { shape, position: (x, y), velocity: (0.0, 0.0), cells: Vec::new() }
+    }
+
+    // Return the cells this body would cover with it's top-left corner at (x, y), which may lie outside of the world
+    pub fn footprint(&self, (x, y): (f32, f32)) -> Vec<(isize, isize)> {
+        let (ox, oy) = (x.round() as isize, y.round() as isize);
+        match self.shape {
+            BodyShape::Box(width, height) => {
+                (0..width as isize).flat_map(|dx| (0..height as isize).map(move |dy| (ox + dx, oy + dy))).collect()
+            },
+            BodyShape::Ball(radius) => {
+                let radius = radius as isize;
+                (-radius..=radius).flat_map(|dx| (-radius..=radius).map(move |dy| (dx, dy)))
+                    .filter(|(dx, dy)| dx * dx + dy * dy <= radius * radius)
+                    .map(|(dx, dy)| (ox + radius + dx, oy + radius + dy))
+                    .collect()
+            }
+        }
+    }
+}
+
+// Return true if any of the cells lie outside of the world, or are taken by something a rigid body can't push through (solids, powders, and other bodies)
+pub fn is_footprint_blocked(world: &World, cells: &[(isize, isize)]) -> bool {
+    cells.iter().any(|&(x, y)| {
+        x < 0 || y < 0 || world.get(x as usize, y as usize)
+            .is_none_or(|p| p.active && matches!(p.variant.get_state(), MatterState::Solid | MatterState::Powder))
+    })
+}
+
+// Step every rigid body: lift it out of the world, move it under gravity and pressure (colliding with the grid a cell at a time),
+// ... then stamp it back into the world, pushing any fluids in it's way out past it's top edge
+fn step_bodies(world: &mut World, pressure: &Grid<f32>, bodies: &mut [RigidBody], gravity: &Gravity) {
+    let (gx, gy) = gravity.get_vector();
+    for body in bodies.iter_mut() {
+        // Lift the body out of the world, so that it doesn't collide with itself
+        for &(x, y) in &body.cells {
+            if world.is_active(x, y) && world.variants[(x, y)] == ParticleVariant::Body {
+                world.deactivate(x, y);
+            }
+        }
+
+        // Accelerate under gravity, and away from the pressure pushing on our edges
+        let footprint = body.footprint(body.position);
+        let mass = footprint.len().max(1) as f32;
+        let mut force = (gx as f32 * GRAVITY, gy as f32 * GRAVITY);
+        for &(x, y) in &footprint {
+            for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || footprint.contains(&(nx, ny)) {
+                    continue;
+                }
+                if let Some(cell_pressure) = pressure.get(nx as usize, ny as usize) {
+                    force.0 -= dx as f32 * cell_pressure * BODY_PRESSURE_PUSH / mass;
+                    force.1 -= dy as f32 * cell_pressure * BODY_PRESSURE_PUSH / mass;
+                }
+            }
+        }
+        body.velocity.0 = (body.velocity.0 + force.0).clamp(-TERMINAL_VELOCITY, TERMINAL_VELOCITY);
+        body.velocity.1 = (body.velocity.1 + force.1).clamp(-TERMINAL_VELOCITY, TERMINAL_VELOCITY);
+
+        // Move along each axis in turn, a cell at a time (so we can't tunnel through thin walls), bouncing off whatever we hit
+        for axis in 0..2 {
+            let mut remaining = if axis == 0 { body.velocity.0 } else { body.velocity.1 };
+            while remaining != 0.0 {
+                let delta = remaining.clamp(-1.0, 1.0);
+                let next = if axis == 0 { (body.position.0 + delta, body.position.1) } else { (body.position.0, body.position.1 + delta) };
+                if is_footprint_blocked(world, &body.footprint(next)) {
+                    // The impact bounces us back along this axis, while the surface drags on the other
+                    if axis == 0 {
+                        body.velocity = (body.velocity.0 * -BODY_BOUNCE, body.velocity.1 * BODY_FRICTION);
+                    } else {
+                        body.velocity = (body.velocity.0 * BODY_FRICTION, body.velocity.1 * -BODY_BOUNCE);
+                    }
+                    break;
+                }
+                body.position = next;
+                remaining -= delta;
+            }
+        }
+
+        // Stamp the body back into the world, pushing any fluids in the way out past our top edge
+        let footprint = body.footprint(body.position);
+        let rise = if gravity == &Gravity::Off { (0, -1) } else { (-gx, -gy) };
+        body.cells.clear();
+        for &(x, y) in &footprint {
+            let (x, y) = (x as usize, y as usize);
+            let Some(cell) = world.get(x, y) else { continue };
+            if cell.active {
+                if matches!(cell.variant.get_state(), MatterState::Solid | MatterState::Powder) {
+                    continue;
+                }
+                let target = (1..=BODY_DISPLACE_REACH)
+                    .map(|distance| step(x, y, (rise.0 * distance, rise.1 * distance)))
+                    .take_while(|&(tx, ty)| world.get(tx, ty).is_some())
+                    .find(|&(tx, ty)| !world.is_active(tx, ty) && !footprint.contains(&(tx as isize, ty as isize)));
+                match target {
+                    Some((tx, ty)) => move_particle(world, x, y, tx, ty),
+                    None => destroy_particle(world, x, y, false)
+                }
+            }
+            world.place(x, y, ParticleVariant::Body);
+            body.cells.push((x, y));
+        }
+    }
+}
+
+// Simulate a single particle for one frame, marking any particle it spawns as already updated within `frame`
+fn update_particle(world: &mut World, pressure: &mut Grid<f32>, wind: &Grid<f32>, rng: &mut Rng, context: &FrameContext, (px, py): (usize, usize)) {
+    let (frame, gravity) = (context.frame, context.gravity);
+
+    // Remember who lives here, so we can tell if they're still resting here after the update
+    let resting_id = world.data[(px, py)].id;
+
+    // Short-lived particles (ie: Fire burning out into Smoke, Smoke dissipating) decay once their lifetime runs out
+    match (world.variants[(px, py)].get_decay(), world.data[(px, py)].lifetime) {
+        (None, _) => world.data[(px, py)].lifetime = None,
+        (Some(decay), None) => world.data[(px, py)].lifetime = Some(rng.gen_range(decay.lifetime.0, decay.lifetime.1 + 1)),
+        (Some(decay), Some(0)) => match decay.product {
+            Some(product) => world.spawn(px, py, product),
+            None => {
+                destroy_particle(world, px, py, false);
+                return;
+            }
+        },
+        (Some(_), Some(lifetime)) => world.data[(px, py)].lifetime = Some(lifetime - 1)
+    }
+
+    // Run the particle's own behaviour (see `ParticleVariant::get_behaviour`), reacting to it's surroundings
+    if let Some(behaviour) = world.variants[(px, py)].get_behaviour() {
+        behaviour(Ctx { world, pressure, rng, frame, gravity, position: (px, py) });
+    }
+
+    // Run any handlers scripted for the particle's variant
+    if !context.scripts.is_empty() {
+        run_scripts(context.scripts, world, rng, frame, gravity, (px, py));
+    }
+
+    // Flammable particles catch alight once ignited, licking Fire (and a little Smoke) into the air around them until their fuel runs out
+    if let Some(flammability) = world.variants[(px, py)].get_flammability() {
+        if !world.data[(px, py)].burning && is_igniting(world, px, py) {
+            world.data[(px, py)].burning = true;
+            world.data[(px, py)].fuel = flammability.burn_frames;
+        }
+        if world.data[(px, py)].burning {
+            let exhaust = match rng.gen_range(0, 100) {
+                0..=19 => Some(ParticleVariant::Fire),
+                20..=22 => Some(ParticleVariant::Smoke),
+                _ => None
+            };
+            if let Some(exhaust) = exhaust {
+                if let Some((ex, ey)) = find_random_neighbour(world, rng, px, py, |p| !p.active) {
+                    world.spawn(ex, ey, exhaust);
+                    world.data[(ex, ey)].last_updated_frame = frame;
+                }
+            }
+            if world.data[(px, py)].fuel > 0 {
+                world.data[(px, py)].fuel -= 1;
+            } else {
+                match flammability.ash {
+                    Some(ash) => world.spawn(px, py, ash),
+                    None => destroy_particle(world, px, py, false)
+                }
+            }
+        }
+    }
+
+    // Powders soak up any Water they touch, slowly drying out again once they're apart
+    if world.variants[(px, py)].get_wet_cohesion() > 0 {
+        if find_neighbour(world, px, py, |p| p.active && p.variant == ParticleVariant::Water).is_some() {
+            world.data[(px, py)].wetness = WET_FRAMES;
+        } else {
+            world.data[(px, py)].wetness = world.data[(px, py)].wetness.saturating_sub(1);
+        }
+    }
+
+    // Particles transform once heated or cooled past their transition temperatures (ie: Ice <---> Water <---> Steam)
+    // Note: the variant is swapped directly (rather than spawned) so the particle keeps it's current temperature
+    let temperature = world.data[(px, py)].temperature;
+    if let Some((heated, threshold)) = world.variants[(px, py)].get_heated_form() {
+        if temperature >= threshold {
+            world.variants[(px, py)] = heated;
+        }
+    }
+    if let Some((cooled, threshold)) = world.variants[(px, py)].get_cooled_form() {
+        if temperature <= threshold {
+            world.variants[(px, py)] = cooled;
+        }
+    }
+
+    // Track how long particles have been touching their reactant, slowly 'forgetting' once they're apart
+    if let Some(reaction) = world.variants[(px, py)].get_contact_reaction() {
+        if let Some((rx, ry)) = find_neighbour(world, px, py, |p| p.active && p.variant == reaction.reactant) {
+            world.data[(px, py)].contact += 1;
+            if world.data[(px, py)].contact >= reaction.frames {
+                world.record(Event::ReactionOccurred { position: (px, py), reactants: (world.variants[(px, py)], reaction.reactant) });
+                world.spawn(px, py, reaction.product);
+                if let Some(reactant_product) = reaction.reactant_product {
+                    world.spawn(rx, ry, reactant_product);
+                }
+            }
+        } else {
+            world.data[(px, py)].contact = world.data[(px, py)].contact.saturating_sub(1);
+        }
+    }
+
+    // React with a random neighbour, if the reaction registry has a reaction between us
+    if world.variants[(px, py)].is_reactive() {
+        let variant = world.variants[(px, py)];
+        if let Some((nx, ny)) = find_random_neighbour(world, rng, px, py, |p| p.active && get_reaction(&variant, &p.variant).is_some()) {
+            if let Some((product, neighbour_product, chance)) = get_reaction(&variant, &world.variants[(nx, ny)]) {
+                if rng.gen_range(0, 100) < chance {
+                    world.record(Event::ReactionOccurred { position: (px, py), reactants: (variant, world.variants[(nx, ny)]) });
+                    for ((rx, ry), product) in [((px, py), product), ((nx, ny), neighbour_product)] {
+                        match product {
+                            Some(product) => world.spawn(rx, ry, product),
+                            None => destroy_particle(world, rx, ry, false)
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Work out which way is 'down' for falling particles, and which way is 'sideways' to it
+    // ... in zero-g, gases wander in any direction and falling particles measure their motion as if gravity were down
+    let has_gravity = *gravity != Gravity::Off;
+    let (gx, gy) = gravity.get_vector();
+    let rise = if has_gravity {
+        (-gx, -gy)
+    } else {
+        [(0, 1), (0, -1), (1, 0), (-1, 0)][rng.gen_range(0, 4)]
+    };
+
+    // Gases diffuse outwards to fill the space around them, drifting upwards as they go
+    if world.is_active(px, py) && world.variants[(px, py)].get_state() == MatterState::Gas {
+        if let Some((x_new, y_new)) = diffuse_gas(world, rng, px, py, rise) {
+            move_particle(world, px, py, x_new, y_new);
+        }
+    }
+
+    // Only process falling particles (powders and liquids) here
+    let state = world.variants[(px, py)].get_state();
+    let (fall, side) = if has_gravity { ((gx, gy), (gy.abs(), gx.abs())) } else { ((0, 1), (1, 0)) };
+    if world.is_active(px, py) && (state == MatterState::Powder || state == MatterState::Liquid) {
+        let (bx, by) = step(px, py, fall);
+        let below = if has_gravity { world.get(bx, by) } else { None };
+        let is_below_free = has_gravity && world.is_free(bx, by);
+        let is_below_lighter = below.is_some_and(|p| p.active && world.variants[(px, py)].can_displace(&p.variant));
+
+        // Wet powders cling to their wet neighbours, holding up overhangs until they dry out (or crumble away)
+        // ... the less cohesive the powder, the higher the per-mille chance of it crumbling each frame
+        let is_held = is_below_free && world.data[(px, py)].wetness > 0 && world.data[(px, py)].velocity == (0.0, 0.0)
+            && rng.gen_range(0, 1000) >= 100 - world.particle(px, py).get_cohesion() as u16
+            && cells_in_radius(world, px, py, 1).iter().filter(|(x, y, _)| {
+                let p = world.particle(*x, *y);
+                (*x, *y) != (px, py) && p.active && p.variant == world.variants[(px, py)] && p.wetness > 0
+            }).count() >= WET_HOLD_NEIGHBOURS;
+
+        // Check for a lighter fluid below us
+        if is_below_lighter {
+            // Sink straight through it, displacing it out of our way (if this particle is quick enough to move this frame)
+            if rng.gen_range(0, 100) < world.variants[(px, py)].get_fall_chance() {
+                sink_particle(world, px, py, bx, by);
+            }
+        // Check if the GPU has already moved us (falling and slipping through empty space)
+        } else if context.is_gpu_powders && world.particle(px, py).is_gpu_powder() {
+            // Leave it be, the GPU step covers this
+        // Check if our wet neighbours are holding us up
+        } else if is_held {
+            // Hang on in mid-air
+        // Check for a floor (or any leftover momentum)
+        } else if is_below_free || world.data[(px, py)].velocity != (0.0, 0.0) {
+            // There's no floor nor any particles below (or we're still moving), so fly! (if this particle is quick enough to move this frame)
+            if rng.gen_range(0, 100) < world.variants[(px, py)].get_fall_chance() {
+                // Split our velocity into it's falling and sideways parts, relative to gravity
+                let ptr = &mut world.data[(px, py)];
+                let mut fall_speed = ptr.velocity.0 * fall.0 as f32 + ptr.velocity.1 * fall.1 as f32;
+                let mut side_speed = ptr.velocity.0 * side.0 as f32 + ptr.velocity.1 * side.1 as f32;
+
+                // Gravity accelerates us while airborne (starting at a cell per frame), while friction slows us along the ground
+                if is_below_free {
+                    fall_speed = (fall_speed + GRAVITY).min(TERMINAL_VELOCITY);
+                    if fall_speed >= 0.0 {
+                        fall_speed = fall_speed.max(1.0);
+                    }
+                } else if has_gravity {
+                    side_speed *= FRICTION;
+                }
+                ptr.velocity = (fall.0 as f32 * fall_speed + side.0 as f32 * side_speed, fall.1 as f32 * fall_speed + side.1 as f32 * side_speed);
+
+                // Travel along our velocity, until we hit something
+                let ((x_new, y_new), collided) = trace_velocity(world, px, py);
+                if collided {
+                    // Carry the momentum into the collision: part of the impact is deflected sideways, the rest is absorbed
+                    // ... without gravity to pin us down, we simply stop dead
+                    if has_gravity {
+                        side_speed += fall_speed.abs() * IMPACT_DEFLECTION * if rng.gen_range(0, 2) == 0 { -1.0 } else { 1.0 };
+                    } else {
+                        side_speed = 0.0;
+                    }
+                    fall_speed = 0.0;
+                }
+
+                // Come to a rest once we're barely moving sideways
+                if fall_speed == 0.0 && side_speed.abs() < 0.5 {
+                    side_speed = 0.0;
+                }
+                world.data[(px, py)].velocity = (fall.0 as f32 * fall_speed + side.0 as f32 * side_speed, fall.1 as f32 * fall_speed + side.1 as f32 * side_speed);
+
+                // Move into the cell we traced to, carrying all of our state (including this frame's `last_updated_frame` stamp) along
+                if (x_new, y_new) != (px, py) {
+                    move_particle(world, px, py, x_new, y_new);
+                }
+            }
+        // Particles resting on a floor spill sideways (there's no floor to spill along in zero-g)
+        } else if has_gravity {
+            // Liquids flow along their surface towards lower ground (finding their own level), while powders slip down the sides of their pile
+            let variant = &world.variants[(px, py)];
+            let flow = if state == MatterState::Liquid {
+                flow_liquid(world, rng, px, py, fall, side, variant.get_flow_distance())
+            } else {
+                find_slip(world, rng, px, py, fall, side, variant.get_pile_steepness())
+            }.filter(|_| rng.gen_range(0, 100) < variant.get_movement_chance());
+
+            // Otherwise, compute the new sideways position based on Particle properties, with some entropy towards the floor
+            let spread = world.particle(px, py).try_generate_movement(rng) as isize;
+            let sink = rng.gen_range(0, 2);
+            let (x_side, y_side) = step(px, py, (side.0 * spread, side.1 * spread));
+            let (x_sink, y_sink) = step(x_side, y_side, (fall.0 * sink, fall.1 * sink));
+
+            // Figure out some context data: denser particles sink through lighter fluids
+            // ... 'sinking' only applies when it's Solid <---> Liquid or physically dense elements
+            let is_sinking = flow.is_none() && world.get(x_sink, y_sink).is_some_and(|p| p.active && world.variants[(px, py)].can_displace(&p.variant));
+            let (x_new, y_new) = match flow {
+                Some(target) => target,
+                None if is_sinking => (x_sink, y_sink),
+                // Powders only ever move sideways by slipping down their pile
+                None if state == MatterState::Powder => (px, py),
+                None => (x_side, y_side)
+            };
+
+            // Ensure the new position is valid
+            if (x_new, y_new) != (px, py) && world.get(x_new, y_new).is_some() {
+                // Cohesive particles (ie: Slime) cling to their own kind, often refusing to move anywhere they'd be left alone
+                let moving_id = world.data[(px, py)].id;
+                let moving_variant = world.variants[(px, py)];
+                let is_clinging = rng.gen_range(0, 100) < world.particle(px, py).get_cohesion()
+                    && find_neighbour(world, x_new, y_new, |p| p.active && p.variant == moving_variant && p.id != moving_id).is_none();
+
+                // Ensure a neighbouring solid particle doesn't exist
+                if (world.is_free(x_new, y_new) || is_sinking) && !is_clinging {
+                    // Move (or sink) into the new cell: our `last_updated_frame` stamp travels with us, so we aren't updated again this frame
+                    // ... if sinking, the lighter particle is displaced out of our way
+                    if is_sinking {
+                        sink_particle(world, px, py, x_new, y_new);
+                    } else {
+                        move_particle(world, px, py, x_new, y_new);
+                    }
+                    // Flowing liquids remember which way they're flowing
+                    if flow.is_some() {
+                        world.data[(x_new, y_new)].direction = ((x_new as isize - px as isize).signum() as i8, (y_new as isize - py as isize).signum() as i8);
+                    }
+                }
+            }
+        }
+    }
+
+    // Particles (other than static solids and energy) under enough pressure are pushed towards the lowest pressure around them
+    // ... so liquids squirt through gaps, gases expand, and shockwaves scatter powders
+    let state = world.variants[(px, py)].get_state();
+    if world.is_active(px, py) && world.data[(px, py)].id == resting_id && state != MatterState::Solid && state != MatterState::Energy {
+        if let Some((nx, ny)) = find_lowest_pressure_neighbour(world, pressure, px, py) {
+            if pressure[(px, py)] - pressure[(nx, ny)] > PRESSURE_PUSH_THRESHOLD {
+                move_particle(world, px, py, nx, ny);
+            }
+        }
+    }
+
+    // Light particles (gases, and powders while airborne) are blown sideways by the wind
+    let (bx, by) = step(px, py, fall);
+    let is_airborne = !has_gravity || world.get(bx, by).is_some_and(|p| !p.active);
+    if world.is_active(px, py) && world.data[(px, py)].id == resting_id && (state == MatterState::Gas || (state == MatterState::Powder && is_airborne)) {
+        let strength = wind[(px, py)];
+        if rng.gen_range(0.0, 1.0) < strength.abs() {
+            let x_new = if strength < 0.0 { px.wrapping_sub(1) } else { px + 1 };
+            if world.is_free(x_new, py) {
+                move_particle(world, px, py, x_new, py);
+            }
+        }
+    }
+
+    // If this particle didn't move anywhere, it's been resting for another frame
+    if world.is_active(px, py) && world.data[(px, py)].id == resting_id {
+        world.data[(px, py)].rest = world.data[(px, py)].rest.saturating_add(1);
+
+        // ... and if it's buried in it's own kind, it can fall asleep until something around it changes
+        let particle = world.particle(px, py);
+        let is_idle = !particle.burning && particle.lifetime.is_none() && particle.wetness == 0 && particle.template.is_none();
+        if has_gravity && is_idle && particle.variant.can_sleep() && is_buried(world, px, py, fall, side) {
+            world.data[(px, py)].sleep = Some(neighbourhood_fingerprint(world, px, py, gravity));
+        }
+    }
+}
+
+// The state shared (read-only) by every thread simulating a frame, see `simulate`
+pub(crate) struct FrameContext<'a> {
+    gravity: &'a Gravity,
+    chunk_awake: &'a [Vec<bool>],
+    // The (wrapping) number of the frame being simulated, see `Particle::last_updated_frame`
+    frame: u32,
+    // Whether the GPU has already moved the plain powders this frame (see `GpuBackend`), leaving the CPU to skip them
+    is_gpu_powders: bool,
+    // The columns and rows of chunks in view of the camera, when the chunks outside of it sit this frame out (see `OffscreenMode`)
+    visible_chunks: Option<(std::ops::Range<usize>, std::ops::Range<usize>)>,
+    scripts: &'a Scripts
+}
+
+// A stripe of columns for a single thread to simulate, alongside the window of the world (and it's fields) the stripe can reach
+pub(crate) struct Stripe<'a> {
+    world: World<'a>,
+    pressure: Grid<'a, f32>,
+    wind: Grid<'a, f32>,
+    // The world-space column the window starts at, and the window-space columns of the stripe itself
+    offset: usize,
+    columns: std::ops::Range<usize>,
+    rng: Rng
+}
+
+// Simulate every (awake) particle within a stripe, returning the events it recorded (positioned within the world)
+fn simulate_stripe(stripe: Stripe, context: &FrameContext) -> Vec<Event> {
+    let Stripe { mut world, mut pressure, wind, offset, columns, mut rng } = stripe;
+    for px in columns {
+        // Only process active elements (inactive is essentially thin air / invisible), skipping through the active index
+        let mut from = 0;
+        while let Some(py) = world.next_active(px, from) {
+            from = py + 1;
+
+            // Particles within settled (or off-screen, when they're sitting this frame out) chunks sleep through the update, so the
+            // ... rest of their chunk's column is skipped over
+            let (cx, cy) = ((offset + px) / CHUNK_SIZE, py / CHUNK_SIZE);
+            let is_offscreen = context.visible_chunks.as_ref().is_some_and(|(columns, rows)| !columns.contains(&cx) || !rows.contains(&cy));
+            if is_offscreen || !context.chunk_awake.get(cx).and_then(|column| column.get(cy)).is_none_or(|&is_awake| is_awake) {
+                from = (cy + 1) * CHUNK_SIZE;
+                continue;
+            }
+
+            // Don't re-simulate particles that have already been simulated this frame
+            // ... this is to avoid 'infinite simulation' since gravity pulls them down the Y-axis progressively.
+            if world.data[(px, py)].last_updated_frame == context.frame {
+                continue;
+            }
+
+            // Particles buried in their own kind sleep through the update too
+            if world.data[(px, py)].sleep.is_some_and(|neighbourhood| neighbourhood == neighbourhood_fingerprint(&world, px, py, context.gravity)) {
+                continue;
+            }
+            world.data[(px, py)].sleep = None;
+            world.data[(px, py)].last_updated_frame = context.frame;
+
+            update_particle(&mut world, &mut pressure, &wind, &mut rng, context, (px, py));
+        }
+    }
+    world.events.unwrap_or_default().into_iter().map(|event| event.shifted(offset)).collect()
+}
+
+// Simulate every particle in the world for one frame, spread across multiple threads as stripes of CHUNK_SIZE columns
+// ... a particle can reach up to a chunk beyond it's own stripe, so stripes are scheduled in 3 alternating passes where no two
+// ... stripes sharing a pass can reach the same columns, and each stripe draws it's own RNG from `rng` so runs stay reproducible
+// ... browsers can't spawn threads, so on the web every stripe is simulated on the main thread
+fn simulate(world: &mut World, pressure: &mut Grid<f32>, wind: &mut Grid<f32>, rng: &mut Rng, context: &FrameContext) {
+    let width = world.width();
+    let threads = if cfg!(target_arch = "wasm32") { 1 } else { std::thread::available_parallelism().map_or(1, |threads| threads.get()) };
+    world.start_frame();
+    for pass in 0..3 {
+        // Carve the world into the (non-overlapping) windows of this pass's stripes
+        let mut groups: Vec<Vec<Stripe>> = (0..threads).map(|_| Vec::new()).collect();
+        let (mut world_rest, mut pressure_rest, mut wind_rest) = (world.reborrow(), pressure.reborrow(), wind.reborrow());
+        let mut carved = 0;
+        for (i, stripe) in (pass..width.div_ceil(CHUNK_SIZE)).step_by(3).enumerate() {
+            let start = stripe.saturating_sub(1) * CHUNK_SIZE;
+            let end = ((stripe + 2) * CHUNK_SIZE).min(width);
+            let (world_window, world_after) = world_rest.split_columns(start - carved).1.split_columns(end - start);
+            let (pressure_window, pressure_after) = pressure_rest.split_columns(start - carved).1.split_columns(end - start);
+            let (wind_window, wind_after) = wind_rest.split_columns(start - carved).1.split_columns(end - start);
+            (world_rest, pressure_rest, wind_rest, carved) = (world_after, pressure_after, wind_after, end);
+            groups[i % threads].push(Stripe {
+                world: world_window,
+                pressure: pressure_window,
+                wind: wind_window,
+                offset: start,
+                columns: stripe * CHUNK_SIZE - start..((stripe + 1) * CHUNK_SIZE).min(width) - start,
+                rng: Rng::new(rng.next_u64())
+            });
+        }
+
+        // Simulate the stripes, each thread working through it's own group of them
+        // ... their events are gathered in the order of the groups, so runs with the same seed (and threads) publish the same events
+        let events: Vec<Event> = if threads == 1 {
+            groups.into_iter().flatten().flat_map(|stripe| simulate_stripe(stripe, context)).collect()
+        } else {
+            std::thread::scope(|scope| {
+                let handles: Vec<_> = groups.into_iter().filter(|group| !group.is_empty())
+                    .map(|group| scope.spawn(move || group.into_iter().flat_map(|stripe| simulate_stripe(stripe, context)).collect::<Vec<Event>>()))
+                    .collect();
+                handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+            })
+        };
+        if let Some(recorded) = world.events.as_mut() {
+            recorded.extend(events);
+        }
+    }
+}
+
+// The state the simulation steps forwards alongside the world and it's fields (see `tick`)
+pub struct SimulationState {
+    // The simulation's RNG (reusing a seed, alongside the same inputs, reproduces the same run)
+    rng: Rng,
+    // The rigid bodies living in the world
+    pub bodies: Vec<RigidBody>,
+    // The direction gravity currently pulls in
+    pub gravity: Gravity,
+    // The wind blowing across the whole world (negative blows left, positive blows right)
+    pub global_wind: f32,
+    // The latest fingerprint of each chunk of the world, and whether each chunk is awake to be simulated (see `update_chunks`)
+    chunk_fingerprints: Vec<Vec<u64>>,
+    pub chunk_awake: Vec<Vec<bool>>,
+    // The number of frames simulated so far
+    frame: u64,
+    // The cells in view of the camera (from the top-left, up to the bottom-right), if there's a camera, and how the rest are simulated
+    pub viewport: Option<((usize, usize), (usize, usize))>,
+    pub offscreen: OffscreenMode,
+    // The quality the simulation is currently running at (see `Quality`)
+    pub quality: Quality,
+    // The element scripts run alongside each variant's own behaviour (see `parse_script`)
+    pub scripts: Scripts,
+    // The subscribers to the simulation's events, handed every event once each frame is simulated
+    pub events: EventBus
+}
+
+impl SimulationState {
+    pub fn new(seed: u64) -> SimulationState {
+        SimulationState {
+            rng: Rng::new(seed),
+            bodies: Vec::new(),
+            gravity: Gravity::Down,
+            global_wind: 0.0,
+            chunk_fingerprints: Vec::new(),
+            chunk_awake: Vec::new(),
+            frame: 0,
+            viewport: None,
+            offscreen: OffscreenMode::Throttled,
+            quality: Quality::Full,
+            scripts: Scripts::default(),
+            events: EventBus::default()
+        }
+    }
+
+    // Return the number of frames simulated so far (ie: the number of the next frame to be simulated)
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+}
+
+// The settings a single frame is stepped forwards with by `step_world`, free of any window, camera or input
+pub struct SimParams<'a> {
+    // The direction gravity pulls in
+    pub gravity: Gravity,
+    // The wind blowing across the whole world (negative blows left, positive blows right)
+    pub global_wind: f32,
+    // The number of the frame being simulated (particles already moved during a frame of the same number are skipped)
+    pub frame: u64,
+    // The element scripts run alongside each variant's own behaviour, if any (see `parse_script`)
+    pub scripts: Option<&'a Scripts>
+}
+
+impl Default for SimParams<'_> {
+    fn default() -> Self {
+        SimParams { gravity: Gravity::Down, global_wind: 0.0, frame: 0, scripts: None }
+    }
+}
+
+// Step the world's particles (and it's fields) forwards by a single frame, with every chunk awake and everything on the CPU
+// ... unlike `tick`, this leaves out rigid bodies, chunk sleeping, the camera and events, so the same world, params and seed
+// ... always step the same way (which makes it the one to test the physics with)
+pub fn step_world(world: &mut World, pressure: &mut Grid<f32>, wind: &mut Grid<f32>, params: &SimParams, rng: &mut Rng) {
+    let scripts = Scripts::default();
+    let context = FrameContext {
+        gravity: &params.gravity, chunk_awake: &[], frame: params.frame as u32, is_gpu_powders: false, visible_chunks: None,
+        scripts: params.scripts.unwrap_or(&scripts)
+    };
+    step_frame(world, pressure, wind, params, rng, &context, None);
+}
+
+// Step the world forwards by a single frame within the given context, see `step_world` and `tick`
+fn step_frame(world: &mut World, pressure: &mut Grid<f32>, wind: &mut Grid<f32>, params: &SimParams, rng: &mut Rng, context: &FrameContext, gpu: Option<&mut GpuBackend>) {
+    // Spread heat, pressure and wind around the world (or as much of it as is awake) before simulating the particles within it
+    let area = FieldArea::new(context.chunk_awake);
+    diffuse_heat(world, &area);
+    update_pressure(world, pressure, &area);
+    update_wind(world, wind, params.global_wind, &area);
+    update_power(world);
+    weather(world, rng, params.frame);
+
+    if let Some(backend) = gpu {
+        backend.step_powders(world, params.frame, rng.gen_range(0.0, 1000.0));
+    }
+    simulate(world, pressure, wind, rng, context);
+}
+
+// Step the whole simulation forwards by a single frame, optionally moving plain powders on the GPU (see `GpuBackend`)
+// ... on top of `step_world`, this moves the rigid bodies, lets settled and off-screen chunks sit the frame out, and publishes events
+pub fn tick(world: &mut World, pressure: &mut Grid<f32>, wind: &mut Grid<f32>, state: &mut SimulationState, gpu: Option<&mut GpuBackend>) {
+    // Only record events while something is listening for them
+    world.events = state.events.is_listening().then(Vec::new);
+
+    step_bodies(world, pressure, &mut state.bodies, &state.gravity);
+
+    // The GPU only knows how to drop powders downwards, so any other gravity keeps them on the CPU (as does a world too big for it)
+    let gpu_powders = gpu.filter(|gpu| state.gravity == Gravity::Down && gpu.fits(world.width(), world.height()));
+    // Off-screen chunks sit out every frame while frozen, or all but every `OFFSCREEN_INTERVAL`th frame while throttled
+    let is_offscreen_paused = match state.offscreen.max(state.quality.get_offscreen()) {
+        OffscreenMode::Full => false,
+        OffscreenMode::Throttled => !state.frame.is_multiple_of(OFFSCREEN_INTERVAL),
+        OffscreenMode::Frozen | OffscreenMode::Stored => true
+    };
+    let visible_chunks = state.viewport.filter(|_| is_offscreen_paused)
+        .map(|((x0, y0), (x1, y1))| (x0 / CHUNK_SIZE..x1.div_ceil(CHUNK_SIZE), y0 / CHUNK_SIZE..y1.div_ceil(CHUNK_SIZE)));
+    let params = SimParams { gravity: state.gravity.clone(), global_wind: state.global_wind, frame: state.frame, scripts: Some(&state.scripts) };
+    let context = FrameContext {
+        gravity: &state.gravity, chunk_awake: &state.chunk_awake, frame: state.frame as u32, is_gpu_powders: gpu_powders.is_some(), visible_chunks,
+        scripts: &state.scripts
+    };
+    step_frame(world, pressure, wind, &params, &mut state.rng, &context, gpu_powders);
+    update_chunks(world, &mut state.chunk_fingerprints, &mut state.chunk_awake);
+    state.frame += 1;
+
+    if let Some(events) = world.events.take() {
+        state.events.publish(&events);
+    }
+}
+
+// The vertex shader of the GPU powder step (macroquad's default, minus the colour and texture coordinates we don't need)
+static GPU_VERTEX_SHADER: &str = r#"#version 100
+attribute vec3 position;
+attribute vec2 texcoord;
+attribute vec4 color0;
+
+uniform mat4 Model;
+uniform mat4 Projection;
+
+void main() {
+    gl_Position = Projection * Model * vec4(position, 1);
+}
+"#;
+
+// The fragment shader of the GPU powder step: a Margolus neighbourhood, where the world is split into 2x2 blocks (shifted by
+// ... `parity` on alternate frames) and every cell works out the same rearrangement of it's block independently
+// ... cells are read as 0 (empty), 1 (a powder free to fall) or 2 (anything else), and each cell writes back what it now holds,
+// ... alongside the slot of it's block it came from (0 = top-left, 1 = top-right, 2 = bottom-left, 3 = bottom-right)
+static GPU_FRAGMENT_SHADER: &str = r#"#version 100
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+precision highp float;
+#else
+precision mediump float;
+#endif
+
+uniform sampler2D cells;
+uniform vec2 size;
+uniform float parity;
+uniform float seed;
+
+float kind(vec2 cell) {
+    if (cell.x < 0.0 || cell.y < 0.0 || cell.x >= size.x || cell.y >= size.y) {
+        return 2.0;
+    }
+    return floor(texture2D(cells, (cell + 0.5) / size).r * 255.0 + 0.5);
+}
+
+void main() {
+    vec2 cell = floor(gl_FragCoord.xy);
+    vec2 origin = floor((cell - parity) / 2.0) * 2.0 + parity;
+    float k0 = kind(origin);
+    float k1 = kind(origin + vec2(1.0, 0.0));
+    float k2 = kind(origin + vec2(0.0, 1.0));
+    float k3 = kind(origin + vec2(1.0, 1.0));
+    float s0 = 0.0;
+    float s1 = 1.0;
+    float s2 = 2.0;
+    float s3 = 3.0;
+    float t;
+
+    // Powders fall straight down into empty space
+    if (k0 == 1.0 && k2 == 0.0) { t = k0; k0 = k2; k2 = t; t = s0; s0 = s2; s2 = t; }
+    if (k1 == 1.0 && k3 == 0.0) { t = k1; k1 = k3; k3 = t; t = s1; s1 = s3; s3 = t; }
+
+    // ... otherwise they slide diagonally off whatever is below them, trying a random side first
+    bool is_right_first = fract(sin(dot(origin, vec2(12.9898, 78.233)) + seed) * 43758.5453) < 0.5;
+    if (is_right_first && k0 == 1.0 && k2 != 0.0 && k3 == 0.0) { t = k0; k0 = k3; k3 = t; t = s0; s0 = s3; s3 = t; }
+    if (k1 == 1.0 && k3 != 0.0 && k2 == 0.0) { t = k1; k1 = k2; k2 = t; t = s1; s1 = s2; s2 = t; }
+    if (!is_right_first && k0 == 1.0 && k2 != 0.0 && k3 == 0.0) { t = k0; k0 = k3; k3 = t; t = s0; s0 = s3; s3 = t; }
+
+    vec2 slot = cell - origin;
+    float index = slot.x + slot.y * 2.0;
+    float k = index == 0.0 ? k0 : index == 1.0 ? k1 : index == 2.0 ? k2 : k3;
+    float s = index == 0.0 ? s0 : index == 1.0 ? s1 : index == 2.0 ? s2 : s3;
+    gl_FragColor = vec4(k / 255.0, s / 255.0, 0.0, 1.0);
+}
+"#;
+
+// An optional backend which moves the plain powders (see `Particle::is_gpu_powder`) on the GPU, ping-ponging the world between
+// ... a texture and a render target, while the CPU keeps simulating everything else (including powders sinking through liquids)
+pub struct GpuBackend {
+    material: Material,
+    // The world's cells as uploaded to the GPU, and the render target the shader writes the next step into
+    image: Image,
+    source: Texture2D,
+    target: RenderTarget,
+    size: (usize, usize),
+    // The biggest texture (along either side) the GPU takes, and an `Image` can hold
+    max_size: usize
+}
+
+impl GpuBackend {
+    // Compile the powder shader, or return None if the GPU can't run it (so the simulation stays on the CPU)
+    pub fn new() -> Option<GpuBackend> {
+        let material = load_material(GPU_VERTEX_SHADER, GPU_FRAGMENT_SHADER, MaterialParams {
+            uniforms: vec![("size".to_string(), UniformType::Float2), ("parity".to_string(), UniformType::Float1), ("seed".to_string(), UniformType::Float1)],
+            textures: vec!["cells".to_string()],
+            ..Default::default()
+        }).ok()?;
+        let mut max_size = 0;
+        unsafe { miniquad::gl::glGetIntegerv(miniquad::gl::GL_MAX_TEXTURE_SIZE, &mut max_size) };
+        let max_size = (max_size.max(0) as usize).min(u16::MAX as usize);
+        Some(GpuBackend { material, image: Image::empty(), source: Texture2D::empty(), target: render_target(1, 1), size: (0, 0), max_size })
+    }
+
+    // Return true if a world of the given size fits within a single texture, so it's powders can be moved on the GPU
+    pub fn fits(&self, width: usize, height: usize) -> bool {
+        width <= self.max_size && height <= self.max_size
+    }
+
+    // Move the world's plain powders by a single step on the GPU, then carry the results back over to the world
+    fn step_powders(&mut self, world: &mut World, frame: u64, seed: f32) {
+        let (width, height) = (world.width(), world.height());
+        if self.size != (width, height) {
+            self.source.delete();
+            self.target.delete();
+            self.image = Image::gen_image_color(width as u16, height as u16, BLANK);
+            self.source = Texture2D::from_image(&self.image);
+            self.source.set_filter(FilterMode::Nearest);
+            self.target = render_target(width as u32, height as u32);
+            self.target.texture.set_filter(FilterMode::Nearest);
+            self.size = (width, height);
+        }
+
+        // Upload the world, as seen by the shader
+        let pixels = self.image.get_image_data_mut();
+        for ((x, y), particle) in world.iter() {
+            let kind = if !particle.active { 0 } else if particle.is_gpu_powder() { 1 } else { 2 };
+            pixels[y * width + x] = [kind, 0, 0, 255];
+        }
+        self.source.update(&self.image);
+
+        // Run the shader over the whole render target (switching cameras flushes the draw through to the GPU)
+        let parity = (frame % 2) as usize;
+        set_camera(&Camera2D { render_target: Some(self.target), ..Camera2D::from_display_rect(Rect::new(0.0, 0.0, width as f32, height as f32)) });
+        self.material.set_texture("cells", self.source);
+        self.material.set_uniform("size", (width as f32, height as f32));
+        self.material.set_uniform("parity", parity as f32);
+        self.material.set_uniform("seed", seed);
+        gl_use_material(self.material);
+        draw_rectangle(0.0, 0.0, width as f32, height as f32, WHITE);
+        gl_use_default_material();
+        set_default_camera();
+
+        // Read the step back, and rearrange the particles within each block to match (by swapping them around)
+        // ... cells beyond the edge of the world are walls to the shader, so they never take part in a rearrangement
+        let result = self.target.texture.get_texture_data();
+        for bx in (0..width + parity).step_by(2) {
+            for by in (0..height + parity).step_by(2) {
+                let cells = [0, 1, 2, 3].map(|slot| ((bx + slot % 2).wrapping_sub(parity), (by + slot / 2).wrapping_sub(parity)));
+                let sources = [0, 1, 2, 3].map(|slot| {
+                    let (x, y) = cells[slot];
+                    if x < width && y < height { result.bytes[(y * width + x) * 4 + 1] as usize } else { slot }
+                });
+                // Each cell takes the particle from it's source slot: follow every cycle of the rearrangement from it's first slot
+                for slot in 0..4 {
+                    let mut source = sources[slot];
+                    while source < slot {
+                        source = sources[source];
+                    }
+                    if source != slot {
+                        world.swap(cells[slot], cells[source]);
+                    }
+                    let (x, y) = cells[slot];
+                    if sources[slot] != slot && world.is_active(x, y) {
+                        world.data[(x, y)].rest = 0;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explosions_leave_a_crater_sized_by_their_power() {
+        let mut particles = Particles::new(21, (0..21 * 21).map(|i| Particle::new(i, ParticleVariant::Stone, true)));
+        let mut pressure_cells = vec![0.0; 21 * 21];
+        let (mut world, mut pressure) = (World::new(&mut particles), Grid::new(21, &mut pressure_cells));
+        world.variants[(10, 12)] = ParticleVariant::Brick;
+
+        explode(&mut world, &mut pressure, 10, 10, 4, 0.5);
+        // The inner half of the blast is blown away, except for indestructible Brick
+        assert!(!world.is_active(10, 10) && !world.is_active(11, 10));
+        assert!(world.is_active(10, 12) && world.variants[(10, 12)] == ParticleVariant::Brick);
+        // The rest of the blast becomes Fire towards the core and Smoke towards the edge, while everything outside of it is untouched
+        assert!(world.variants[(12, 10)] == ParticleVariant::Fire);
+        assert!(world.variants[(14, 10)] == ParticleVariant::Smoke);
+        assert!(world.variants[(15, 10)] == ParticleVariant::Stone);
+        assert!(pressure[(10, 10)] > 0.0);
+    }
+
+    #[test]
+    fn sinking_pushes_liquid_out_to_the_surface() {
+        let mut particles = Particles::new(3, [
+            Some(ParticleVariant::Sand), Some(ParticleVariant::Water), Some(ParticleVariant::Water),
+            None, None, Some(ParticleVariant::Stone)
+        ].into_iter().map(|v| Particle::new(0, v.unwrap_or(ParticleVariant::Sand), v.is_some())));
+        let mut world = World::new(&mut particles);
+
+        sink_particle(&mut world, 0, 0, 0, 1);
+        assert!(!world.is_active(0, 0));
+        assert!(world.is_active(0, 1) && world.variants[(0, 1)] == ParticleVariant::Sand);
+        assert!(world.is_active(1, 1) && world.variants[(1, 1)] == ParticleVariant::Water);
+    }
+
+    #[test]
+    fn rng_is_reproducible_and_in_range() {
+        let (mut a, mut b) = (Rng::new(42), Rng::new(42));
+        for _ in 0..1000 {
+            assert_eq!(a.next_u64(), b.next_u64());
+            let int = a.gen_range(-3, 4);
+            assert!((-3..4).contains(&int));
+            let float = a.gen_range(0.0, 1.0);
+            assert!((0.0..1.0).contains(&float));
+            b.gen_range(0, 1);
+            b.gen_range(0.0, 1.0);
+        }
+        assert_ne!(Rng::new(1).next_u64(), Rng::new(2).next_u64());
+    }
+
+    #[test]
+    fn settled_chunks_sleep_until_something_changes_nearby() {
+        let size = CHUNK_SIZE * 3;
+        let mut particles = Particles::new(size, (0..size * size).map(|i| Particle::new(i as u32, ParticleVariant::Sand, i % size > size / 2)));
+        let mut world = World::new(&mut particles);
+        let mut fingerprints = Vec::new();
+        let mut awake = Vec::new();
+
+        // A freshly sized world starts awake, then sleeps once nothing changes
+        update_chunks(&mut world, &mut fingerprints, &mut awake);
+        assert!(awake.iter().flatten().all(|&is_awake| is_awake));
+        update_chunks(&mut world, &mut fingerprints, &mut awake);
+        assert!(awake.iter().flatten().all(|&is_awake| !is_awake));
+
+        // A change wakes it's own chunk and the chunks bordering it, but not those further away
+        world.spawn(size - 1, size - 1, ParticleVariant::Water);
+        update_chunks(&mut world, &mut fingerprints, &mut awake);
+        assert!(awake[2][2] && awake[1][1] && awake[2][1]);
+        assert!(!awake[0][0] && !awake[0][2]);
+
+        // The fields are only kept up to date around the chunks awake
+        let area = FieldArea::new(&[vec![true, false, false, false], vec![false; 4], vec![false; 4]]);
+        assert_eq!(area.rows(0, size + 10).collect::<Vec<_>>(), vec![0..CHUNK_SIZE * 2]);
+        assert!(area.rows(CHUNK_SIZE * 2, size).next().is_none() && FieldArea::new(&[]).rows(5, size).eq(std::iter::once(0..size)));
+    }
+
+    #[test]
+    fn buried_particles_sleep_until_their_neighbourhood_changes() {
+        let mut particles = Particles::new(3, (0..9).map(|i| Particle::new(i, ParticleVariant::Sand, i % 3 > 0)));
+        let mut world = World::new(&mut particles);
+        let (fall, side) = (Gravity::Down.get_vector(), (1, 0));
+        assert!(is_buried(&world, 1, 1, fall, side));
+
+        // Anything changing nearby (including gravity) changes the neighbourhood
+        let neighbourhood = neighbourhood_fingerprint(&world, 1, 1, &Gravity::Down);
+        assert_ne!(neighbourhood, neighbourhood_fingerprint(&world, 1, 1, &Gravity::Left));
+        world.spawn(0, 0, ParticleVariant::Water);
+        assert_ne!(neighbourhood, neighbourhood_fingerprint(&world, 1, 1, &Gravity::Down));
+
+        // Touching something else, or having somewhere to go, keeps it awake
+        assert!(!is_buried(&world, 1, 1, fall, side));
+        world.deactivate(0, 0);
+        world.deactivate(2, 1);
+        assert!(!is_buried(&world, 1, 1, fall, side));
+    }
+
+    #[test]
+    fn gpu_powders_are_only_left_falling_to_the_gpu() {
+        let mut particles = Particles::new(3, [Particle::new(0, ParticleVariant::Sand, true), Particle::new(1, ParticleVariant::Sand, false), Particle::new(2, ParticleVariant::Water, true)]);
+        let (mut pressure_cells, mut wind_cells) = (vec![0.0; 3], vec![0.0; 3]);
+        let mut world = World::new(&mut particles);
+        let mut rng = Rng::new(3);
+        let context = FrameContext { gravity: &Gravity::Down, chunk_awake: &[], frame: 0, is_gpu_powders: true, visible_chunks: None, scripts: &Scripts::default() };
+
+        // Falling through empty space is the GPU's job, but sinking through liquids is still the CPU's
+        for _ in 0..50 {
+            update_particle(&mut world, &mut Grid::new(3, &mut pressure_cells), &Grid::new(3, &mut wind_cells), &mut rng, &context, (0, 0));
+        }
+        assert_eq!(world.data[(0, 0)].id, 0);
+        world.swap((0, 0), (0, 1));
+        for _ in 0..50 {
+            update_particle(&mut world, &mut Grid::new(3, &mut pressure_cells), &Grid::new(3, &mut wind_cells), &mut rng, &context, (0, 1));
+        }
+        assert_eq!(world.data[(0, 2)].id, 0);
+    }
+
+    #[test]
+    fn threaded_simulation_is_reproducible() {
+        let run = || {
+            let (width, height) = (CHUNK_SIZE * 4 + 5, 40);
+            let mut particles = Particles::new(height, (0..width * height).map(|i| {
+                let variant = if (i / height) % 2 == 0 { ParticleVariant::Sand } else { ParticleVariant::Water };
+                Particle::new(i as u32, variant, i % height < 10)
+            }));
+            let (mut pressure_cells, mut wind_cells) = (vec![0.0; width * height], vec![0.0; width * height]);
+            let mut world = World::new(&mut particles);
+            let mut rng = Rng::new(7);
+            for frame in 0..30 {
+                let context = FrameContext { gravity: &Gravity::Down, chunk_awake: &[], frame: frame as u32, is_gpu_powders: false, visible_chunks: None, scripts: &Scripts::default() };
+                simulate(&mut world, &mut Grid::new(height, &mut pressure_cells), &mut Grid::new(height, &mut wind_cells), &mut rng, &context);
+            }
+            world.iter().map(|(_, p)| (p.active, p.id)).collect::<Vec<_>>()
+        };
+
+        let first = run();
+        assert_eq!(first, run());
+        // ... and the particles actually fell, including those along the stripe borders
+        assert!(first.chunks(40).all(|column| column.iter().take(10).any(|&(active, _)| !active)));
+    }
+
+    #[test]
+    fn quality_degrades_and_restores_a_level_at_a_time() {
+        let mut quality = Quality::Full;
+        for offscreen in [OffscreenMode::Throttled, OffscreenMode::Frozen, OffscreenMode::Frozen] {
+            quality = quality.degrade();
+            assert!(quality.get_offscreen() == offscreen);
+        }
+        assert!(quality == Quality::Minimal && quality.degrade() == Quality::Minimal && quality.get_tick_interval() == 2);
+        assert!(quality.restore().restore().restore() == Quality::Full);
+
+        // The user's own off-screen mode only ever cuts back further than the quality level does
+        assert!(OffscreenMode::Full.max(Quality::Reduced.get_offscreen()) == OffscreenMode::Throttled);
+        assert!(OffscreenMode::Frozen.max(Quality::Reduced.get_offscreen()) == OffscreenMode::Frozen);
+    }
+
+    #[test]
+    fn offscreen_chunks_are_throttled_or_frozen() {
+        let (width, height) = (CHUNK_SIZE * 2, CHUNK_SIZE);
+        let mut particles = Particles::new(height, (0..width * height).map(|i| Particle::new(i as u32, ParticleVariant::Sand, i % height == 0 && i / height % CHUNK_SIZE == 0)));
+        let (mut pressure_cells, mut wind_cells) = (vec![0.0; width * height], vec![0.0; width * height]);
+        let mut world = World::new(&mut particles);
+        let (mut pressure, mut wind) = (Grid::new(height, &mut pressure_cells), Grid::new(height, &mut wind_cells));
+        let mut state = SimulationState::new(1);
+        state.viewport = Some(((0, 0), (CHUNK_SIZE, height)));
+
+        // Frozen: only the grain in view falls
+        state.offscreen = OffscreenMode::Frozen;
+        tick(&mut world, &mut pressure, &mut wind, &mut state, None);
+        assert!(!world.is_active(0, 0) && world.is_active(CHUNK_SIZE, 0));
+
+        // Throttled: the grain out of view falls only every `OFFSCREEN_INTERVAL`th frame
+        state.offscreen = OffscreenMode::Throttled;
+        state.frame = OFFSCREEN_INTERVAL - 1;
+        tick(&mut world, &mut pressure, &mut wind, &mut state, None);
+        assert!(world.is_active(CHUNK_SIZE, 0));
+        tick(&mut world, &mut pressure, &mut wind, &mut state, None);
+        assert!(!world.is_active(CHUNK_SIZE, 0));
+    }
+
+    // Step a world laid out as rows of cells (`S` for Sand, `W` for Water, `#` for Stone, and `.` for empty) forwards with `step_world`
+    // ... for the given number of frames, returning it's rows laid out the same way
+    fn step_rows(rows: &[&str], gravity: Gravity, frames: u64) -> Vec<String> {
+        let (width, height) = (rows[0].len(), rows.len());
+        let mut particles = Particles::new(height, (0..width * height).map(|i| {
+            let variant = match rows[i % height].as_bytes()[i / height] {
+                b'S' => Some(ParticleVariant::Sand),
+                b'W' => Some(ParticleVariant::Water),
+                b'#' => Some(ParticleVariant::Stone),
+                _ => None
+            };
+            Particle::new(i as u32, variant.unwrap_or(ParticleVariant::Sand), variant.is_some())
+        }));
+        let (mut pressure_cells, mut wind_cells) = (vec![0.0; width * height], vec![0.0; width * height]);
+        let mut world = World::new(&mut particles);
+        let (mut pressure, mut wind) = (Grid::new(height, &mut pressure_cells), Grid::new(height, &mut wind_cells));
+        let mut rng = Rng::new(1);
+        for frame in 0..frames {
+            step_world(&mut world, &mut pressure, &mut wind, &SimParams { gravity: gravity.clone(), frame, ..SimParams::default() }, &mut rng);
+        }
+        (0..height).map(|y| (0..width).map(|x| match world.get(x, y).filter(|particle| particle.active).map(|particle| particle.variant) {
+            Some(ParticleVariant::Sand)  => 'S',
+            Some(ParticleVariant::Water) => 'W',
+            Some(ParticleVariant::Stone) => '#',
+            Some(_)                      => '?',
+            None                         => '.'
+        }).collect()).collect()
+    }
+
+    #[test]
+    fn powders_fall_until_they_land() {
+        assert_eq!(step_rows(&["S", ".", ".", ".", "."], Gravity::Down, 20), [".", ".", ".", ".", "S"]);
+        assert_eq!(step_rows(&["S", ".", "#", "."], Gravity::Down, 20), [".", "S", "#", "."]);
+    }
+
+    #[test]
+    fn powders_stack_into_a_pile() {
+        let rows = step_rows(&["...S...", "...S...", "...S...", "...S...", ".......", "......."], Gravity::Down, 60);
+        let grains: Vec<(usize, usize)> = rows.iter().enumerate().flat_map(|(y, row)| row.match_indices('S').map(move |(x, _)| (x, y))).collect();
+        // Every grain either rests on the floor or on another grain, and the pile spreads out rather than standing as a column
+        assert_eq!(grains.len(), 4);
+        assert!(grains.iter().all(|&(x, y)| y == 5 || grains.contains(&(x, y + 1))));
+        assert!(rows[5].matches('S').count() >= 2);
+    }
+
+    #[test]
+    fn powders_sink_through_water_and_displace_it() {
+        assert_eq!(step_rows(&["S", "W", "W"], Gravity::Down, 30), ["W", "W", "S"]);
+        assert_eq!(step_rows(&["#S#", "#W#", "#W#", "###"], Gravity::Down, 30), ["#W#", "#W#", "#S#", "###"]);
+    }
+
+    #[test]
+    fn particles_come_to_rest_against_the_edges_of_the_world() {
+        // Nothing falls (or flows) off the world, in any direction gravity pulls
+        assert_eq!(step_rows(&["WWWW", "....", "...."], Gravity::Down, 60), ["....", "....", "WWWW"]);
+        assert_eq!(step_rows(&["...", "...", "S.."], Gravity::Up, 20), ["S..", "...", "..."]);
+        assert_eq!(step_rows(&["..S", "..."], Gravity::Left, 20), ["S..", "..."]);
+        assert_eq!(step_rows(&["S..", "..."], Gravity::Right, 20), ["..S", "..."]);
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{particle::*, world::*};
+
+// A compact copy of what's in every cell of the world at a moment in time: a byte per cell (zero for an empty cell, otherwise
+// ... one more than the variant), laid out column by column like the world itself
+// ... only the variants are kept, so two snapshots compare (and diff) by what's where, rather than by every particle's state
+#[derive(Clone, PartialEq)]
+pub struct Snapshot {
+    size: (usize, usize),
+    cells: Vec<u8>
+}
+
+// A single cell which differs between two snapshots, and what it held in each (`None` being empty)
+#[derive(Clone, Copy, PartialEq)]
+pub struct Change {
+    pub position: (usize, usize),
+    pub before: Option<ParticleVariant>,
+    pub after: Option<ParticleVariant>
+}
+
+// Every cell which differs between two snapshots of the same size (see `Snapshot::diff`), column by column
+#[derive(Clone, Default, PartialEq)]
+pub struct Diff {
+    pub changes: Vec<Change>
+}
+
+impl Snapshot {
+    pub fn take(world: &World) -> Snapshot {
+        let cells = world.iter().map(|(_, particle)| if particle.active { particle.variant.index() as u8 + 1 } else { 0 }).collect();
+        Snapshot { size: (world.width(), world.height()), cells }
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        self.size
+    }
+
+    // Return the snapshot's bytes, see `Snapshot`
+    pub(crate) fn cells(&self) -> &[u8] {
+        &self.cells
+    }
+
+    // Return the variant in the cell at (x, y), if it's within the snapshot and isn't empty
+    pub fn get(&self, x: usize, y: usize) -> Option<ParticleVariant> {
+        if x >= self.size.0 || y >= self.size.1 {
+            return None;
+        }
+        decode(self.cells[x * self.size.1 + y])
+    }
+
+    // Return every cell which changed between this snapshot and a `later` one, or nothing if they're not the same size
+    pub fn diff(&self, later: &Snapshot) -> Option<Diff> {
+        if self.size != later.size {
+            return None;
+        }
+        let height = self.size.1;
+        let changes = self.cells.iter().zip(later.cells.iter()).enumerate()
+            .filter(|(_, (before, after))| before != after)
+            .map(|(i, (&before, &after))| Change { position: (i / height, i % height), before: decode(before), after: decode(after) })
+            .collect();
+        Some(Diff { changes })
+    }
+
+    // Replay a diff taken from this snapshot (or one just like it), bringing it up to the later snapshot
+    pub fn apply(&mut self, diff: &Diff) {
+        for change in diff.changes.iter().filter(|change| change.position.0 < self.size.0 && change.position.1 < self.size.1) {
+            self.cells[change.position.0 * self.size.1 + change.position.1] = change.after.map_or(0, |variant| variant.index() as u8 + 1);
+        }
+    }
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    // Return how many cells changed from one thing into another (ie: 12 cells of Water became Steam), most common first
+    pub fn summary(&self) -> Vec<(Option<ParticleVariant>, Option<ParticleVariant>, usize)> {
+        let mut summary: Vec<(Option<ParticleVariant>, Option<ParticleVariant>, usize)> = Vec::new();
+        for change in self.changes.iter() {
+            match summary.iter_mut().find(|(before, after, _)| *before == change.before && *after == change.after) {
+                Some((_, _, count)) => *count += 1,
+                None => summary.push((change.before, change.after, 1))
+            }
+        }
+        summary.sort_by_key(|(_, _, count)| std::cmp::Reverse(*count));
+        summary
+    }
+}
+
+// Return the variant a snapshot's byte stands for (if any)
+pub(crate) fn decode(cell: u8) -> Option<ParticleVariant> {
+    cell.checked_sub(1).and_then(|index| ParticleVariant::from_index(index as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diffs_track_what_changed_into_what() {
+        let mut simulation = crate::Simulation::new(4, 4);
+        simulation.paint(1, 0, 0, ParticleVariant::Sand);
+        simulation.paint(2, 0, 0, ParticleVariant::Sand);
+        let before = simulation.snapshot();
+        assert!(before.get(1, 0) == Some(ParticleVariant::Sand) && before.get(1, 1).is_none() && before.get(4, 0).is_none());
+
+        // Both grains fall a cell, emptying the top row and filling the next
+        simulation.step();
+        let after = simulation.snapshot();
+        let diff = before.diff(&after).unwrap();
+        assert!(diff.summary() == [(Some(ParticleVariant::Sand), None, 2), (None, Some(ParticleVariant::Sand), 2)]);
+
+        let mut replayed = before.clone();
+        replayed.apply(&diff);
+        assert!(replayed == after && before.diff(&crate::Simulation::new(2, 2).snapshot()).is_none());
+    }
+}
@@ -0,0 +1,145 @@
+// The sandbox's own files (the settings, save slots, autosaves and stamps), each named by it's path within the config folder
+// ... (ie: "saves/slot-1.world"): on the desktop they're files within the config folder (see `config_folder`), while on the web
+// ... (which has no file system to write to) they're kept in the page's localStorage, through the JS plugin in web/storage.js
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use native::*;
+#[cfg(target_arch = "wasm32")]
+pub use web::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use crate::config::config_folder;
+    use std::path::PathBuf;
+
+    // Return the path of the named file within the config folder, if the platform has one
+    fn path(name: &str) -> Result<PathBuf, String> {
+        config_folder().map(|folder| folder.join(name)).ok_or_else(|| format!("there's no config folder to keep {} in", name))
+    }
+
+    // Return where the named file is kept, for messages about it
+    pub fn describe(name: &str) -> String {
+        path(name).map_or_else(|_| name.to_owned(), |path| path.display().to_string())
+    }
+
+    // Read the named file
+    pub fn read(name: &str) -> Result<Vec<u8>, String> {
+        let path = path(name)?;
+        std::fs::read(&path).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    // Write the named file (creating it's folder if it doesn't exist yet), writing it alongside first and then swapping it in, so
+    // ... a crash mid-write can't ruin the file it replaces
+    pub fn write(name: &str, bytes: &[u8]) -> Result<(), String> {
+        let path = path(name)?;
+        let partial = path.with_extension("partial");
+        path.parent().map_or(Ok(()), std::fs::create_dir_all).and_then(|_| std::fs::write(&partial, bytes))
+            .and_then(|_| std::fs::rename(&partial, &path)).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    // Remove the named file, if it's there
+    pub fn remove(name: &str) {
+        if let Ok(path) = path(name) {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    pub fn exists(name: &str) -> bool {
+        path(name).is_ok_and(|path| path.exists())
+    }
+
+    // Return how long ago (in seconds) the named file was written, if it's there
+    pub fn age(name: &str) -> Option<u64> {
+        let modified = std::fs::metadata(path(name).ok()?).ok()?.modified().ok()?;
+        Some(modified.elapsed().map_or(0, |age| age.as_secs()))
+    }
+
+    // Return the names of the files within the named folder (just their own names, not their paths)
+    pub fn list(folder: &str) -> Vec<String> {
+        let Some(Ok(entries)) = path(folder).ok().map(std::fs::read_dir) else { return Vec::new() };
+        entries.filter_map(|entry| entry.ok()).filter(|entry| entry.path().is_file())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned()).collect()
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod web {
+    // The prefix of every localStorage key the sandbox keeps it's files under, so they can't clash with anything else on the page
+    static STORAGE_PREFIX: &str = "rusty-sandbox/";
+
+    // The functions web/storage.js adds to the page's miniquad loader (keys and values are passed as pointers into our memory)
+    // ... a value (or a listing) is fetched in two steps: measured first (which stashes it within the plugin), then taken into a
+    // ... buffer of that length
+    extern "C" {
+        fn rs_storage_measure(key: *const u8, key_length: usize) -> i32;
+        fn rs_storage_list(prefix: *const u8, prefix_length: usize) -> i32;
+        fn rs_storage_take(buffer: *mut u8);
+        fn rs_storage_write(key: *const u8, key_length: usize, value: *const u8, value_length: usize) -> i32;
+        fn rs_storage_remove(key: *const u8, key_length: usize);
+        fn rs_storage_age(key: *const u8, key_length: usize) -> f64;
+    }
+
+    // The version of the storage plugin (0.1.0) web/storage.js is checked against, packed the way miniquad's loader expects
+    #[no_mangle]
+    pub extern "C" fn rusty_sandbox_storage_crate_version() -> u32 {
+        1 << 16
+    }
+
+    fn key(name: &str) -> String {
+        format!("{}{}", STORAGE_PREFIX, name)
+    }
+
+    // Take the value just measured (see `rs_storage_measure`), if there was one
+    fn take(length: i32) -> Option<Vec<u8>> {
+        let mut buffer = vec![0; usize::try_from(length).ok()?];
+        // SAFETY: the plugin copies exactly the measured length into the buffer
+        unsafe { rs_storage_take(buffer.as_mut_ptr()) };
+        Some(buffer)
+    }
+
+    pub fn describe(name: &str) -> String {
+        format!("localStorage '{}'", key(name))
+    }
+
+    pub fn read(name: &str) -> Result<Vec<u8>, String> {
+        let key = key(name);
+        // SAFETY: the plugin only reads the key within it's length
+        take(unsafe { rs_storage_measure(key.as_ptr(), key.len()) }).ok_or_else(|| format!("{}: not found", describe(name)))
+    }
+
+    // Write the named file, which can fail once the page's storage quota is used up
+    pub fn write(name: &str, bytes: &[u8]) -> Result<(), String> {
+        let key = key(name);
+        // SAFETY: the plugin only reads the key and value within their lengths
+        match unsafe { rs_storage_write(key.as_ptr(), key.len(), bytes.as_ptr(), bytes.len()) } {
+            0 => Err(format!("{}: the browser's storage is full", describe(name))),
+            _ => Ok(())
+        }
+    }
+
+    pub fn remove(name: &str) {
+        let key = key(name);
+        // SAFETY: see `read`
+        unsafe { rs_storage_remove(key.as_ptr(), key.len()) }
+    }
+
+    pub fn exists(name: &str) -> bool {
+        let key = key(name);
+        // SAFETY: see `read`
+        unsafe { rs_storage_measure(key.as_ptr(), key.len()) >= 0 }
+    }
+
+    pub fn age(name: &str) -> Option<u64> {
+        let key = key(name);
+        // SAFETY: see `read`
+        let age = unsafe { rs_storage_age(key.as_ptr(), key.len()) };
+        (age >= 0.0).then_some(age as u64)
+    }
+
+    pub fn list(folder: &str) -> Vec<String> {
+        let prefix = key(&format!("{}/", folder));
+        // SAFETY: see `read`
+        let listing = take(unsafe { rs_storage_list(prefix.as_ptr(), prefix.len()) }).unwrap_or_default();
+        String::from_utf8_lossy(&listing).lines().filter(|name| !name.is_empty() && !name.contains('/')).map(str::to_owned).collect()
+    }
+}
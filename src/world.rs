@@ -0,0 +1,444 @@
+use crate::{chunk::CHUNK_SIZE, event::*, particle::*};
+
+// Return the cell one (dx, dy) step away from (x, y), wrapping out-of-bounds steps past zero so they fail any later bounds checks
+pub(crate) fn step(x: usize, y: usize, (dx, dy): (isize, isize)) -> (usize, usize) {
+    (x.wrapping_add_signed(dx), y.wrapping_add_signed(dy))
+}
+
+// A 2D grid of cells (ie: the world's particles, or one of the fields laid over them), borrowed from a single flat block of cells
+// ... the cells are stored column-by-column (indexed by `x * height + y`), so any run of neighbouring columns is itself a
+// ... contiguous grid, letting each simulation thread borrow it's own window of the world (see `simulate`)
+pub struct Grid<'a, T> {
+    height: usize,
+    pub(crate) cells: &'a mut [T]
+}
+
+impl<'a, T> Grid<'a, T> {
+    pub fn new(height: usize, cells: &'a mut [T]) -> Grid<'a, T> {
+        Grid { height, cells }
+    }
+
+    // Return the number of columns within the grid
+    pub(crate) fn width(&self) -> usize {
+        self.cells.len().checked_div(self.height).unwrap_or(0)
+    }
+
+    // Return the number of cells within each column of the grid
+    pub(crate) fn height(&self) -> usize {
+        self.height
+    }
+
+    // Return the flat index of (x, y), if it's within the grid
+    fn index_of(&self, x: usize, y: usize) -> Option<usize> {
+        x.checked_mul(self.height).and_then(|start| start.checked_add(y)).filter(|&i| y < self.height && i < self.cells.len())
+    }
+
+    pub(crate) fn get(&self, x: usize, y: usize) -> Option<&T> {
+        self.index_of(x, y).map(|i| &self.cells[i])
+    }
+
+    // Return every cell within the grid alongside it's position, column by column
+    fn iter(&self) -> impl Iterator<Item = ((usize, usize), &T)> {
+        let height = self.height;
+        self.cells.iter().enumerate().map(move |(i, cell)| ((i / height, i % height), cell))
+    }
+
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = ((usize, usize), &mut T)> {
+        let height = self.height;
+        self.cells.iter_mut().enumerate().map(move |(i, cell)| ((i / height, i % height), cell))
+    }
+
+    // Swap the cells at a and b
+    fn swap(&mut self, (ax, ay): (usize, usize), (bx, by): (usize, usize)) {
+        debug_assert!(ay < self.height && by < self.height, "a row is outside of the grid");
+        self.cells.swap(ax * self.height + ay, bx * self.height + by);
+    }
+
+    // Split the grid in two: the columns before `x`, and the columns from `x` onwards
+    pub(crate) fn split_columns(self, x: usize) -> (Grid<'a, T>, Grid<'a, T>) {
+        let (before, after) = self.cells.split_at_mut(x * self.height);
+        (Grid::new(self.height, before), Grid::new(self.height, after))
+    }
+
+    // Borrow the grid again for a shorter lifetime, so it can be split up without giving it away
+    pub(crate) fn reborrow(&mut self) -> Grid<'_, T> {
+        Grid::new(self.height, self.cells)
+    }
+}
+
+impl<T> std::ops::Index<(usize, usize)> for Grid<'_, T> {
+    type Output = T;
+
+    fn index(&self, (x, y): (usize, usize)) -> &T {
+        debug_assert!(y < self.height, "row {} is outside of the grid", y);
+        &self.cells[x * self.height + y]
+    }
+}
+
+impl<T> std::ops::IndexMut<(usize, usize)> for Grid<'_, T> {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut T {
+        debug_assert!(y < self.height, "row {} is outside of the grid", y);
+        &mut self.cells[x * self.height + y]
+    }
+}
+
+// Resize a flat block of grid cells (see `Grid`), shifting every cell by `offset` (cropping any which end up outside of the
+// ... new size) and filling the rest with `fill`
+pub fn resize_grid<T>(cells: Vec<T>, (width, height): (usize, usize), (new_width, new_height): (usize, usize), (dx, dy): (isize, isize), mut fill: impl FnMut() -> T) -> Vec<T> {
+    let mut cells: Vec<Option<T>> = cells.into_iter().map(Some).collect();
+    let mut resized = Vec::with_capacity(new_width * new_height);
+    for x in 0..new_width {
+        for y in 0..new_height {
+            let (old_x, old_y) = step(x, y, (-dx, -dy));
+            let cell = if old_x < width && old_y < height { cells[old_x * height + old_y].take() } else { None };
+            resized.push(cell.unwrap_or_else(&mut fill));
+        }
+    }
+    resized
+}
+
+// The world's particles, stored as parallel flat blocks of grid cells (see `Grid`) rather than as one block of whole particles
+// ... so the hot loops, which mostly check what's active and what variant it is, only touch those few bytes per cell
+// ... which cells are active is kept as a bitset (each column padded out to whole words), doubling as an index of the active
+// ... particles: the particle loop, and the passes looking for particular particles (ie: Fans, weathering and the chunks'
+// ... fingerprints), skip straight past empty space 64 cells at a time, while the fields (heat and pressure) still visit every cell
+pub(crate) struct Particles {
+    height: usize,
+    variants: Vec<ParticleVariant>,
+    active: Vec<u64>,
+    // A copy of the active bitset from the start of the frame, see `World::is_free`
+    previous_active: Vec<u64>,
+    data: Vec<ParticleData>,
+    // Whether each chunk has been changed outside of the simulation's stripes since it was last fingerprinted (see `update_chunks`)
+    changed_chunks: Vec<bool>
+}
+
+impl Particles {
+    // Lay out the given particles (column by column) as a grid of the given height
+    pub(crate) fn new(height: usize, particles: impl IntoIterator<Item = Particle>) -> Particles {
+        let mut laid_out = Particles { height, variants: Vec::new(), active: Vec::new(), previous_active: Vec::new(), data: Vec::new(), changed_chunks: Vec::new() };
+        for (i, particle) in particles.into_iter().enumerate() {
+            let y = i % height;
+            if y.is_multiple_of(64) {
+                laid_out.active.push(0);
+            }
+            *laid_out.active.last_mut().unwrap() |= (particle.active as u64) << (y % 64);
+            laid_out.variants.push(particle.variant);
+            laid_out.data.push(particle.data);
+        }
+        laid_out.previous_active = laid_out.active.clone();
+        let width = laid_out.variants.len().checked_div(height).unwrap_or(0);
+        laid_out.changed_chunks = vec![false; width.div_ceil(CHUNK_SIZE) * height.div_ceil(CHUNK_SIZE)];
+        laid_out
+    }
+
+    // Return whether the particle at (x, y) is active, like `World::is_active`
+    fn is_active(&self, x: usize, y: usize) -> bool {
+        self.active[x * self.height.div_ceil(64) + y / 64] >> (y % 64) & 1 == 1
+    }
+
+    // Return the particle at (x, y), if it's within the grid
+    pub(crate) fn get(&self, x: usize, y: usize) -> Option<ParticleRef<'_>> {
+        let i = (y < self.height).then(|| x.checked_mul(self.height).and_then(|start| start.checked_add(y))).flatten().filter(|&i| i < self.variants.len())?;
+        Some(ParticleRef { variant: self.variants[i], active: self.is_active(x, y), data: &self.data[i] })
+    }
+
+    // Resize the particles like `resize_grid`, filling any new cells with `fill`
+    pub(crate) fn resize(self, new_size: (usize, usize), offset: (isize, isize), fill: impl FnMut() -> Particle) -> Particles {
+        let size = (self.variants.len().checked_div(self.height).unwrap_or(0), self.height);
+        let active: Vec<bool> = (0..self.variants.len()).map(|i| self.is_active(i / size.1, i % size.1)).collect();
+        let particles = self.variants.into_iter().zip(self.data).zip(active).map(|((variant, data), active)| Particle { variant, active, data }).collect();
+        Particles::new(new_size.1, resize_grid(particles, size, new_size, offset, fill))
+    }
+}
+
+// A 2D grid of the world's particles, borrowed from `Particles` as one `Grid` per array
+pub struct World<'a> {
+    pub(crate) variants: Grid<'a, ParticleVariant>,
+    // The active bitset, and it's copy from the start of the frame, as grids of words (see `Particles`)
+    active: Grid<'a, u64>,
+    previous_active: Grid<'a, u64>,
+    pub(crate) data: Grid<'a, ParticleData>,
+    // The chunks changed since they were last fingerprinted, by anything besides the particles simulated within the stripes (ie:
+    // ... painting, undo, rigid bodies or weathering), which a frame's fingerprints wouldn't otherwise look at (see `update_chunks`)
+    // ... the stripes themselves don't keep track of this, as they can only reach the chunks next to those awake
+    changed_chunks: Option<Grid<'a, bool>>,
+    // The events recorded so far this frame, if anything is listening for them (see `EventBus`)
+    pub(crate) events: Option<Vec<Event>>,
+    // The cells changed so far by the edit being made, as they were before it changed them (see `History::edit`)
+    pub(crate) journal: Option<Vec<((usize, usize), Particle)>>
+}
+
+impl<'a> World<'a> {
+    pub(crate) fn new(particles: &'a mut Particles) -> World<'a> {
+        let height = particles.height;
+        World {
+            variants: Grid::new(height, &mut particles.variants),
+            active: Grid::new(height.div_ceil(64), &mut particles.active),
+            previous_active: Grid::new(height.div_ceil(64), &mut particles.previous_active),
+            data: Grid::new(height, &mut particles.data),
+            changed_chunks: Some(Grid::new(height.div_ceil(CHUNK_SIZE), &mut particles.changed_chunks)),
+            events: None,
+            journal: None
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.variants.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.variants.height()
+    }
+
+    // Return the particle at (x, y), which must be within the world
+    pub(crate) fn particle(&self, x: usize, y: usize) -> ParticleRef<'_> {
+        ParticleRef { variant: self.variants[(x, y)], active: self.is_active(x, y), data: &self.data[(x, y)] }
+    }
+
+    // Return the particle at (x, y), if it's within the world
+    pub fn get(&self, x: usize, y: usize) -> Option<ParticleRef<'_>> {
+        self.variants.index_of(x, y).map(|_| self.particle(x, y))
+    }
+
+    // Return a copy of the cell at (x, y), if it's within the world
+    pub fn cell(&self, x: usize, y: usize) -> Option<Cell> {
+        self.get(x, y).map(|particle| particle.to_cell())
+    }
+
+    // Return the number of active particles of the given variant
+    pub fn count(&self, variant: ParticleVariant) -> usize {
+        self.iter().filter(|(_, particle)| particle.active && particle.variant == variant).count()
+    }
+
+    // Return the number of active particles of every variant, indexed by the variant
+    pub fn counts(&self) -> Vec<usize> {
+        let mut counts = vec![0; variants().count()];
+        for (_, particle) in self.iter().filter(|(_, particle)| particle.active) {
+            counts[particle.variant.index()] += 1;
+        }
+        counts
+    }
+
+    // Return every particle within a rectangle of the world (from the top-left, up to the bottom-right) alongside it's position,
+    // ... column by column, cropping any of the rectangle which falls outside of the world
+    pub fn cells_in_rect(&self, (x0, y0): (usize, usize), (x1, y1): (usize, usize)) -> impl Iterator<Item = ((usize, usize), ParticleRef<'_>)> {
+        let (x1, y1) = (x1.min(self.width()), y1.min(self.height()));
+        (x0..x1).flat_map(move |x| (y0..y1).map(move |y| ((x, y), self.particle(x, y))))
+    }
+
+    // Return every particle within the world alongside it's position, column by column
+    pub fn iter(&self) -> impl Iterator<Item = ((usize, usize), ParticleRef<'_>)> {
+        self.variants.iter().map(|(position, _)| (position, self.particle(position.0, position.1)))
+    }
+
+    // Return whether the particle at (x, y) is active
+    pub fn is_active(&self, x: usize, y: usize) -> bool {
+        debug_assert!(y < self.height(), "row {} is outside of the world", y);
+        self.active[(x, y / 64)] >> (y % 64) & 1 == 1
+    }
+
+    fn set_active(&mut self, x: usize, y: usize, active: bool) {
+        debug_assert!(y < self.height(), "row {} is outside of the world", y);
+        let word = &mut self.active[(x, y / 64)];
+        *word = *word & !(1 << (y % 64)) | (active as u64) << (y % 64);
+        self.mark_changed(x, y);
+    }
+
+    // Note that the chunk holding (x, y) has changed, see `changed_chunks`
+    pub(crate) fn mark_changed(&mut self, x: usize, y: usize) {
+        if let Some(changed) = self.changed_chunks.as_mut() {
+            changed[(x / CHUNK_SIZE, y / CHUNK_SIZE)] = true;
+        }
+    }
+
+    // Return whether the chunk (in the given chunk column and row) has changed since the last call, see `changed_chunks`
+    pub(crate) fn take_changed(&mut self, cx: usize, cy: usize) -> bool {
+        self.changed_chunks.as_mut().is_some_and(|changed| std::mem::take(&mut changed[(cx, cy)]))
+    }
+
+    // Return whether (x, y) is within the world, and free for a particle to move into: empty now, and at the start of the frame
+    // ... so cells are read as they were before the frame (while moves write straight to the world), and no particle can follow
+    // ... another into the space it left in the same frame, which would otherwise let whole columns shift at once in iteration order
+    pub(crate) fn is_free(&self, x: usize, y: usize) -> bool {
+        y < self.height() && self.active.index_of(x, y / 64).is_some() && !self.is_active(x, y) && self.previous_active[(x, y / 64)] >> (y % 64) & 1 == 0
+    }
+
+    // Return the number of active particles in the world
+    pub fn active_count(&self) -> usize {
+        self.active.iter().map(|(_, word)| word.count_ones() as usize).sum()
+    }
+
+    // Copy the active bitset over to the start-of-frame copy, see `is_free`
+    pub(crate) fn start_frame(&mut self) {
+        self.previous_active.cells.copy_from_slice(self.active.cells);
+    }
+
+    // Deactivate the particle at (x, y), leaving thin air
+    pub(crate) fn deactivate(&mut self, x: usize, y: usize) {
+        self.note(x, y);
+        self.set_active(x, y, false);
+    }
+
+    // Return the first row of column x, from row `y` downwards, holding an active particle
+    pub(crate) fn next_active(&self, x: usize, y: usize) -> Option<usize> {
+        let words = self.active.height();
+        (y / 64..words).find_map(|word| {
+            let bits = self.active[(x, word)] & if word == y / 64 { u64::MAX << (y % 64) } else { u64::MAX };
+            (bits != 0).then(|| word * 64 + bits.trailing_zeros() as usize)
+        })
+    }
+
+    // Return every row of column x within `rows` holding an active particle, from the top down (see `next_active`)
+    pub(crate) fn active_rows(&self, x: usize, rows: std::ops::Range<usize>) -> impl Iterator<Item = usize> + '_ {
+        let mut from = rows.start;
+        std::iter::from_fn(move || {
+            let y = self.next_active(x, from).filter(|&y| y < rows.end)?;
+            from = y + 1;
+            Some(y)
+        })
+    }
+
+    // Swap two particles (and all of their state) as a whole
+    pub(crate) fn swap(&mut self, a: (usize, usize), b: (usize, usize)) {
+        let (a_active, b_active) = (self.is_active(a.0, a.1), self.is_active(b.0, b.1));
+        self.set_active(a.0, a.1, b_active);
+        self.set_active(b.0, b.1, a_active);
+        self.variants.swap(a, b);
+        self.data.swap(a, b);
+    }
+
+    // Bring the particle at (x, y) to life as a fresh particle of the given variant, resetting any per-particle state
+    pub fn spawn(&mut self, x: usize, y: usize, variant: ParticleVariant) {
+        self.record(Event::ParticleSpawned { position: (x, y), variant });
+        self.note(x, y);
+        self.place(x, y, variant);
+    }
+
+    // Spawn a particle like `spawn`, without recording it as an event (ie: a rigid body being stamped back into the world)
+    pub(crate) fn place(&mut self, x: usize, y: usize, variant: ParticleVariant) {
+        if let Some(temperature) = variant.get_spawn_temperature() {
+            self.data[(x, y)].temperature = temperature;
+        }
+        self.variants[(x, y)] = variant;
+        self.set_active(x, y, true);
+        self.data[(x, y)].reset_state();
+    }
+
+    // Record an event, if anything is listening for them
+    pub(crate) fn record(&mut self, event: Event) {
+        if let Some(events) = self.events.as_mut() {
+            events.push(event);
+        }
+    }
+
+    // Note the particle at (x, y) as it was before an edit changed it, if an edit is being made
+    fn note(&mut self, x: usize, y: usize) {
+        if self.journal.is_none() {
+            return;
+        }
+        let particle = self.particle(x, y);
+        let before = Particle { variant: particle.variant, active: particle.active, data: particle.data.clone() };
+        if let Some(journal) = self.journal.as_mut() {
+            journal.push(((x, y), before));
+        }
+    }
+
+    // Put a particle back into (x, y) exactly as it was (ie: undoing an edit), keeping the cell's own ID
+    pub(crate) fn restore(&mut self, x: usize, y: usize, particle: &Particle) {
+        let id = self.data[(x, y)].id;
+        self.variants[(x, y)] = particle.variant;
+        self.set_active(x, y, particle.active);
+        self.data[(x, y)] = ParticleData { id, ..particle.data.clone() };
+    }
+
+    // Split the world in two like `Grid::split_columns`
+    // ... each half records it's own events (in it's own columns), see `simulate`, but neither keeps track of the chunks it changes
+    pub(crate) fn split_columns(self, x: usize) -> (World<'a>, World<'a>) {
+        let (variants_before, variants_after) = self.variants.split_columns(x);
+        let (active_before, active_after) = self.active.split_columns(x);
+        let (previous_before, previous_after) = self.previous_active.split_columns(x);
+        let (data_before, data_after) = self.data.split_columns(x);
+        let events = || self.events.as_ref().map(|_| Vec::new());
+        (
+            World { variants: variants_before, active: active_before, previous_active: previous_before, data: data_before, changed_chunks: None, events: events(), journal: None },
+            World { variants: variants_after, active: active_after, previous_active: previous_after, data: data_after, changed_chunks: None, events: events(), journal: None }
+        )
+    }
+
+    // Borrow the world again for a shorter lifetime, like `Grid::reborrow` (and like `split_columns`, for the stripes)
+    pub(crate) fn reborrow(&mut self) -> World<'_> {
+        let events = self.events.as_ref().map(|_| Vec::new());
+        World {
+            variants: self.variants.reborrow(), active: self.active.reborrow(), previous_active: self.previous_active.reborrow(), data: self.data.reborrow(),
+            changed_chunks: None, events, journal: None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::move_particle;
+
+    #[test]
+    fn resizing_a_grid_shifts_cells_by_the_offset() {
+        let mut cells: Vec<u8> = resize_grid(vec![1, 2, 3, 4], (2, 2), (3, 3), (0, 0), || 0);
+        let grid = Grid::new(3, &mut cells);
+        assert_eq!((grid.width(), grid.height()), (3, 3));
+        assert_eq!([grid[(0, 0)], grid[(0, 1)], grid[(1, 0)], grid[(1, 1)]], [1, 2, 3, 4]);
+        assert_eq!((grid[(0, 2)], grid[(2, 0)]), (0, 0));
+        // Anything outside of the grid (including past the end of a column) is out of bounds
+        assert!(grid.get(0, 3).is_none() && grid.get(3, 0).is_none() && grid.get(usize::MAX, 0).is_none());
+
+        // Shrinking crops whatever is shifted out of the grid
+        let cells = resize_grid(cells, (3, 3), (2, 1), (-1, -1), || 0);
+        assert_eq!(cells, [4, 0]);
+    }
+
+    #[test]
+    fn world_can_be_queried_without_touching_the_grid() {
+        let mut particles = Particles::new(3, (0..9).map(|i| Particle::new(i, ParticleVariant::Sand, i % 2 == 0)));
+        let mut world = World::new(&mut particles);
+        world.spawn(1, 1, ParticleVariant::Water);
+        world.data[(1, 1)].wetness = 10;
+
+        assert!(world.count(ParticleVariant::Sand) == 4 && world.counts()[ParticleVariant::Water.index()] == 1);
+        assert_eq!(world.cells_in_rect((1, 0), (5, 2)).filter(|(_, particle)| particle.active).count(), 2);
+        let cell = world.cell(1, 1).unwrap();
+        assert!(cell.variant == Some(ParticleVariant::Water) && cell.is_wet && world.cell(0, 1).unwrap().variant.is_none() && world.cell(0, 3).is_none());
+    }
+
+    #[test]
+    fn active_index_follows_the_particles() {
+        let mut particles = Particles::new(100, (0..200).map(|i| Particle::new(i, ParticleVariant::Sand, i == 3)));
+        let mut world = World::new(&mut particles);
+        assert_eq!((world.next_active(0, 0), world.next_active(0, 4), world.next_active(1, 0)), (Some(3), None, None));
+
+        // Spawning, moving and swapping particles keeps the index up to date (including across words of the bitset)
+        world.spawn(0, 70, ParticleVariant::Water);
+        move_particle(&mut world, 0, 3, 1, 99);
+        assert_eq!((world.next_active(0, 0), world.next_active(1, 0)), (Some(70), Some(99)));
+        world.spawn(0, 10, ParticleVariant::Sand);
+        assert!(world.active_rows(0, 0..100).eq([10, 70]) && world.active_rows(0, 11..70).next().is_none());
+        world.deactivate(0, 10);
+        world.swap((0, 70), (1, 10));
+        world.deactivate(1, 99);
+        assert_eq!((world.next_active(0, 0), world.next_active(1, 0), world.next_active(1, 11)), (None, Some(10), None));
+        assert!(world.variants[(1, 10)] == ParticleVariant::Water);
+    }
+
+    #[test]
+    fn cells_vacated_this_frame_stay_taken_until_the_next() {
+        let mut particles = Particles::new(3, (0..3).map(|i| Particle::new(i, ParticleVariant::Sand, i == 1)));
+        let mut world = World::new(&mut particles);
+        world.start_frame();
+        assert!(world.is_free(0, 2) && !world.is_free(0, 1) && !world.is_free(0, 3));
+
+        // Moving out of a cell leaves it empty, but it's still taken as far as other moves this frame are concerned
+        move_particle(&mut world, 0, 1, 0, 2);
+        assert!(!world.is_active(0, 1) && !world.is_free(0, 1));
+        world.start_frame();
+        assert!(world.is_free(0, 1) && !world.is_free(0, 2));
+    }
+}
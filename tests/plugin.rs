@@ -0,0 +1,59 @@
+// Installing materials is once per run, so these run in their own process rather than alongside the unit tests
+use rusty_sandbox::{behaviour::*, material::*, particle::*, plugin::*, sim::*, Simulation};
+
+// Adds Sponge: Sand which soaks up any Water it touches, turning it into more Sponge
+struct SpongePlugin;
+
+fn update_sponge(ctx: Ctx) {
+    let Ctx { world, position: (px, py), .. } = ctx;
+    let sponge = world.get(px, py).unwrap().variant;
+    if let Some((x, y)) = find_neighbour(world, px, py, |p| p.active && p.variant == ParticleVariant::Water) {
+        world.spawn(x, y, sponge);
+    }
+}
+
+impl Plugin for SpongePlugin {
+    fn name(&self) -> &str {
+        "Sponge"
+    }
+
+    fn added_materials(&self) -> Vec<MaterialDefinition> {
+        let invalid = MaterialDefinition::new("Sp@nge", ParticleVariant::Sand);
+        let sponge = MaterialDefinition { density: Some(40), behaviour: Some(update_sponge), ..MaterialDefinition::new("Sponge", ParticleVariant::Sand) };
+        vec![invalid, sponge]
+    }
+}
+
+// Count the particles of a variant within the simulation
+fn count(simulation: &Simulation, variant: ParticleVariant) -> usize {
+    (0..simulation.width()).flat_map(|x| (0..simulation.height()).map(move |y| (x, y))).filter(|&(x, y)| simulation.get(x, y) == Some(variant)).count()
+}
+
+#[test]
+fn plugins_add_materials_with_their_own_behaviour() {
+    let mut plugins = Plugins::default();
+    plugins.register(SpongePlugin);
+    let mut materials = Materials::default();
+    let problems = plugins.load_materials(&mut materials);
+    assert!(problems.len() == 1 && problems[0].contains("Sp@nge"));
+    assert_eq!(fingerprint_materials(), 0);
+    assert!(install_materials(materials));
+    assert!(fingerprint_materials() != 0);
+
+    // Sponge is numbered after the built-in materials
+    let sponge = find_variant("sponge").unwrap();
+    assert!(sponge.index() == VARIANTS.len() && sponge.to_string() == "Sponge");
+
+    // Painted Sponge falls like Sand, then soaks up the pool of Water below it
+    let mut simulation = Simulation::new(8, 8);
+    let water = (0..8).filter(|&x| simulation.paint(x, 7, 0, ParticleVariant::Water) == 1).count();
+    assert_eq!(water, 8);
+    assert_eq!(simulation.paint(4, 0, 0, sponge), 1);
+    simulation.step();
+    assert!(simulation.get(4, 0).is_none() && simulation.get(4, 1) == Some(sponge));
+    for _ in 0..20 {
+        simulation.step();
+    }
+    assert_eq!(count(&simulation, ParticleVariant::Water), 0);
+    assert_eq!(count(&simulation, sponge), 9);
+}
@@ -0,0 +1,75 @@
+// Rusty Sandbox's storage plugin for miniquad's loader (gl.js): keeps the sandbox's own files (it's settings, save slots,
+// autosaves and stamps) in the page's localStorage, as base64 text alongside the time each was written (see src/storage.rs)
+"use strict";
+
+// The value (or listing) last measured, waiting to be taken into the sandbox's memory
+var rs_storage_pending = null;
+
+function rs_storage_string(pointer, length) {
+    return new TextDecoder().decode(new Uint8Array(wasm_memory.buffer, pointer, length));
+}
+
+function rs_storage_encode(bytes) {
+    let text = "";
+    for (let i = 0; i < bytes.length; i += 0x8000) {
+        text += String.fromCharCode.apply(null, bytes.subarray(i, i + 0x8000));
+    }
+    return btoa(text);
+}
+
+function rs_storage_decode(text) {
+    const binary = atob(text);
+    const bytes = new Uint8Array(binary.length);
+    for (let i = 0; i < binary.length; i++) {
+        bytes[i] = binary.charCodeAt(i);
+    }
+    return bytes;
+}
+
+miniquad_add_plugin({
+    name: "rusty_sandbox_storage",
+    version: "0.1.0",
+    register_plugin: function (importObject) {
+        importObject.env.rs_storage_measure = function (key, key_length) {
+            const value = localStorage.getItem(rs_storage_string(key, key_length));
+            rs_storage_pending = value === null ? null : rs_storage_decode(value);
+            return rs_storage_pending === null ? -1 : rs_storage_pending.length;
+        };
+        importObject.env.rs_storage_list = function (prefix, prefix_length) {
+            const start = rs_storage_string(prefix, prefix_length);
+            const names = [];
+            for (let i = 0; i < localStorage.length; i++) {
+                const key = localStorage.key(i);
+                if (key.startsWith(start) && !key.endsWith("#written")) {
+                    names.push(key.slice(start.length));
+                }
+            }
+            rs_storage_pending = new TextEncoder().encode(names.join("\n"));
+            return rs_storage_pending.length;
+        };
+        importObject.env.rs_storage_take = function (buffer) {
+            new Uint8Array(wasm_memory.buffer, buffer, rs_storage_pending.length).set(rs_storage_pending);
+            rs_storage_pending = null;
+        };
+        importObject.env.rs_storage_write = function (key, key_length, value, value_length) {
+            const name = rs_storage_string(key, key_length);
+            try {
+                localStorage.setItem(name, rs_storage_encode(new Uint8Array(wasm_memory.buffer, value, value_length)));
+                localStorage.setItem(name + "#written", String(Date.now() / 1000));
+                return 1;
+            } catch (error) {
+                // Most likely the storage quota being used up
+                return 0;
+            }
+        };
+        importObject.env.rs_storage_remove = function (key, key_length) {
+            const name = rs_storage_string(key, key_length);
+            localStorage.removeItem(name);
+            localStorage.removeItem(name + "#written");
+        };
+        importObject.env.rs_storage_age = function (key, key_length) {
+            const written = localStorage.getItem(rs_storage_string(key, key_length) + "#written");
+            return written === null ? -1 : Math.max(0, Date.now() / 1000 - Number(written));
+        };
+    }
+});